@@ -0,0 +1,605 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{
+            players::PlayersArgumentConsumer, simple::SimpleArgConsumer, ConsumedArgs, FindArg,
+        },
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::text::TextComponent;
+
+use crate::config::{self, RateLimit};
+use crate::cooldowns::{self, format_wait};
+use crate::diag::{self, Outcome};
+use crate::history::MAX_HISTORY;
+use crate::messages::{self, format_count, format_count_short, msg, msg_string};
+use crate::notify::{notify, Level, Listing};
+use crate::permissions::{require_permission, ADMIN};
+use crate::schematic;
+use crate::state::{
+    memory_usage, repoint_schematics_dir, schematic_io_position, schematics_dir, sender_uuid,
+    sender_world, DATA_FOLDER, MAX_BLOCKS, PLAYER_DATA,
+};
+use crate::stats::{
+    active_operations, cancel_operation, cancel_world_operations, format_duration,
+    running_operations, ActiveOperation, STATS,
+};
+use crate::tools;
+
+/// Argument name for the player in `stats <player>`.
+pub const ARG_PLAYER: &str = "player";
+/// Argument name for the operation number in `queue cancel <id>`.
+pub const ARG_OPERATION_ID: &str = "id";
+/// Argument name for the world in `world disable|enable [world]`.
+pub const ARG_WORLD: &str = "world";
+
+/// One line describing a running operation, with its owner's name if they are online.
+async fn operation_line(server: &Server, operation: &ActiveOperation) -> TextComponent {
+    let owner = match server.get_player_by_uuid(operation.player_id).await {
+        Some(player) => player.gameprofile.name.clone(),
+        None => operation.player_id.to_string(),
+    };
+    msg!(
+        "queue_entry",
+        operation.id,
+        owner,
+        operation.command,
+        format_count_short(operation.blocks()),
+        format_duration(operation.started.elapsed())
+    )
+}
+
+// ============================================================================
+// /we stats
+// ============================================================================
+
+pub struct StatsExecutor;
+
+#[async_trait]
+impl CommandExecutor for StatsExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+
+            let (total, mut by_command, slowest) = {
+                let stats = STATS.lock();
+                (
+                    stats.total,
+                    stats
+                        .by_command
+                        .iter()
+                        .map(|(name, counter)| (name.clone(), *counter))
+                        .collect::<Vec<_>>(),
+                    stats.slowest.clone(),
+                )
+            };
+            by_command.sort_by(|a, b| b.1.blocks.cmp(&a.1.blocks));
+            let (clipboard_bytes, history_bytes) = memory_usage();
+
+            let mut listing = Listing::new(msg!("stats_header"));
+            listing.push(msg!(
+                "stats_total",
+                format_count(total.operations),
+                format_count(total.blocks)
+            ));
+            listing.push(msg!(
+                "stats_memory",
+                format_count(clipboard_bytes / 1024),
+                format_count(history_bytes / 1024)
+            ));
+
+            if !by_command.is_empty() {
+                listing.push(msg!("stats_by_command"));
+                for (name, counter) in &by_command {
+                    listing.push(msg!(
+                        "stats_command_entry",
+                        name,
+                        format_count(counter.operations),
+                        format_count(counter.blocks)
+                    ));
+                }
+            }
+
+            if !slowest.is_empty() {
+                listing.push(msg!("stats_slowest"));
+                for (i, op) in slowest.iter().enumerate() {
+                    listing.push(msg!(
+                        "stats_slow_entry",
+                        i + 1,
+                        op.command,
+                        format_count(op.blocks),
+                        format_duration(op.duration)
+                    ));
+                }
+            }
+
+            listing.send(sender).await;
+
+            Ok(total.operations as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we stats <player>
+// ============================================================================
+
+pub struct PlayerStatsExecutor;
+
+#[async_trait]
+impl CommandExecutor for PlayerStatsExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let targets = PlayersArgumentConsumer::find_arg(args, ARG_PLAYER)?;
+
+            for target in &targets {
+                let player_id = target.gameprofile.id;
+                let counter = STATS
+                    .lock()
+                    .unwrap()
+                    .by_player
+                    .get(&player_id)
+                    .copied()
+                    .unwrap_or_default();
+                let (clipboard_blocks, history_blocks) = {
+                    let state = PLAYER_DATA.lock();
+                    state.get(&player_id).map_or((0, 0), |data| {
+                        (
+                            data.clipboard.as_ref().map_or(0, |c| c.blocks.len()),
+                            data.history.stored_blocks(),
+                        )
+                    })
+                };
+
+                notify(
+                    sender,
+                    Level::Info,
+                    msg!(
+                        "stats_player",
+                        target.gameprofile.name,
+                        format_count(counter.operations),
+                        format_count(counter.blocks),
+                        format_count(clipboard_blocks),
+                        format_count(history_blocks)
+                    ),
+                )
+                .await;
+            }
+
+            Ok(targets.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we version
+// ============================================================================
+
+/// Human-readable size in KiB, MiB or GiB.
+fn format_bytes(bytes: u64) -> String {
+    const KIB: u64 = 1024;
+    const MIB: u64 = KIB * 1024;
+    const GIB: u64 = MIB * 1024;
+    if bytes >= GIB {
+        format!("{:.1} GiB", bytes as f64 / GIB as f64)
+    } else if bytes >= MIB {
+        format!("{:.1} MiB", bytes as f64 / MIB as f64)
+    } else {
+        format!("{} KiB", format_count(bytes / KIB))
+    }
+}
+
+/// Prints the plugin version and limits for bug reports. Paths, disk space and memory are
+/// only shown to senders with the admin permission.
+pub struct VersionExecutor;
+
+#[async_trait]
+impl CommandExecutor for VersionExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let config = config::get();
+            notify(sender, Level::Info, msg!("version", env!("CARGO_PKG_VERSION"))).await;
+            notify(
+                sender,
+                Level::Info,
+                msg!(
+                    "version_limits",
+                    format_count(MAX_BLOCKS),
+                    MAX_HISTORY,
+                    config.max_scan_radius,
+                    config.max_entity_radius
+                ),
+            )
+            .await;
+
+            if !sender.has_permission(ADMIN).await {
+                return Ok(1);
+            }
+
+            if let Some(dir) = schematics_dir() {
+                let free = fs2::available_space(&dir)
+                    .map_or_else(|_| msg_string!("version_unknown"), format_bytes);
+                notify(sender, Level::Info, msg!("version_schematics", dir.display(), free)).await;
+            }
+            let players = PLAYER_DATA.lock().len();
+            let (clipboard_bytes, history_bytes) = memory_usage();
+            notify(
+                sender,
+                Level::Info,
+                msg!(
+                    "version_state",
+                    format_count(players),
+                    format_count(clipboard_bytes / 1024),
+                    format_count(history_bytes / 1024),
+                    running_operations()
+                ),
+            )
+            .await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we reload
+// ============================================================================
+
+/// Re-reads `config.toml` and the language files. A changed `schematics_dir` is prepared and
+/// switched to once no schematic file is being read or written. Other settings read at load
+/// time, such as the other directories and tracking, still need a restart.
+pub struct ReloadExecutor;
+
+#[async_trait]
+impl CommandExecutor for ReloadExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let data_folder = DATA_FOLDER
+                .get()
+                .ok_or(CommandError::CommandFailed(msg!("reload_failed", "not loaded")))?;
+
+            config::load(data_folder)
+                .and_then(|()| messages::load(data_folder, &config::get().language))
+                .map_err(|e| CommandError::CommandFailed(msg!("reload_failed", e)))?;
+            tools::validate_bindings();
+            for world in config::get().disabled_worlds {
+                cancel_world_operations(&world);
+            }
+
+            notify(sender, Level::Success, msg!("reload_done")).await;
+            match schematic::prepare_dir(data_folder, &config::get().schematics_dir) {
+                Ok(dir) if schematics_dir().as_ref() == Some(&dir) => {}
+                Ok(dir) => match repoint_schematics_dir(dir.clone()) {
+                    Ok(()) => {
+                        schematic::invalidate_schematic_list();
+                        log::info!("Schematics directory: {}", dir.display());
+                        let message = msg!("reload_schematics_dir", dir.display());
+                        notify(sender, Level::Success, message).await;
+                    }
+                    Err(e) => {
+                        notify(sender, Level::Warning, msg!("reload_schematics_kept", e)).await;
+                    }
+                },
+                Err(e) => {
+                    notify(sender, Level::Warning, msg!("reload_schematics_kept", e)).await;
+                }
+            }
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we diag
+// ============================================================================
+
+/// Runs the self-test checks and lists each as passed, failed or skipped. The set/undo check
+/// briefly fills a 3×3×3 cube at the admin's feet with glass and reverts it.
+pub struct DiagExecutor;
+
+#[async_trait]
+impl CommandExecutor for DiagExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let checks = diag::run(sender).await;
+
+            let mut listing = Listing::new(msg!("diag_header", checks.len()));
+            let mut failed = 0;
+            for check in &checks {
+                let key = match check.outcome {
+                    Outcome::Pass => "diag_pass",
+                    Outcome::Fail => {
+                        failed += 1;
+                        "diag_fail"
+                    }
+                    Outcome::Skipped => "diag_skipped",
+                };
+                listing.push(msg!(key, check.name, check.detail));
+            }
+            listing.send(sender).await;
+
+            if failed == 0 {
+                notify(sender, Level::Success, msg!("diag_passed")).await;
+            } else {
+                notify(sender, Level::Warning, msg!("diag_failed", failed, checks.len())).await;
+            }
+            Ok(checks.len() as i32 - failed)
+        })
+    }
+}
+
+// ============================================================================
+// /we status
+// ============================================================================
+
+/// Shows the player's own running operation, if any, and whether they are waiting for a
+/// schematic file read or write.
+pub struct StatusExecutor;
+
+#[async_trait]
+impl CommandExecutor for StatusExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let operation = active_operations()
+                .into_iter()
+                .find(|operation| operation.player_id == player_id);
+
+            let message = match &operation {
+                Some(operation) => msg!(
+                    "status_running",
+                    operation.command,
+                    format_count_short(operation.blocks()),
+                    format_duration(operation.started.elapsed()),
+                    running_operations()
+                ),
+                None => msg!("status_idle", running_operations()),
+            };
+            notify(sender, Level::Info, message).await;
+            if let Some(ahead) = schematic_io_position(&player_id) {
+                notify(sender, Level::Info, msg!("status_io_waiting", format_count(ahead))).await;
+            }
+
+            Ok(i32::from(operation.is_some()))
+        })
+    }
+}
+
+// ============================================================================
+// /we cooldowns
+// ============================================================================
+
+/// Shows the player's budget in each rate-limited category and when the next use comes back.
+pub struct CooldownsExecutor;
+
+#[async_trait]
+impl CommandExecutor for CooldownsExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let mut listing = Listing::new(msg!("cooldowns_header"));
+            let mut limited = 0;
+            for budget in cooldowns::budgets(player_id) {
+                let name = budget.category.name();
+                let RateLimit { uses, seconds } = budget.limit;
+                if uses == 0 {
+                    listing.push(msg!("cooldowns_unlimited", name));
+                    continue;
+                }
+                let line = match budget.next {
+                    Some(next) => {
+                        let next = format_wait(next);
+                        msg!("cooldowns_used", name, budget.left, uses, seconds, next)
+                    }
+                    None => msg!("cooldowns_ready", name, uses, seconds),
+                };
+                if budget.left == 0 {
+                    limited += 1;
+                }
+                listing.push(line);
+            }
+            listing.send(sender).await;
+
+            Ok(limited)
+        })
+    }
+}
+
+// ============================================================================
+// /we queue, /we queue cancel <id>
+// ============================================================================
+
+/// Lists every running operation with its owner, command, progress and age.
+pub struct QueueExecutor;
+
+#[async_trait]
+impl CommandExecutor for QueueExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let operations = active_operations();
+            if operations.is_empty() {
+                notify(sender, Level::Info, msg!("queue_empty")).await;
+                return Ok(0);
+            }
+
+            let mut listing = Listing::new(msg!("queue_header", operations.len()));
+            for operation in &operations {
+                listing.push(operation_line(server, operation).await);
+            }
+            listing.send(sender).await;
+
+            Ok(operations.len() as i32)
+        })
+    }
+}
+
+/// Stops a running operation at its next block; its changes so far stay undoable.
+pub struct QueueCancelExecutor;
+
+#[async_trait]
+impl CommandExecutor for QueueCancelExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let value = SimpleArgConsumer::find_arg(args, ARG_OPERATION_ID)?;
+            let operation = value
+                .trim_start_matches('#')
+                .parse()
+                .ok()
+                .and_then(cancel_operation)
+                .ok_or(CommandError::CommandFailed(msg!("queue_not_found", value)))?;
+
+            notify(sender, Level::Success, msg!("queue_cancelled", operation.id)).await;
+            notify(sender, Level::Info, operation_line(server, &operation).await).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we world disable|enable [world]
+// ============================================================================
+
+/// Turns WorldEdit off or back on in a world (default: the sender's) and saves the change to
+/// `config.toml`. Disabling also stops operations already running there.
+pub struct WorldToggleExecutor {
+    pub enable: bool,
+}
+
+#[async_trait]
+impl CommandExecutor for WorldToggleExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let world = match SimpleArgConsumer::find_arg(args, ARG_WORLD) {
+                Ok(name) => name.to_string(),
+                Err(_) => sender_world(sender)?.get_world_name().to_string(),
+            };
+            let data_folder = DATA_FOLDER
+                .get()
+                .ok_or(CommandError::CommandFailed(msg!("config_save_failed", "not loaded")))?;
+
+            let enable = self.enable;
+            let saved = config::update(data_folder, |config| {
+                config.disabled_worlds.retain(|name| *name != world);
+                if !enable {
+                    config.disabled_worlds.push(world.clone());
+                }
+            });
+
+            if enable {
+                notify(sender, Level::Success, msg!("world_toggle_enabled", world)).await;
+            } else {
+                let stopped = cancel_world_operations(&world);
+                notify(sender, Level::Success, msg!("world_toggle_disabled", world, stopped)).await;
+            }
+            if let Err(e) = saved {
+                notify(sender, Level::Warning, msg!("config_save_failed", e)).await;
+            }
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the administration commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("stats")
+            .execute(StatsExecutor)
+            .then(argument(ARG_PLAYER, PlayersArgumentConsumer).execute(PlayerStatsExecutor)),
+    )
+    .then(literal("version").execute(VersionExecutor))
+    .then(literal("reload").execute(ReloadExecutor))
+    .then(literal("diag").execute(DiagExecutor))
+    .then(literal("status").execute(StatusExecutor))
+    .then(literal("cooldowns").execute(CooldownsExecutor))
+    .then(
+        literal("queue").execute(QueueExecutor).then(
+            literal("cancel")
+                .then(argument(ARG_OPERATION_ID, SimpleArgConsumer).execute(QueueCancelExecutor)),
+        ),
+    )
+    .then(
+        literal("world")
+            .then(
+                literal("disable").execute(WorldToggleExecutor { enable: false }).then(
+                    argument(ARG_WORLD, SimpleArgConsumer)
+                        .execute(WorldToggleExecutor { enable: false }),
+                ),
+            )
+            .then(
+                literal("enable").execute(WorldToggleExecutor { enable: true }).then(
+                    argument(ARG_WORLD, SimpleArgConsumer)
+                        .execute(WorldToggleExecutor { enable: true }),
+                ),
+            ),
+    )
+}
@@ -1,25 +1,88 @@
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::time::Instant;
+
 use async_trait::async_trait;
 use pumpkin::{
     command::{
-        args::ConsumedArgs, dispatcher::CommandError, CommandExecutor, CommandResult, CommandSender,
+        args::{
+            block::BlockArgumentConsumer, message::MsgArgConsumer, simple::SimpleArgConsumer,
+            ConsumedArgs, FindArg,
+        },
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
     },
     server::Server,
 };
-use pumpkin_util::{
-    math::{position::BlockPos, vector3::Vector3},
-    text::{color::NamedColor, TextComponent},
-};
-use pumpkin_world::world::BlockFlags;
+use pumpkin_data::Block;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+use uuid::Uuid;
 
+use crate::cooldowns::{self, Category};
+use crate::engine::{read_region, read_region_biomes, sort_for_placement, EditSession};
+use crate::flags::{FlagSpec, Flags};
+use crate::history::biome_cell;
+use crate::mask::{parse_block_list, Mask};
+use crate::messages::{format_count, msg};
+use crate::notify::{notify, Level, Listing};
+use crate::permissions::{
+    protected_blocks, require_permission, ADMIN, BYPASS_BLOCK_LIMIT, CLIPBOARD_FETCH,
+    CLIPBOARD_PUBLISH,
+};
+use crate::shared;
 use crate::state::{
-    check_selection_size, get_selection, sender_block_pos, sender_uuid, sender_world,
-    ClipboardData, PLAYER_DATA,
+    check_clipboard_size, get_selection, placement_flags, placement_note, sender_block_pos,
+    sender_player, selection_volume, sender_uuid, sender_world, ClipboardData, MemoryReservation,
+    MAX_BLOCKS, PLAYER_DATA,
 };
+use crate::stats::format_duration;
+use crate::transform::{
+    carry_properties, clipboard_bounds, crop_air, is_air, paste_bounds, trim_air, FlipAxis,
+    Rotation, Transform,
+};
+
+use super::{flag_error, ARG_FLAGS};
+
+/// Argument name for the shared clipboard name.
+pub const ARG_SHARED_NAME: &str = "name";
+/// Argument name for the angle in `rotate`.
+pub const ARG_ANGLE: &str = "angle";
+/// Argument name for the direction in `flip`.
+pub const ARG_DIRECTION: &str = "direction";
+/// Argument name for the blocks to rewrite in `clipboard replace`.
+pub const ARG_REMAP_FROM: &str = "from";
+/// Argument name for the new block in `clipboard replace`.
+pub const ARG_REMAP_TO: &str = "to";
 
 // ============================================================================
-// //copy
+// //copy [-b] [-y <minY>] [-m <mask>]
 // ============================================================================
 
+/// Flags accepted by `//copy`.
+const COPY_FLAGS: &[FlagSpec] = &[
+    FlagSpec::switch("-b"),
+    FlagSpec::with_value("-y", "minY"),
+    FlagSpec::with_value("-m", "mask"),
+];
+
+/// Lowest Y copied with `copy -y`: an absolute height, or `~n` for `n` blocks above (or with a
+/// negative `n`, below) the player's feet.
+fn parse_min_y(value: &str, feet_y: i32) -> Result<i32, CommandError> {
+    let min_y = match value.strip_prefix('~') {
+        Some("") => Some(feet_y),
+        Some(relative) => relative.parse::<i32>().ok().and_then(|n| feet_y.checked_add(n)),
+        None => value.parse::<i32>().ok(),
+    };
+    min_y.ok_or(CommandError::CommandFailed(msg!("invalid_min_y", value)))
+}
+
+/// Copies the selection relative to the player. `-b` also copies its biomes. `-y` leaves out
+/// blocks below a height and `-m` blocks not matching a mask, e.g. the ground under a
+/// building; the air left around what remains is then cropped off.
 pub struct CopyExecutor;
 
 #[async_trait]
@@ -28,46 +91,92 @@ impl CommandExecutor for CopyExecutor {
         &'a self,
         sender: &'a CommandSender,
         _server: &'a Server,
-        _args: &'a ConsumedArgs<'a>,
+        args: &'a ConsumedArgs<'a>,
     ) -> CommandResult<'a> {
         Box::pin(async move {
             let player_id = sender_uuid(sender)?;
             let player_pos = sender_block_pos(sender)?;
             let world = sender_world(sender)?;
 
-            let (min, max) = get_selection(&player_id)?;
-            check_selection_size(&min, &max)?;
-
-            let mut blocks = Vec::new();
-            for x in min.0.x..=max.0.x {
-                for y in min.0.y..=max.0.y {
-                    for z in min.0.z..=max.0.z {
-                        let pos = BlockPos(Vector3::new(x, y, z));
-                        let state_id = world.get_block_state_id(&pos).await;
-                        let offset = Vector3::new(
-                            x - player_pos.0.x,
-                            y - player_pos.0.y,
-                            z - player_pos.0.z,
-                        );
-                        blocks.push((offset, state_id));
-                    }
+            let flags = Flags::from_args(args, COPY_FLAGS)?;
+            let with_biomes = flags.has("-b");
+            let min_y = match flags.value("-y") {
+                Some(value) => Some(parse_min_y(value, player_pos.0.y)?),
+                None => None,
+            };
+            let mask = match flags.value("-m") {
+                Some(source) => Some(Mask::parse(source).map_err(flag_error)?),
+                None => None,
+            };
+
+            let (min, max) = get_selection(sender)?;
+            let volume = selection_volume(&min, &max) as u64;
+            check_clipboard_size(volume)?;
+            // Every position may hold a block, plus one biome byte each with -b
+            let entry = size_of::<(Vector3<i32>, u16)>() as u64 + u64::from(with_biomes);
+            let _reservation = MemoryReservation::acquire(volume * entry)?;
+
+            let started = Instant::now();
+            let blocks = read_region(world.as_ref(), &min, &max, &player_pos)
+                .await
+                .map_err(|e| CommandError::CommandFailed(msg!("copy_failed", e)))?;
+            let biomes = if with_biomes {
+                let biomes = read_region_biomes(world.as_ref(), &min, &max)
+                    .await
+                    .map_err(|e| CommandError::CommandFailed(msg!("copy_failed", e)))?;
+                Some(biomes)
+            } else {
+                None
+            };
+
+            let mut clipboard = ClipboardData { blocks, biomes, source: None };
+
+            // Excluded positions aren't recorded at all, like air left out by `paste -a`
+            let mut excluded = None;
+            if min_y.is_some() || mask.is_some() {
+                let keep: Vec<bool> = clipboard
+                    .blocks
+                    .iter()
+                    .map(|(offset, state_id)| {
+                        let pos = BlockPos(Vector3::new(
+                            player_pos.0.x + offset.x,
+                            player_pos.0.y + offset.y,
+                            player_pos.0.z + offset.z,
+                        ));
+                        min_y.is_none_or(|min_y| pos.0.y >= min_y)
+                            && mask.as_ref().is_none_or(|m| m.matches(&pos, *state_id, &(min, max)))
+                    })
+                    .collect();
+                if let Some(biomes) = &mut clipboard.biomes {
+                    let mut kept = keep.iter();
+                    biomes.retain(|_| kept.next().copied().unwrap_or(false));
                 }
+                let mut kept = keep.iter();
+                clipboard.blocks.retain(|_| kept.next().copied().unwrap_or(false));
+                let (low, high) = crop_air(&mut clipboard)
+                    .ok_or(CommandError::CommandFailed(msg!("copy_all_excluded")))?;
+                let left_out = keep.iter().filter(|kept| !**kept).count();
+                let size = Vector3::new(high.x - low.x + 1, high.y - low.y + 1, high.z - low.z + 1);
+                excluded = Some((left_out, size));
             }
-
-            let block_count = blocks.len();
+            let block_count = clipboard.blocks.len();
 
             {
-                let mut state = PLAYER_DATA.lock().unwrap();
+                let mut state = PLAYER_DATA.lock();
                 let data = state.entry(player_id).or_default();
-                data.clipboard = Some(ClipboardData { blocks });
+                data.clipboard = Some(clipboard);
             }
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("{block_count} block(s) copied to clipboard."))
-                        .color_named(NamedColor::Aqua),
-                )
-                .await;
+            notify(
+                sender,
+                Level::Success,
+                msg!("copied", format_count(block_count), format_duration(started.elapsed())),
+            )
+            .await;
+            if let Some((left_out, size)) = excluded {
+                let message = msg!("copy_excluded", format_count(left_out), size.x, size.y, size.z);
+                notify(sender, Level::Info, message).await;
+            }
 
             Ok(block_count as i32)
         })
@@ -75,9 +184,23 @@ impl CommandExecutor for CopyExecutor {
 }
 
 // ============================================================================
-// //paste
+// //paste [-u] [-b] [-a] [-m <mask>] [-r <angle>] [-f <direction>]
 // ============================================================================
 
+/// Flags accepted by `//paste` and `schem paste`.
+pub const PASTE_FLAGS: &[FlagSpec] = &[
+    FlagSpec::switch("-u"),
+    FlagSpec::switch("-b"),
+    FlagSpec::switch("-a"),
+    FlagSpec::with_value("-m", "mask"),
+    FlagSpec::with_value("-r", "angle"),
+    FlagSpec::with_value("-f", "direction"),
+];
+
+/// Pastes the clipboard at the player. `-u` places with neighbor updates even in fast mode;
+/// `-b` also pastes the biomes copied with `copy -b`; `-a` skips air in the clipboard;
+/// `-m` only pastes where the block already in the world matches the mask;
+/// `-r` / `-f` rotate or flip a temporary copy, leaving the stored clipboard unchanged.
 pub struct PasteExecutor;
 
 #[async_trait]
@@ -86,60 +209,748 @@ impl CommandExecutor for PasteExecutor {
         &'a self,
         sender: &'a CommandSender,
         _server: &'a Server,
-        _args: &'a ConsumedArgs<'a>,
+        args: &'a ConsumedArgs<'a>,
     ) -> CommandResult<'a> {
         Box::pin(async move {
             let player_id = sender_uuid(sender)?;
-            let player_pos = sender_block_pos(sender)?;
-            let world = sender_world(sender)?;
+            let flags = Flags::from_args(args, PASTE_FLAGS)?;
 
             // Clone clipboard data so the lock is released before async work
-            let clipboard_blocks = {
-                let state = PLAYER_DATA.lock().unwrap();
+            let clipboard = {
+                let state = PLAYER_DATA.lock();
                 let data = state.get(&player_id).ok_or(CommandError::CommandFailed(
-                    TextComponent::text("Clipboard is empty. Use //copy first.")
-                        .color_named(NamedColor::Red),
+                    msg!("clipboard_empty"),
                 ))?;
-                let clipboard = data.clipboard.as_ref().ok_or(CommandError::CommandFailed(
-                    TextComponent::text("Clipboard is empty. Use //copy first.")
-                        .color_named(NamedColor::Red),
-                ))?;
-                clipboard.blocks.clone()
+                data.clipboard.clone().ok_or(CommandError::CommandFailed(msg!("clipboard_empty")))?
             };
+            paste_clipboard(sender, clipboard, &flags, "//paste").await
+        })
+    }
+}
 
-            let mut undo_blocks = Vec::new();
-            let mut count = 0i32;
+/// Paste `clipboard` at the sender's position as one undoable operation, applying the paste
+/// flags. `command` is the undo history's name for the paste, before its flags.
+pub async fn paste_clipboard(
+    sender: &CommandSender,
+    mut clipboard: ClipboardData,
+    flags: &Flags,
+    command: &str,
+) -> Result<i32, CommandError> {
+    let player_id = sender_uuid(sender)?;
+    let player_pos = sender_block_pos(sender)?;
+    let world = sender_world(sender)?;
 
-            for (offset, state_id) in &clipboard_blocks {
-                let target = BlockPos(Vector3::new(
-                    player_pos.0.x + offset.x,
-                    player_pos.0.y + offset.y,
-                    player_pos.0.z + offset.z,
-                ));
+    let transform = Transform::from_flags(flags).map_err(flag_error)?;
+    let updates = flags.has("-u");
+    let with_biomes = flags.has("-b");
+    let skip_air = flags.has("-a");
+    let mask = match flags.value("-m") {
+        Some(source) => Some((source, Mask::parse(source).map_err(flag_error)?)),
+        None => None,
+    };
+
+    if with_biomes && clipboard.biomes.is_none() {
+        return Err(CommandError::CommandFailed(msg!("clipboard_no_biomes")));
+    }
+    // Clipboards may be larger than the edit limit, see `max_clipboard_blocks`
+    let block_count = clipboard.blocks.len();
+    if block_count as i64 > MAX_BLOCKS && !sender.has_permission(BYPASS_BLOCK_LIMIT).await {
+        return Err(CommandError::CommandFailed(msg!(
+            "clipboard_too_large",
+            format_count(block_count),
+            format_count(MAX_BLOCKS)
+        )));
+    }
+    transform.apply(&mut clipboard).map_err(flag_error)?;
+
+    // Expression masks are normalized across the pasted box
+    let bounds = paste_bounds(&clipboard, &player_pos).unwrap_or((player_pos, player_pos));
+    let writes = paste_writes(&clipboard, &player_pos, skip_air);
+
+    // One write per biome cell, taken from the first block landing in it
+    let mut biome_writes: Vec<(BlockPos, u8)> = Vec::new();
+    if let Some(biomes) = clipboard.biomes.as_ref().filter(|_| with_biomes) {
+        let mut cells = HashSet::new();
+        for ((offset, _), biome) in clipboard.blocks.iter().zip(biomes) {
+            let target = target_of(&player_pos, offset);
+            if cells.insert(biome_cell(&target)) {
+                biome_writes.push((target, *biome));
+            }
+        }
+    }
+
+    let flags = placement_flags(&player_id, updates);
+    let mut command = command.to_string();
+    if with_biomes {
+        command.push_str(" -b");
+    }
+    if skip_air {
+        command.push_str(" -a");
+    }
+    if let Some((source, _)) = &mask {
+        command.push_str(&format!(" -m {source}"));
+    }
+    let mut session = EditSession::new(world, player_id, command, flags)?;
+    cooldowns::take(sender, Category::Paste)?;
+    session.backup(sender, &bounds.0, &bounds.1).await?;
+    session.protect(protected_blocks(sender).await);
+    let mut masked = 0usize;
+    let paste_mask = mask.as_ref().map(|(_, mask)| (mask, &bounds));
+    let result = write_paste(&mut session, &writes, &biome_writes, paste_mask, &mut masked).await;
+    session.refresh_biomes().await;
+    let done = session.finish(result)?;
+
+    notify(
+        sender,
+        Level::Success,
+        msg!(
+            "pasted",
+            format_count(done.blocks),
+            transform.describe(),
+            done.time(),
+            placement_note(flags)
+        ),
+    )
+    .await;
+    if transform.is_lossy() {
+        notify(sender, Level::Warning, msg!("rotation_lossy")).await;
+    }
+    if mask.is_some() {
+        notify(sender, Level::Info, msg!("paste_masked", format_count(masked))).await;
+    }
+    if done.protected > 0 {
+        notify(sender, Level::Warning, msg!("protected_skipped", format_count(done.protected)))
+            .await;
+    }
+
+    Ok(done.blocks as i32)
+}
+
+/// Where the block at clipboard `offset` lands when pasting at `at`.
+fn target_of(at: &BlockPos, offset: &Vector3<i32>) -> BlockPos {
+    BlockPos(Vector3::new(at.0.x + offset.x, at.0.y + offset.y, at.0.z + offset.z))
+}
+
+/// The clipboard's blocks placed at `at` in placement order, without air when `skip_air`.
+fn paste_writes(clipboard: &ClipboardData, at: &BlockPos, skip_air: bool) -> Vec<(BlockPos, u16)> {
+    let mut writes: Vec<(BlockPos, u16)> = clipboard
+        .blocks
+        .iter()
+        .filter(|(_, state_id)| !(skip_air && is_air(*state_id)))
+        .map(|(offset, state_id)| (target_of(at, offset), *state_id))
+        .collect();
+    sort_for_placement(&mut writes);
+    writes
+}
+
+/// Write a paste's blocks, then its biomes. With a mask and the pasted box it is normalized
+/// across, a block is only written where the block already there matches; the others are
+/// counted in `masked`.
+async fn write_paste(
+    session: &mut EditSession,
+    writes: &[(BlockPos, u16)],
+    biome_writes: &[(BlockPos, u8)],
+    mask: Option<(&Mask, &(BlockPos, BlockPos))>,
+    masked: &mut usize,
+) -> Result<(), String> {
+    for (target, state_id) in writes {
+        let Some((mask, bounds)) = mask else {
+            session.set_block(*target, *state_id).await?;
+            continue;
+        };
+        // The destination is read once, for both the mask and undo
+        let old_state = session.get_block(target).await?;
+        if mask.matches(target, old_state, bounds) {
+            session.set_block_from(*target, old_state, *state_id).await?;
+        } else {
+            *masked += 1;
+        }
+    }
+    for (target, biome) in biome_writes {
+        session.set_biome(*target, *biome).await?;
+    }
+    Ok(())
+}
+
+// ============================================================================
+// //rotate <angle>
+// ============================================================================
+
+/// Rotate the stored clipboard, and the player's position relative to it, around the Y axis.
+pub struct RotateExecutor;
+
+#[async_trait]
+impl CommandExecutor for RotateExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let angle = SimpleArgConsumer::find_arg(args, ARG_ANGLE)?;
+            let rotation = Rotation::parse(angle).map_err(flag_error)?;
+            let transform = Transform {
+                rotation: Some(rotation),
+                flip: None,
+            };
 
-                let old_state = world.get_block_state_id(&target).await;
-                undo_blocks.push((target, old_state));
+            let block_count = transform_clipboard(&player_id, &transform)?;
 
-                world
-                    .set_block_state(&target, *state_id, BlockFlags::FORCE_STATE)
-                    .await;
-                count += 1;
+            notify(
+                sender,
+                Level::Success,
+                msg!("clipboard_rotated", rotation.degrees(), format_count(block_count)),
+            )
+            .await;
+            if rotation.is_lossy() {
+                notify(sender, Level::Warning, msg!("rotation_lossy")).await;
             }
 
+            Ok(block_count as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //flip <direction>
+// ============================================================================
+
+/// Mirror the stored clipboard along the axis of the given direction.
+pub struct FlipExecutor;
+
+#[async_trait]
+impl CommandExecutor for FlipExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let direction = SimpleArgConsumer::find_arg(args, ARG_DIRECTION)?;
+            let axis = FlipAxis::parse(direction).map_err(flag_error)?;
+            let transform = Transform {
+                rotation: None,
+                flip: Some(axis),
+            };
+
+            let block_count = transform_clipboard(&player_id, &transform)?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("clipboard_flipped", axis.name(), format_count(block_count)),
+            )
+            .await;
+
+            Ok(block_count as i32)
+        })
+    }
+}
+
+/// Apply `transform` to the player's stored clipboard, returning its block count.
+fn transform_clipboard(player_id: &Uuid, transform: &Transform) -> Result<usize, CommandError> {
+    let mut state = PLAYER_DATA.lock();
+    let clipboard = state
+        .get_mut(player_id)
+        .and_then(|data| data.clipboard.as_mut())
+        .ok_or(CommandError::CommandFailed(msg!("clipboard_empty")))?;
+    transform.apply(clipboard).map_err(flag_error)?;
+    Ok(clipboard.blocks.len())
+}
+
+// ============================================================================
+// /we clipboard info
+// ============================================================================
+
+/// What `clipboard info` and `size` report about a player's clipboard.
+pub struct ClipboardSummary {
+    /// Width, height and length of the box around the clipboard's blocks.
+    pub size: Vector3<i32>,
+    pub blocks: usize,
+    pub biomes: bool,
+    /// Schematic the clipboard was loaded from.
+    pub source: Option<String>,
+}
+
+impl ClipboardSummary {
+    /// Summary of the player's clipboard, `None` if they have none or it is empty.
+    pub fn of(player_id: &Uuid) -> Option<Self> {
+        let state = PLAYER_DATA.lock();
+        let clipboard = state.get(player_id).and_then(|data| data.clipboard.as_ref())?;
+        let (min, max) = clipboard_bounds(clipboard)?;
+        Some(Self {
+            size: Vector3::new(max.x - min.x + 1, max.y - min.y + 1, max.z - min.z + 1),
+            blocks: clipboard.blocks.len(),
+            biomes: clipboard.biomes.is_some(),
+            source: clipboard.source.clone(),
+        })
+    }
+
+    /// Send the size and block count, then whether it holds biomes and where it came from.
+    pub async fn send(&self, sender: &CommandSender) {
+        let Vector3 { x, y, z } = self.size;
+        notify(sender, Level::Info, msg!("clipboard_info", x, y, z, format_count(self.blocks)))
+            .await;
+        if self.biomes {
+            notify(sender, Level::Info, msg!("clipboard_info_biomes")).await;
+        }
+        if let Some(source) = &self.source {
+            notify(sender, Level::Info, msg!("clipboard_info_source", source)).await;
+        }
+    }
+}
+
+/// Shows the clipboard's size and block count, whether it holds biomes, and the schematic
+/// it was loaded from.
+pub struct ClipboardInfoExecutor;
+
+#[async_trait]
+impl CommandExecutor for ClipboardInfoExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let summary = ClipboardSummary::of(&player_id)
+                .ok_or(CommandError::CommandFailed(msg!("clipboard_empty")))?;
+            summary.send(sender).await;
+
+            Ok(summary.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we clipboard trim
+// ============================================================================
+
+pub struct ClipboardTrimExecutor;
+
+#[async_trait]
+impl CommandExecutor for ClipboardTrimExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+
+            let (trimmed, block_count) = {
+                let mut state = PLAYER_DATA.lock();
+                let clipboard = state
+                    .get_mut(&player_id)
+                    .and_then(|data| data.clipboard.as_mut())
+                    .ok_or(CommandError::CommandFailed(msg!("clipboard_empty")))?;
+                (trim_air(clipboard), clipboard.blocks.len())
+            };
+            let (old, new) = trimmed.ok_or(CommandError::CommandFailed(
+                msg!("clipboard_only_air"),
+            ))?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "clipboard_trimmed",
+                    old.x,
+                    old.y,
+                    old.z,
+                    new.x,
+                    new.y,
+                    new.z,
+                    format_count(block_count)
+                ),
+            )
+            .await;
+
+            Ok(block_count as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we clipboard replace <from> <to>
+// ============================================================================
+
+/// Rewrites clipboard entries matching `from` (block list or mask, e.g. `oak_planks,oak_log`)
+/// to `to`, keeping properties both blocks share such as facing or half. Touches only the
+/// clipboard, so there is nothing to undo.
+pub struct ClipboardReplaceExecutor;
+
+#[async_trait]
+impl CommandExecutor for ClipboardReplaceExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let from = SimpleArgConsumer::find_arg(args, ARG_REMAP_FROM)?;
+            let sources = parse_block_list(from).map_err(flag_error)?;
+            let to_block = BlockArgumentConsumer::find_arg(args, ARG_REMAP_TO)?;
+
+            let changed = {
+                let mut state = PLAYER_DATA.lock();
+                let clipboard = state
+                    .get_mut(&player_id)
+                    .and_then(|data| data.clipboard.as_mut())
+                    .ok_or(CommandError::CommandFailed(msg!("clipboard_empty")))?;
+                remap_clipboard(clipboard, &sources, to_block)
+            };
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("clipboard_replaced", format_count(changed), from, to_block.name),
+            )
+            .await;
+
+            Ok(changed as i32)
+        })
+    }
+}
+
+/// Replace the entries of `clipboard` whose block is one of `sources` with `to`, returning how
+/// many changed. Each distinct state is converted once.
+fn remap_clipboard(clipboard: &mut ClipboardData, sources: &[&Block], to: &Block) -> usize {
+    let mut targets: HashMap<u16, u16> = HashMap::new();
+    let mut changed = 0;
+    for (_, state_id) in &mut clipboard.blocks {
+        let id = Block::from_state_id(*state_id).id;
+        if !sources.iter().any(|block| block.id == id) {
+            continue;
+        }
+        let target = *targets.entry(*state_id).or_insert_with(|| carry_properties(*state_id, to));
+        if target != *state_id {
+            *state_id = target;
+            changed += 1;
+        }
+    }
+    changed
+}
+
+// ============================================================================
+// /we clipboard publish <name>
+// ============================================================================
+
+pub struct ClipboardPublishExecutor;
+
+#[async_trait]
+impl CommandExecutor for ClipboardPublishExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, CLIPBOARD_PUBLISH).await?;
+            let player = sender_player(sender)?;
+            let player_id = player.gameprofile.id;
+            let name = SimpleArgConsumer::find_arg(args, ARG_SHARED_NAME)?;
+            let is_admin = sender.has_permission(ADMIN).await;
+
+            let clipboard = {
+                let state = PLAYER_DATA.lock();
+                state
+                    .get(&player_id)
+                    .and_then(|data| data.clipboard.clone())
+                    .ok_or(CommandError::CommandFailed(msg!("clipboard_empty")))?
+            };
+            let block_count = clipboard.blocks.len();
+
+            shared::publish(
+                name,
+                player_id,
+                player.gameprofile.name.clone(),
+                clipboard,
+                is_admin,
+            )
+            .map_err(flag_error)?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("shared_published", format_count(block_count), name),
+            )
+            .await;
+
+            Ok(block_count as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we clipboard fetch <name>
+// ============================================================================
+
+pub struct ClipboardFetchExecutor;
+
+#[async_trait]
+impl CommandExecutor for ClipboardFetchExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, CLIPBOARD_FETCH).await?;
+            let player_id = sender_uuid(sender)?;
+            let name = SimpleArgConsumer::find_arg(args, ARG_SHARED_NAME)?;
+
+            let clipboard = shared::fetch(name).ok_or(CommandError::CommandFailed(
+                msg!("shared_not_found", name),
+            ))?;
+            let block_count = clipboard.blocks.len();
+
             {
-                let mut state = PLAYER_DATA.lock().unwrap();
+                let mut state = PLAYER_DATA.lock();
                 let data = state.entry(player_id).or_default();
-                data.undo_data = Some(undo_blocks);
+                data.clipboard = Some(clipboard);
             }
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("{count} block(s) pasted."))
-                        .color_named(NamedColor::Aqua),
-                )
+            notify(sender, Level::Success, msg!("shared_fetched", name, format_count(block_count)))
                 .await;
 
-            Ok(count)
+            Ok(block_count as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we clipboard unpublish <name>
+// ============================================================================
+
+pub struct ClipboardUnpublishExecutor;
+
+#[async_trait]
+impl CommandExecutor for ClipboardUnpublishExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, CLIPBOARD_PUBLISH).await?;
+            let player_id = sender_uuid(sender)?;
+            let name = SimpleArgConsumer::find_arg(args, ARG_SHARED_NAME)?;
+            let is_admin = sender.has_permission(ADMIN).await;
+
+            shared::remove(name, player_id, is_admin).map_err(flag_error)?;
+
+            notify(sender, Level::Success, msg!("shared_removed", name)).await;
+
+            Ok(1)
         })
     }
 }
+
+// ============================================================================
+// /we clipboard shared
+// ============================================================================
+
+pub struct ClipboardSharedExecutor;
+
+#[async_trait]
+impl CommandExecutor for ClipboardSharedExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, CLIPBOARD_FETCH).await?;
+
+            let entries = shared::list();
+            if entries.is_empty() {
+                notify(sender, Level::Warning, msg!("shared_none")).await;
+                return Ok(0);
+            }
+
+            let mut listing = Listing::new(msg!("shared_header", entries.len()));
+            for entry in &entries {
+                listing.push(msg!(
+                    "shared_entry",
+                    entry.name,
+                    entry.owner_name,
+                    format_count(entry.blocks),
+                    entry.idle.as_secs() / 60
+                ));
+            }
+            listing.send(sender).await;
+
+            Ok(entries.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the clipboard commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("copy")
+            .execute(CopyExecutor)
+            .then(argument(ARG_FLAGS, MsgArgConsumer).execute(CopyExecutor)),
+    )
+    .then(
+        literal("paste")
+            .execute(PasteExecutor)
+            .then(argument(ARG_FLAGS, MsgArgConsumer).execute(PasteExecutor)),
+    )
+    .then(literal("rotate").then(argument(ARG_ANGLE, SimpleArgConsumer).execute(RotateExecutor)))
+    .then(literal("flip").then(argument(ARG_DIRECTION, SimpleArgConsumer).execute(FlipExecutor)))
+    .then(
+        literal("clipboard")
+            .then(literal("publish").then(
+                argument(ARG_SHARED_NAME, SimpleArgConsumer).execute(ClipboardPublishExecutor),
+            ))
+            .then(
+                literal("fetch").then(
+                    argument(ARG_SHARED_NAME, SimpleArgConsumer).execute(ClipboardFetchExecutor),
+                ),
+            )
+            .then(literal("unpublish").then(
+                argument(ARG_SHARED_NAME, SimpleArgConsumer).execute(ClipboardUnpublishExecutor),
+            ))
+            .then(literal("shared").execute(ClipboardSharedExecutor))
+            .then(literal("info").execute(ClipboardInfoExecutor))
+            .then(literal("trim").execute(ClipboardTrimExecutor))
+            .then(literal("replace").then(argument(ARG_REMAP_FROM, SimpleArgConsumer).then(
+                argument(ARG_REMAP_TO, BlockArgumentConsumer).execute(ClipboardReplaceExecutor),
+            ))),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use pumpkin_world::world::BlockFlags;
+
+    use super::*;
+    use crate::engine::restore;
+    use crate::testing::{player, pop_undo, pos, start, FakeWorld};
+    use crate::transform::{property, with_property};
+
+    /// A small build to copy: a stone floor with a glass block and a torch on it.
+    fn build(world: &FakeWorld) {
+        world.fill(pos(0, 64, 0), pos(2, 64, 2), Block::STONE.default_state.id);
+        world.set(pos(1, 65, 1), Block::GLASS.default_state.id);
+        world.set(pos(0, 65, 0), Block::TORCH.default_state.id);
+    }
+
+    /// Copy `min..=max` relative to `origin`, like `//copy` standing at `origin`.
+    async fn copy(
+        world: &FakeWorld,
+        min: BlockPos,
+        max: BlockPos,
+        origin: BlockPos,
+    ) -> ClipboardData {
+        match read_region(world, &min, &max, &origin).await {
+            Ok(blocks) => ClipboardData { blocks, biomes: None, source: None },
+            Err(e) => panic!("copying from the test world failed: {e}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn paste_reproduces_the_copy_and_undo_removes_it() {
+        let world = FakeWorld::new("overworld");
+        build(&world);
+        let original = world.snapshot();
+        // Something in the way of the paste's air, which it overwrites
+        world.set(pos(11, 66, 0), Block::DIRT.default_state.id);
+        let in_the_way = world.snapshot();
+
+        let clipboard = copy(&world, pos(0, 64, 0), pos(2, 66, 2), pos(0, 64, 0)).await;
+        let writes = paste_writes(&clipboard, &pos(10, 64, 0), false);
+        let mut session = start(&world, player(16), "//paste");
+        assert_eq!(write_paste(&mut session, &writes, &[], None, &mut 0).await, Ok(()));
+        session.commit();
+
+        let mut pasted = original.clone();
+        pasted.extend(original.iter().map(|(&(x, y, z), &state_id)| ((x + 10, y, z), state_id)));
+        assert_eq!(world.snapshot(), pasted);
+        // The torch is placed after the stone it stands on
+        let torch = writes.iter().position(|(p, _)| *p == pos(10, 65, 0));
+        let floor = writes.iter().position(|(p, _)| *p == pos(10, 64, 0));
+        assert!(floor < torch);
+
+        let entry = pop_undo(player(16));
+        let (_, result) = restore(world.as_ref(), &entry.changes, BlockFlags::FORCE_STATE).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(world.snapshot(), in_the_way);
+    }
+
+    #[tokio::test]
+    async fn paste_without_air_or_outside_the_mask_keeps_the_world() {
+        let world = FakeWorld::new("overworld");
+        build(&world);
+        let clipboard = copy(&world, pos(0, 64, 0), pos(2, 65, 2), pos(0, 64, 0)).await;
+        let dirt = Block::DIRT.default_state.id;
+        let target = (pos(10, 64, 0), pos(12, 65, 2));
+        world.fill(target.0, target.1, dirt);
+
+        // -a: the air above the floor doesn't clear the dirt
+        let writes = paste_writes(&clipboard, &target.0, true);
+        assert_eq!(writes.len(), 11);
+        let mut session = start(&world, player(17), "//paste -a");
+        assert_eq!(write_paste(&mut session, &writes, &[], None, &mut 0).await, Ok(()));
+        session.commit();
+        assert_eq!(world.get(pos(12, 65, 2)), dirt);
+        assert_eq!(world.get(pos(11, 65, 1)), Block::GLASS.default_state.id);
+
+        // -m: only the one position holding glass takes the pasted block
+        let target = (pos(20, 64, 0), pos(22, 65, 2));
+        world.fill(target.0, target.1, dirt);
+        world.set(target.0, Block::GLASS.default_state.id);
+        let writes = paste_writes(&clipboard, &target.0, false);
+        let mask = Mask::parse("glass").unwrap();
+        let mut masked = 0;
+        let mut session = start(&world, player(17), "//paste -m glass");
+        let result = write_paste(&mut session, &writes, &[], Some((&mask, &target)), &mut masked);
+        assert_eq!(result.await, Ok(()));
+        assert_eq!((session.commit().blocks, masked), (1, 17));
+        assert_eq!(world.get(target.0), Block::STONE.default_state.id);
+        assert_eq!(world.get(pos(21, 65, 1)), dirt);
+    }
+
+    #[test]
+    fn remap_replaces_tagged_blocks_and_keeps_properties() {
+        let oak = with_property(Block::OAK_LOG.default_state.id, "axis", "x").unwrap();
+        let birch = Block::BIRCH_LOG.default_state.id;
+        let stone = Block::STONE.default_state.id;
+        let mut clipboard = ClipboardData {
+            blocks: vec![
+                (Vector3::new(0, 0, 0), oak),
+                (Vector3::new(1, 0, 0), birch),
+                (Vector3::new(2, 0, 0), stone),
+            ],
+            biomes: None,
+            source: None,
+        };
+
+        let sources = parse_block_list("#minecraft:logs").unwrap();
+        assert_eq!(remap_clipboard(&mut clipboard, &sources, &Block::SPRUCE_LOG), 2);
+
+        let states: Vec<u16> = clipboard.blocks.iter().map(|(_, state_id)| *state_id).collect();
+        assert_eq!(Block::from_state_id(states[0]).id, Block::SPRUCE_LOG.id);
+        assert_eq!(property(states[0], "axis"), Some("x"));
+        assert_eq!(Block::from_state_id(states[1]).id, Block::SPRUCE_LOG.id);
+        assert_eq!(states[2], stone);
+    }
+}
@@ -0,0 +1,182 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{message::MsgArgConsumer, simple::SimpleArgConsumer, ConsumedArgs, FindArg},
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
+    server::Server,
+};
+
+use crate::config;
+use crate::entities::{self, MobKind, REMOVABLE};
+use crate::messages::{format_count, msg};
+use crate::notify::{notify, Level};
+use crate::permissions::{require_permission, BUTCHER, REMOVE};
+use crate::state::{check_world_enabled, sender_position, sender_world};
+
+use super::{parse_radius, ARG_FLAGS};
+
+/// Argument name for the entity type in `remove`.
+pub const ARG_ENTITY_TYPE: &str = "type";
+/// Argument name for the radius in `remove`.
+pub const ARG_RADIUS: &str = "radius";
+
+/// Radius used by `butcher` when none is given.
+const DEFAULT_BUTCHER_RADIUS: u32 = 32;
+
+/// Send "Removed N entities: zombie ×12, skeleton ×3" sorted by count, using the catalog
+/// message `key`.
+async fn send_summary(sender: &CommandSender, key: &str, counts: HashMap<&'static str, usize>) {
+    let total: usize = counts.values().sum();
+    if total == 0 {
+        notify(sender, Level::Warning, msg!("entities_none")).await;
+        return;
+    }
+
+    let mut counts: Vec<_> = counts.into_iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let breakdown = counts
+        .iter()
+        .map(|(name, count)| format!("{name} ×{count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    notify(sender, Level::Success, msg!(key, format_count(total), breakdown)).await;
+}
+
+// ============================================================================
+// /we butcher [radius] [-a] [-b] [-n]
+// ============================================================================
+
+/// Removes hostile mobs around the player. `-a` includes animals, `-b` ambient mobs
+/// (bats) and `-n` entities with a custom name, which are otherwise spared.
+pub struct ButcherExecutor;
+
+#[async_trait]
+impl CommandExecutor for ButcherExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, BUTCHER).await?;
+
+            let flag_input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+            let mut radius = DEFAULT_BUTCHER_RADIUS.min(config::get().max_entity_radius);
+            let (mut animals, mut ambient, mut named) = (false, false, false);
+            for token in flag_input.split_whitespace() {
+                match token {
+                    "-a" => animals = true,
+                    "-b" => ambient = true,
+                    "-n" => named = true,
+                    value if !value.starts_with('-') => {
+                        radius = parse_radius(value, config::get().max_entity_radius)?;
+                    }
+                    other => return Err(CommandError::CommandFailed(msg!("unknown_flag", other))),
+                }
+            }
+
+            let world = sender_world(sender)?;
+            check_world_enabled(world.get_world_name())?;
+            let center = sender_position(sender)?;
+
+            let mut counts = HashMap::new();
+            for entity in entities::entities_near(&world, center, f64::from(radius)).await {
+                let name = entities::type_name(entity.as_ref());
+                let included = match entities::mob_kind(name) {
+                    Some(MobKind::Hostile) => true,
+                    Some(MobKind::Animal) => animals,
+                    Some(MobKind::Ambient) => ambient,
+                    None => false,
+                };
+                if !included || (!named && entities::is_named(entity.as_ref())) {
+                    continue;
+                }
+                entities::despawn(entity.as_ref()).await;
+                *counts.entry(name).or_insert(0) += 1;
+            }
+
+            let total: usize = counts.values().sum();
+            send_summary(sender, "butcher_done", counts).await;
+            Ok(total as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we remove <type> <radius>
+// ============================================================================
+
+/// Despawns loose entities (items, arrows, experience orbs, falling blocks, primed TNT).
+pub struct RemoveExecutor;
+
+#[async_trait]
+impl CommandExecutor for RemoveExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, REMOVE).await?;
+
+            let kind = SimpleArgConsumer::find_arg(args, ARG_ENTITY_TYPE)?;
+            let radius = parse_radius(
+                SimpleArgConsumer::find_arg(args, ARG_RADIUS)?,
+                config::get().max_entity_radius,
+            )?;
+            let (_, types) = REMOVABLE
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(kind))
+                .ok_or_else(|| {
+                    let names: Vec<_> = REMOVABLE.iter().map(|(name, _)| *name).collect();
+                    CommandError::CommandFailed(msg!("unknown_entity_type", kind, names.join(", ")))
+                })?;
+
+            let world = sender_world(sender)?;
+            check_world_enabled(world.get_world_name())?;
+            let center = sender_position(sender)?;
+
+            let mut counts = HashMap::new();
+            for entity in entities::entities_near(&world, center, f64::from(radius)).await {
+                let name = entities::type_name(entity.as_ref());
+                if types.contains(&name) {
+                    entities::despawn(entity.as_ref()).await;
+                    *counts.entry(name).or_insert(0) += 1;
+                }
+            }
+
+            let total: usize = counts.values().sum();
+            send_summary(sender, "remove_done", counts).await;
+            Ok(total as i32)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the entity commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("butcher")
+            .execute(ButcherExecutor)
+            .then(argument(ARG_FLAGS, MsgArgConsumer).execute(ButcherExecutor)),
+    )
+    .then(
+        literal("remove").then(
+            argument(ARG_ENTITY_TYPE, SimpleArgConsumer)
+                .then(argument(ARG_RADIUS, SimpleArgConsumer).execute(RemoveExecutor)),
+        ),
+    )
+}
@@ -0,0 +1,371 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{
+            block::BlockArgumentConsumer, message::MsgArgConsumer, simple::SimpleArgConsumer,
+            ConsumedArgs, FindArg,
+        },
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_data::Block;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+
+use crate::config;
+use crate::cooldowns;
+use crate::engine::{BlockAccess, EditSession};
+use crate::flags::{FlagSpec, Flags};
+use crate::messages::{format_count, msg};
+use crate::notify::{notify, Level};
+use crate::shapes::{Ellipsoid, Half, Tube};
+use crate::state::{
+    check_selection_size, placement_flags, placement_note, sender_block_pos, sender_uuid,
+    sender_world,
+};
+
+use super::{parse_radius, ARG_FLAGS};
+
+/// Argument name for the block of a generated shape.
+pub const ARG_SHAPE_BLOCK: &str = "block";
+/// Argument name for the radius of `sphere`, `dome` and `bowl`, and the X radius of `ellipsoid`.
+pub const ARG_SHAPE_RADIUS: &str = "radius";
+/// Argument name for the Y radius of `ellipsoid`.
+pub const ARG_RADIUS_Y: &str = "radius_y";
+/// Argument name for the Z radius of `ellipsoid`.
+pub const ARG_RADIUS_Z: &str = "radius_z";
+/// Argument name for the height of `tube`.
+pub const ARG_TUBE_HEIGHT: &str = "height";
+/// Argument name for the wall thickness of `tube` and `ring`.
+pub const ARG_THICKNESS: &str = "thickness";
+
+/// Flags accepted by every shape command.
+const SHAPE_FLAGS: &[FlagSpec] = &[FlagSpec::switch("-h")];
+
+/// Place the blocks at `offsets` from `center` as one undoable operation and report the
+/// result. `bounds` are the shape's smallest and largest offsets, checked against the size
+/// limit first. Blocks outside the world's build height are left out with a warning.
+async fn shape_operation(
+    sender: &CommandSender,
+    command: String,
+    block: &Block,
+    center: BlockPos,
+    (min, max): (Vector3<i32>, Vector3<i32>),
+    offsets: Vec<Vector3<i32>>,
+) -> Result<i32, CommandError> {
+    let player_id = sender_uuid(sender)?;
+    let world = sender_world(sender)?;
+    let low = BlockPos(Vector3::new(center.0.x + min.x, center.0.y + min.y, center.0.z + min.z));
+    let high = BlockPos(Vector3::new(center.0.x + max.x, center.0.y + max.y, center.0.z + max.z));
+    check_selection_size(&low, &high)?;
+
+    let (min_y, max_y) = world.height_range();
+    let state_id = block.default_state.id;
+    let flags = placement_flags(&player_id, false);
+    let mut session = EditSession::new(world, player_id, command, flags)?;
+    cooldowns::take_edit(sender, &low, &high)?;
+    let mut clipped = 0usize;
+    let result = async {
+        for offset in offsets {
+            let pos = BlockPos(Vector3::new(
+                center.0.x + offset.x,
+                center.0.y + offset.y,
+                center.0.z + offset.z,
+            ));
+            if pos.0.y < min_y || pos.0.y > max_y {
+                clipped += 1;
+                continue;
+            }
+            session.set_block(pos, state_id).await?;
+        }
+        Ok(())
+    }
+    .await;
+    let done = session.finish(result)?;
+
+    notify(
+        sender,
+        Level::Success,
+        msg!("shape_done", format_count(done.blocks), done.time(), placement_note(flags)),
+    )
+    .await;
+    if clipped > 0 {
+        notify(sender, Level::Warning, msg!("shape_clipped", format_count(clipped))).await;
+    }
+
+    Ok(done.blocks as i32)
+}
+
+/// Command suffix recording the hollow flag.
+fn hollow_suffix(hollow: bool) -> &'static str {
+    if hollow { " -h" } else { "" }
+}
+
+// ============================================================================
+// //sphere <block> <radius> [-h], //ellipsoid <block> <rx> <ry> <rz> [-h]
+// ============================================================================
+
+/// Places a sphere centered on the player's feet; `-h` makes it hollow.
+pub struct SphereExecutor;
+
+#[async_trait]
+impl CommandExecutor for SphereExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_SHAPE_BLOCK)?;
+            let max = config::get().max_scan_radius;
+            let radius =
+                parse_radius(SimpleArgConsumer::find_arg(args, ARG_SHAPE_RADIUS)?, max)? as i32;
+            let hollow = Flags::from_args(args, SHAPE_FLAGS)?.has("-h");
+
+            let center = sender_block_pos(sender)?;
+            let shape = Ellipsoid::new(Vector3::new(radius, radius, radius), Half::Whole, hollow);
+            let command = format!("//sphere {} {radius}{}", block.name, hollow_suffix(hollow));
+            shape_operation(sender, command, block, center, shape.bounds(), shape.offsets()).await
+        })
+    }
+}
+
+/// Places an ellipsoid with separate X, Y and Z radii centered on the player's feet; `-h`
+/// makes it hollow.
+pub struct EllipsoidExecutor;
+
+#[async_trait]
+impl CommandExecutor for EllipsoidExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_SHAPE_BLOCK)?;
+            let max = config::get().max_scan_radius;
+            let radius = |name: &str| -> Result<i32, CommandError> {
+                Ok(parse_radius(SimpleArgConsumer::find_arg(args, name)?, max)? as i32)
+            };
+            let radii = Vector3::new(
+                radius(ARG_SHAPE_RADIUS)?,
+                radius(ARG_RADIUS_Y)?,
+                radius(ARG_RADIUS_Z)?,
+            );
+            let hollow = Flags::from_args(args, SHAPE_FLAGS)?.has("-h");
+
+            let center = sender_block_pos(sender)?;
+            let shape = Ellipsoid::new(radii, Half::Whole, hollow);
+            let command = format!(
+                "//ellipsoid {} {} {} {}{}",
+                block.name,
+                radii.x,
+                radii.y,
+                radii.z,
+                hollow_suffix(hollow)
+            );
+            shape_operation(sender, command, block, center, shape.bounds(), shape.offsets()).await
+        })
+    }
+}
+
+// ============================================================================
+// //dome <block> <radius> [height] [-h], //bowl <block> <radius> [depth] [-h]
+// ============================================================================
+
+/// Places half an ellipsoid: a dome rising from the player's feet, or a bowl hanging below
+/// them with its rim level with the ground. The optional value after the radius is the
+/// dome's height or the bowl's depth (default: the radius); `-h` makes it a shell. A bowl
+/// of air digs a crater.
+pub struct HalfShapeExecutor {
+    pub half: Half,
+}
+
+#[async_trait]
+impl CommandExecutor for HalfShapeExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_SHAPE_BLOCK)?;
+            let max = config::get().max_scan_radius;
+            let radius =
+                parse_radius(SimpleArgConsumer::find_arg(args, ARG_SHAPE_RADIUS)?, max)? as i32;
+            let (values, flags) = Flags::from_args_with_values(args, 1, SHAPE_FLAGS)?;
+            let height = match values.first() {
+                Some(value) => parse_radius(value, max)? as i32,
+                None => radius,
+            };
+            let hollow = flags.has("-h");
+
+            let feet = sender_block_pos(sender)?;
+            let (name, center) = match self.half {
+                Half::Lower => ("bowl", BlockPos(Vector3::new(feet.0.x, feet.0.y - 1, feet.0.z))),
+                _ => ("dome", feet),
+            };
+            let shape = Ellipsoid::new(Vector3::new(radius, height, radius), self.half, hollow);
+            let command =
+                format!("//{name} {} {radius} {height}{}", block.name, hollow_suffix(hollow));
+            shape_operation(sender, command, block, center, shape.bounds(), shape.offsets()).await
+        })
+    }
+}
+
+// ============================================================================
+// //tube <block> <radius> <height> [thickness], //ring <block> <radius> [thickness]
+// ============================================================================
+
+/// Ring thickness from the optional argument, 1 if it was left out.
+fn parse_thickness(args: &ConsumedArgs<'_>) -> Result<i32, CommandError> {
+    let Ok(value) = SimpleArgConsumer::find_arg(args, ARG_THICKNESS) else {
+        return Ok(1);
+    };
+    value
+        .parse::<i32>()
+        .ok()
+        .filter(|thickness| *thickness >= 1)
+        .ok_or(CommandError::CommandFailed(msg!("invalid_thickness", value)))
+}
+
+/// Places a hollow vertical cylinder standing on the player's feet level, for towers and
+/// silos. The wall is `thickness` blocks thick (default 1), measured inwards from the radius.
+pub struct TubeExecutor;
+
+#[async_trait]
+impl CommandExecutor for TubeExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_SHAPE_BLOCK)?;
+            let max = config::get().max_scan_radius;
+            let radius =
+                parse_radius(SimpleArgConsumer::find_arg(args, ARG_SHAPE_RADIUS)?, max)? as i32;
+            let height = SimpleArgConsumer::find_arg(args, ARG_TUBE_HEIGHT)?;
+            let height = height
+                .parse::<i32>()
+                .ok()
+                .filter(|height| *height >= 1)
+                .ok_or(CommandError::CommandFailed(msg!("invalid_height", height)))?;
+            let thickness = parse_thickness(args)?;
+
+            let center = sender_block_pos(sender)?;
+            let tube = Tube::new(radius, thickness, height);
+            let command = format!("//tube {} {radius} {height} {thickness}", block.name);
+            shape_operation(sender, command, block, center, tube.bounds(), tube.offsets()).await
+        })
+    }
+}
+
+/// Places a flat ring at the player's feet level, `thickness` blocks wide (default 1).
+pub struct RingExecutor;
+
+#[async_trait]
+impl CommandExecutor for RingExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_SHAPE_BLOCK)?;
+            let max = config::get().max_scan_radius;
+            let radius =
+                parse_radius(SimpleArgConsumer::find_arg(args, ARG_SHAPE_RADIUS)?, max)? as i32;
+            let thickness = parse_thickness(args)?;
+
+            let center = sender_block_pos(sender)?;
+            let ring = Tube::new(radius, thickness, 1);
+            let command = format!("//ring {} {radius} {thickness}", block.name);
+            shape_operation(sender, command, block, center, ring.bounds(), ring.offsets()).await
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the generation commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("sphere").then(
+            argument(ARG_SHAPE_BLOCK, BlockArgumentConsumer).then(
+                argument(ARG_SHAPE_RADIUS, SimpleArgConsumer)
+                    .execute(SphereExecutor)
+                    .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SphereExecutor)),
+            ),
+        ),
+    )
+    .then(
+        literal("ellipsoid").then(
+            argument(ARG_SHAPE_BLOCK, BlockArgumentConsumer).then(
+                argument(ARG_SHAPE_RADIUS, SimpleArgConsumer).then(
+                    argument(ARG_RADIUS_Y, SimpleArgConsumer).then(
+                        argument(ARG_RADIUS_Z, SimpleArgConsumer)
+                            .execute(EllipsoidExecutor)
+                            .then(argument(ARG_FLAGS, MsgArgConsumer).execute(EllipsoidExecutor)),
+                    ),
+                ),
+            ),
+        ),
+    )
+    .then(
+        literal("dome").then(
+            argument(ARG_SHAPE_BLOCK, BlockArgumentConsumer).then(
+                argument(ARG_SHAPE_RADIUS, SimpleArgConsumer)
+                    .execute(HalfShapeExecutor { half: Half::Upper })
+                    .then(
+                        argument(ARG_FLAGS, MsgArgConsumer)
+                            .execute(HalfShapeExecutor { half: Half::Upper }),
+                    ),
+            ),
+        ),
+    )
+    .then(
+        literal("bowl").then(
+            argument(ARG_SHAPE_BLOCK, BlockArgumentConsumer).then(
+                argument(ARG_SHAPE_RADIUS, SimpleArgConsumer)
+                    .execute(HalfShapeExecutor { half: Half::Lower })
+                    .then(
+                        argument(ARG_FLAGS, MsgArgConsumer)
+                            .execute(HalfShapeExecutor { half: Half::Lower }),
+                    ),
+            ),
+        ),
+    )
+    .then(
+        literal("tube").then(
+            argument(ARG_SHAPE_BLOCK, BlockArgumentConsumer).then(
+                argument(ARG_SHAPE_RADIUS, SimpleArgConsumer).then(
+                    argument(ARG_TUBE_HEIGHT, SimpleArgConsumer)
+                        .execute(TubeExecutor)
+                        .then(argument(ARG_THICKNESS, SimpleArgConsumer).execute(TubeExecutor)),
+                ),
+            ),
+        ),
+    )
+    .then(
+        literal("ring").then(
+            argument(ARG_SHAPE_BLOCK, BlockArgumentConsumer).then(
+                argument(ARG_SHAPE_RADIUS, SimpleArgConsumer)
+                    .execute(RingExecutor)
+                    .then(argument(ARG_THICKNESS, SimpleArgConsumer).execute(RingExecutor)),
+            ),
+        ),
+    )
+}
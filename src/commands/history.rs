@@ -1,23 +1,183 @@
+use std::time::Instant;
+
 use async_trait::async_trait;
 use pumpkin::{
     command::{
-        args::ConsumedArgs, dispatcher::CommandError, CommandExecutor, CommandResult, CommandSender,
+        args::{message::MsgArgConsumer, simple::SimpleArgConsumer, ConsumedArgs, FindArg},
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
     },
     server::Server,
 };
-use pumpkin_util::text::{color::NamedColor, TextComponent};
-use pumpkin_world::world::BlockFlags;
 
-use crate::state::{sender_uuid, sender_world, PLAYER_DATA};
+use crate::engine::{restore, BlockAccess};
+use crate::flags::{FlagSpec, Flags};
+use crate::history::{History, HistoryEntry};
+use crate::history_store;
+use crate::messages::{format_count, msg};
+use crate::notify::{notify, Level, Listing};
+use crate::state::{
+    check_world_enabled, placement_flags, sender_uuid, sender_world, OperationGuard, PLAYER_DATA,
+};
+use crate::stats::{self, format_duration};
+use crate::tracking;
+
+use super::ARG_FLAGS;
+
+/// Argument name for the repetition count in `repeat`.
+pub const ARG_COUNT: &str = "count";
+
+/// Largest count accepted by `repeat`.
+const MAX_REPEAT: u32 = 100;
+
+/// Flags accepted by `undo`.
+const UNDO_FLAGS: &[FlagSpec] = &[FlagSpec::with_value("-i", "id")];
 
 // ============================================================================
-// //undo
+// //undo [-i <id>]
 // ============================================================================
 
+/// Undoes the newest history entry, or with `-i` the entry with that id from `/we history`.
+/// An older entry is only undone where no newer entry changed the same blocks, so newer edits
+/// stay intact; the blocks left alone are reported.
 pub struct UndoExecutor;
 
 #[async_trait]
 impl CommandExecutor for UndoExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let id = match Flags::from_args(args, UNDO_FLAGS)?.value("-i") {
+                Some(value) => Some(value.trim_start_matches('#').parse::<u64>().map_err(|_| {
+                    CommandError::CommandFailed(msg!("invalid_history_id", value))
+                })?),
+                None => None,
+            };
+            let world = sender_world(sender)?;
+            check_world_enabled(world.get_world_name())?;
+            let _guard = OperationGuard::acquire(player_id)?;
+            history_store::reload(player_id);
+
+            // Take the entry out of state (releases the lock before async work)
+            let (entry, skipped) = {
+                let mut state = PLAYER_DATA.lock();
+                let history = &mut state.entry(player_id).or_default().history;
+                take_undo(history, id, &world.world_name())?
+            };
+
+            // Restore newest-first so overlapping writes unwind in the right order,
+            // capturing the current states so the entry can be redone
+            let flags = placement_flags(&player_id, false);
+            let started = Instant::now();
+            let (redo_blocks, result) = restore(world.as_ref(), &entry.changes, flags).await;
+            let count = redo_blocks.len();
+            let elapsed = started.elapsed();
+            stats::record_operation(player_id, "//undo", count, elapsed);
+            tracking::record(
+                &world.world_name(),
+                player_id,
+                &format!("//undo {}", entry.info.label()),
+                &redo_blocks.positions(),
+            );
+
+            let description = entry.describe();
+            {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player_id).or_default();
+                data.history.push_redo(HistoryEntry {
+                    info: entry.info,
+                    changes: redo_blocks,
+                });
+            }
+
+            result.map_err(|e| {
+                CommandError::CommandFailed(msg!("undo_failed", format_count(count), e))
+            })?;
+
+            let message = match id {
+                Some(id) if skipped > 0 => msg!(
+                    "undo_partial",
+                    id,
+                    format_count(count),
+                    format_duration(elapsed),
+                    format_count(skipped)
+                ),
+                _ => msg!("undo_done", format_count(count), format_duration(elapsed), description),
+            };
+            notify(sender, Level::Success, message).await;
+
+            Ok(count as i32)
+        })
+    }
+}
+
+/// Take the entry `//undo` reverts: the newest, or the one with `id` without the positions
+/// newer entries changed since, returned with how many that leaves out. An entry made in
+/// another world than `world` stays where it is, as undoing it here would write its blocks
+/// into the wrong world.
+fn take_undo(
+    history: &mut History,
+    id: Option<u64>,
+    world: &str,
+) -> Result<(HistoryEntry, usize), CommandError> {
+    let Some(id) = id else {
+        let entry =
+            history.pop_undo().ok_or(CommandError::CommandFailed(msg!("nothing_to_undo")))?;
+        if let Err(error) = check_entry_world(&entry, world) {
+            history.push_undo(entry);
+            return Err(error);
+        }
+        return Ok((entry, 0));
+    };
+    let not_found = || CommandError::CommandFailed(msg!("history_id_not_found", id));
+    let entry = history.entries().find(|entry| entry.info.id == id).ok_or_else(not_found)?;
+    check_entry_world(entry, world)?;
+    let (mut entry, newer) = history.take_undo_by_id(id).ok_or_else(not_found)?;
+    let (changes, skipped) = entry.changes.without(&newer.positions, &newer.cells);
+    entry.changes = changes;
+    Ok((entry, skipped))
+}
+
+/// Take the entry `//redo` reapplies, leaving it in place if it was made in another world.
+fn take_redo(history: &mut History, world: &str) -> Result<HistoryEntry, CommandError> {
+    let entry = history.pop_redo().ok_or(CommandError::CommandFailed(msg!("nothing_to_redo")))?;
+    if let Err(error) = check_entry_world(&entry, world) {
+        history.push_redo(entry);
+        return Err(error);
+    }
+    Ok(entry)
+}
+
+/// Refuse an entry made in another world than `world`, naming the world it belongs to.
+fn check_entry_world(entry: &HistoryEntry, world: &str) -> Result<(), CommandError> {
+    if entry.info.world == world {
+        Ok(())
+    } else {
+        Err(CommandError::CommandFailed(msg!(
+            "history_other_world",
+            entry.info.label(),
+            entry.info.world
+        )))
+    }
+}
+
+// ============================================================================
+// //redo
+// ============================================================================
+
+pub struct RedoExecutor;
+
+#[async_trait]
+impl CommandExecutor for RedoExecutor {
     fn execute<'a>(
         &'a self,
         sender: &'a CommandSender,
@@ -27,34 +187,219 @@ impl CommandExecutor for UndoExecutor {
         Box::pin(async move {
             let player_id = sender_uuid(sender)?;
             let world = sender_world(sender)?;
+            check_world_enabled(world.get_world_name())?;
+            let _guard = OperationGuard::acquire(player_id)?;
+
+            let entry = {
+                let mut state = PLAYER_DATA.lock();
+                let data = state
+                    .get_mut(&player_id)
+                    .ok_or(CommandError::CommandFailed(msg!("nothing_to_redo")))?;
+                take_redo(&mut data.history, &world.world_name())?
+            };
+
+            // The redo entry was recorded while undoing, so reversing it replays the
+            // original operation in its original order
+            let flags = placement_flags(&player_id, false);
+            let started = Instant::now();
+            let (undo_blocks, result) = restore(world.as_ref(), &entry.changes, flags).await;
+            let count = undo_blocks.len();
+            let elapsed = started.elapsed();
+            stats::record_operation(player_id, "//redo", count, elapsed);
+            tracking::record(
+                &world.world_name(),
+                player_id,
+                &format!("//redo {}", entry.info.label()),
+                &undo_blocks.positions(),
+            );
+
+            let description = entry.describe();
+            {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player_id).or_default();
+                data.history.push_undo(HistoryEntry {
+                    info: entry.info,
+                    changes: undo_blocks,
+                });
+            }
+
+            result.map_err(|e| {
+                CommandError::CommandFailed(msg!("redo_failed", format_count(count), e))
+            })?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("redo_done", format_count(count), format_duration(elapsed), description),
+            )
+            .await;
+
+            Ok(count as i32)
+        })
+    }
+}
 
-            // Take undo data out of state (releases the lock before async work)
-            let undo_blocks = {
-                let mut state = PLAYER_DATA.lock().unwrap();
-                let data = state.get_mut(&player_id).ok_or(CommandError::CommandFailed(
-                    TextComponent::text("Nothing to undo.").color_named(NamedColor::Red),
-                ))?;
-                data.undo_data.take().ok_or(CommandError::CommandFailed(
-                    TextComponent::text("Nothing to undo.").color_named(NamedColor::Red),
-                ))?
+// ============================================================================
+// //history
+// ============================================================================
+
+pub struct HistoryExecutor;
+
+#[async_trait]
+impl CommandExecutor for HistoryExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            history_store::reload(player_id);
+
+            let (lines, redo_count) = {
+                let state = PLAYER_DATA.lock();
+                match state.get(&player_id) {
+                    Some(data) => (
+                        data.history
+                            .entries()
+                            .map(|entry| (entry.info.id, entry.describe()))
+                            .collect::<Vec<_>>(),
+                        data.history.redo_len(),
+                    ),
+                    None => (Vec::new(), 0),
+                }
+            };
+
+            if lines.is_empty() {
+                notify(sender, Level::Warning, msg!("history_empty")).await;
+                return Ok(0);
+            }
+
+            let mut listing = Listing::new(msg!("history_header", lines.len()));
+            for (id, line) in &lines {
+                listing.push(msg!("history_entry", id, line));
+            }
+            if redo_count > 0 {
+                listing.push(msg!("history_redo_count", redo_count));
+            }
+            listing.send(sender).await;
+
+            Ok(lines.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we repeat [count]
+// ============================================================================
+
+/// Re-runs the player's last repeatable command `count` times (default 1). Each run goes
+/// through the dispatcher on its own, so it gets its own history entry.
+pub struct RepeatExecutor;
+
+#[async_trait]
+impl CommandExecutor for RepeatExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let count = match SimpleArgConsumer::find_arg(args, ARG_COUNT) {
+                Ok(value) => value
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| (1..=MAX_REPEAT).contains(n))
+                    .ok_or_else(|| CommandError::CommandFailed(msg!("repeat_count", MAX_REPEAT)))?,
+                Err(_) => 1,
             };
 
-            let mut count = 0i32;
-            for (pos, old_state_id) in &undo_blocks {
-                world
-                    .set_block_state(pos, *old_state_id, BlockFlags::FORCE_STATE)
-                    .await;
-                count += 1;
+            let command = {
+                let state = PLAYER_DATA.lock();
+                state.get(&player_id).and_then(|data| data.last_command.clone())
             }
+            .ok_or(CommandError::CommandFailed(msg!("repeat_nothing")))?;
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("Undo: {count} block(s) restored."))
-                        .color_named(NamedColor::Green),
-                )
-                .await;
+            for run in 1..=count {
+                // Locked per run only, so the repeats don't hold the dispatcher between them
+                let dispatcher = server.command_dispatcher.read().await;
+                let result = dispatcher.dispatch(sender, server, &command).await;
+                drop(dispatcher);
+                if let Err(e) = result {
+                    if let CommandError::CommandFailed(message) = e {
+                        sender.send_message(message).await;
+                    }
+                    return Err(CommandError::CommandFailed(msg!(
+                        "repeat_stopped",
+                        run,
+                        count,
+                        command
+                    )));
+                }
+            }
+
+            if count > 1 {
+                notify(sender, Level::Success, msg!("repeat_done", command, count)).await;
+            }
 
-            Ok(count)
+            Ok(count as i32)
         })
     }
 }
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the history commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("undo")
+            .execute(UndoExecutor)
+            .then(argument(ARG_FLAGS, MsgArgConsumer).execute(UndoExecutor)),
+    )
+    .then(literal("redo").execute(RedoExecutor))
+    .then(literal("history").execute(HistoryExecutor))
+    .then(
+        literal("repeat")
+            .execute(RepeatExecutor)
+            .then(argument(ARG_COUNT, SimpleArgConsumer).execute(RepeatExecutor)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{OperationInfo, UndoEntry};
+    use crate::testing::pos;
+
+    fn entry(world: &str) -> HistoryEntry {
+        let mut changes = UndoEntry::new();
+        changes.record(pos(0, 64, 0), 1, None);
+        HistoryEntry { info: OperationInfo::new("//set stone", 1, world), changes }
+    }
+
+    #[test]
+    fn undo_and_redo_stay_in_their_world() {
+        let mut history = History::default();
+        history.record(entry("overworld"));
+
+        assert!(take_undo(&mut history, None, "the_nether").is_err());
+        assert!(take_undo(&mut history, Some(1), "the_nether").is_err());
+        assert_eq!(history.entries().count(), 1);
+        let (undone, skipped) = match take_undo(&mut history, None, "overworld") {
+            Ok(taken) => taken,
+            Err(_) => panic!("undo in the entry's own world was refused"),
+        };
+        assert_eq!((undone.info.id, skipped), (1, 0));
+
+        history.push_redo(undone);
+        assert!(take_redo(&mut history, "the_nether").is_err());
+        assert_eq!(history.redo_len(), 1);
+        assert!(take_redo(&mut history, "overworld").is_ok());
+        assert_eq!(history.redo_len(), 0);
+    }
+}
@@ -1,77 +1,66 @@
-pub mod clipboard;
-pub mod history;
-pub mod region;
-pub mod schematic;
-pub mod selection;
+mod admin;
+mod clipboard;
+mod entity;
+mod generation;
+mod history;
+mod region;
+mod schematic;
+mod script;
+mod selection;
+mod settings;
+mod snapshot;
+mod tool;
+mod utility;
 
-use pumpkin::{
-    command::{
-        args::{block::BlockArgumentConsumer, simple::SimpleArgConsumer},
-        tree::{
-            builder::{argument, literal},
-            CommandTree,
-        },
-    },
-};
+use pumpkin::command::{dispatcher::CommandError, tree::CommandTree};
+use pumpkin_util::text::{color::NamedColor, TextComponent};
 
-use clipboard::{CopyExecutor, PasteExecutor};
-use history::UndoExecutor;
-use region::{
-    ClearExecutor, HollowExecutor, ReplaceExecutor, SetExecutor, WallsExecutor, ARG_BLOCK,
-    ARG_FROM, ARG_TO,
-};
-use schematic::{
-    SchemDeleteExecutor, SchemListExecutor, SchemLoadExecutor, SchemSaveExecutor, ARG_SCHEM_NAME,
-};
-use selection::{Pos1Executor, Pos2Executor, SizeExecutor};
+use crate::flags::flag_tokens;
+use crate::messages::msg;
 
-const COMMAND_NAMES: [&str; 2] = ["we", "worldedit"];
+pub const COMMAND_NAMES: [&str; 2] = ["we", "worldedit"];
 const COMMAND_DESCRIPTION: &str = "WorldEdit commands for region editing.";
 
+/// Argument name for trailing flags with values (`-r 90 -f north`).
+pub const ARG_FLAGS: &str = "flags";
+
+/// Red error carrying a message from a helper module (mask, transform, script parsing), which
+/// renders it from the catalog with `msg_string!`.
+fn flag_error(message: impl Into<String>) -> CommandError {
+    CommandError::CommandFailed(TextComponent::text(message.into()).color_named(NamedColor::Red))
+}
+
+/// Parse a radius argument in `1..=max`.
+fn parse_radius(value: &str, max: u32) -> Result<u32, CommandError> {
+    let radius: u32 = value
+        .parse()
+        .map_err(|_| CommandError::CommandFailed(msg!("invalid_radius", value)))?;
+    if radius == 0 || radius > max {
+        return Err(CommandError::CommandFailed(msg!("radius_range", max)));
+    }
+    Ok(radius)
+}
+
+/// Every command module's `register`, in the order the commands are listed. A module left
+/// out here leaves its `register` unused, which fails the build under `-D warnings`.
+const MODULES: [fn(CommandTree) -> CommandTree; 13] = [
+    selection::register,
+    region::register,
+    utility::register,
+    generation::register,
+    clipboard::register,
+    history::register,
+    settings::register,
+    tool::register,
+    entity::register,
+    admin::register,
+    schematic::register,
+    snapshot::register,
+    script::register,
+];
+
 /// Build the full `/we` command tree with all subcommands.
 pub fn build_command_tree() -> CommandTree {
-    CommandTree::new(COMMAND_NAMES, COMMAND_DESCRIPTION)
-        // Selection
-        .then(literal("pos1").execute(Pos1Executor))
-        .then(literal("pos2").execute(Pos2Executor))
-        .then(literal("size").execute(SizeExecutor))
-        // Region editing
-        .then(
-            literal("set").then(argument(ARG_BLOCK, BlockArgumentConsumer).execute(SetExecutor)),
-        )
-        .then(literal("replace").then(
-            argument(ARG_FROM, BlockArgumentConsumer)
-                .then(argument(ARG_TO, BlockArgumentConsumer).execute(ReplaceExecutor)),
-        ))
-        .then(
-            literal("walls")
-                .then(argument(ARG_BLOCK, BlockArgumentConsumer).execute(WallsExecutor)),
-        )
-        .then(literal("clear").execute(ClearExecutor))
-        .then(literal("hollow").execute(HollowExecutor))
-        // Clipboard
-        .then(literal("copy").execute(CopyExecutor))
-        .then(literal("paste").execute(PasteExecutor))
-        // History
-        .then(literal("undo").execute(UndoExecutor))
-        // Schematics
-        .then(
-            literal("schem")
-                .then(
-                    literal("load").then(
-                        argument(ARG_SCHEM_NAME, SimpleArgConsumer).execute(SchemLoadExecutor),
-                    ),
-                )
-                .then(
-                    literal("save").then(
-                        argument(ARG_SCHEM_NAME, SimpleArgConsumer).execute(SchemSaveExecutor),
-                    ),
-                )
-                .then(literal("list").execute(SchemListExecutor))
-                .then(
-                    literal("delete").then(
-                        argument(ARG_SCHEM_NAME, SimpleArgConsumer).execute(SchemDeleteExecutor),
-                    ),
-                ),
-        )
+    let tree = CommandTree::new(COMMAND_NAMES, COMMAND_DESCRIPTION);
+    MODULES.into_iter().fold(tree, |tree, register| register(tree))
 }
@@ -1,33 +1,105 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::mem::size_of;
+use std::time::Instant;
+
 use async_trait::async_trait;
 use pumpkin::{
     command::{
-        args::{block::BlockArgumentConsumer, ConsumedArgs, FindArg},
+        args::{
+            block::BlockArgumentConsumer, message::MsgArgConsumer, simple::SimpleArgConsumer,
+            ConsumedArgs, FindArg,
+        },
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
         CommandExecutor, CommandResult, CommandSender,
     },
     server::Server,
 };
 use pumpkin_data::Block;
-use pumpkin_util::{
-    math::{position::BlockPos, vector3::Vector3},
-    text::{color::NamedColor, TextComponent},
-};
-use pumpkin_world::world::BlockFlags;
+use pumpkin_nbt::{compound::NbtCompound, tag::NbtTag};
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
 
+use crate::config;
+use crate::cooldowns;
+use crate::engine::{
+    column_tops, read_region, resend_region, sort_for_placement, BlockAccess, EditSession,
+    Neighborhood,
+};
+use crate::expr::Program;
+use crate::flags::{FlagSpec, Flags};
+use crate::heightmap;
+use crate::history::BlockChange;
+use crate::mask::{normalize, parse_block_list, Mask, SurfaceTransparency};
+use crate::messages::{format_count, format_count_short, msg, msg_string};
+use crate::notify::{notify, Level};
+use crate::permissions::{protected_blocks, require_permission, ADMIN};
+use crate::preview::{self, preview_state, PREVIEW_DURATION};
 use crate::state::{
-    check_selection_size, get_selection, sender_uuid, sender_world, PLAYER_DATA,
+    check_selection_size, check_world_enabled, get_selection, get_selection_shape, placement_flags,
+    placement_note, selection_volume, sender_block_pos, sender_facing, sender_player, sender_uuid,
+    sender_world, MemoryReservation, SelectionShape, MAX_BLOCKS,
 };
+use crate::stats::format_duration;
+use crate::support::{can_support, support_pos};
+use crate::transform::{direction_name, has_property, is_air, parse_direction, with_property};
+
+use super::{flag_error, ARG_FLAGS};
 
 /// Argument name used for single-block commands (set, walls).
 pub const ARG_BLOCK: &str = "block";
-/// Argument name for the source block in replace.
+/// Argument name for the source blocks in replace, e.g. `stone,andesite`.
 pub const ARG_FROM: &str = "from";
 /// Argument name for the target block in replace.
 pub const ARG_TO: &str = "to";
+/// Argument name for the `<property>=<value>` of `setstate`.
+pub const ARG_PROPERTY: &str = "property";
+/// Argument name for the distance of `move` and the count of `stack`.
+pub const ARG_SHIFT_COUNT: &str = "count";
+/// Argument name for the radius in chunks of `clearchunk`.
+pub const ARG_CHUNK_RADIUS: &str = "radius";
 
 // ============================================================================
-// //set <block>
+// //set <block> [-u] [-x] [-p] [-m <mask>]
 // ============================================================================
 
+/// Flags accepted by `//set`.
+const SET_FLAGS: &[FlagSpec] = &[
+    FlagSpec::switch("-u"),
+    FlagSpec::switch("-x"),
+    FlagSpec::switch("-p"),
+    FlagSpec::with_value("-m", "mask"),
+];
+
+/// Show the sender the blocks an edit would place at `positions` for a few seconds instead
+/// of making it.
+async fn send_preview(
+    sender: &CommandSender,
+    positions: Vec<BlockPos>,
+    state_id: u16,
+) -> Result<i32, CommandError> {
+    let total = positions.len();
+    let player = sender_player(sender)?;
+    let shown = preview::show(player, positions, preview_state(state_id)).await;
+    let seconds = PREVIEW_DURATION.as_secs();
+    notify(sender, Level::Info, msg!("preview_shown", format_count(total), seconds)).await;
+    if shown < total {
+        notify(
+            sender,
+            Level::Info,
+            msg!("preview_sampled", format_count(shown), format_count(total)),
+        )
+        .await;
+    }
+    Ok(total as i32)
+}
+
+/// Fills the selection. `-u` places with neighbor updates even in fast mode;
+/// `-x` only changes blocks with an air neighbor; `-m` only changes positions matching the mask;
+/// `-p` previews the blocks that would change instead of changing them.
 pub struct SetExecutor;
 
 #[async_trait]
@@ -44,49 +116,289 @@ impl CommandExecutor for SetExecutor {
             let player_id = sender_uuid(sender)?;
             let world = sender_world(sender)?;
 
-            let (min, max) = get_selection(&player_id)?;
-            check_selection_size(&min, &max)?;
-
-            let mut undo_blocks = Vec::new();
-            let mut count = 0i32;
+            // Parse flags before touching the world so mask errors surface immediately
+            let flags = Flags::from_args(args, SET_FLAGS)?;
+            let updates = flags.has("-u");
+            let exposed = flags.has("-x");
+            let preview = flags.has("-p");
+            let mask = match flags.value("-m") {
+                Some(source) => Some((source, Mask::parse(source).map_err(flag_error)?)),
+                None => None,
+            };
 
-            for x in min.0.x..=max.0.x {
-                for y in min.0.y..=max.0.y {
-                    for z in min.0.z..=max.0.z {
-                        let pos = BlockPos(Vector3::new(x, y, z));
-                        let old_state = world.get_block_state_id(&pos).await;
-                        undo_blocks.push((pos, old_state));
+            let selection = get_selection(sender)?;
+            let (min, max) = selection;
+            check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, updates);
+            let before =
+                Neighborhood::read(world.as_ref(), &min, &max).await.map_err(read_failed)?;
 
-                        world
-                            .set_block_state(&pos, block_state_id, BlockFlags::FORCE_STATE)
-                            .await;
-                        count += 1;
+            if preview {
+                let mut positions = Vec::new();
+                for x in min.0.x..=max.0.x {
+                    for y in min.0.y..=max.0.y {
+                        for z in min.0.z..=max.0.z {
+                            let pos = BlockPos(Vector3::new(x, y, z));
+                            let old_state = before.state(&pos);
+                            if let Some((_, mask)) = &mask {
+                                if !mask.matches(&pos, old_state, &selection) {
+                                    continue;
+                                }
+                            }
+                            if exposed && !before.is_exposed(&pos) {
+                                continue;
+                            }
+                            positions.push(pos);
+                        }
                     }
                 }
+                return send_preview(sender, positions, block_state_id).await;
             }
 
-            {
-                let mut state = PLAYER_DATA.lock().unwrap();
-                let data = state.entry(player_id).or_default();
-                data.undo_data = Some(undo_blocks);
+            let mut command = format!("//set {}", block.name);
+            if exposed {
+                command.push_str(" -x");
             }
+            if let Some((source, _)) = &mask {
+                command.push_str(&format!(" -m {source}"));
+            }
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            session.protect(protected_blocks(sender).await);
+            let mut interior = 0usize;
+            let mask = mask.as_ref().map(|(_, mask)| mask);
+            let result = set_blocks(
+                &mut session,
+                &before,
+                &selection,
+                block_state_id,
+                mask,
+                exposed,
+                &mut interior,
+            )
+            .await;
+            let done = session.finish(result)?;
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("{count} block(s) changed."))
-                        .color_named(NamedColor::Aqua),
+            notify(
+                sender,
+                Level::Success,
+                msg!("set_done", format_count(done.blocks), done.time(), placement_note(flags)),
+            )
+            .await;
+            if exposed {
+                notify(sender, Level::Info, msg!("interior_skipped", format_count(interior))).await;
+            }
+            if done.protected > 0 {
+                notify(
+                    sender,
+                    Level::Warning,
+                    msg!("protected_skipped", format_count(done.protected)),
                 )
                 .await;
+            }
 
-            Ok(count)
+            Ok(done.blocks as i32)
         })
     }
 }
 
+/// Write `block_state_id` over the selection `min..=max` for `//set`, leaving out positions
+/// `mask` doesn't match and, with `exposed`, blocks without an air neighbor, which are counted
+/// in `interior`. `before` holds the selection's states from before the edit.
+async fn set_blocks(
+    session: &mut EditSession,
+    before: &Neighborhood,
+    selection: &(BlockPos, BlockPos),
+    block_state_id: u16,
+    mask: Option<&Mask>,
+    exposed: bool,
+    interior: &mut usize,
+) -> Result<(), String> {
+    let (min, max) = selection;
+    for x in min.0.x..=max.0.x {
+        for y in min.0.y..=max.0.y {
+            for z in min.0.z..=max.0.z {
+                let pos = BlockPos(Vector3::new(x, y, z));
+                let old_state = before.state(&pos);
+                if let Some(mask) = mask {
+                    if !mask.matches(&pos, old_state, selection) {
+                        continue;
+                    }
+                }
+                if exposed && !before.is_exposed(&pos) {
+                    *interior += 1;
+                    continue;
+                }
+                session.set_block_from(pos, old_state, block_state_id).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Error for a block of the selection that could not be read.
+fn read_failed(error: String) -> CommandError {
+    CommandError::CommandFailed(msg!("read_selection_failed", error))
+}
+
 // ============================================================================
-// //replace <from> <to>
+// //replace <from> <to> [-u] [-x] [-i] [-p]
 // ============================================================================
 
+/// Flags accepted by `//replace`.
+const REPLACE_FLAGS: &[FlagSpec] = &[
+    FlagSpec::switch("-u"),
+    FlagSpec::switch("-x"),
+    FlagSpec::switch("-i"),
+    FlagSpec::switch("-p"),
+];
+
+/// Block entity id and slot count of containers whose inventory is a plain row of item
+/// slots, so their contents can move into another such container.
+fn container_inventory(block: &Block) -> Option<(&'static str, usize)> {
+    match block.name {
+        "chest" => Some(("minecraft:chest", 27)),
+        "trapped_chest" => Some(("minecraft:trapped_chest", 27)),
+        "barrel" => Some(("minecraft:barrel", 27)),
+        "dispenser" => Some(("minecraft:dispenser", 9)),
+        "dropper" => Some(("minecraft:dropper", 9)),
+        "hopper" => Some(("minecraft:hopper", 5)),
+        name if name.ends_with("shulker_box") => Some(("minecraft:shulker_box", 27)),
+        _ => None,
+    }
+}
+
+/// Block entity data for a new container at `pos` holding the items of `old` whose slot
+/// exists in a container of `slots` slots, and the number of item stacks left out.
+fn migrate_items(
+    old: &NbtCompound,
+    id: &str,
+    slots: usize,
+    pos: &BlockPos,
+) -> (NbtCompound, usize) {
+    let mut kept = Vec::new();
+    let mut overflow = 0;
+    for tag in old.get_list("Items").map_or(&[][..], Vec::as_slice) {
+        let NbtTag::Compound(item) = tag else {
+            continue;
+        };
+        let slot = item.get_byte("Slot").and_then(|slot| usize::try_from(slot).ok());
+        if slot.is_some_and(|slot| slot < slots) {
+            kept.push(tag.clone());
+        } else {
+            overflow += 1;
+        }
+    }
+
+    let mut nbt = NbtCompound::new();
+    nbt.put("id", NbtTag::String(id.to_string()));
+    nbt.put_int("x", pos.0.x);
+    nbt.put_int("y", pos.0.y);
+    nbt.put_int("z", pos.0.z);
+    nbt.put("Items", NbtTag::List(kept));
+    (nbt, overflow)
+}
+
+/// Most source types listed one by one in the breakdown of a multi-source `replace`.
+const BREAKDOWN_ENTRIES: usize = 8;
+
+/// Blocks replaced per source type, most first, e.g. `stone: 4210, andesite: 902`. Types that
+/// weren't found are left out; past [`BREAKDOWN_ENTRIES`] the rest are summed up.
+fn replace_breakdown(sources: &[&Block], counts: &[usize]) -> String {
+    let mut found: Vec<(&str, usize)> = sources
+        .iter()
+        .zip(counts)
+        .filter(|(_, count)| **count > 0)
+        .map(|(block, count)| (block.name, *count))
+        .collect();
+    found.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let mut parts: Vec<String> = found
+        .iter()
+        .take(BREAKDOWN_ENTRIES)
+        .map(|(name, count)| format!("{name}: {}", format_count(*count)))
+        .collect();
+    if found.len() > BREAKDOWN_ENTRIES {
+        let rest = &found[BREAKDOWN_ENTRIES..];
+        let blocks: usize = rest.iter().map(|(_, count)| count).sum();
+        parts.push(msg_string!("replace_breakdown_more", rest.len(), format_count(blocks)));
+    }
+    parts.join(", ")
+}
+
+/// What `//replace` writes: the block types to replace, the new state, whether only exposed
+/// blocks are replaced (`-x`) and the container inventory contents move into (`-i`).
+struct Replacement<'a> {
+    sources: &'a [&'static Block],
+    to_state_id: u16,
+    exposed: bool,
+    inventory: Option<(&'static str, usize)>,
+}
+
+/// What a `//replace` did besides writing blocks, for its report.
+struct ReplaceCounts {
+    /// Matching blocks left alone by `-x`.
+    interior: usize,
+    /// Containers whose contents moved into the new block.
+    migrated: usize,
+    /// Item stacks that didn't fit the new container.
+    overflow: usize,
+    /// Blocks replaced per source type, in the order of `Replacement::sources`.
+    replaced: Vec<usize>,
+}
+
+impl ReplaceCounts {
+    fn new(sources: usize) -> Self {
+        Self { interior: 0, migrated: 0, overflow: 0, replaced: vec![0; sources] }
+    }
+}
+
+/// Replace the blocks of the selection `min..=max` that match `replacement`, whose states
+/// before the edit are in `before`.
+async fn replace_blocks(
+    session: &mut EditSession,
+    before: &Neighborhood,
+    (min, max): (&BlockPos, &BlockPos),
+    replacement: &Replacement<'_>,
+    counts: &mut ReplaceCounts,
+) -> Result<(), String> {
+    let to_state_id = replacement.to_state_id;
+    for x in min.0.x..=max.0.x {
+        for y in min.0.y..=max.0.y {
+            for z in min.0.z..=max.0.z {
+                let pos = BlockPos(Vector3::new(x, y, z));
+                let old_state = before.state(&pos);
+                let old_id = Block::from_state_id(old_state).id;
+                let Some(source) = replacement.sources.iter().position(|b| b.id == old_id) else {
+                    continue;
+                };
+                if replacement.exposed && !before.is_exposed(&pos) {
+                    counts.interior += 1;
+                    continue;
+                }
+                counts.replaced[source] += 1;
+                if let Some((id, slots)) = replacement.inventory {
+                    if let Some(old) = session.get_block_entity(&pos).await? {
+                        let (nbt, dropped) = migrate_items(&old, id, slots, &pos);
+                        counts.migrated += 1;
+                        counts.overflow += dropped;
+                        session.set_block_with_entity(pos, old_state, to_state_id, nbt).await?;
+                        continue;
+                    }
+                }
+                session.set_block_from(pos, old_state, to_state_id).await?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces one or more block types, given as a list like `stone,andesite`, with another.
+/// `-u` places with neighbor updates even in fast mode; `-x` only replaces blocks with an air
+/// neighbor; `-i` moves container contents into the new containers, dropping stacks beyond
+/// the target's size (undo brings them back); `-p` previews the blocks that would be replaced
+/// instead of replacing them. With several sources, the count per source is reported beneath
+/// the total.
 pub struct ReplaceExecutor;
 
 #[async_trait]
@@ -98,59 +410,130 @@ impl CommandExecutor for ReplaceExecutor {
         args: &'a ConsumedArgs<'a>,
     ) -> CommandResult<'a> {
         Box::pin(async move {
-            let from_block = BlockArgumentConsumer::find_arg(args, ARG_FROM)?;
+            let from = SimpleArgConsumer::find_arg(args, ARG_FROM)?;
+            let sources = parse_block_list(from).map_err(flag_error)?;
             let to_block = BlockArgumentConsumer::find_arg(args, ARG_TO)?;
             let to_state_id = to_block.default_state.id;
             let player_id = sender_uuid(sender)?;
             let world = sender_world(sender)?;
 
-            let (min, max) = get_selection(&player_id)?;
-            check_selection_size(&min, &max)?;
-
-            let mut undo_blocks = Vec::new();
-            let mut count = 0i32;
+            let flags = Flags::from_args(args, REPLACE_FLAGS)?;
+            let updates = flags.has("-u");
+            let exposed = flags.has("-x");
+            let preview = flags.has("-p");
+            let inventory = if flags.has("-i") {
+                let target = container_inventory(to_block)
+                    .filter(|_| sources.iter().all(|block| container_inventory(block).is_some()))
+                    .ok_or(CommandError::CommandFailed(msg!(
+                        "replace_not_containers",
+                        from,
+                        to_block.name
+                    )))?;
+                Some(target)
+            } else {
+                None
+            };
 
-            for x in min.0.x..=max.0.x {
-                for y in min.0.y..=max.0.y {
-                    for z in min.0.z..=max.0.z {
-                        let pos = BlockPos(Vector3::new(x, y, z));
-                        let current_block = world.get_block(&pos).await;
-
-                        if current_block.id == from_block.id {
-                            let old_state = world.get_block_state_id(&pos).await;
-                            undo_blocks.push((pos, old_state));
+            let (min, max) = get_selection(sender)?;
+            check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, updates);
+            let before =
+                Neighborhood::read(world.as_ref(), &min, &max).await.map_err(read_failed)?;
 
-                            world
-                                .set_block_state(&pos, to_state_id, BlockFlags::FORCE_STATE)
-                                .await;
-                            count += 1;
+            if preview {
+                let mut positions = Vec::new();
+                for x in min.0.x..=max.0.x {
+                    for y in min.0.y..=max.0.y {
+                        for z in min.0.z..=max.0.z {
+                            let pos = BlockPos(Vector3::new(x, y, z));
+                            let old_id = Block::from_state_id(before.state(&pos)).id;
+                            if sources.iter().any(|b| b.id == old_id)
+                                && (!exposed || before.is_exposed(&pos))
+                            {
+                                positions.push(pos);
+                            }
                         }
                     }
                 }
+                return send_preview(sender, positions, to_state_id).await;
             }
 
-            {
-                let mut state = PLAYER_DATA.lock().unwrap();
-                let data = state.entry(player_id).or_default();
-                data.undo_data = Some(undo_blocks);
+            let mut command = format!("//replace {from} {}", to_block.name);
+            if exposed {
+                command.push_str(" -x");
             }
+            if inventory.is_some() {
+                command.push_str(" -i");
+            }
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let replacement = Replacement { sources: &sources, to_state_id, exposed, inventory };
+            let mut counts = ReplaceCounts::new(sources.len());
+            let result =
+                replace_blocks(&mut session, &before, (&min, &max), &replacement, &mut counts)
+                    .await;
+            let done = session.finish(result)?;
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("{count} block(s) replaced."))
-                        .color_named(NamedColor::Aqua),
-                )
-                .await;
+            notify(
+                sender,
+                Level::Success,
+                msg!("replace_done", format_count(done.blocks), done.time(), placement_note(flags)),
+            )
+            .await;
+            if sources.len() > 1 && done.blocks > 0 {
+                let breakdown = replace_breakdown(&sources, &counts.replaced);
+                notify(sender, Level::Info, msg!("replace_breakdown", breakdown)).await;
+            }
+            if exposed {
+                let message = msg!("interior_skipped", format_count(counts.interior));
+                notify(sender, Level::Info, message).await;
+            }
+            if inventory.is_some() {
+                let message = msg!("replace_items_moved", format_count(counts.migrated));
+                notify(sender, Level::Info, message).await;
+            }
+            if counts.overflow > 0 {
+                let overflow = format_count(counts.overflow);
+                let message = msg!("replace_items_overflow", overflow, to_block.name);
+                notify(sender, Level::Warning, message).await;
+            }
 
-            Ok(count)
+            Ok(done.blocks as i32)
         })
     }
 }
 
 // ============================================================================
-// //walls <block>
+// //walls <block> [-u] [-t <thickness>]
 // ============================================================================
 
+/// Flags accepted by `//walls`.
+const WALLS_FLAGS: &[FlagSpec] = &[FlagSpec::switch("-u"), FlagSpec::with_value("-t", "thickness")];
+
+/// Write `block_state_id` to every position of `shape`'s walls `thickness` blocks thick.
+async fn build_walls(
+    session: &mut EditSession,
+    shape: &SelectionShape,
+    thickness: i32,
+    block_state_id: u16,
+) -> Result<(), String> {
+    let (min, max) = shape.bounds();
+    for x in min.0.x..=max.0.x {
+        for y in min.0.y..=max.0.y {
+            for z in min.0.z..=max.0.z {
+                let pos = BlockPos(Vector3::new(x, y, z));
+                if shape.is_wall(&pos, thickness) {
+                    session.set_block(pos, block_state_id).await?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Builds walls around the selection's sides: every position within `thickness` blocks
+/// (default 1) of its outside along X or Z, over the selection's full height.
 pub struct WallsExecutor;
 
 #[async_trait]
@@ -167,56 +550,573 @@ impl CommandExecutor for WallsExecutor {
             let player_id = sender_uuid(sender)?;
             let world = sender_world(sender)?;
 
-            let (min, max) = get_selection(&player_id)?;
+            let flags = Flags::from_args(args, WALLS_FLAGS)?;
+            let updates = flags.has("-u");
+            let thickness = match flags.value("-t") {
+                Some(value) => value
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|t| *t >= 1)
+                    .ok_or(CommandError::CommandFailed(msg!("invalid_thickness", value)))?,
+                None => 1,
+            };
+
+            let shape = get_selection_shape(sender)?;
+            let (min, max) = shape.bounds();
             check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, updates);
 
-            let mut undo_blocks = Vec::new();
-            let mut count = 0i32;
+            let command = if thickness > 1 {
+                format!("//walls {} -t {thickness}", block.name)
+            } else {
+                format!("//walls {}", block.name)
+            };
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let result = build_walls(&mut session, &shape, thickness, block_state_id).await;
+            let done = session.finish(result)?;
 
-            for x in min.0.x..=max.0.x {
-                for y in min.0.y..=max.0.y {
-                    for z in min.0.z..=max.0.z {
-                        let is_wall = x == min.0.x
-                            || x == max.0.x
-                            || z == min.0.z
-                            || z == max.0.z;
+            notify(
+                sender,
+                Level::Success,
+                msg!("walls_done", format_count(done.blocks), done.time(), placement_note(flags)),
+            )
+            .await;
 
-                        if is_wall {
-                            let pos = BlockPos(Vector3::new(x, y, z));
-                            let old_state = world.get_block_state_id(&pos).await;
-                            undo_blocks.push((pos, old_state));
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //setstate <property>=<value> [-u]
+// ============================================================================
+
+/// Flags accepted by `//setstate`.
+const SETSTATE_FLAGS: &[FlagSpec] = &[FlagSpec::switch("-u")];
+
+/// What `//setstate` does to one block state.
+#[derive(Clone, Copy)]
+enum StateEdit {
+    /// The block has no such property.
+    Missing,
+    /// The block has the property but not the requested value.
+    Rejected,
+    To(u16),
+}
+
+/// Sets one block state property across the selection, e.g. `hanging=true` on every lantern.
+/// Blocks without the property are skipped; everything else about a block, including its
+/// block entity, is kept.
+pub struct SetStateExecutor;
+
+#[async_trait]
+impl CommandExecutor for SetStateExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let input = SimpleArgConsumer::find_arg(args, ARG_PROPERTY)?;
+            let (key, value) = input
+                .split_once('=')
+                .map(|(key, value)| (key.trim(), value.trim()))
+                .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+                .ok_or(CommandError::CommandFailed(msg!("invalid_property", input)))?;
+            let updates = Flags::from_args(args, SETSTATE_FLAGS)?.has("-u");
+            let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
 
-                            world
-                                .set_block_state(&pos, block_state_id, BlockFlags::FORCE_STATE)
-                                .await;
-                            count += 1;
+            let (min, max) = get_selection(sender)?;
+            check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, updates);
+
+            let command = format!("//setstate {key}={value}");
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let mut edits: HashMap<u16, StateEdit> = HashMap::new();
+            let mut skipped = 0usize;
+            let mut rejected = 0usize;
+            let result = async {
+                for x in min.0.x..=max.0.x {
+                    for y in min.0.y..=max.0.y {
+                        for z in min.0.z..=max.0.z {
+                            let pos = BlockPos(Vector3::new(x, y, z));
+                            let old_state = session.get_block(&pos).await?;
+                            let edit = *edits.entry(old_state).or_insert_with(|| {
+                                if !has_property(old_state, key) {
+                                    StateEdit::Missing
+                                } else {
+                                    with_property(old_state, key, value)
+                                        .map_or(StateEdit::Rejected, StateEdit::To)
+                                }
+                            });
+                            let state_id = match edit {
+                                StateEdit::Missing => {
+                                    if !is_air(old_state) {
+                                        skipped += 1;
+                                    }
+                                    continue;
+                                }
+                                StateEdit::Rejected => {
+                                    rejected += 1;
+                                    continue;
+                                }
+                                StateEdit::To(state_id) if state_id == old_state => continue,
+                                StateEdit::To(state_id) => state_id,
+                            };
+                            match session.get_block_entity(&pos).await? {
+                                Some(nbt) => {
+                                    session
+                                        .set_block_with_entity(pos, old_state, state_id, nbt)
+                                        .await?
+                                }
+                                None => session.set_block_from(pos, old_state, state_id).await?,
+                            }
                         }
                     }
                 }
+                Ok(())
             }
+            .await;
+            let done = session.finish(result)?;
 
-            {
-                let mut state = PLAYER_DATA.lock().unwrap();
-                let data = state.entry(player_id).or_default();
-                data.undo_data = Some(undo_blocks);
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "setstate_done",
+                    format_count(done.blocks),
+                    done.time(),
+                    placement_note(flags)
+                ),
+            )
+            .await;
+            notify(sender, Level::Info, msg!("setstate_skipped", format_count(skipped), key)).await;
+            if rejected > 0 {
+                let message = msg!("setstate_rejected", format_count(rejected), key, value);
+                notify(sender, Level::Warning, message).await;
             }
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("{count} block(s) changed."))
-                        .color_named(NamedColor::Aqua),
-                )
-                .await;
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //move <distance> [direction] [-u] [-r] [-m <mask>]
+// //stack <count> [direction] [-u] [-r] [-m <mask>]
+// ============================================================================
+
+/// Flags accepted by `//move` and `//stack`.
+const SHIFT_FLAGS: &[FlagSpec] =
+    &[FlagSpec::switch("-u"), FlagSpec::switch("-r"), FlagSpec::with_value("-m", "mask")];
+
+/// Largest distance accepted by `//move`.
+const MAX_MOVE_DISTANCE: u32 = 1024;
+/// Largest count accepted by `//stack`.
+const MAX_STACK_COUNT: u32 = 100;
 
-            Ok(count)
+/// Parse a whole number in `1..=max`, failing with `key` (which takes `max`).
+fn parse_count(value: &str, max: u32, key: &str) -> Result<u32, CommandError> {
+    value
+        .parse()
+        .ok()
+        .filter(|n| (1..=max).contains(n))
+        .ok_or_else(|| CommandError::CommandFailed(msg!(key, max)))
+}
+
+/// The direction given after the count, or the one the player is facing. Without `raw`
+/// only the sign of each component counts, so `3,0,1` steps like `1,0,1`.
+fn shift_direction(
+    sender: &CommandSender,
+    values: &[String],
+    raw: bool,
+) -> Result<Vector3<i32>, CommandError> {
+    let direction = match values.first() {
+        Some(direction) => parse_direction(direction).map_err(flag_error)?,
+        None => sender_facing(sender)?,
+    };
+    if raw {
+        return Ok(direction);
+    }
+    Ok(Vector3::new(direction.x.signum(), direction.y.signum(), direction.z.signum()))
+}
+
+/// Command suffix recording the `-r` and `-m` flags of `//move` and `//stack`.
+fn shift_suffix(raw: bool, mask: Option<&str>) -> String {
+    let mut suffix = String::new();
+    if raw {
+        suffix.push_str(" -r");
+    }
+    if let Some(source) = mask {
+        suffix.push_str(&format!(" -m {source}"));
+    }
+    suffix
+}
+
+/// The selected blocks that `mask` matches (all of them without a mask), with their
+/// positions, and how many were left out.
+async fn masked_selection(
+    world: &dyn BlockAccess,
+    selection: &(BlockPos, BlockPos),
+    mask: Option<&Mask>,
+) -> Result<(Vec<(BlockPos, u16)>, usize), CommandError> {
+    let (min, max) = selection;
+    let origin = BlockPos(Vector3::new(0, 0, 0));
+    let blocks = read_region(world, min, max, &origin)
+        .await
+        .map_err(|e| CommandError::CommandFailed(msg!("read_selection_failed", e)))?;
+    let total = blocks.len();
+    let matching: Vec<_> = blocks
+        .into_iter()
+        .map(|(pos, state_id)| (BlockPos(pos), state_id))
+        .filter(|(pos, state_id)| {
+            mask.is_none_or(|mask| mask.matches(pos, *state_id, selection))
+        })
+        .collect();
+    let left = total - matching.len();
+    Ok((matching, left))
+}
+
+/// Block entities (chest contents, sign text) of the non-air `blocks`, read before `move` or
+/// `stack` overwrites anything. Each is keyed by where a copy lands: its position moved by
+/// each of `shifts`.
+async fn shifted_block_entities(
+    world: &dyn BlockAccess,
+    blocks: &[(BlockPos, u16)],
+    shifts: &[Vector3<i32>],
+) -> Result<HashMap<(i32, i32, i32), NbtCompound>, CommandError> {
+    let mut entities = HashMap::new();
+    for (pos, _) in blocks.iter().filter(|(_, state_id)| !is_air(*state_id)) {
+        let Some(nbt) = world.read_block_entity(pos).await.map_err(read_failed)? else {
+            continue;
+        };
+        let p = pos.0;
+        for shift in shifts {
+            entities.insert((p.x + shift.x, p.y + shift.y, p.z + shift.z), nbt.clone());
+        }
+    }
+    Ok(entities)
+}
+
+/// Split `blocks` into those still within the build height `(min_y, max_y)` after moving `dy`
+/// up, and those that would leave it.
+fn split_at_height(
+    blocks: Vec<(BlockPos, u16)>,
+    dy: i32,
+    (min_y, max_y): (i32, i32),
+) -> (Vec<(BlockPos, u16)>, Vec<(BlockPos, u16)>) {
+    blocks.into_iter().partition(|(pos, _)| (min_y..=max_y).contains(&(pos.0.y + dy)))
+}
+
+/// The box covering `min..=max` moved by every shift from `near` to `far` (the same direction),
+/// cut to the build height `(min_y, max_y)`: what `move` or `stack` may overwrite there. `None`
+/// when all of it is outside the build height.
+fn shifted_bounds(
+    (min, max): &(BlockPos, BlockPos),
+    near: Vector3<i32>,
+    far: Vector3<i32>,
+    (min_y, max_y): (i32, i32),
+) -> Option<(BlockPos, BlockPos)> {
+    let low = Vector3::new(
+        min.0.x + near.x.min(far.x),
+        (min.0.y + near.y.min(far.y)).max(min_y),
+        min.0.z + near.z.min(far.z),
+    );
+    let high = Vector3::new(
+        max.0.x + near.x.max(far.x),
+        (max.0.y + near.y.max(far.y)).min(max_y),
+        max.0.z + near.z.max(far.z),
+    );
+    (low.y <= high.y).then_some((BlockPos(low), BlockPos(high)))
+}
+
+/// Write `state_id` at `pos`, giving it a copy of `nbt` moved to `pos` when the source block
+/// had a block entity.
+async fn place_with_entity(
+    session: &mut EditSession,
+    pos: BlockPos,
+    state_id: u16,
+    nbt: Option<&NbtCompound>,
+) -> Result<(), String> {
+    let Some(nbt) = nbt else {
+        return session.set_block(pos, state_id).await;
+    };
+    let mut nbt = nbt.clone();
+    nbt.put_int("x", pos.0.x);
+    nbt.put_int("y", pos.0.y);
+    nbt.put_int("z", pos.0.z);
+    let old_state = session.get_block(&pos).await?;
+    session.set_block_with_entity(pos, old_state, state_id, nbt).await
+}
+
+/// What `move` or `stack` writes: the sources to clear (none for `stack`), the copies within
+/// the build height sorted for placement, the block entities travelling with them, and how
+/// many blocks were left out at the build height.
+struct ShiftPlan {
+    sources: Vec<(BlockPos, u16)>,
+    writes: Vec<(BlockPos, u16)>,
+    entities: HashMap<(i32, i32, i32), NbtCompound>,
+    clipped: usize,
+}
+
+/// Plan moving `selected` by `shift`. Blocks that would leave the build height stay put.
+async fn plan_move(
+    world: &dyn BlockAccess,
+    selected: Vec<(BlockPos, u16)>,
+    shift: Vector3<i32>,
+) -> Result<ShiftPlan, CommandError> {
+    let (sources, clipped) = split_at_height(selected, shift.y, world.height_range());
+    let entities = shifted_block_entities(world, &sources, &[shift]).await?;
+    let mut writes: Vec<(BlockPos, u16)> = sources
+        .iter()
+        .map(|(pos, state_id)| {
+            let p = pos.0;
+            (BlockPos(Vector3::new(p.x + shift.x, p.y + shift.y, p.z + shift.z)), *state_id)
+        })
+        .collect();
+    sort_for_placement(&mut writes);
+    Ok(ShiftPlan { sources, writes, entities, clipped: clipped.len() })
+}
+
+/// Plan `count` copies of `blocks`, each `step` further on than the previous one. Copies
+/// outside the build height are left out.
+async fn plan_stack(
+    world: &dyn BlockAccess,
+    blocks: &[(BlockPos, u16)],
+    step: Vector3<i32>,
+    count: i32,
+) -> Result<ShiftPlan, CommandError> {
+    let shifts: Vec<_> =
+        (1..=count).map(|copy| Vector3::new(step.x * copy, step.y * copy, step.z * copy)).collect();
+    let copies: Vec<(BlockPos, u16)> = shifts
+        .iter()
+        .flat_map(|shift| {
+            blocks.iter().map(move |(pos, state_id)| {
+                let p = pos.0;
+                (BlockPos(Vector3::new(p.x + shift.x, p.y + shift.y, p.z + shift.z)), *state_id)
+            })
+        })
+        .collect();
+    let (mut writes, clipped) = split_at_height(copies, 0, world.height_range());
+    sort_for_placement(&mut writes);
+    let entities = shifted_block_entities(world, blocks, &shifts).await?;
+    Ok(ShiftPlan { sources: Vec::new(), writes, entities, clipped: clipped.len() })
+}
+
+/// Clear the planned sources, then place every copy with its block entity.
+async fn write_shifted(session: &mut EditSession, plan: &ShiftPlan) -> Result<(), String> {
+    // Sources the moved blocks land on are overwritten, not cleared first
+    let targets: HashSet<(i32, i32, i32)> =
+        plan.writes.iter().map(|(pos, _)| (pos.0.x, pos.0.y, pos.0.z)).collect();
+    let air_state_id = Block::AIR.default_state.id;
+    for (pos, state_id) in &plan.sources {
+        if !targets.contains(&(pos.0.x, pos.0.y, pos.0.z)) {
+            session.set_block_from(*pos, *state_id, air_state_id).await?;
+        }
+    }
+    for (pos, state_id) in &plan.writes {
+        let nbt = plan.entities.get(&(pos.0.x, pos.0.y, pos.0.z));
+        place_with_entity(session, *pos, *state_id, nbt).await?;
+    }
+    Ok(())
+}
+
+/// Moves the selected blocks `distance` blocks in a direction (default: where the player is
+/// looking), leaving air behind. With `-m` only blocks matching the mask move; the rest stay
+/// where they are and keep what they would have covered at the destination. Block entities
+/// move with their blocks, and blocks that would leave the world's build height stay put.
+pub struct MoveExecutor;
+
+#[async_trait]
+impl CommandExecutor for MoveExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let value = SimpleArgConsumer::find_arg(args, ARG_SHIFT_COUNT)?;
+            let distance = parse_count(value, MAX_MOVE_DISTANCE, "move_distance_range")? as i32;
+            let (values, flags) = Flags::from_args_with_values(args, 1, SHIFT_FLAGS)?;
+            let raw = flags.has("-r");
+            let direction = shift_direction(sender, &values, raw)?;
+            let updates = flags.has("-u");
+            let mask = match flags.value("-m") {
+                Some(source) => Some((source, Mask::parse(source).map_err(flag_error)?)),
+                None => None,
+            };
+            let shift = Vector3::new(
+                direction.x * distance,
+                direction.y * distance,
+                direction.z * distance,
+            );
+            if [shift.x, shift.y, shift.z].iter().any(|c| c.unsigned_abs() > MAX_MOVE_DISTANCE) {
+                return Err(CommandError::CommandFailed(msg!(
+                    "move_distance_range",
+                    MAX_MOVE_DISTANCE
+                )));
+            }
+            let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
+
+            let selection = get_selection(sender)?;
+            check_selection_size(&selection.0, &selection.1)?;
+            let (selected, left) =
+                masked_selection(world.as_ref(), &selection, mask.as_ref().map(|(_, m)| m)).await?;
+            let plan = plan_move(world.as_ref(), selected, shift).await?;
+            let destination = shifted_bounds(&selection, shift, shift, world.height_range());
+
+            let flags = placement_flags(&player_id, updates);
+            let command = format!(
+                "//move {distance} {}{}",
+                direction_name(direction),
+                shift_suffix(raw, mask.as_ref().map(|(source, _)| *source))
+            );
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &selection.0, &selection.1)?;
+            // Both the cleared source and the blocks the move lands on can be restored
+            session.backup(sender, &selection.0, &selection.1).await?;
+            if let Some((low, high)) = destination {
+                session.backup(sender, &low, &high).await?;
+            }
+            let result = write_shifted(&mut session, &plan).await;
+            let done = session.finish(result)?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "move_done",
+                    format_count(plan.sources.len()),
+                    distance,
+                    direction_name(direction),
+                    done.time(),
+                    placement_note(flags)
+                ),
+            )
+            .await;
+            if mask.is_some() {
+                notify(sender, Level::Info, msg!("mask_left_in_place", format_count(left))).await;
+            }
+            if plan.clipped > 0 {
+                notify(sender, Level::Warning, msg!("move_clipped", format_count(plan.clipped)))
+                    .await;
+            }
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+/// Repeats the selected blocks `count` times in a direction (default: where the player is
+/// looking), each copy directly after the previous one. With `-m` only blocks matching the
+/// mask are repeated, so the copies don't overwrite with what was excluded. Block entities
+/// are copied with their blocks; copies are cut off at the world's build height.
+pub struct StackExecutor;
+
+#[async_trait]
+impl CommandExecutor for StackExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let value = SimpleArgConsumer::find_arg(args, ARG_SHIFT_COUNT)?;
+            let count = parse_count(value, MAX_STACK_COUNT, "stack_count_range")? as i32;
+            let (values, flags) = Flags::from_args_with_values(args, 1, SHIFT_FLAGS)?;
+            let raw = flags.has("-r");
+            let direction = shift_direction(sender, &values, raw)?;
+            let updates = flags.has("-u");
+            let mask = match flags.value("-m") {
+                Some(source) => Some((source, Mask::parse(source).map_err(flag_error)?)),
+                None => None,
+            };
+            let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
+
+            let selection = get_selection(sender)?;
+            let (min, max) = selection;
+            check_selection_size(&min, &max)?;
+            let (blocks, _) =
+                masked_selection(world.as_ref(), &selection, mask.as_ref().map(|(_, m)| m)).await?;
+            let total = blocks.len() * count as usize;
+            if total as i64 > MAX_BLOCKS {
+                return Err(CommandError::CommandFailed(msg!(
+                    "selection_too_large",
+                    format_count(total),
+                    format_count(MAX_BLOCKS)
+                )));
+            }
+
+            // Each copy starts where the previous one ends along the direction, or with -r
+            // exactly the given vector further on
+            let size = Vector3::new(
+                max.0.x - min.0.x + 1,
+                max.0.y - min.0.y + 1,
+                max.0.z - min.0.z + 1,
+            );
+            let step = if raw {
+                direction
+            } else {
+                Vector3::new(direction.x * size.x, direction.y * size.y, direction.z * size.z)
+            };
+            let plan = plan_stack(world.as_ref(), &blocks, step, count).await?;
+            let last = Vector3::new(step.x * count, step.y * count, step.z * count);
+            let copies = shifted_bounds(&selection, step, last, world.height_range());
+
+            let flags = placement_flags(&player_id, updates);
+            let command = format!(
+                "//stack {count} {}{}",
+                direction_name(direction),
+                shift_suffix(raw, mask.as_ref().map(|(source, _)| *source))
+            );
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            if let Some((low, high)) = copies {
+                session.backup(sender, &low, &high).await?;
+            }
+            let result = write_shifted(&mut session, &plan).await;
+            let done = session.finish(result)?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "stack_done",
+                    count,
+                    direction_name(direction),
+                    format_count(done.blocks),
+                    done.time(),
+                    placement_note(flags)
+                ),
+            )
+            .await;
+            if plan.clipped > 0 {
+                notify(sender, Level::Warning, msg!("stack_clipped", format_count(plan.clipped)))
+                    .await;
+            }
+
+            Ok(done.blocks as i32)
         })
     }
 }
 
 // ============================================================================
-// //clear
+// //clear [-u]
 // ============================================================================
 
+/// Flags accepted by `//clear`.
+const CLEAR_FLAGS: &[FlagSpec] = &[FlagSpec::switch("-u")];
+
 pub struct ClearExecutor;
 
 #[async_trait]
@@ -225,58 +1125,188 @@ impl CommandExecutor for ClearExecutor {
         &'a self,
         sender: &'a CommandSender,
         _server: &'a Server,
-        _args: &'a ConsumedArgs<'a>,
+        args: &'a ConsumedArgs<'a>,
     ) -> CommandResult<'a> {
         Box::pin(async move {
+            let updates = Flags::from_args(args, CLEAR_FLAGS)?.has("-u");
             let player_id = sender_uuid(sender)?;
             let world = sender_world(sender)?;
 
-            let (min, max) = get_selection(&player_id)?;
+            let (min, max) = get_selection(sender)?;
             check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, updates);
 
             let air_state_id = Block::AIR.default_state.id;
 
-            let mut undo_blocks = Vec::new();
-            let mut count = 0i32;
-
-            for x in min.0.x..=max.0.x {
-                for y in min.0.y..=max.0.y {
-                    for z in min.0.z..=max.0.z {
-                        let pos = BlockPos(Vector3::new(x, y, z));
-                        let old_state = world.get_block_state_id(&pos).await;
-                        if old_state != air_state_id {
-                            undo_blocks.push((pos, old_state));
-                            world
-                                .set_block_state(&pos, air_state_id, BlockFlags::FORCE_STATE)
-                                .await;
-                            count += 1;
+            let mut session = EditSession::new(world, player_id, "//clear", flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            session.protect(protected_blocks(sender).await);
+            let result = async {
+                for x in min.0.x..=max.0.x {
+                    for y in min.0.y..=max.0.y {
+                        for z in min.0.z..=max.0.z {
+                            let pos = BlockPos(Vector3::new(x, y, z));
+                            let old_state = session.get_block(&pos).await?;
+                            if old_state != air_state_id {
+                                session.set_block_from(pos, old_state, air_state_id).await?;
+                            }
                         }
                     }
                 }
+                Ok(())
             }
+            .await;
+            let done = session.finish(result)?;
 
-            {
-                let mut state = PLAYER_DATA.lock().unwrap();
-                let data = state.entry(player_id).or_default();
-                data.undo_data = Some(undo_blocks);
-            }
-
-            sender
-                .send_message(
-                    TextComponent::text(format!("{count} block(s) cleared."))
-                        .color_named(NamedColor::Aqua),
+            notify(
+                sender,
+                Level::Success,
+                msg!("clear_done", format_count(done.blocks), done.time(), placement_note(flags)),
+            )
+            .await;
+            if done.protected > 0 {
+                notify(
+                    sender,
+                    Level::Warning,
+                    msg!("protected_skipped", format_count(done.protected)),
                 )
                 .await;
+            }
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //clearchunk [radius]
+// ============================================================================
+
+/// Clears whole chunk columns, bottom to top of the world: the chunk the player stands in
+/// and `radius` chunks around it. Bypasses the selection and `MAX_BLOCKS`, so it is
+/// admin-only with its own `max_clearchunk_blocks` limit.
+pub struct ClearChunkExecutor;
+
+#[async_trait]
+impl CommandExecutor for ClearChunkExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let player_id = sender_uuid(sender)?;
+            let center = sender_block_pos(sender)?;
+            let world = sender_world(sender)?;
+
+            let radius = match SimpleArgConsumer::find_arg(args, ARG_CHUNK_RADIUS) {
+                Ok(value) => value
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|r| *r >= 0)
+                    .ok_or(CommandError::CommandFailed(msg!("invalid_radius", value)))?,
+                Err(_) => 0,
+            };
+
+            let (min_y, max_y) = world.height_range();
+            let chunk_x = center.0.x.div_euclid(16);
+            let chunk_z = center.0.z.div_euclid(16);
+            let min = BlockPos(Vector3::new(
+                (chunk_x - radius) * 16,
+                min_y,
+                (chunk_z - radius) * 16,
+            ));
+            let max = BlockPos(Vector3::new(
+                (chunk_x + radius) * 16 + 15,
+                max_y,
+                (chunk_z + radius) * 16 + 15,
+            ));
+            let volume = selection_volume(&min, &max) as u64;
+            let limit = config::get().max_clearchunk_blocks;
+            if volume > limit {
+                return Err(CommandError::CommandFailed(msg!(
+                    "clearchunk_too_large",
+                    format_count(volume),
+                    format_count(limit)
+                )));
+            }
+            // Every cleared block is kept for undo
+            let undo_bytes = volume * size_of::<BlockChange>() as u64;
+            let _reservation = MemoryReservation::acquire(undo_bytes)?;
+
+            let flags = placement_flags(&player_id, false);
+            let air_state_id = Block::AIR.default_state.id;
+            let command = format!("//clearchunk {radius}");
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let result = async {
+                for x in min.0.x..=max.0.x {
+                    for z in min.0.z..=max.0.z {
+                        for y in min.0.y..=max.0.y {
+                            let pos = BlockPos(Vector3::new(x, y, z));
+                            let old_state = session.get_block(&pos).await?;
+                            if old_state != air_state_id {
+                                session.set_block_from(pos, old_state, air_state_id).await?;
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+            .await;
+            let done = session.finish(result)?;
+
+            let chunks = (2 * radius + 1) * (2 * radius + 1);
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "clearchunk_done",
+                    format_count(done.blocks),
+                    chunks,
+                    done.time(),
+                    placement_note(flags)
+                ),
+            )
+            .await;
 
-            Ok(count)
+            Ok(done.blocks as i32)
         })
     }
 }
 
 // ============================================================================
-// //hollow
+// //hollow [-u]
 // ============================================================================
 
+/// Flags accepted by `//hollow`.
+const HOLLOW_FLAGS: &[FlagSpec] = &[FlagSpec::switch("-u")];
+
+/// Clear the interior of `shape`: positions inside it with no face touching its outside,
+/// which leaves a one-block shell following the selection's outline.
+async fn hollow_out(session: &mut EditSession, shape: &SelectionShape) -> Result<(), String> {
+    let air_state_id = Block::AIR.default_state.id;
+    let (min, max) = shape.bounds();
+    for x in min.0.x..=max.0.x {
+        for y in min.0.y..=max.0.y {
+            for z in min.0.z..=max.0.z {
+                let pos = BlockPos(Vector3::new(x, y, z));
+                if shape.is_interior(&pos) {
+                    let old_state = session.get_block(&pos).await?;
+                    if old_state != air_state_id {
+                        session.set_block_from(pos, old_state, air_state_id).await?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 pub struct HollowExecutor;
 
 #[async_trait]
@@ -285,59 +1315,973 @@ impl CommandExecutor for HollowExecutor {
         &'a self,
         sender: &'a CommandSender,
         _server: &'a Server,
-        _args: &'a ConsumedArgs<'a>,
+        args: &'a ConsumedArgs<'a>,
     ) -> CommandResult<'a> {
         Box::pin(async move {
+            let updates = Flags::from_args(args, HOLLOW_FLAGS)?.has("-u");
             let player_id = sender_uuid(sender)?;
             let world = sender_world(sender)?;
 
-            let (min, max) = get_selection(&player_id)?;
+            let shape = get_selection_shape(sender)?;
+            let (min, max) = shape.bounds();
             check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, updates);
 
-            let air_state_id = Block::AIR.default_state.id;
+            let mut session = EditSession::new(world, player_id, "//hollow", flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let result = hollow_out(&mut session, &shape).await;
+            let done = session.finish(result)?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("hollow_done", format_count(done.blocks), done.time(), placement_note(flags)),
+            )
+            .await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //smooth [iterations] [-u] [-m <mask>]
+// ============================================================================
+
+/// Flags accepted by `//smooth`.
+const SMOOTH_FLAGS: &[FlagSpec] = &[FlagSpec::switch("-u"), FlagSpec::with_value("-m", "mask")];
+/// Most smoothing passes one command may run.
+const MAX_SMOOTH_ITERATIONS: u32 = 25;
+
+/// The surface of one column before smoothing.
+struct Column {
+    top: i32,
+    /// The top block, kept on top when the column is raised or lowered.
+    surface: u16,
+    /// The block under the top, used to fill in when the column is raised.
+    fill: u16,
+}
 
-            let mut undo_blocks = Vec::new();
-            let mut count = 0i32;
+/// Smooths the terrain in the selection by blurring its heightmap (default: 1 pass) and
+/// raising or lowering each column to match. With `-m`, only columns whose top block matches
+/// the mask count as terrain; the rest (buildings, say) are left alone and the surface is
+/// interpolated across them, and only blocks matching the mask are added or removed.
+pub struct SmoothExecutor;
 
+#[async_trait]
+impl CommandExecutor for SmoothExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let (values, flags) = Flags::from_args_with_values(args, 1, SMOOTH_FLAGS)?;
+            let iterations = match values.first() {
+                Some(value) => {
+                    parse_count(value, MAX_SMOOTH_ITERATIONS, "smooth_iterations_range")?
+                }
+                None => 1,
+            };
+            let updates = flags.has("-u");
+            let mask = match flags.value("-m") {
+                Some(source) => Some((source, Mask::parse(source).map_err(flag_error)?)),
+                None => None,
+            };
+            let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
+
+            let selection = get_selection(sender)?;
+            let (min, max) = selection;
+            check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, updates);
+            let matches = |pos: &BlockPos, state_id: u16| {
+                mask.as_ref().is_none_or(|(_, mask)| mask.matches(pos, state_id, &selection))
+            };
+
+            let transparency = SurfaceTransparency::from_config();
+            let tops =
+                column_tops(world.as_ref(), &min, &max, |state| transparency.is_transparent(state))
+                    .await
+                    .map_err(read_failed)?;
+            let length = (max.0.z - min.0.z + 1) as usize;
+            let mut columns = Vec::with_capacity(tops.len());
+            for (index, top) in tops.into_iter().enumerate() {
+                let x = min.0.x + (index / length) as i32;
+                let z = min.0.z + (index % length) as i32;
+                let column = match top {
+                    Some(top) => {
+                        let pos = BlockPos(Vector3::new(x, top, z));
+                        let surface = world.read_state(&pos).await.map_err(read_failed)?;
+                        let below = BlockPos(Vector3::new(x, top - 1, z));
+                        let fill = if top > min.0.y {
+                            world.read_state(&below).await.map_err(read_failed)?
+                        } else {
+                            surface
+                        };
+                        let fill =
+                            if is_air(fill) || !matches(&below, fill) { surface } else { fill };
+                        matches(&pos, surface).then_some(Column { top, surface, fill })
+                    }
+                    None => None,
+                };
+                columns.push(column);
+            }
+            let heights: Vec<Option<f64>> =
+                columns.iter().map(|column| column.as_ref().map(|c| f64::from(c.top))).collect();
+            let width = (max.0.x - min.0.x + 1) as usize;
+            let smoothed = heightmap::smooth(&heights, width, length, iterations);
+
+            let mut command = format!("//smooth {iterations}");
+            if let Some((source, _)) = &mask {
+                command.push_str(&format!(" -m {source}"));
+            }
+            let holes = columns.iter().filter(|column| column.is_none()).count();
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let result = async {
+                for (index, column) in columns.iter().enumerate() {
+                    let (Some(column), Some(height)) = (column, smoothed[index]) else {
+                        continue;
+                    };
+                    let x = min.0.x + (index / length) as i32;
+                    let z = min.0.z + (index % length) as i32;
+                    let target = (height.round() as i32).clamp(min.0.y, max.0.y);
+                    let (low, high) = (target.min(column.top), target.max(column.top));
+                    for y in low..=high {
+                        let pos = BlockPos(Vector3::new(x, y, z));
+                        let state_id = match y.cmp(&target) {
+                            Ordering::Greater => Block::AIR.default_state.id,
+                            Ordering::Equal => column.surface,
+                            Ordering::Less => column.fill,
+                        };
+                        let old_state = session.get_block(&pos).await?;
+                        if old_state != state_id && (is_air(old_state) || matches(&pos, old_state))
+                        {
+                            session.set_block_from(pos, old_state, state_id).await?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            .await;
+            let done = session.finish(result)?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "smooth_done",
+                    format_count(done.blocks),
+                    iterations,
+                    done.time(),
+                    placement_note(flags)
+                ),
+            )
+            .await;
+            if mask.is_some() {
+                notify(sender, Level::Info, msg!("smooth_masked", format_count(holes))).await;
+            }
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //deform [-r] <expression>
+// ============================================================================
+
+/// Argument name for the deform expression.
+pub const ARG_EXPRESSION: &str = "expression";
+
+/// Flags accepted by `//deform`, in front of the expression.
+const DEFORM_FLAGS: &[FlagSpec] = &[FlagSpec::switch("-r")];
+
+/// Variables a deform program reads and assigns.
+const DEFORM_VARIABLES: &[&str] = &["x", "y", "z"];
+
+/// Moves blocks within the selection: for each target position the program turns its
+/// coordinates into the source coordinates to sample from. Coordinates are normalized to
+/// -1..1 across the selection unless `-r` (raw block coordinates) is given.
+pub struct DeformExecutor;
+
+/// Inverse of [`normalize`], rounded to the nearest block.
+fn from_unit(value: f64, min: i32, max: i32) -> f64 {
+    (f64::from(min) + (value + 1.0) / 2.0 * f64::from(max - min)).round()
+}
+
+#[async_trait]
+impl CommandExecutor for DeformExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let input = MsgArgConsumer::find_arg(args, ARG_EXPRESSION)?;
+            let (flags, source) = Flags::parse_leading(&input, DEFORM_FLAGS)?;
+            let raw = flags.has("-r");
+            let program = Program::parse(source, DEFORM_VARIABLES)
+                .map_err(|e| CommandError::CommandFailed(msg!("invalid_deform", e)))?;
+
+            let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
+            let (min, max) = get_selection(sender)?;
+            check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, false);
+
+            // Sample from a snapshot so reads never see this operation's own writes
+            let snapshot = read_region(world.as_ref(), &min, &max, &min)
+                .await
+                .map_err(|e| CommandError::CommandFailed(msg!("read_selection_failed", e)))?;
+            let height = (max.0.y - min.0.y + 1) as usize;
+            let length = (max.0.z - min.0.z + 1) as usize;
+            let state_at = |x: i32, y: i32, z: i32| {
+                let index = ((x - min.0.x) as usize * height + (y - min.0.y) as usize) * length
+                    + (z - min.0.z) as usize;
+                snapshot[index].1
+            };
+
+            let mut session =
+                EditSession::new(world, player_id, format!("//deform {source}"), flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let result = async {
+                for x in min.0.x..=max.0.x {
+                    for y in min.0.y..=max.0.y {
+                        for z in min.0.z..=max.0.z {
+                            let mut values = if raw {
+                                [f64::from(x), f64::from(y), f64::from(z)]
+                            } else {
+                                [
+                                    normalize(x, min.0.x, max.0.x),
+                                    normalize(y, min.0.y, max.0.y),
+                                    normalize(z, min.0.z, max.0.z),
+                                ]
+                            };
+                            program.run(&mut values);
+                            if values.iter().any(|v| !v.is_finite()) {
+                                continue;
+                            }
+
+                            // Nearest neighbor, clamped to the selection
+                            let [sx, sy, sz] = if raw {
+                                values.map(f64::round)
+                            } else {
+                                [
+                                    from_unit(values[0], min.0.x, max.0.x),
+                                    from_unit(values[1], min.0.y, max.0.y),
+                                    from_unit(values[2], min.0.z, max.0.z),
+                                ]
+                            };
+                            let source_state = state_at(
+                                (sx as i32).clamp(min.0.x, max.0.x),
+                                (sy as i32).clamp(min.0.y, max.0.y),
+                                (sz as i32).clamp(min.0.z, max.0.z),
+                            );
+
+                            let old_state = state_at(x, y, z);
+                            if source_state != old_state {
+                                let pos = BlockPos(Vector3::new(x, y, z));
+                                session.set_block_from(pos, old_state, source_state).await?;
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+            .await;
+            let done = session.finish(result)?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("deform_done", format_count(done.blocks), done.time(), placement_note(flags)),
+            )
+            .await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //update
+// ============================================================================
+
+/// Blocks between progress messages for `//update`.
+const UPDATE_PROGRESS_INTERVAL: i64 = 25_000;
+
+pub struct UpdateExecutor;
+
+#[async_trait]
+impl CommandExecutor for UpdateExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let world = sender_world(sender)?;
+            check_world_enabled(world.get_world_name())?;
+
+            let (min, max) = get_selection(sender)?;
+            check_selection_size(&min, &max)?;
+            let volume = selection_volume(&min, &max);
+
+            // Re-send neighbor updates for every position so fences, walls, redstone and
+            // fluids react to blocks that were force-placed without notifications
+            let started = Instant::now();
+            let mut count = 0i64;
             for x in min.0.x..=max.0.x {
                 for y in min.0.y..=max.0.y {
                     for z in min.0.z..=max.0.z {
-                        let is_interior = x > min.0.x
-                            && x < max.0.x
-                            && y > min.0.y
-                            && y < max.0.y
-                            && z > min.0.z
-                            && z < max.0.z;
-
-                        if is_interior {
-                            let pos = BlockPos(Vector3::new(x, y, z));
-                            let old_state = world.get_block_state_id(&pos).await;
-                            if old_state != air_state_id {
-                                undo_blocks.push((pos, old_state));
-                                world
-                                    .set_block_state(&pos, air_state_id, BlockFlags::FORCE_STATE)
-                                    .await;
-                                count += 1;
-                            }
+                        let pos = BlockPos(Vector3::new(x, y, z));
+                        world.update_neighbors(&pos, None).await;
+                        count += 1;
+
+                        if count % UPDATE_PROGRESS_INTERVAL == 0 && count < volume {
+                            notify(
+                                sender,
+                                Level::Progress,
+                                msg!(
+                                    "update_progress",
+                                    format_count_short(count),
+                                    format_count_short(volume)
+                                ),
+                            )
+                            .await;
                         }
                     }
                 }
             }
 
+            notify(
+                sender,
+                Level::Success,
+                msg!("update_done", format_count(count), format_duration(started.elapsed())),
+            )
+            .await;
+
+            Ok(count as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //fixlight
+// ============================================================================
+
+/// Resends every chunk section overlapping the selection so clients drop the dark patches
+/// left by force-placed blocks and take the server's current light data.
+pub struct FixLightExecutor;
+
+#[async_trait]
+impl CommandExecutor for FixLightExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let world = sender_world(sender)?;
+
+            let (min, max) = get_selection(sender)?;
+            check_selection_size(&min, &max)?;
+
+            let started = Instant::now();
+            let sections = resend_region(world.as_ref(), &min, &max).await;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("fixlight_done", format_count(sections), format_duration(started.elapsed())),
+            )
+            .await;
+
+            Ok(sections as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //fixfloat
+// ============================================================================
+
+/// Removes blocks in the selection whose support is gone, such as torches, carpets and
+/// flowers left in the air after the ground around a build was cleared. Blocks placed
+/// without updates never learn that their neighbor was removed, so they would float on.
+pub struct FixFloatExecutor;
+
+#[async_trait]
+impl CommandExecutor for FixFloatExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
+
+            let (min, max) = get_selection(sender)?;
+            check_selection_size(&min, &max)?;
+            let flags = placement_flags(&player_id, false);
+            let before =
+                Neighborhood::read(world.as_ref(), &min, &max).await.map_err(read_failed)?;
+            let floating = floating_blocks(&before, &min, &max);
+
+            let air_state_id = Block::AIR.default_state.id;
+            let mut session = EditSession::new(world, player_id, "//fixfloat", flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let result = async {
+                for (pos, state_id) in &floating {
+                    session.set_block_from(*pos, *state_id, air_state_id).await?;
+                }
+                Ok(())
+            }
+            .await;
+            let done = session.finish(result)?;
+
+            let note = placement_note(flags);
+            notify(
+                sender,
+                Level::Success,
+                msg!("fixfloat_done", format_count(done.blocks), done.time(), note),
+            )
+            .await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+/// The attached blocks in `min..=max` whose supporting block is air or a fluid, or is itself
+/// removed here, with their states, bottom-up. `before` must cover the selection and its
+/// border, so supports just outside it count.
+fn floating_blocks(before: &Neighborhood, min: &BlockPos, max: &BlockPos) -> Vec<(BlockPos, u16)> {
+    // Bottom-up, so a floating stack such as a tall flower goes in one pass
+    let mut attached = Vec::new();
+    for y in min.0.y..=max.0.y {
+        for x in min.0.x..=max.0.x {
+            for z in min.0.z..=max.0.z {
+                let pos = BlockPos(Vector3::new(x, y, z));
+                let state_id = before.state(&pos);
+                if let Some(support) = support_pos(state_id, &pos) {
+                    attached.push((pos, state_id, support));
+                }
+            }
+        }
+    }
+
+    // A block resting on one removed here floats too, so repeat until nothing more goes; a
+    // chain hanging from above loses one block per pass
+    let mut removed = HashSet::new();
+    loop {
+        let found = removed.len();
+        for (pos, _, support) in &attached {
+            let key = (pos.0.x, pos.0.y, pos.0.z);
+            if !removed.contains(&key)
+                && (removed.contains(&(support.0.x, support.0.y, support.0.z))
+                    || !can_support(before.state(support)))
             {
-                let mut state = PLAYER_DATA.lock().unwrap();
-                let data = state.entry(player_id).or_default();
-                data.undo_data = Some(undo_blocks);
+                removed.insert(key);
             }
+        }
+        if removed.len() == found {
+            break;
+        }
+    }
+    attached
+        .into_iter()
+        .filter(|(pos, _, _)| removed.contains(&(pos.0.x, pos.0.y, pos.0.z)))
+        .map(|(pos, state_id, _)| (pos, state_id))
+        .collect()
+}
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("{count} block(s) hollowed out."))
-                        .color_named(NamedColor::Aqua),
-                )
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the region editing commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("set").then(
+            argument(ARG_BLOCK, BlockArgumentConsumer)
+                .execute(SetExecutor)
+                .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SetExecutor)),
+        ),
+    )
+    .then(
+        literal("replace").then(
+            argument(ARG_FROM, SimpleArgConsumer).then(
+                argument(ARG_TO, BlockArgumentConsumer)
+                    .execute(ReplaceExecutor)
+                    .then(argument(ARG_FLAGS, MsgArgConsumer).execute(ReplaceExecutor)),
+            ),
+        ),
+    )
+    .then(
+        literal("walls").then(
+            argument(ARG_BLOCK, BlockArgumentConsumer)
+                .execute(WallsExecutor)
+                .then(argument(ARG_FLAGS, MsgArgConsumer).execute(WallsExecutor)),
+        ),
+    )
+    .then(
+        literal("setstate").then(
+            argument(ARG_PROPERTY, SimpleArgConsumer)
+                .execute(SetStateExecutor)
+                .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SetStateExecutor)),
+        ),
+    )
+    .then(
+        literal("move").then(
+            argument(ARG_SHIFT_COUNT, SimpleArgConsumer)
+                .execute(MoveExecutor)
+                .then(argument(ARG_FLAGS, MsgArgConsumer).execute(MoveExecutor)),
+        ),
+    )
+    .then(
+        literal("stack").then(
+            argument(ARG_SHIFT_COUNT, SimpleArgConsumer)
+                .execute(StackExecutor)
+                .then(argument(ARG_FLAGS, MsgArgConsumer).execute(StackExecutor)),
+        ),
+    )
+    .then(
+        literal("clear")
+            .execute(ClearExecutor)
+            .then(argument(ARG_FLAGS, MsgArgConsumer).execute(ClearExecutor)),
+    )
+    .then(
+        literal("clearchunk")
+            .execute(ClearChunkExecutor)
+            .then(argument(ARG_CHUNK_RADIUS, SimpleArgConsumer).execute(ClearChunkExecutor)),
+    )
+    .then(
+        literal("hollow")
+            .execute(HollowExecutor)
+            .then(argument(ARG_FLAGS, MsgArgConsumer).execute(HollowExecutor)),
+    )
+    .then(
+        literal("smooth")
+            .execute(SmoothExecutor)
+            .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SmoothExecutor)),
+    )
+    .then(literal("update").execute(UpdateExecutor))
+    .then(literal("fixlight").execute(FixLightExecutor))
+    .then(literal("fixfloat").execute(FixFloatExecutor))
+    .then(literal("deform").then(argument(ARG_EXPRESSION, MsgArgConsumer).execute(DeformExecutor)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use pumpkin_world::world::BlockFlags;
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::engine::restore;
+    use crate::mask::parse_block_list;
+    use crate::testing::{air, cuboid, player, pop_undo, pos, start, FakeWorld};
+
+    fn stone() -> u16 {
+        Block::STONE.default_state.id
+    }
+
+    fn glass() -> u16 {
+        Block::GLASS.default_state.id
+    }
+
+    fn dirt() -> u16 {
+        Block::DIRT.default_state.id
+    }
+
+    /// The block map a world should hold over `min..=max`, air left out like
+    /// [`FakeWorld::snapshot`].
+    fn expected(
+        min: BlockPos,
+        max: BlockPos,
+        state_of: impl Fn(&BlockPos) -> u16,
+    ) -> HashMap<(i32, i32, i32), u16> {
+        cuboid(min, max)
+            .into_iter()
+            .map(|pos| ((pos.0.x, pos.0.y, pos.0.z), state_of(&pos)))
+            .filter(|(_, state_id)| *state_id != air())
+            .collect()
+    }
+
+    async fn neighborhood(world: &FakeWorld, min: BlockPos, max: BlockPos) -> Neighborhood {
+        match Neighborhood::read(world, &min, &max).await {
+            Ok(before) => before,
+            Err(e) => panic!("reading the test world failed: {e}"),
+        }
+    }
+
+    /// Undo the player's newest edit, like `//undo`.
+    async fn undo(world: &FakeWorld, player_id: Uuid) {
+        let entry = pop_undo(player_id);
+        let (_, result) = restore(world, &entry.changes, BlockFlags::FORCE_STATE).await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn set_fills_the_selection_and_undo_restores_it() {
+        let world = FakeWorld::new("overworld");
+        let (min, max) = (pos(0, 64, 0), pos(3, 66, 3));
+        world.fill(pos(-1, 63, -1), pos(4, 67, 4), stone());
+        world.set(pos(1, 65, 1), dirt());
+        let original = world.snapshot();
+
+        let before = neighborhood(&world, min, max).await;
+        let mut session = start(&world, player(10), "//set glass");
+        let mut interior = 0;
+        let result =
+            set_blocks(&mut session, &before, &(min, max), glass(), None, false, &mut interior)
                 .await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(session.commit().blocks, 48);
 
-            Ok(count)
-        })
+        let inside = |p: &BlockPos| {
+            (min.0.x..=max.0.x).contains(&p.0.x)
+                && (min.0.y..=max.0.y).contains(&p.0.y)
+                && (min.0.z..=max.0.z).contains(&p.0.z)
+        };
+        let around = (pos(-1, 63, -1), pos(4, 67, 4));
+        let filled = expected(around.0, around.1, |p| if inside(p) { glass() } else { stone() });
+        assert_eq!(world.snapshot(), filled);
+
+        undo(&world, player(10)).await;
+        assert_eq!(world.snapshot(), original);
+    }
+
+    #[tokio::test]
+    async fn set_with_a_mask_and_exposed_only_skips_the_rest() {
+        let world = FakeWorld::new("overworld");
+        let (min, max) = (pos(0, 64, 0), pos(2, 66, 2));
+        world.fill(min, max, stone());
+        world.set(pos(0, 64, 0), dirt());
+
+        // -x: the middle block has no air neighbor
+        let before = neighborhood(&world, min, max).await;
+        let mut session = start(&world, player(11), "//set glass -x");
+        let mut interior = 0;
+        let result =
+            set_blocks(&mut session, &before, &(min, max), glass(), None, true, &mut interior)
+                .await;
+        assert_eq!(result, Ok(()));
+        session.commit();
+        assert_eq!(interior, 1);
+        let centre = pos(1, 65, 1);
+        assert_eq!(
+            world.snapshot(),
+            expected(min, max, |p| if *p == centre { stone() } else { glass() })
+        );
+
+        // -m: only positions whose block matches the mask change
+        world.fill(min, max, stone());
+        world.set(pos(0, 64, 0), dirt());
+        let mask = Mask::parse("dirt").unwrap();
+        let before = neighborhood(&world, min, max).await;
+        let mut session = start(&world, player(11), "//set glass -m dirt");
+        let result =
+            set_blocks(&mut session, &before, &(min, max), glass(), Some(&mask), false, &mut 0)
+                .await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(session.commit().blocks, 1);
+        let corner = pos(0, 64, 0);
+        assert_eq!(
+            world.snapshot(),
+            expected(min, max, |p| if *p == corner { glass() } else { stone() })
+        );
+    }
+
+    #[tokio::test]
+    async fn replace_only_changes_the_listed_types() {
+        let world = FakeWorld::new("overworld");
+        let (min, max) = (pos(0, 64, 0), pos(3, 64, 0));
+        world.set(pos(0, 64, 0), stone());
+        world.set(pos(1, 64, 0), dirt());
+        world.set(pos(2, 64, 0), Block::SAND.default_state.id);
+        world.set(pos(3, 64, 0), stone());
+        let original = world.snapshot();
+
+        let sources = parse_block_list("stone,dirt").unwrap();
+        let replacement = Replacement {
+            sources: &sources,
+            to_state_id: glass(),
+            exposed: false,
+            inventory: None,
+        };
+        let mut counts = ReplaceCounts::new(sources.len());
+        let before = neighborhood(&world, min, max).await;
+        let mut session = start(&world, player(12), "//replace stone,dirt glass");
+        let result =
+            replace_blocks(&mut session, &before, (&min, &max), &replacement, &mut counts).await;
+        assert_eq!(result, Ok(()));
+        assert_eq!(session.commit().blocks, 3);
+        assert_eq!(counts.replaced, [2, 1]);
+
+        let sand = Block::SAND.default_state.id;
+        let replaced = expected(min, max, |p| if p.0.x == 2 { sand } else { glass() });
+        assert_eq!(world.snapshot(), replaced);
+
+        undo(&world, player(12)).await;
+        assert_eq!(world.snapshot(), original);
+    }
+
+    #[tokio::test]
+    async fn replace_moves_chest_items_into_the_new_container() {
+        let world = FakeWorld::new("overworld");
+        let at = pos(0, 64, 0);
+        world.set(at, Block::CHEST.default_state.id);
+        world.set_block_entity(at, chest_with_items(at));
+
+        let sources = [&Block::CHEST];
+        let replacement = Replacement {
+            sources: &sources,
+            to_state_id: Block::BARREL.default_state.id,
+            exposed: false,
+            inventory: container_inventory(&Block::BARREL),
+        };
+        let mut counts = ReplaceCounts::new(1);
+        let before = neighborhood(&world, at, at).await;
+        let mut session = start(&world, player(13), "//replace chest barrel -i");
+        let result =
+            replace_blocks(&mut session, &before, (&at, &at), &replacement, &mut counts).await;
+        assert_eq!(result, Ok(()));
+        session.commit();
+
+        assert_eq!((counts.migrated, counts.overflow), (1, 0));
+        assert_eq!(world.get(at), Block::BARREL.default_state.id);
+        let barrel = world.read_block_entity(&at).await.unwrap().expect("barrel data written");
+        assert_eq!(barrel.get_string("id"), Some("minecraft:barrel"));
+        assert_eq!(barrel.get_list("Items").map(Vec::len), Some(1));
+    }
+
+    #[tokio::test]
+    async fn walls_cover_only_the_sides() {
+        let (min, max) = (pos(0, 64, 0), pos(5, 66, 5));
+        let shape = SelectionShape::Cuboid { min, max };
+        for thickness in [1, 2] {
+            let world = FakeWorld::new("overworld");
+            let mut session = start(&world, player(14), "//walls stone");
+            assert_eq!(build_walls(&mut session, &shape, thickness, stone()).await, Ok(()));
+            session.commit();
+
+            let is_wall = |p: &BlockPos| {
+                p.0.x < thickness
+                    || p.0.x > 5 - thickness
+                    || p.0.z < thickness
+                    || p.0.z > 5 - thickness
+            };
+            let walls = expected(min, max, |p| if is_wall(p) { stone() } else { air() });
+            assert_eq!(world.snapshot(), walls, "thickness {thickness}");
+        }
+    }
+
+    #[tokio::test]
+    async fn hollow_leaves_a_shell_and_undo_fills_it_again() {
+        let world = FakeWorld::new("overworld");
+        let (min, max) = (pos(0, 64, 0), pos(4, 68, 4));
+        world.fill(min, max, stone());
+        let original = world.snapshot();
+
+        let mut session = start(&world, player(15), "//hollow");
+        let shape = SelectionShape::Cuboid { min, max };
+        assert_eq!(hollow_out(&mut session, &shape).await, Ok(()));
+        assert_eq!(session.commit().blocks, 27);
+
+        let is_shell = |p: &BlockPos| {
+            [p.0.x, p.0.y - 64, p.0.z].iter().any(|coordinate| *coordinate == 0 || *coordinate == 4)
+        };
+        assert_eq!(
+            world.snapshot(),
+            expected(min, max, |p| if is_shell(p) { stone() } else { air() })
+        );
+
+        undo(&world, player(15)).await;
+        assert_eq!(world.snapshot(), original);
+    }
+
+    async fn floating(world: &FakeWorld, min: BlockPos, max: BlockPos) -> Vec<(i32, i32, i32)> {
+        let before = match Neighborhood::read(world, &min, &max).await {
+            Ok(before) => before,
+            Err(e) => panic!("reading the test world failed: {e}"),
+        };
+        let found = floating_blocks(&before, &min, &max);
+        found.iter().map(|(pos, _)| (pos.0.x, pos.0.y, pos.0.z)).collect()
+    }
+
+    #[tokio::test]
+    async fn wall_torches_fall_with_their_wall() {
+        let world = FakeWorld::new("overworld");
+        // Torches facing east hang on the block west of them
+        let torch = with_property(Block::WALL_TORCH.default_state.id, "facing", "east").unwrap();
+        world.set(pos(0, 64, 0), stone());
+        world.set(pos(1, 64, 0), torch);
+        world.set(pos(1, 64, 2), torch);
+
+        let found = floating(&world, pos(0, 64, 0), pos(1, 64, 2)).await;
+        assert_eq!(found, [(1, 64, 2)]);
+
+        // The wall at the selection's edge was cleared without updates
+        world.set(pos(0, 64, 0), air());
+        let found = floating(&world, pos(1, 64, 0), pos(1, 64, 2)).await;
+        assert_eq!(found, [(1, 64, 0), (1, 64, 2)]);
+    }
+
+    #[tokio::test]
+    async fn chains_of_attached_blocks_fall_together() {
+        let world = FakeWorld::new("overworld");
+        let carpet = Block::WHITE_CARPET.default_state.id;
+        // A torch on a carpet on air, and the same stack on stone next to it
+        world.set(pos(0, 64, 0), carpet);
+        world.set(pos(0, 65, 0), Block::TORCH.default_state.id);
+        world.set(pos(2, 63, 0), stone());
+        world.set(pos(2, 64, 0), carpet);
+        world.set(pos(2, 65, 0), Block::TORCH.default_state.id);
+        // Spore blossoms hanging from each other under nothing
+        world.set(pos(4, 65, 0), Block::SPORE_BLOSSOM.default_state.id);
+        world.set(pos(4, 64, 0), Block::SPORE_BLOSSOM.default_state.id);
+
+        let found = floating(&world, pos(0, 64, 0), pos(4, 65, 0)).await;
+        assert_eq!(found, [(0, 64, 0), (4, 64, 0), (0, 65, 0), (4, 65, 0)]);
+    }
+
+    fn chest_with_items(at: BlockPos) -> NbtCompound {
+        let mut item = NbtCompound::new();
+        item.put("id", NbtTag::String("minecraft:diamond".to_string()));
+        let mut nbt = NbtCompound::new();
+        nbt.put("id", NbtTag::String("minecraft:chest".to_string()));
+        nbt.put("Items", NbtTag::List(vec![NbtTag::Compound(item)]));
+        nbt.put_int("x", at.0.x);
+        nbt.put_int("y", at.0.y);
+        nbt.put_int("z", at.0.z);
+        nbt
+    }
+
+    #[tokio::test]
+    async fn move_carries_chests_and_leaves_what_would_leave_the_world() {
+        let world = FakeWorld::new("overworld");
+        let chest = Block::CHEST.default_state.id;
+        let (from, to) = (pos(0, 316, 0), pos(3, 318, 0));
+        world.set(from, chest);
+        world.set_block_entity(from, chest_with_items(from));
+        world.set(pos(1, 317, 0), glass());
+        world.set(pos(0, 318, 0), stone());
+        let original = world.snapshot();
+
+        // Three east and two up: the chest lands at the top, the row above it stays put
+        let selection = (pos(0, 316, 0), pos(1, 318, 0));
+        let Ok((selected, _)) = masked_selection(world.as_ref(), &selection, None).await else {
+            panic!("reading the selection failed");
+        };
+        let Ok(plan) = plan_move(world.as_ref(), selected, Vector3::new(3, 2, 0)).await else {
+            panic!("planning the move failed");
+        };
+        assert_eq!(plan.clipped, 2);
+        let mut session = start(&world, player(18), "//move 3");
+        assert_eq!(write_shifted(&mut session, &plan).await, Ok(()));
+        session.commit();
+
+        let moved =
+            HashMap::from([((3, 318, 0), chest), ((4, 319, 0), glass()), ((0, 318, 0), stone())]);
+        assert_eq!(world.snapshot(), moved);
+        assert!(!world.has_block_entity(from));
+        let nbt = world.read_block_entity(&to).await.unwrap().expect("chest data moved");
+        assert_eq!(nbt.get_list("Items").map(Vec::len), Some(1));
+        // The copy knows its new position
+        assert_eq!((nbt.get_int("x"), nbt.get_int("y")), (Some(3), Some(318)));
+
+        undo(&world, player(18)).await;
+        assert_eq!(world.snapshot(), original);
+        assert!(world.has_block_entity(from) && !world.has_block_entity(to));
+    }
+
+    #[tokio::test]
+    async fn stack_copies_chests_and_skips_copies_above_the_world() {
+        let world = FakeWorld::new("overworld");
+        let chest = Block::CHEST.default_state.id;
+        let from = pos(0, 305, 0);
+        world.set(from, chest);
+        world.set_block_entity(from, chest_with_items(from));
+        world.set(pos(0, 309, 0), glass());
+        let original = world.snapshot();
+
+        // Three copies, each two east and five up: the third would start at y 320
+        let selection = (from, pos(0, 309, 0));
+        let Ok((blocks, _)) = masked_selection(world.as_ref(), &selection, None).await else {
+            panic!("reading the selection failed");
+        };
+        let Ok(plan) = plan_stack(world.as_ref(), &blocks, Vector3::new(2, 5, 0), 3).await else {
+            panic!("planning the stack failed");
+        };
+        assert_eq!(plan.clipped, 5);
+        let mut session = start(&world, player(19), "//stack 3");
+        assert_eq!(write_shifted(&mut session, &plan).await, Ok(()));
+        session.commit();
+
+        let mut stacked = original.clone();
+        stacked.extend([((2, 310, 0), chest), ((2, 314, 0), glass())]);
+        stacked.extend([((4, 315, 0), chest), ((4, 319, 0), glass())]);
+        assert_eq!(world.snapshot(), stacked);
+        for copy in [from, pos(2, 310, 0), pos(4, 315, 0)] {
+            let nbt = world.read_block_entity(&copy).await.unwrap().expect("chest data copied");
+            assert_eq!(nbt.get_list("Items").map(Vec::len), Some(1));
+            assert_eq!(nbt.get_int("y"), Some(copy.0.y));
+        }
+
+        undo(&world, player(19)).await;
+        assert_eq!(world.snapshot(), original);
+        assert!(!world.has_block_entity(pos(2, 310, 0)) && !world.has_block_entity(pos(4, 315, 0)));
+    }
+
+    #[test]
+    fn blocks_leaving_the_build_height_are_clipped() {
+        let height = (-64, 319);
+        let column = |ys: &[i32]| ys.iter().map(|&y| (pos(0, y, 0), stone())).collect::<Vec<_>>();
+        let ys = |blocks: &[(BlockPos, u16)]| blocks.iter().map(|(p, _)| p.0.y).collect::<Vec<_>>();
+
+        // Moving up two near the top: the top two rows would land above the limit
+        let (kept, clipped) = split_at_height(column(&[316, 317, 318, 319]), 2, height);
+        assert_eq!((ys(&kept), ys(&clipped)), (vec![316, 317], vec![318, 319]));
+
+        // Moving down past the bottom
+        let (kept, clipped) = split_at_height(column(&[-63, -62]), -2, height);
+        assert_eq!((ys(&kept), ys(&clipped)), (vec![-62], vec![-63]));
+
+        // Stack checks its targets in place, the limits themselves included
+        let (kept, clipped) = split_at_height(column(&[-65, -64, 319, 320]), 0, height);
+        assert_eq!((ys(&kept), ys(&clipped)), (vec![-64, 319], vec![-65, 320]));
+    }
+
+    #[test]
+    fn backups_cover_every_copy_within_the_build_height() {
+        let height = (-64, 319);
+        let selection = (pos(0, 300, 0), pos(1, 309, 1));
+        let v = |x, y, z| Vector3::new(x, y, z);
+
+        // Three copies west: from one step to three steps away
+        let bounds = shifted_bounds(&selection, v(-2, 0, 0), v(-6, 0, 0), height);
+        assert_eq!(bounds, Some((pos(-6, 300, 0), pos(-1, 309, 1))));
+
+        // Upward copies stop at the top of the world
+        let bounds = shifted_bounds(&selection, v(0, 10, 0), v(0, 30, 0), height);
+        assert_eq!(bounds, Some((pos(0, 310, 0), pos(1, 319, 1))));
+
+        // A move entirely above it writes nothing there
+        assert_eq!(shifted_bounds(&selection, v(0, 20, 0), v(0, 20, 0), height), None);
     }
 }
@@ -1,34 +1,184 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use async_trait::async_trait;
 use pumpkin::{
     command::{
-        args::{simple::SimpleArgConsumer, ConsumedArgs, FindArg},
+        args::{
+            message::MsgArgConsumer, players::PlayersArgumentConsumer, simple::SimpleArgConsumer,
+            ConsumedArgs, FindArg,
+        },
         dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
         CommandExecutor, CommandResult, CommandSender,
     },
     server::Server,
 };
-use pumpkin_util::text::{color::NamedColor, TextComponent};
+use pumpkin_data::Block;
+use pumpkin_nbt::compound::NbtCompound;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+use uuid::Uuid;
 
-use crate::schematic;
-use crate::state::{sender_uuid, ClipboardData, PLAYER_DATA, SCHEMATICS_DIR};
+use crate::config;
+use crate::cooldowns::{self, Category};
+use crate::engine::{column_tops, read_region, BlockAccess, EditSession};
+use crate::flags::Flags;
+use crate::heightmap;
+use crate::history::format_ago;
+use crate::mask::SurfaceTransparency;
+use crate::messages::{format_count, msg, msg_string};
+use crate::notify::{notify, Level, Listing};
+use crate::permissions::{protected_blocks, require_permission, ADMIN, BYPASS_BLOCK_LIMIT};
+use crate::schematic::{
+    self, BackupEntry, RegistryFallbacks, SchematicData, SchematicEntry, SchematicMetadata,
+    TileEntry, TileManifest,
+};
+use crate::state::{
+    check_clipboard_size, check_selection_size, forget_clipboard_source, get_selection,
+    placement_flags, placement_note, schematic_io, schematics_dir, selection_volume,
+    sender_block_pos, sender_uuid, sender_world, ClipboardData, MemoryReservation, PLAYER_DATA,
+};
+use crate::stats::RunningOperation;
+use crate::transform::{parse_transform_flags, trim_air};
+
+use super::admin::ARG_PLAYER;
+use super::clipboard::{paste_clipboard, PASTE_FLAGS};
+use super::{flag_error, flag_tokens, ARG_FLAGS};
 
 pub const ARG_SCHEM_NAME: &str = "name";
+/// Argument name for the tile edge length of `schem savetiles`.
+pub const ARG_TILE_SIZE: &str = "tile_size";
+/// Subfolder of the schematics directory that `schem debug` writes its dumps to.
+const DEBUG_DIR: &str = "debug";
+/// Argument name for the backup number in `schem backups <player> restore <number>`.
+pub const ARG_BACKUP_NUMBER: &str = "number";
 
 /// Helper: get the schematics directory path.
-fn get_schematics_dir() -> Result<std::path::PathBuf, CommandError> {
-    SCHEMATICS_DIR
-        .get()
-        .cloned()
-        .ok_or(CommandError::CommandFailed(
-            TextComponent::text("Schematics directory not initialized.")
-                .color_named(NamedColor::Red),
-        ))
+fn get_schematics_dir() -> Result<PathBuf, CommandError> {
+    schematics_dir().ok_or(CommandError::CommandFailed(msg!("schematics_dir_missing")))
+}
+
+/// Reject names that would reach outside the schematics directory.
+fn check_schem_name(name: &str) -> Result<(), CommandError> {
+    if name.contains(['/', '\\']) || name.contains("..") {
+        return Err(CommandError::CommandFailed(msg!("schem_invalid_name", name)));
+    }
+    Ok(())
+}
+
+/// Resolve a schematic name with or without its `.schem`/`.litematic` extension to an
+/// existing file, trying `.schem` first.
+fn find_schematic(dir: &Path, name: &str) -> Result<PathBuf, CommandError> {
+    check_schem_name(name)?;
+    let candidates = if name.ends_with(".schem") || name.ends_with(".litematic") {
+        vec![dir.join(name)]
+    } else {
+        vec![dir.join(format!("{name}.schem")), dir.join(format!("{name}.litematic"))]
+    };
+    candidates
+        .into_iter()
+        .find(|path| path.exists())
+        .ok_or(CommandError::CommandFailed(msg!("schem_not_found", name)))
+}
+
+/// Show the display name, author, creation date and generator stored in a schematic.
+async fn notify_metadata(sender: &CommandSender, metadata: &SchematicMetadata) {
+    if let Some(name) = &metadata.name {
+        notify(sender, Level::Info, msg!("schem_meta_name", name)).await;
+    }
+    if let Some(author) = &metadata.author {
+        notify(sender, Level::Info, msg!("schem_meta_author", author)).await;
+    }
+    if let Some(date) = metadata.date {
+        let created = UNIX_EPOCH + Duration::from_millis(date.max(0) as u64);
+        let age = SystemTime::now().duration_since(created).unwrap_or_default();
+        notify(sender, Level::Info, msg!("schem_meta_date", format_ago(age))).await;
+    }
+    if let Some(generator) = &metadata.generator {
+        notify(sender, Level::Info, msg!("schem_meta_generator", generator)).await;
+    }
 }
 
 // ============================================================================
-// /we schem load <name>
+// /we schem load <name> [-t] [-i] [-r <angle>] [-f <direction>], /we schem paste <name>
 // ============================================================================
 
+/// A decoded schematic and the memory reservation covering it while it is in use.
+struct LoadedSchematic {
+    filename: String,
+    data: SchematicData,
+    /// Loaded with `-i` although it exceeds the clipboard limit.
+    oversized: bool,
+    _reservation: MemoryReservation,
+}
+
+/// Read and decompress the schematic file at `path` once a schematic I/O slot is free.
+async fn read_schematic(sender: &CommandSender, path: &Path) -> Result<NbtCompound, String> {
+    let path = path.to_path_buf();
+    schematic_io(sender, move || schematic::read_schematic_file(&path)).await
+}
+
+/// Warn that some blocks of `file` didn't match this server's block registry, as in a file
+/// saved on a newer Minecraft version.
+async fn report_fallbacks(sender: &CommandSender, file: &str, fallbacks: RegistryFallbacks) {
+    if fallbacks.is_empty() {
+        return;
+    }
+    let message = msg!(
+        "schem_registry_fallbacks",
+        file,
+        format_count(fallbacks.defaulted),
+        format_count(fallbacks.unknown)
+    );
+    notify(sender, Level::Warning, message).await;
+}
+
+/// Find and decode the schematic `name`. The header is checked against the clipboard limit
+/// and the memory budget before any block data is decoded; with `inspect`, admins may load
+/// a file over the clipboard limit.
+async fn load_schematic(
+    sender: &CommandSender,
+    name: &str,
+    inspect: bool,
+) -> Result<LoadedSchematic, CommandError> {
+    let schematics_dir = get_schematics_dir()?;
+
+    let file_path = find_schematic(&schematics_dir, name)?;
+    let filename = file_path.file_name().and_then(|n| n.to_str()).unwrap_or(name).to_string();
+    cooldowns::take(sender, Category::SchematicIo)?;
+
+    notify(sender, Level::Progress, msg!("schem_loading", filename)).await;
+
+    let load_failed = |e| CommandError::CommandFailed(msg!("schem_load_failed", e));
+    let root = read_schematic(sender, &file_path).await.map_err(load_failed)?;
+    let info = schematic::read_schematic_info(&root).map_err(load_failed)?;
+    // The volume bounds the non-air block count, so oversized files are refused before
+    // decoding. Admins may load them with -i to look at, not to paste.
+    let limit = config::get().max_clipboard_blocks;
+    let oversized = info.volume > limit && !sender.has_permission(BYPASS_BLOCK_LIMIT).await;
+    if oversized && !inspect {
+        return Err(CommandError::CommandFailed(msg!(
+            "schem_too_large",
+            filename,
+            format_count(info.volume),
+            format_count(limit)
+        )));
+    }
+    if oversized {
+        require_permission(sender, ADMIN).await?;
+    }
+    let reservation = MemoryReservation::acquire(info.estimated_bytes())?;
+    let data = schematic::decode_schematic(&root).map_err(load_failed)?;
+    report_fallbacks(sender, &filename, data.fallbacks).await;
+
+    Ok(LoadedSchematic { filename, data, oversized, _reservation: reservation })
+}
+
 pub struct SchemLoadExecutor;
 
 #[async_trait]
@@ -42,82 +192,109 @@ impl CommandExecutor for SchemLoadExecutor {
         Box::pin(async move {
             let player_id = sender_uuid(sender)?;
             let schem_name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
-
-            let schematics_dir = get_schematics_dir()?;
-
-            // Resolve path: accept name with or without .schem/.litematic extension
-            let file_path = if schem_name.ends_with(".schem") || schem_name.ends_with(".litematic") {
-                schematics_dir.join(schem_name)
-            } else {
-                let schem_path = schematics_dir.join(format!("{schem_name}.schem"));
-                let litematic_path = schematics_dir.join(format!("{schem_name}.litematic"));
-                if schem_path.exists() {
-                    schem_path
-                } else if litematic_path.exists() {
-                    litematic_path
-                } else {
-                    schematics_dir.join(format!("{schem_name}.schem")) // will fail below
+            let flag_input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+            let (transform, rest) =
+                parse_transform_flags(flag_tokens(&flag_input)).map_err(flag_error)?;
+            let mut trim = false;
+            let mut inspect = false;
+            for flag in rest {
+                match flag {
+                    "-t" => trim = true,
+                    "-i" => inspect = true,
+                    other => return Err(CommandError::CommandFailed(msg!("unknown_flag", other))),
                 }
-            };
-
-            let filename = file_path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or(schem_name);
-
-            if !file_path.exists() {
-                return Err(CommandError::CommandFailed(
-                    TextComponent::text(format!(
-                        "Schematic '{schem_name}' not found (tried .schem and .litematic)."
-                    ))
-                    .color_named(NamedColor::Red),
-                ));
             }
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("Loading schematic '{filename}'..."))
-                        .color_named(NamedColor::Yellow),
-                )
-                .await;
-
-            // Load schematic (blocking I/O, done on the current task)
-            let schem_data = schematic::load_schematic(&file_path).map_err(|e| {
-                CommandError::CommandFailed(
-                    TextComponent::text(format!("Failed to load schematic: {e}"))
-                        .color_named(NamedColor::Red),
-                )
-            })?;
+            let loaded = load_schematic(sender, schem_name, inspect).await?;
+            let filename = &loaded.filename;
+            let schem_data = &loaded.data;
 
-            let block_count = schem_data.blocks.len();
             let width = schem_data.width;
             let height = schem_data.height;
             let length = schem_data.length;
 
-            // Store in clipboard
-            let clipboard = schematic::schematic_to_clipboard(&schem_data);
+            // Store in clipboard, trimmed and rotated/flipped as requested
+            let mut clipboard = schematic::schematic_to_clipboard(schem_data);
+            clipboard.source = Some(filename.clone());
+            let trimmed = if trim {
+                trim_air(&mut clipboard)
+            } else {
+                None
+            };
+            transform.apply(&mut clipboard).map_err(flag_error)?;
+            let block_count = clipboard.blocks.len();
             {
-                let mut state = PLAYER_DATA.lock().unwrap();
+                let mut state = PLAYER_DATA.lock();
                 let data = state.entry(player_id).or_default();
                 data.clipboard = Some(clipboard);
             }
 
-            sender
-                .send_message(
-                    TextComponent::text(format!(
-                        "Schematic '{filename}' loaded into clipboard ({width}x{height}x{length}, {block_count} blocks). Use /we paste to place it."
-                    ))
-                    .color_named(NamedColor::Aqua),
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "schem_loaded",
+                    filename,
+                    transform.describe(),
+                    width,
+                    height,
+                    length,
+                    format_count(block_count)
+                ),
+            )
+            .await;
+            if transform.is_lossy() {
+                notify(sender, Level::Warning, msg!("rotation_lossy")).await;
+            }
+            if loaded.oversized {
+                let limit = config::get().max_clipboard_blocks;
+                notify(sender, Level::Warning, msg!("schem_inspect_only", format_count(limit)))
+                    .await;
+            }
+            if let Some(metadata) = &schem_data.metadata {
+                notify_metadata(sender, metadata).await;
+            }
+
+            if let Some((old, new)) = trimmed {
+                notify(
+                    sender,
+                    Level::Success,
+                    msg!("schem_trimmed", old.x, old.y, old.z, new.x, new.y, new.z),
                 )
                 .await;
+            }
 
             Ok(block_count as i32)
         })
     }
 }
 
+/// Pastes a schematic at the player without going through their clipboard, which is left as
+/// it was. Takes the flags of `//paste`, and the paste is one undoable operation.
+pub struct SchemPasteExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemPasteExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let schem_name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            let flags = Flags::from_args(args, PASTE_FLAGS)?;
+
+            let loaded = load_schematic(sender, schem_name, false).await?;
+            let clipboard = schematic::schematic_to_clipboard(&loaded.data);
+            let command = format!("/we schem paste {}", loaded.filename);
+            paste_clipboard(sender, clipboard, &flags, &command).await
+        })
+    }
+}
+
 // ============================================================================
-// /we schem save <name>
+// /we schem save <name> [-s] [-u] [-n "<display name>"]
 // ============================================================================
 
 pub struct SchemSaveExecutor;
@@ -133,22 +310,60 @@ impl CommandExecutor for SchemSaveExecutor {
         Box::pin(async move {
             let player_id = sender_uuid(sender)?;
             let schem_name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            check_schem_name(schem_name)?;
+            let flag_input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+
+            // -u writes raw NBT instead of gzip, for external tools that can't inflate.
+            // -s saves the selection straight from the world, anchored at its min corner,
+            // leaving the clipboard untouched. -n sets the display name in the metadata.
+            let mut uncompressed = false;
+            let mut selection = false;
+            let mut display_name = None;
+            let mut tokens = flag_tokens(&flag_input).into_iter();
+            while let Some(flag) = tokens.next() {
+                match flag {
+                    "-u" => uncompressed = true,
+                    "-s" => selection = true,
+                    "-n" => {
+                        let name = tokens
+                            .next()
+                            .filter(|name| !name.is_empty())
+                            .ok_or(CommandError::CommandFailed(msg!("missing_schem_name")))?;
+                        display_name = Some(name.to_string());
+                    }
+                    other => return Err(CommandError::CommandFailed(msg!("unknown_flag", other))),
+                }
+            }
+
+            // Only players have an identity to record; console saves carry no metadata
+            let metadata = sender.as_player().map(|player| {
+                SchematicMetadata::new(player.gameprofile.name.clone(), display_name)
+            });
 
             let schematics_dir = get_schematics_dir()?;
 
-            // Get clipboard data
-            let clipboard_blocks = {
-                let state = PLAYER_DATA.lock().unwrap();
+            let clipboard_data = if selection {
+                let world = sender_world(sender)?;
+                let (min, max) = get_selection(sender)?;
+                check_clipboard_size(selection_volume(&min, &max) as u64)?;
+                let blocks = read_region(world.as_ref(), &min, &max, &min)
+                    .await
+                    .map_err(|e| CommandError::CommandFailed(msg!("read_selection_failed", e)))?;
+                ClipboardData {
+                    blocks,
+                    biomes: None,
+                    source: None,
+                }
+            } else {
+                let state = PLAYER_DATA.lock();
                 let data = state.get(&player_id).ok_or(CommandError::CommandFailed(
-                    TextComponent::text("Clipboard is empty. Use /we copy first.")
-                        .color_named(NamedColor::Red),
+                    msg!("clipboard_empty"),
                 ))?;
                 let clipboard =
                     data.clipboard.as_ref().ok_or(CommandError::CommandFailed(
-                        TextComponent::text("Clipboard is empty. Use /we copy first.")
-                            .color_named(NamedColor::Red),
+                        msg!("clipboard_empty"),
                     ))?;
-                clipboard.blocks.clone()
+                clipboard.clone()
             };
 
             // Build file path
@@ -159,30 +374,29 @@ impl CommandExecutor for SchemSaveExecutor {
             };
             let file_path = schematics_dir.join(&filename);
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("Saving schematic '{filename}'..."))
-                        .color_named(NamedColor::Yellow),
-                )
-                .await;
+            cooldowns::take(sender, Category::SchematicIo)?;
+            notify(sender, Level::Progress, msg!("schem_saving", filename)).await;
 
-            let clipboard_data = ClipboardData {
-                blocks: clipboard_blocks,
+            let compression = if uncompressed {
+                schematic::Compression::None
+            } else {
+                schematic::Compression::Gzip
             };
 
-            schematic::save_schematic(&file_path, &clipboard_data).map_err(|e| {
-                CommandError::CommandFailed(
-                    TextComponent::text(format!("Failed to save schematic: {e}"))
-                        .color_named(NamedColor::Red),
-                )
-            })?;
+            schematic_io(sender, move || {
+                let metadata = metadata.as_ref();
+                schematic::save_schematic(&file_path, &clipboard_data, compression, metadata)
+            })
+            .await
+            .map_err(|e| CommandError::CommandFailed(msg!("schem_save_failed", e)))?;
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("Schematic saved as '{filename}'."))
-                        .color_named(NamedColor::Aqua),
-                )
-                .await;
+            let key = if selection { "schem_saved_selection" } else { "schem_saved" };
+            let suffix = if uncompressed {
+                msg_string!("schem_uncompressed")
+            } else {
+                String::new()
+            };
+            notify(sender, Level::Success, msg!(key, filename, suffix)).await;
 
             Ok(1)
         })
@@ -190,92 +404,325 @@ impl CommandExecutor for SchemSaveExecutor {
 }
 
 // ============================================================================
-// /we schem list
+// /we schem heightmap-export <name>
 // ============================================================================
 
-pub struct SchemListExecutor;
+/// Writes the height of the ground in each selected column as a grayscale PNG in the
+/// schematics directory, from black at the selection's bottom to white at its top. Trees,
+/// plants and snow layers (`surface_transparent`) are looked through.
+pub struct SchemHeightmapExportExecutor;
 
 #[async_trait]
-impl CommandExecutor for SchemListExecutor {
+impl CommandExecutor for SchemHeightmapExportExecutor {
     fn execute<'a>(
         &'a self,
         sender: &'a CommandSender,
         _server: &'a Server,
-        _args: &'a ConsumedArgs<'a>,
+        args: &'a ConsumedArgs<'a>,
     ) -> CommandResult<'a> {
         Box::pin(async move {
+            let name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            check_schem_name(name)?;
             let schematics_dir = get_schematics_dir()?;
+            let world = sender_world(sender)?;
+            let (min, max) = get_selection(sender)?;
+            check_selection_size(&min, &max)?;
 
-            if !schematics_dir.exists() {
-                sender
-                    .send_message(
-                        TextComponent::text("No schematics found.")
-                            .color_named(NamedColor::Yellow),
-                    )
-                    .await;
-                return Ok(0);
-            }
+            let surface = SurfaceTransparency::from_config();
+            let tops =
+                column_tops(world.as_ref(), &min, &max, |state| surface.is_transparent(state))
+                .await
+                .map_err(|e| CommandError::CommandFailed(msg!("read_selection_failed", e)))?;
+            let width = (max.0.x - min.0.x + 1) as usize;
+            let length = (max.0.z - min.0.z + 1) as usize;
+            let pixels = heightmap::render(&tops, width, length, min.0.y, max.0.y);
 
-            let entries = std::fs::read_dir(&schematics_dir).map_err(|e| {
-                CommandError::CommandFailed(
-                    TextComponent::text(format!("Failed to read schematics directory: {e}"))
-                        .color_named(NamedColor::Red),
-                )
-            })?;
+            let filename = if name.ends_with(".png") {
+                name.to_string()
+            } else {
+                format!("{name}.png")
+            };
+            let path = schematics_dir.join(&filename);
+            heightmap::write_png(path, width as u32, length as u32, pixels)
+                .await
+                .map_err(|e| CommandError::CommandFailed(msg!("heightmap_export_failed", e)))?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("heightmap_exported", filename, width, length, min.0.y, max.0.y),
+            )
+            .await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we schem savetiles <name> <tile_size>, /we schem loadtiles <name>
+// ============================================================================
+
+/// Saves the selection as a grid of schematics, each `tile_size` blocks along X and Z and
+/// the selection's full height, plus a manifest recording the layout. Tiles are read and
+/// written one at a time, so selections far beyond the block limit can be exported.
+pub struct SchemSaveTilesExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemSaveTilesExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            check_schem_name(name)?;
+            let value = SimpleArgConsumer::find_arg(args, ARG_TILE_SIZE)?;
+            let tile_size = value
+                .parse::<i32>()
+                .ok()
+                .filter(|size| *size >= 1)
+                .ok_or(CommandError::CommandFailed(msg!("invalid_tile_size", value)))?;
+            let schematics_dir = get_schematics_dir()?;
+            let world = sender_world(sender)?;
+            let (min, max) = get_selection(sender)?;
+            // Tiles are checked against the clipboard limit one by one, not the whole selection
+            let tile_end = |start: i32, edge: i32| (start + tile_size - 1).min(edge);
+            let first_tile_max =
+                Vector3::new(tile_end(min.0.x, max.0.x), max.0.y, tile_end(min.0.z, max.0.z));
+            check_clipboard_size(selection_volume(&min, &BlockPos(first_tile_max)) as u64)?;
+            cooldowns::take(sender, Category::SchematicIo)?;
 
-            let mut schem_files: Vec<String> = Vec::new();
-            for entry in entries.flatten() {
-                let path = entry.path();
-                if path.extension().is_some_and(|ext| ext == "schem" || ext == "litematic") {
-                    if let Some(name) = path.file_stem() {
-                        schem_files.push(name.to_string_lossy().to_string());
+            let size = [
+                max.0.x - min.0.x + 1,
+                max.0.y - min.0.y + 1,
+                max.0.z - min.0.z + 1,
+            ];
+            let mut manifest = TileManifest::new(tile_size, size);
+            let total = manifest.columns * manifest.rows;
+            let metadata = sender.as_player().map(|player| {
+                SchematicMetadata::new(player.gameprofile.name.clone(), Some(name.to_string()))
+            });
+            let command = format!("/we schem savetiles {name} {tile_size}");
+            let running = RunningOperation::start(player_id, &command, world.world_name());
+
+            for row in 0..manifest.rows {
+                for column in 0..manifest.columns {
+                    if running.is_cancelled() {
+                        return Err(CommandError::CommandFailed(msg!(
+                            "schem_tiles_cancelled",
+                            manifest.tiles.len(),
+                            total
+                        )));
                     }
+                    let x = column * tile_size;
+                    let z = row * tile_size;
+                    let tile_min = BlockPos(Vector3::new(min.0.x + x, min.0.y, min.0.z + z));
+                    let tile_max = BlockPos(Vector3::new(
+                        tile_end(tile_min.0.x, max.0.x),
+                        max.0.y,
+                        tile_end(tile_min.0.z, max.0.z),
+                    ));
+                    let blocks = read_region(world.as_ref(), &tile_min, &tile_max, &tile_min)
+                        .await
+                        .map_err(|e| {
+                            CommandError::CommandFailed(msg!("read_selection_failed", e))
+                        })?;
+                    let clipboard = ClipboardData { blocks, biomes: None, source: None };
+                    let file = schematic::tile_file_name(name, column, row);
+                    let (path, metadata) = (schematics_dir.join(&file), metadata.clone());
+                    schematic_io(sender, move || {
+                        let compression = schematic::Compression::Gzip;
+                        schematic::save_schematic(&path, &clipboard, compression, metadata.as_ref())
+                    })
+                    .await
+                    .map_err(|e| CommandError::CommandFailed(msg!("schem_save_failed", e)))?;
+                    manifest.tiles.push(TileEntry { file, x, z });
+                    running.set_blocks(manifest.tiles.len());
+
+                    let done = manifest.tiles.len();
+                    notify(sender, Level::Progress, msg!("schem_tiles_progress", done, total))
+                        .await;
                 }
             }
 
-            if schem_files.is_empty() {
-                sender
-                    .send_message(
-                        TextComponent::text("No schematics found.")
-                            .color_named(NamedColor::Yellow),
-                    )
-                    .await;
-                return Ok(0);
+            let manifest_path = schematic::tile_manifest_path(&schematics_dir, name);
+            schematic::write_tile_manifest(&manifest_path, &manifest)
+                .map_err(|e| CommandError::CommandFailed(msg!("schem_save_failed", e)))?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("schem_tiles_saved", total, manifest.columns, manifest.rows, name),
+            )
+            .await;
+
+            Ok(total)
+        })
+    }
+}
+
+/// Pastes a tiled save back at the player's position in its original arrangement, one tile
+/// at a time so only a single tile is held in memory. The whole load is one undo entry.
+pub struct SchemLoadTilesExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemLoadTilesExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            check_schem_name(name)?;
+            let schematics_dir = get_schematics_dir()?;
+            let manifest_path = schematic::tile_manifest_path(&schematics_dir, name);
+            if !manifest_path.exists() {
+                return Err(CommandError::CommandFailed(msg!("schem_tiles_not_found", name)));
             }
+            let manifest = schematic::read_tile_manifest(&manifest_path)
+                .map_err(|e| CommandError::CommandFailed(msg!("schem_load_failed", e)))?;
+            let origin = sender_block_pos(sender)?;
+            let world = sender_world(sender)?;
 
-            schem_files.sort();
+            let flags = placement_flags(&player_id, false);
+            let command = format!("/we schem loadtiles {name}");
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take(sender, Category::SchematicIo)?;
+            session.protect(protected_blocks(sender).await);
+            let total = manifest.tiles.len();
+            let mut loaded = 0usize;
+            let mut fallbacks = RegistryFallbacks::default();
+            let result = async {
+                for tile in &manifest.tiles {
+                    let tile_error = |e: String| format!("{}: {e}", tile.file);
+                    let root = read_schematic(sender, &schematics_dir.join(&tile.file))
+                        .await
+                        .map_err(tile_error)?;
+                    let info = schematic::read_schematic_info(&root).map_err(tile_error)?;
+                    let _reservation = MemoryReservation::acquire(info.estimated_bytes())
+                        .map_err(|_| msg_string!("schem_tile_memory", tile.file))?;
+                    let schem = schematic::decode_schematic(&root).map_err(tile_error)?;
+                    drop(root);
+                    fallbacks.add(schem.fallbacks);
+                    for (offset, state_id) in &schem.blocks {
+                        let pos = BlockPos(Vector3::new(
+                            origin.0.x + tile.x + offset.x,
+                            origin.0.y + offset.y,
+                            origin.0.z + tile.z + offset.z,
+                        ));
+                        session.set_block(pos, *state_id).await?;
+                    }
+                    loaded += 1;
+                    notify(sender, Level::Progress, msg!("schem_tiles_progress", loaded, total))
+                        .await;
+                }
+                Ok(())
+            }
+            .await;
+            let done = session.finish(result)?;
 
-            sender
-                .send_message(
-                    TextComponent::text(format!(
-                        "--- Schematics ({}) ---",
-                        schem_files.len()
-                    ))
-                    .color_named(NamedColor::Gold),
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "schem_tiles_loaded",
+                    total,
+                    name,
+                    format_count(done.blocks),
+                    done.time(),
+                    placement_note(flags)
+                ),
+            )
+            .await;
+            report_fallbacks(sender, name, fallbacks).await;
+            if done.protected > 0 {
+                notify(
+                    sender,
+                    Level::Warning,
+                    msg!("protected_skipped", format_count(done.protected)),
                 )
                 .await;
+            }
 
-            for name in &schem_files {
-                sender
-                    .send_message(
-                        TextComponent::text(format!("  - {name}")).color_named(NamedColor::Green),
-                    )
-                    .await;
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we schem info <name>
+// ============================================================================
+
+pub struct SchemInfoExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemInfoExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let schem_name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            let schematics_dir = get_schematics_dir()?;
+            let file_path = find_schematic(&schematics_dir, schem_name)?;
+            let filename = file_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or(schem_name);
+
+            let load_failed = |e| CommandError::CommandFailed(msg!("schem_load_failed", e));
+            let root = read_schematic(sender, &file_path).await.map_err(load_failed)?;
+            let info = schematic::read_schematic_info(&root).map_err(load_failed)?;
+            let _reservation = MemoryReservation::acquire(info.estimated_bytes())?;
+            let schem_data = schematic::decode_schematic(&root).map_err(load_failed)?;
+
+            notify(
+                sender,
+                Level::Info,
+                msg!(
+                    "schem_info",
+                    filename,
+                    schem_data.width,
+                    schem_data.height,
+                    schem_data.length,
+                    format_count(schem_data.blocks.len())
+                ),
+            )
+            .await;
+            match &schem_data.metadata {
+                Some(metadata) => notify_metadata(sender, metadata).await,
+                None => notify(sender, Level::Info, msg!("schem_meta_none")).await,
             }
+            report_fallbacks(sender, filename, schem_data.fallbacks).await;
 
-            Ok(schem_files.len() as i32)
+            Ok(1)
         })
     }
 }
 
 // ============================================================================
-// /we schem delete <name>
+// /we schem debug <name>
 // ============================================================================
 
-pub struct SchemDeleteExecutor;
+/// Lines of a schematic's NBT dump shown in chat; the rest is only in the dump file.
+const DEBUG_CHAT_LINES: usize = 12;
+
+/// Prints the NBT tag tree of a schematic, structure only, to see why a file from another
+/// tool fails to load. The start goes to chat and the whole tree to `debug/<file>.txt` in
+/// the schematics directory.
+pub struct SchemDebugExecutor;
 
 #[async_trait]
-impl CommandExecutor for SchemDeleteExecutor {
+impl CommandExecutor for SchemDebugExecutor {
     fn execute<'a>(
         &'a self,
         sender: &'a CommandSender,
@@ -283,54 +730,433 @@ impl CommandExecutor for SchemDeleteExecutor {
         args: &'a ConsumedArgs<'a>,
     ) -> CommandResult<'a> {
         Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
             let schem_name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            let schematics_dir = get_schematics_dir()?;
+            let file_path = find_schematic(&schematics_dir, schem_name)?;
+            let filename = file_path.file_name().and_then(|n| n.to_str()).unwrap_or(schem_name);
+
+            let root = read_schematic(sender, &file_path)
+                .await
+                .map_err(|e| CommandError::CommandFailed(msg!("schem_load_failed", e)))?;
+            let lines = schematic::dump_nbt(&root);
+
+            let debug_dir = schematics_dir.join(DEBUG_DIR);
+            let dump_path = debug_dir.join(format!("{filename}.txt"));
+            fs::create_dir_all(&debug_dir)
+                .and_then(|()| fs::write(&dump_path, lines.join("\n") + "\n"))
+                .map_err(|e| CommandError::CommandFailed(msg!("schem_debug_failed", e)))?;
+
+            let mut listing = Listing::new(msg!("schem_debug_header", filename));
+            for line in lines.iter().take(DEBUG_CHAT_LINES) {
+                listing.push(msg!("schem_debug_line", line));
+            }
+            let dump_name = format!("{DEBUG_DIR}/{filename}.txt");
+            listing.push(msg!("schem_debug_written", format_count(lines.len()), dump_name));
+            listing.send(sender).await;
+
+            Ok(lines.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we schem list
+// ============================================================================
+
+/// Sort order for `/we schem list`.
+enum ListOrder {
+    Name,
+    Newest,
+    Largest,
+}
+
+/// Lists saved schematics by name, or with `-d` newest first and `-s` largest first.
+pub struct SchemListExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemListExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let flag_input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+            let mut order = ListOrder::Name;
+            for flag in flag_tokens(&flag_input) {
+                match flag {
+                    "-d" => order = ListOrder::Newest,
+                    "-s" => order = ListOrder::Largest,
+                    other => return Err(CommandError::CommandFailed(msg!("unknown_flag", other))),
+                }
+            }
 
             let schematics_dir = get_schematics_dir()?;
+            let schematics = schematic::list_schematics(&schematics_dir)
+                .await
+                .map_err(|e| CommandError::CommandFailed(msg!("schem_dir_read_failed", e)))?;
 
-            // Resolve path: try .schem then .litematic if no extension given
-            let file_path = if schem_name.ends_with(".schem") || schem_name.ends_with(".litematic") {
-                schematics_dir.join(schem_name)
-            } else {
-                let schem_path = schematics_dir.join(format!("{schem_name}.schem"));
-                let litematic_path = schematics_dir.join(format!("{schem_name}.litematic"));
-                if schem_path.exists() {
-                    schem_path
-                } else if litematic_path.exists() {
-                    litematic_path
+            if schematics.is_empty() {
+                notify(sender, Level::Warning, msg!("schem_none")).await;
+                return Ok(0);
+            }
+
+            let mut sorted: Vec<&SchematicEntry> = schematics.iter().collect();
+            match order {
+                ListOrder::Name => {}
+                ListOrder::Newest => sorted.sort_by(|a, b| b.modified.cmp(&a.modified)),
+                ListOrder::Largest => sorted.sort_by(|a, b| b.size.cmp(&a.size)),
+            }
+
+            // The file the player's clipboard was loaded from, forgotten if it is gone
+            let loaded = sender.as_player().and_then(|player| {
+                let mut state = PLAYER_DATA.lock();
+                let clipboard = state.get_mut(&player.gameprofile.id)?.clipboard.as_mut()?;
+                let source = clipboard.source.clone()?;
+                if !schematics_dir.join(&source).exists() {
+                    clipboard.source = None;
+                    return None;
+                }
+                Path::new(&source).file_stem().and_then(|stem| stem.to_str()).map(str::to_string)
+            });
+
+            let mut listing = Listing::new(msg!("schem_header", sorted.len()));
+            for entry in &sorted {
+                let size_kib = entry.size.div_ceil(1024);
+                let key = if loaded.as_deref() == Some(entry.name.as_str()) {
+                    "schem_list_entry_loaded"
                 } else {
-                    schematics_dir.join(format!("{schem_name}.schem"))
+                    "schem_list_entry"
+                };
+                listing.push(msg!(key, entry.name, format_count(size_kib)));
+            }
+            listing.send(sender).await;
+
+            Ok(sorted.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we schem delete <name> -f
+// ============================================================================
+
+/// Moves a schematic into the trash. Requires `-f` so a mistyped name isn't lost by accident.
+pub struct SchemDeleteExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemDeleteExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let schem_name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            let flag_input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+            let mut confirmed = false;
+            for flag in flag_tokens(&flag_input) {
+                match flag {
+                    "-f" => confirmed = true,
+                    other => return Err(CommandError::CommandFailed(msg!("unknown_flag", other))),
                 }
-            };
+            }
 
+            let schematics_dir = get_schematics_dir()?;
+            let file_path = find_schematic(&schematics_dir, schem_name)?;
             let filename = file_path
                 .file_name()
                 .and_then(|n| n.to_str())
                 .unwrap_or(schem_name);
 
-            if !file_path.exists() {
-                return Err(CommandError::CommandFailed(
-                    TextComponent::text(format!(
-                        "Schematic '{schem_name}' not found (tried .schem and .litematic)."
-                    ))
-                    .color_named(NamedColor::Red),
-                ));
+            if !confirmed {
+                notify(sender, Level::Warning, msg!("schem_delete_confirm", filename, schem_name))
+                    .await;
+                return Ok(0);
             }
 
-            std::fs::remove_file(&file_path).map_err(|e| {
-                CommandError::CommandFailed(
-                    TextComponent::text(format!("Failed to delete schematic: {e}"))
-                        .color_named(NamedColor::Red),
-                )
+            schematic::trash_file(&schematics_dir, &file_path).map_err(|e| {
+                CommandError::CommandFailed(msg!("schem_delete_failed", e))
             })?;
+            forget_clipboard_source(filename);
 
-            sender
-                .send_message(
-                    TextComponent::text(format!("Schematic '{filename}' deleted."))
-                        .color_named(NamedColor::Aqua),
-                )
-                .await;
+            let stem = file_path.file_stem().and_then(|n| n.to_str()).unwrap_or(schem_name);
+            let days = config::get().schematic_trash_days;
+            notify(sender, Level::Success, msg!("schem_deleted", filename, stem, days)).await;
 
             Ok(1)
         })
     }
 }
+
+// ============================================================================
+// /we schem restore <name>
+// ============================================================================
+
+/// Moves the most recently deleted schematic of a name back out of the trash.
+pub struct SchemRestoreExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemRestoreExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let schem_name = SimpleArgConsumer::find_arg(args, ARG_SCHEM_NAME)?;
+            check_schem_name(schem_name)?;
+            let stem = schem_name
+                .strip_suffix(".schem")
+                .or_else(|| schem_name.strip_suffix(".litematic"))
+                .unwrap_or(schem_name);
+
+            let schematics_dir = get_schematics_dir()?;
+            let restored = schematic::restore_file(&schematics_dir, stem)
+                .map_err(|e| CommandError::CommandFailed(msg!("schem_restore_failed", e)))?
+                .ok_or(CommandError::CommandFailed(msg!("schem_not_in_trash", stem)))?;
+
+            let filename = restored.file_name().and_then(|n| n.to_str()).unwrap_or(stem);
+            notify(sender, Level::Success, msg!("schem_restored", filename)).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we schem backups <player> [restore <number>]
+// ============================================================================
+
+/// A player's automatic backups, newest first.
+fn player_backups(player_id: &Uuid) -> Result<Vec<BackupEntry>, CommandError> {
+    let dir = schematic::backup_dir(&get_schematics_dir()?, player_id);
+    Ok(schematic::list_backups(&dir))
+}
+
+/// Lists a player's automatic backups, newest first and numbered for `restore`.
+pub struct SchemBackupsExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemBackupsExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let targets = PlayersArgumentConsumer::find_arg(args, ARG_PLAYER)?;
+
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            let mut total = 0;
+            for target in &targets {
+                let backups = player_backups(&target.gameprofile.id)?;
+                let name = &target.gameprofile.name;
+                if backups.is_empty() {
+                    notify(sender, Level::Warning, msg!("backup_none", name)).await;
+                    continue;
+                }
+                let mut listing = Listing::new(msg!("backup_header", name, backups.len()));
+                for (index, backup) in backups.iter().enumerate() {
+                    let age = now.saturating_sub(Duration::from_secs(backup.secs));
+                    listing.push(msg!(
+                        "backup_entry",
+                        index + 1,
+                        format_ago(age),
+                        backup.origin.x,
+                        backup.origin.y,
+                        backup.origin.z,
+                        backup.world,
+                        format_count(backup.size.div_ceil(1024))
+                    ));
+                }
+                listing.send(sender).await;
+                total += backups.len();
+            }
+
+            Ok(total as i32)
+        })
+    }
+}
+
+/// Writes a backup back where it was taken, air included, as one undoable operation. The
+/// sender must be in the backup's world; 1 is the newest backup.
+pub struct SchemBackupRestoreExecutor;
+
+#[async_trait]
+impl CommandExecutor for SchemBackupRestoreExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, ADMIN).await?;
+            let targets = PlayersArgumentConsumer::find_arg(args, ARG_PLAYER)?;
+            let [target] = targets.as_slice() else {
+                return Err(CommandError::CommandFailed(msg!("backup_one_player")));
+            };
+            let number = SimpleArgConsumer::find_arg(args, ARG_BACKUP_NUMBER)?;
+            let backups = player_backups(&target.gameprofile.id)?;
+            let backup = number
+                .parse::<usize>()
+                .ok()
+                .and_then(|number| backups.get(number.checked_sub(1)?))
+                .ok_or(CommandError::CommandFailed(msg!(
+                    "backup_not_found",
+                    number,
+                    backups.len()
+                )))?;
+
+            let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
+            if world.get_world_name() != backup.world {
+                return Err(CommandError::CommandFailed(msg!("backup_other_world", backup.world)));
+            }
+
+            let load_failed = |e| CommandError::CommandFailed(msg!("schem_load_failed", e));
+            let root = read_schematic(sender, &backup.path).await.map_err(load_failed)?;
+            let info = schematic::read_schematic_info(&root).map_err(load_failed)?;
+            let _reservation = MemoryReservation::acquire(info.estimated_bytes())?;
+            let data = schematic::decode_schematic(&root).map_err(load_failed)?;
+            // Air was left out when decoding, but the backup covers its whole box
+            let stored: HashMap<Vector3<i32>, u16> = data.blocks.into_iter().collect();
+            let air_state_id = Block::AIR.default_state.id;
+
+            let flags = placement_flags(&player_id, false);
+            let name = &target.gameprofile.name;
+            let command = format!("/we schem backups {name} restore {number}");
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            let result = async {
+                for y in 0..i32::from(data.height) {
+                    for x in 0..i32::from(data.width) {
+                        for z in 0..i32::from(data.length) {
+                            let offset = Vector3::new(
+                                x + data.offset.x,
+                                y + data.offset.y,
+                                z + data.offset.z,
+                            );
+                            let state_id = stored.get(&offset).copied().unwrap_or(air_state_id);
+                            let pos = BlockPos(Vector3::new(
+                                backup.origin.x + offset.x,
+                                backup.origin.y + offset.y,
+                                backup.origin.z + offset.z,
+                            ));
+                            session.set_block(pos, state_id).await?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            .await;
+            let done = session.finish(result)?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "backup_restored",
+                    number,
+                    name,
+                    format_count(done.blocks),
+                    done.time(),
+                    placement_note(flags)
+                ),
+            )
+            .await;
+            let file = backup.path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+            report_fallbacks(sender, file, data.fallbacks).await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the schematic commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("schem")
+            .then(
+                literal("load").then(
+                    argument(ARG_SCHEM_NAME, SimpleArgConsumer)
+                        .execute(SchemLoadExecutor)
+                        .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SchemLoadExecutor)),
+                ),
+            )
+            .then(
+                literal("paste").then(
+                    argument(ARG_SCHEM_NAME, SimpleArgConsumer)
+                        .execute(SchemPasteExecutor)
+                        .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SchemPasteExecutor)),
+                ),
+            )
+            .then(
+                literal("save").then(
+                    argument(ARG_SCHEM_NAME, SimpleArgConsumer)
+                        .execute(SchemSaveExecutor)
+                        .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SchemSaveExecutor)),
+                ),
+            )
+            .then(literal("savetiles").then(
+                argument(ARG_SCHEM_NAME, SimpleArgConsumer).then(
+                    argument(ARG_TILE_SIZE, SimpleArgConsumer).execute(SchemSaveTilesExecutor),
+                ),
+            ))
+            .then(
+                literal("loadtiles").then(
+                    argument(ARG_SCHEM_NAME, SimpleArgConsumer).execute(SchemLoadTilesExecutor),
+                ),
+            )
+            .then(
+                literal("info")
+                    .then(argument(ARG_SCHEM_NAME, SimpleArgConsumer).execute(SchemInfoExecutor)),
+            )
+            .then(
+                literal("debug")
+                    .then(argument(ARG_SCHEM_NAME, SimpleArgConsumer).execute(SchemDebugExecutor)),
+            )
+            .then(literal("heightmap-export").then(
+                argument(ARG_SCHEM_NAME, SimpleArgConsumer).execute(SchemHeightmapExportExecutor),
+            ))
+            .then(
+                literal("list")
+                    .execute(SchemListExecutor)
+                    .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SchemListExecutor)),
+            )
+            .then(
+                literal("delete").then(
+                    argument(ARG_SCHEM_NAME, SimpleArgConsumer)
+                        .execute(SchemDeleteExecutor)
+                        .then(argument(ARG_FLAGS, MsgArgConsumer).execute(SchemDeleteExecutor)),
+                ),
+            )
+            .then(
+                literal("restore").then(
+                    argument(ARG_SCHEM_NAME, SimpleArgConsumer).execute(SchemRestoreExecutor),
+                ),
+            )
+            .then(
+                literal("backups").then(
+                    argument(ARG_PLAYER, PlayersArgumentConsumer)
+                        .execute(SchemBackupsExecutor)
+                        .then(
+                            literal("restore").then(
+                                argument(ARG_BACKUP_NUMBER, SimpleArgConsumer)
+                                    .execute(SchemBackupRestoreExecutor),
+                            ),
+                        ),
+                ),
+            ),
+    )
+}
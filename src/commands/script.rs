@@ -0,0 +1,174 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{message::MsgArgConsumer, simple::SimpleArgConsumer, ConsumedArgs, FindArg},
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
+    server::Server,
+};
+
+use crate::messages::msg;
+use crate::notify::{notify, Level};
+use crate::permissions::{require_permission, RUN};
+use crate::state::SCRIPTS_DIR;
+
+use super::{flag_tokens, COMMAND_NAMES};
+
+/// Argument name for the script in `run`.
+pub const ARG_SCRIPT_NAME: &str = "script";
+/// Argument name for the positional arguments substituted into `$1`, `$2`, ...
+pub const ARG_SCRIPT_ARGS: &str = "args";
+
+/// File extension of script files.
+const SCRIPT_EXTENSION: &str = "txt";
+
+fn script_path(name: &str) -> Result<PathBuf, CommandError> {
+    if name.contains(['/', '\\']) || name.contains("..") {
+        return Err(CommandError::CommandFailed(msg!("script_invalid_name", name)));
+    }
+    let dir = SCRIPTS_DIR.get().ok_or(CommandError::CommandFailed(msg!("scripts_dir_missing")))?;
+    let file_name = if name.ends_with(&format!(".{SCRIPT_EXTENSION}")) {
+        name.to_string()
+    } else {
+        format!("{name}.{SCRIPT_EXTENSION}")
+    };
+    let path = dir.join(file_name);
+    if !path.is_file() {
+        return Err(CommandError::CommandFailed(msg!("script_not_found", name)));
+    }
+    Ok(path)
+}
+
+/// Replace `$1`..`$9` with the given arguments. `$$` is a literal `$`.
+fn substitute(line: &str, args: &[&str]) -> Result<String, String> {
+    let mut out = String::with_capacity(line.len());
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            out.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('$') => {
+                chars.next();
+                out.push('$');
+            }
+            Some(digit @ '1'..='9') => {
+                chars.next();
+                let index = digit as usize - '1' as usize;
+                let value = args.get(index).ok_or_else(|| {
+                    format!("uses ${digit} but only {} argument(s) were given", args.len())
+                })?;
+                out.push_str(value);
+            }
+            _ => out.push('$'),
+        }
+    }
+    Ok(out)
+}
+
+/// Turn a script line into a full `/we` command: `set stone`, `we set stone` and
+/// `/we set stone` are all accepted.
+fn to_command(line: &str) -> Result<String, String> {
+    let line = line.trim_start_matches('/');
+    let first = line.split_whitespace().next().unwrap_or_default();
+    let (command, subcommand) = if COMMAND_NAMES.contains(&first) {
+        (line.to_string(), line.split_whitespace().nth(1).unwrap_or_default())
+    } else {
+        (format!("{} {line}", COMMAND_NAMES[0]), first)
+    };
+    if subcommand == "run" {
+        return Err("scripts cannot call run".to_string());
+    }
+    Ok(command)
+}
+
+// ============================================================================
+// /we run <script> [args...]
+// ============================================================================
+
+/// Runs the WorldEdit subcommands in `scripts/<name>.txt` one line at a time as the
+/// sender. Blank lines and `#` comments are skipped; the first failing line stops the script.
+pub struct RunExecutor;
+
+#[async_trait]
+impl CommandExecutor for RunExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, RUN).await?;
+
+            let name = SimpleArgConsumer::find_arg(args, ARG_SCRIPT_NAME)?;
+            let arg_input = MsgArgConsumer::find_arg(args, ARG_SCRIPT_ARGS).unwrap_or_default();
+            let script_args = flag_tokens(&arg_input);
+
+            let source = std::fs::read_to_string(script_path(name)?)
+                .map_err(|e| CommandError::CommandFailed(msg!("script_read_failed", name, e)))?;
+
+            // Resolve every line before running any, so a typo on line 9 doesn't leave
+            // the first 8 lines applied
+            let mut commands = Vec::new();
+            for (index, line) in source.lines().enumerate() {
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                let line_number = index + 1;
+                let command = substitute(line, &script_args)
+                    .and_then(|line| to_command(&line))
+                    .map_err(|e| {
+                        CommandError::CommandFailed(msg!("script_line_error", name, line_number, e))
+                    })?;
+                commands.push((line_number, command));
+            }
+
+            for (line_number, command) in &commands {
+                // Locked per line only, so a long script doesn't hold the dispatcher throughout
+                let dispatcher = server.command_dispatcher.read().await;
+                let result = dispatcher.dispatch(sender, server, command).await;
+                drop(dispatcher);
+                if let Err(e) = result {
+                    if let CommandError::CommandFailed(message) = e {
+                        sender.send_message(message).await;
+                    }
+                    return Err(CommandError::CommandFailed(msg!(
+                        "script_stopped",
+                        name,
+                        line_number,
+                        command
+                    )));
+                }
+            }
+
+            notify(sender, Level::Success, msg!("script_finished", name, commands.len())).await;
+
+            Ok(commands.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the script commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("run").then(
+            argument(ARG_SCRIPT_NAME, SimpleArgConsumer)
+                .execute(RunExecutor)
+                .then(argument(ARG_SCRIPT_ARGS, MsgArgConsumer).execute(RunExecutor)),
+        ),
+    )
+}
@@ -1,13 +1,51 @@
+use std::collections::HashMap;
+
 use async_trait::async_trait;
 use pumpkin::{
-    command::{args::ConsumedArgs, CommandExecutor, CommandResult, CommandSender},
+    command::{
+        args::{
+            block::BlockArgumentConsumer, message::MsgArgConsumer, simple::SimpleArgConsumer,
+            ConsumedArgs, FindArg,
+        },
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
     server::Server,
 };
-use pumpkin_util::text::{color::NamedColor, TextComponent};
+use pumpkin_data::Block;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+use pumpkin_util::text::TextComponent;
 
+use crate::config;
+use crate::engine::{raycast_block, BlockAccess};
+use crate::flags::{FlagSpec, Flags};
+use crate::messages::{format_count, msg};
+use crate::notify::{notify, Level, Listing};
+use crate::schematic::build_block_state_string;
 use crate::state::{
-    get_selection, sender_block_pos, sender_uuid, selection_volume, PLAYER_DATA,
+    check_selection_size, get_selection, get_selection_shape, selection_volume, sender_block_pos,
+    sender_facing, sender_look, sender_uuid, sender_world, PLAYER_DATA,
 };
+use crate::transform::{parse_direction, paste_bounds};
+
+use super::clipboard::ClipboardSummary;
+use super::utility::TARGET_RANGE;
+use super::{flag_error, flag_tokens, parse_radius, ARG_FLAGS};
+
+/// Argument name for the block in `count`.
+pub const ARG_COUNT_BLOCK: &str = "block";
+/// Argument name for the block in `blockinfo`.
+pub const ARG_INFO_BLOCK: &str = "block";
+/// Argument name for the page of `blockinfo`.
+pub const ARG_PAGE: &str = "page";
+/// Argument name for the coordinates in `sel coords`, `sel shift-to` and `sel contains`.
+pub const ARG_COORDS: &str = "coords";
+/// Argument name for the amount of `expand`, `contract`, `outset` and `inset`.
+pub const ARG_RESIZE_AMOUNT: &str = "amount";
 
 // ============================================================================
 // //pos1
@@ -26,22 +64,23 @@ impl CommandExecutor for Pos1Executor {
         Box::pin(async move {
             let block_pos = sender_block_pos(sender)?;
             let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
 
-            {
-                let mut state = PLAYER_DATA.lock().unwrap();
+            let cleared = {
+                let mut state = PLAYER_DATA.lock();
                 let data = state.entry(player_id).or_default();
-                data.pos1 = Some(block_pos);
-            }
+                data.set_corner(true, block_pos, world.get_world_name())
+            };
 
-            sender
-                .send_message(
-                    TextComponent::text(format!(
-                        "Position 1 set to ({}, {}, {})",
-                        block_pos.0.x, block_pos.0.y, block_pos.0.z
-                    ))
-                    .color_named(NamedColor::Aqua),
-                )
-                .await;
+            notify(
+                sender,
+                Level::Success,
+                msg!("pos1_set", block_pos.0.x, block_pos.0.y, block_pos.0.z),
+            )
+            .await;
+            if let Some(other_world) = cleared {
+                notify(sender, Level::Warning, msg!("sel_corner_cleared", 2, other_world)).await;
+            }
 
             Ok(1)
         })
@@ -65,22 +104,23 @@ impl CommandExecutor for Pos2Executor {
         Box::pin(async move {
             let block_pos = sender_block_pos(sender)?;
             let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
 
-            {
-                let mut state = PLAYER_DATA.lock().unwrap();
+            let cleared = {
+                let mut state = PLAYER_DATA.lock();
                 let data = state.entry(player_id).or_default();
-                data.pos2 = Some(block_pos);
-            }
+                data.set_corner(false, block_pos, world.get_world_name())
+            };
 
-            sender
-                .send_message(
-                    TextComponent::text(format!(
-                        "Position 2 set to ({}, {}, {})",
-                        block_pos.0.x, block_pos.0.y, block_pos.0.z
-                    ))
-                    .color_named(NamedColor::Aqua),
-                )
-                .await;
+            notify(
+                sender,
+                Level::Success,
+                msg!("pos2_set", block_pos.0.x, block_pos.0.y, block_pos.0.z),
+            )
+            .await;
+            if let Some(other_world) = cleared {
+                notify(sender, Level::Warning, msg!("sel_corner_cleared", 1, other_world)).await;
+            }
 
             Ok(1)
         })
@@ -91,6 +131,8 @@ impl CommandExecutor for Pos2Executor {
 // //size
 // ============================================================================
 
+/// Shows the selection's dimensions and corners, then the clipboard's size, block count and
+/// source. Either part is left out when there is none; only having neither is an error.
 pub struct SizeExecutor;
 
 #[async_trait]
@@ -103,33 +145,933 @@ impl CommandExecutor for SizeExecutor {
     ) -> CommandResult<'a> {
         Box::pin(async move {
             let player_id = sender_uuid(sender)?;
-            let (min, max) = get_selection(&player_id)?;
-
-            let dx = max.0.x - min.0.x + 1;
-            let dy = max.0.y - min.0.y + 1;
-            let dz = max.0.z - min.0.z + 1;
-            let volume = selection_volume(&min, &max);
-
-            sender
-                .send_message(
-                    TextComponent::text(format!(
-                        "Selection: {dx} x {dy} x {dz} ({volume} blocks)"
-                    ))
-                    .color_named(NamedColor::Aqua),
-                )
-                .await;
+            let clipboard = ClipboardSummary::of(&player_id);
+            let selection = match (get_selection(sender), &clipboard) {
+                // Neither exists: the selection's error says what is missing
+                (Err(error), None) => return Err(error),
+                (selection, _) => selection.ok(),
+            };
+
+            if let Some((min, max)) = &selection {
+                let dx = max.0.x - min.0.x + 1;
+                let dy = max.0.y - min.0.y + 1;
+                let dz = max.0.z - min.0.z + 1;
+                let volume = selection_volume(min, max);
 
-            sender
-                .send_message(
-                    TextComponent::text(format!(
-                        "  From: ({}, {}, {})  To: ({}, {}, {})",
-                        min.0.x, min.0.y, min.0.z, max.0.x, max.0.y, max.0.z
-                    ))
-                    .color_named(NamedColor::Gray),
+                notify(sender, Level::Info, msg!("size", dx, dy, dz, format_count(volume))).await;
+
+                notify(
+                    sender,
+                    Level::Info,
+                    msg!("size_bounds", min.0.x, min.0.y, min.0.z, max.0.x, max.0.y, max.0.z),
                 )
                 .await;
+            }
+            if let Some(clipboard) = &clipboard {
+                clipboard.send(sender).await;
+            }
+
+            Ok(i32::from(selection.is_some()) + i32::from(clipboard.is_some()))
+        })
+    }
+}
+
+// ============================================================================
+// //sel clipboard
+// ============================================================================
+
+/// Select the region the clipboard would occupy if pasted at the player's position.
+pub struct SelClipboardExecutor;
+
+#[async_trait]
+impl CommandExecutor for SelClipboardExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let origin = sender_block_pos(sender)?;
+            let world = sender_world(sender)?.get_world_name().to_string();
+
+            let (min, max) = {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player_id).or_default();
+                let (min, max) = data
+                    .clipboard
+                    .as_ref()
+                    .and_then(|clipboard| paste_bounds(clipboard, &origin))
+                    .ok_or(CommandError::CommandFailed(msg!("clipboard_empty")))?;
+                data.set_selection(Some(min), Some(max), Some(world));
+                (min, max)
+            };
+
+            notify_selection(sender, "sel_clipboard", &min, &max).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// //sel coords <x1> <y1> <z1> <x2> <y2> <z2>, //sel shift-to <x> <y> <z>
+// ============================================================================
+
+/// Parse `count` coordinates such as `12 64 -5`, `~ ~3 ~-2` (relative to `origin`) or text
+/// pasted from chat like `(123, 64, -512) to (200, 90, -400)`, and check each Y against the
+/// world's build height.
+fn parse_coords(
+    input: &str,
+    count: usize,
+    origin: &BlockPos,
+    (min_y, max_y): (i32, i32),
+) -> Result<Vec<BlockPos>, CommandError> {
+    let tokens: Vec<&str> = input
+        .split(|c: char| c.is_whitespace() || matches!(c, ',' | '(' | ')'))
+        .filter(|token| !token.is_empty() && !token.eq_ignore_ascii_case("to"))
+        .collect();
+    if tokens.len() != count * 3 {
+        return Err(CommandError::CommandFailed(msg!("coords_count", count * 3, tokens.len())));
+    }
+
+    let base = [origin.0.x, origin.0.y, origin.0.z];
+    let values = tokens
+        .iter()
+        .enumerate()
+        .map(|(i, token)| {
+            let value = match token.strip_prefix('~') {
+                Some("") => Some(base[i % 3]),
+                Some(offset) => offset.parse::<i32>().ok().map(|offset| base[i % 3] + offset),
+                None => token.parse().ok(),
+            };
+            value.ok_or_else(|| CommandError::CommandFailed(msg!("invalid_coordinate", token)))
+        })
+        .collect::<Result<Vec<i32>, _>>()?;
+
+    values
+        .chunks(3)
+        .map(|xyz| {
+            if xyz[1] < min_y || xyz[1] > max_y {
+                return Err(CommandError::CommandFailed(msg!(
+                    "coords_out_of_height",
+                    xyz[1],
+                    min_y,
+                    max_y
+                )));
+            }
+            Ok(BlockPos(Vector3::new(xyz[0], xyz[1], xyz[2])))
+        })
+        .collect()
+}
+
+/// Tell the player the new selection's size and corners.
+async fn notify_selection(sender: &CommandSender, key: &str, min: &BlockPos, max: &BlockPos) {
+    let dx = max.0.x - min.0.x + 1;
+    let dy = max.0.y - min.0.y + 1;
+    let dz = max.0.z - min.0.z + 1;
+    notify(sender, Level::Success, msg!(key, dx, dy, dz)).await;
+    notify(
+        sender,
+        Level::Info,
+        msg!("size_bounds", min.0.x, min.0.y, min.0.z, max.0.x, max.0.y, max.0.z),
+    )
+    .await;
+}
+
+/// Sets both corners at once from six coordinates.
+pub struct SelCoordsExecutor;
+
+#[async_trait]
+impl CommandExecutor for SelCoordsExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let input = MsgArgConsumer::find_arg(args, ARG_COORDS)?;
+            let origin = sender_block_pos(sender)?;
+            let world = sender_world(sender)?;
+            let height = world.height_range();
+            let corners = parse_coords(&input, 2, &origin, height)?;
+            let (pos1, pos2) = (corners[0], corners[1]);
+
+            {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player_id).or_default();
+                let world = world.get_world_name().to_string();
+                data.set_selection(Some(pos1), Some(pos2), Some(world));
+            }
+            let (min, max) = get_selection(sender)?;
+
+            notify_selection(sender, "sel_coords_done", &min, &max).await;
+
+            Ok(1)
+        })
+    }
+}
+
+/// Moves the selection so its min corner lands on the given point, keeping its size.
+pub struct SelShiftToExecutor;
+
+#[async_trait]
+impl CommandExecutor for SelShiftToExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let input = MsgArgConsumer::find_arg(args, ARG_COORDS)?;
+            let origin = sender_block_pos(sender)?;
+            let (min_y, max_y) = sender_world(sender)?.height_range();
+            let target = parse_coords(&input, 1, &origin, (min_y, max_y))?[0];
+
+            let (min, max) = get_selection(sender)?;
+            let shift = |pos: BlockPos| {
+                BlockPos(Vector3::new(
+                    pos.0.x + target.0.x - min.0.x,
+                    pos.0.y + target.0.y - min.0.y,
+                    pos.0.z + target.0.z - min.0.z,
+                ))
+            };
+            let (min, max) = (shift(min), shift(max));
+            if max.0.y > max_y {
+                return Err(CommandError::CommandFailed(msg!(
+                    "coords_out_of_height",
+                    max.0.y,
+                    min_y,
+                    max_y
+                )));
+            }
+
+            {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player_id).or_default();
+                let world = data.selection_world.clone();
+                data.set_selection(data.pos1.map(shift), data.pos2.map(shift), world);
+            }
+
+            notify_selection(sender, "sel_shifted", &min, &max).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// //expand, //contract <amount> [direction] [-h|-v], //outset, //inset <amount> [-h|-v]
+// ============================================================================
+
+/// Flags accepted by the selection resize commands: only the horizontal or vertical faces.
+const RESIZE_FLAGS: &[FlagSpec] = &[FlagSpec::switch("-h"), FlagSpec::switch("-v")];
+
+/// Largest amount accepted by the selection resize commands.
+const MAX_RESIZE: u32 = 1024;
+
+/// How a resize command moves the selection's faces. A negative amount does the inverse:
+/// `outset -3` is `inset 3`, and `expand -3 north` pulls back the face `expand 3 north` pushed.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Resize {
+    /// Move the face in a direction outward.
+    Expand,
+    /// Move the face opposite a direction inward, shrinking toward the direction.
+    Contract,
+    /// Move every face outward.
+    Outset,
+    /// Move every face inward.
+    Inset,
+}
+
+impl Resize {
+    fn takes_direction(self) -> bool {
+        matches!(self, Self::Expand | Self::Contract)
+    }
+
+    fn grows(self) -> bool {
+        matches!(self, Self::Expand | Self::Outset)
+    }
+}
+
+/// The faces a resize moves: the one a direction points at, or both faces along each axis
+/// the `-h` and `-v` flags leave.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Faces {
+    Toward(Vector3<i32>),
+    Axes { horizontal: bool, vertical: bool },
+}
+
+/// Work out the faces to move from the direction argument and the `-h`/`-v` flags. Expand and
+/// contract take a direction (default: where the player is facing) or a restriction flag but
+/// not both; outset and inset move every face the flags allow.
+fn resize_faces(
+    resize: Resize,
+    direction: Option<Vector3<i32>>,
+    horizontal: bool,
+    vertical: bool,
+    facing: impl FnOnce() -> Result<Vector3<i32>, CommandError>,
+) -> Result<Faces, CommandError> {
+    let restricted = horizontal || vertical;
+    if !resize.takes_direction() {
+        return Ok(if restricted {
+            Faces::Axes { horizontal, vertical }
+        } else {
+            Faces::Axes { horizontal: true, vertical: true }
+        });
+    }
+    match direction {
+        Some(_) if restricted => {
+            Err(CommandError::CommandFailed(msg!("resize_direction_and_axis")))
+        }
+        Some(direction) => Ok(Faces::Toward(direction)),
+        None if restricted => Ok(Faces::Axes { horizontal, vertical }),
+        None => facing().map(Faces::Toward),
+    }
+}
+
+/// Outward growth of each face in the order west, east, down, up, north, south; negative
+/// values shrink the selection. `amount` may be negative for the inverse operation.
+fn face_growth(resize: Resize, amount: i32, faces: Faces) -> [i32; 6] {
+    let change = if resize.grows() { amount } else { -amount };
+    let mut growth = [0; 6];
+    match faces {
+        Faces::Toward(direction) => {
+            for (axis, component) in [direction.x, direction.y, direction.z].into_iter().enumerate()
+            {
+                if component == 0 {
+                    continue;
+                }
+                // The face the direction points at; contract moves the one across from it
+                let toward = 2 * axis + usize::from(component > 0);
+                let face = if resize.grows() { toward } else { toward ^ 1 };
+                growth[face] = change;
+            }
+        }
+        Faces::Axes { horizontal, vertical } => {
+            for (face, growth) in growth.iter_mut().enumerate() {
+                let is_vertical = face / 2 == 1;
+                if (is_vertical && vertical) || (!is_vertical && horizontal) {
+                    *growth = change;
+                }
+            }
+        }
+    }
+    growth
+}
+
+/// The selection `min..=max` with each face moved outward by its `growth` (see
+/// [`face_growth`]) and Y kept within the build height `(min_y, max_y)`. A face that would
+/// pass the one across from it stops there instead; the flag tells whether that happened.
+fn resized(
+    min: &BlockPos,
+    max: &BlockPos,
+    growth: [i32; 6],
+    (min_y, max_y): (i32, i32),
+) -> (BlockPos, BlockPos, bool) {
+    let (min_x, max_x, clamped_x) =
+        clamp_axis(min.0.x, max.0.x, min.0.x - growth[0], max.0.x + growth[1]);
+    let (low_y, high_y, clamped_y) = clamp_axis(
+        min.0.y,
+        max.0.y,
+        (min.0.y - growth[2]).max(min_y),
+        (max.0.y + growth[3]).min(max_y),
+    );
+    let (min_z, max_z, clamped_z) =
+        clamp_axis(min.0.z, max.0.z, min.0.z - growth[4], max.0.z + growth[5]);
+    (
+        BlockPos(Vector3::new(min_x, low_y, min_z)),
+        BlockPos(Vector3::new(max_x, high_y, max_z)),
+        clamped_x || clamped_y || clamped_z,
+    )
+}
+
+/// Keep one axis of a resized selection from inverting. If only one side moved inward it stops
+/// at the other; if both did, they meet in the middle of the old range, leaving one layer.
+fn clamp_axis(low: i32, high: i32, new_low: i32, new_high: i32) -> (i32, i32, bool) {
+    if new_low <= new_high {
+        return (new_low, new_high, false);
+    }
+    let meet = if new_low <= low {
+        new_low
+    } else if new_high >= high {
+        new_high
+    } else {
+        (low + high).div_euclid(2)
+    };
+    (meet, meet, true)
+}
+
+/// Grows or shrinks the selection. `expand` and `contract` move one face toward a direction,
+/// `outset` and `inset` every face; `-h` and `-v` limit them to the horizontal or vertical
+/// faces.
+pub struct ResizeExecutor {
+    pub resize: Resize,
+}
+
+#[async_trait]
+impl CommandExecutor for ResizeExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let value = SimpleArgConsumer::find_arg(args, ARG_RESIZE_AMOUNT)?;
+            let amount = value
+                .parse::<i32>()
+                .ok()
+                .filter(|amount| (1..=MAX_RESIZE).contains(&amount.unsigned_abs()))
+                .ok_or_else(|| {
+                    CommandError::CommandFailed(msg!("resize_amount_range", MAX_RESIZE))
+                })?;
+            let max_values = usize::from(self.resize.takes_direction());
+            let (values, flags) = Flags::from_args_with_values(args, max_values, RESIZE_FLAGS)?;
+            let direction = match values.first() {
+                Some(direction) => Some(parse_direction(direction).map_err(flag_error)?),
+                None => None,
+            };
+            let faces =
+                resize_faces(self.resize, direction, flags.has("-h"), flags.has("-v"), || {
+                    sender_facing(sender)
+                })?;
+            let growth = face_growth(self.resize, amount, faces);
+
+            let (min, max) = get_selection(sender)?;
+            let height = sender_world(sender)?.height_range();
+            let (min, max, clamped) = resized(&min, &max, growth, height);
+
+            {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player_id).or_default();
+                let world = data.selection_world.clone();
+                data.set_selection(Some(min), Some(max), world);
+            }
+
+            notify_selection(sender, "sel_resized", &min, &max).await;
+            if clamped {
+                notify(sender, Level::Warning, msg!("sel_resize_clamped")).await;
+            }
 
             Ok(1)
         })
     }
 }
+
+// ============================================================================
+// //sel contains [x y z]
+// ============================================================================
+
+/// Reports whether a position is inside the selection, its offset from the min corner and how
+/// far it is from the nearest face. Without coordinates it checks the targeted block, or the
+/// block the player stands on if none is in sight.
+pub struct SelContainsExecutor;
+
+#[async_trait]
+impl CommandExecutor for SelContainsExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let origin = sender_block_pos(sender)?;
+            let world = sender_world(sender)?;
+            let pos = match MsgArgConsumer::find_arg(args, ARG_COORDS) {
+                Ok(input) => parse_coords(&input, 1, &origin, world.height_range())?[0],
+                Err(_) => {
+                    let (eye, direction) = sender_look(sender)?;
+                    raycast_block(world.as_ref(), eye, direction, TARGET_RANGE)
+                        .await
+                        .map_err(flag_error)?
+                        .unwrap_or(BlockPos(Vector3::new(origin.0.x, origin.0.y - 1, origin.0.z)))
+                }
+            };
+
+            let shape = get_selection_shape(sender)?;
+            let (min, _) = shape.bounds();
+            let inside = shape.contains(&pos);
+            let key = if inside { "sel_contains_inside" } else { "sel_contains_outside" };
+            notify(sender, Level::Info, msg!(key, pos.0.x, pos.0.y, pos.0.z)).await;
+            notify(
+                sender,
+                Level::Info,
+                msg!(
+                    "sel_contains_offset",
+                    pos.0.x - min.0.x,
+                    pos.0.y - min.0.y,
+                    pos.0.z - min.0.z,
+                    shape.face_distance(&pos)
+                ),
+            )
+            .await;
+
+            Ok(i32::from(inside))
+        })
+    }
+}
+
+// ============================================================================
+// //sel undo, //sel redo
+// ============================================================================
+
+/// Steps back through the player's last few selections, or forward again with `redo`.
+pub struct SelUndoExecutor {
+    pub redo: bool,
+}
+
+#[async_trait]
+impl CommandExecutor for SelUndoExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            // Steps left in the same direction, if there was one to take, and whether the
+            // restored selection has both corners
+            let stepped = {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player_id).or_default();
+                let left = if self.redo {
+                    data.redo_selection().then(|| data.selection_redo.len())
+                } else {
+                    data.undo_selection().then(|| data.selection_undo.len())
+                };
+                left.map(|left| (left, data.pos1.is_some() && data.pos2.is_some()))
+            };
+            let Some((left, complete)) = stepped else {
+                let key = if self.redo { "sel_redo_empty" } else { "sel_undo_empty" };
+                return Err(CommandError::CommandFailed(msg!(key)));
+            };
+
+            let key = if self.redo { "sel_redone" } else { "sel_undone" };
+            if complete {
+                // Still refused if the restored selection is in another world
+                let (min, max) = get_selection(sender)?;
+                notify_selection(sender, key, &min, &max).await;
+            } else {
+                notify(sender, Level::Success, msg!("sel_restored_incomplete")).await;
+            }
+            notify(sender, Level::Info, msg!("sel_history_left", left)).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// //count <block> [-r <radius>], //distr [-r <radius>]
+// ============================================================================
+
+/// Region counted by `count` and `distr`: the selection, or with `-r` the cube of that
+/// radius around the player. Returns the bounds and the radius, if one was given.
+fn count_region(
+    sender: &CommandSender,
+    args: &ConsumedArgs<'_>,
+) -> Result<(BlockPos, BlockPos, Option<u32>), CommandError> {
+    let flag_input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+    let mut radius = None;
+    let mut tokens = flag_tokens(&flag_input).into_iter();
+    while let Some(flag) = tokens.next() {
+        match flag {
+            "-r" => {
+                let value = tokens.next().unwrap_or_default();
+                radius = Some(parse_radius(value, config::get().max_scan_radius)?);
+            }
+            other => return Err(CommandError::CommandFailed(msg!("unknown_flag", other))),
+        }
+    }
+
+    let (min, max) = match radius {
+        Some(radius) => {
+            let center = sender_block_pos(sender)?;
+            let r = radius as i32;
+            let corner = |d: i32| {
+                BlockPos(Vector3::new(center.0.x + d, center.0.y + d, center.0.z + d))
+            };
+            (corner(-r), corner(r))
+        }
+        None => get_selection(sender)?,
+    };
+    check_selection_size(&min, &max)?;
+    Ok((min, max, radius))
+}
+
+/// Number of blocks of each type (any state), by block name, between `min` and `max`.
+async fn tally(
+    world: &dyn BlockAccess,
+    min: &BlockPos,
+    max: &BlockPos,
+) -> Result<HashMap<&'static str, usize>, CommandError> {
+    let mut counts = HashMap::new();
+    for x in min.0.x..=max.0.x {
+        for y in min.0.y..=max.0.y {
+            for z in min.0.z..=max.0.z {
+                let state_id = world
+                    .read_state(&BlockPos(Vector3::new(x, y, z)))
+                    .await
+                    .map_err(|e| CommandError::CommandFailed(msg!("read_selection_failed", e)))?;
+                *counts.entry(Block::from_state_id(state_id).name).or_insert(0) += 1;
+            }
+        }
+    }
+    Ok(counts)
+}
+
+/// Which region was counted, so radius results can be interpreted.
+fn count_region_line(min: &BlockPos, max: &BlockPos, radius: Option<u32>) -> TextComponent {
+    match radius {
+        Some(radius) => msg!(
+            "count_radius_bounds",
+            radius,
+            min.0.x,
+            min.0.y,
+            min.0.z,
+            max.0.x,
+            max.0.y,
+            max.0.z
+        ),
+        None => msg!("size_bounds", min.0.x, min.0.y, min.0.z, max.0.x, max.0.y, max.0.z),
+    }
+}
+
+/// Counts the blocks of one type in the selection or around the player.
+pub struct CountExecutor;
+
+#[async_trait]
+impl CommandExecutor for CountExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_COUNT_BLOCK)?;
+            let (min, max, radius) = count_region(sender, args)?;
+            let world = sender_world(sender)?;
+
+            let counts = tally(world.as_ref(), &min, &max).await?;
+            let count = counts.get(block.name).copied().unwrap_or(0);
+
+            notify(sender, Level::Info, msg!("count_done", format_count(count), block.name)).await;
+            notify(sender, Level::Info, count_region_line(&min, &max, radius)).await;
+
+            Ok(count as i32)
+        })
+    }
+}
+
+/// Lists every block type in the selection or around the player, most common first.
+pub struct DistrExecutor;
+
+#[async_trait]
+impl CommandExecutor for DistrExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let (min, max, radius) = count_region(sender, args)?;
+            let world = sender_world(sender)?;
+
+            let mut counts: Vec<(&str, usize)> =
+                tally(world.as_ref(), &min, &max).await?.into_iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+            let total = selection_volume(&min, &max);
+
+            let mut listing = Listing::new(msg!("distr_header", format_count(total)));
+            listing.intro(count_region_line(&min, &max, radius));
+            for (name, count) in &counts {
+                let percent = format!("{:.1}", *count as f64 * 100.0 / total as f64);
+                listing.push(msg!("distr_entry", format_count(*count), percent, name));
+            }
+            listing.send(sender).await;
+
+            Ok(counts.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //blockinfo <block> [page]
+// ============================================================================
+
+/// Properties listed per page of `blockinfo`.
+const BLOCKINFO_PAGE_SIZE: usize = 8;
+
+/// Every property of `block` with its allowed values, in registry order.
+fn block_properties(block: &Block) -> Vec<(String, Vec<String>)> {
+    let mut properties: Vec<(String, Vec<String>)> = Vec::new();
+    for state in block.states.iter() {
+        let Some(props) = block.properties(state.id) else {
+            continue;
+        };
+        for (key, value) in props.to_props() {
+            let index = match properties.iter().position(|(name, _)| name == key) {
+                Some(index) => index,
+                None => {
+                    properties.push((key.to_string(), Vec::new()));
+                    properties.len() - 1
+                }
+            };
+            let values = &mut properties[index].1;
+            if !values.iter().any(|known| known == value) {
+                values.push(value.to_string());
+            }
+        }
+    }
+    properties
+}
+
+/// Shows a block's registry name, state count, default state and each property with its
+/// allowed values, as a reference for writing patterns and masks.
+pub struct BlockInfoExecutor;
+
+#[async_trait]
+impl CommandExecutor for BlockInfoExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_INFO_BLOCK)?;
+            let properties = block_properties(block);
+            let page = SimpleArgConsumer::find_arg(args, ARG_PAGE).ok();
+
+            let default_state = build_block_state_string(block.default_state.id);
+            let mut listing = Listing::new(msg!("blockinfo_header", block.name));
+            listing.intro(msg!("blockinfo_states", block.states.len(), default_state));
+            if properties.is_empty() {
+                listing.intro(msg!("blockinfo_no_properties"));
+            }
+            for (name, values) in &properties {
+                listing.push(msg!("blockinfo_property", name, values.join(", ")));
+            }
+            let command = format!("/we blockinfo {}", block.name);
+            listing.send_page(sender, page, BLOCKINFO_PAGE_SIZE, &command).await?;
+
+            Ok(properties.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the selection commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(literal("pos1").execute(Pos1Executor))
+        .then(literal("pos2").execute(Pos2Executor))
+        .then(literal("size").execute(SizeExecutor))
+        .then(
+            literal("sel")
+                .then(literal("clipboard").execute(SelClipboardExecutor))
+                .then(
+                    literal("coords")
+                        .then(argument(ARG_COORDS, MsgArgConsumer).execute(SelCoordsExecutor)),
+                )
+                .then(
+                    literal("shift-to")
+                        .then(argument(ARG_COORDS, MsgArgConsumer).execute(SelShiftToExecutor)),
+                )
+                .then(
+                    literal("contains")
+                        .execute(SelContainsExecutor)
+                        .then(argument(ARG_COORDS, MsgArgConsumer).execute(SelContainsExecutor)),
+                )
+                .then(literal("undo").execute(SelUndoExecutor { redo: false }))
+                .then(literal("redo").execute(SelUndoExecutor { redo: true })),
+        )
+        .then(
+            literal("expand").then(
+                argument(ARG_RESIZE_AMOUNT, SimpleArgConsumer)
+                    .execute(ResizeExecutor { resize: Resize::Expand })
+                    .then(
+                        argument(ARG_FLAGS, MsgArgConsumer)
+                            .execute(ResizeExecutor { resize: Resize::Expand }),
+                    ),
+            ),
+        )
+        .then(
+            literal("contract").then(
+                argument(ARG_RESIZE_AMOUNT, SimpleArgConsumer)
+                    .execute(ResizeExecutor { resize: Resize::Contract })
+                    .then(
+                        argument(ARG_FLAGS, MsgArgConsumer)
+                            .execute(ResizeExecutor { resize: Resize::Contract }),
+                    ),
+            ),
+        )
+        .then(
+            literal("outset").then(
+                argument(ARG_RESIZE_AMOUNT, SimpleArgConsumer)
+                    .execute(ResizeExecutor { resize: Resize::Outset })
+                    .then(
+                        argument(ARG_FLAGS, MsgArgConsumer)
+                            .execute(ResizeExecutor { resize: Resize::Outset }),
+                    ),
+            ),
+        )
+        .then(
+            literal("inset").then(
+                argument(ARG_RESIZE_AMOUNT, SimpleArgConsumer)
+                    .execute(ResizeExecutor { resize: Resize::Inset })
+                    .then(
+                        argument(ARG_FLAGS, MsgArgConsumer)
+                            .execute(ResizeExecutor { resize: Resize::Inset }),
+                    ),
+            ),
+        )
+        .then(
+            literal("count").then(
+                argument(ARG_COUNT_BLOCK, BlockArgumentConsumer)
+                    .execute(CountExecutor)
+                    .then(argument(ARG_FLAGS, MsgArgConsumer).execute(CountExecutor)),
+            ),
+        )
+        .then(
+            literal("distr")
+                .execute(DistrExecutor)
+                .then(argument(ARG_FLAGS, MsgArgConsumer).execute(DistrExecutor)),
+        )
+        .then(
+            literal("blockinfo").then(
+                argument(ARG_INFO_BLOCK, BlockArgumentConsumer)
+                    .execute(BlockInfoExecutor)
+                    .then(argument(ARG_PAGE, SimpleArgConsumer).execute(BlockInfoExecutor)),
+            ),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::pos;
+
+    const NORTH: Vector3<i32> = Vector3::new(0, 0, -1);
+    const UP: Vector3<i32> = Vector3::new(0, 1, 0);
+
+    fn faces(
+        resize: Resize,
+        direction: Option<Vector3<i32>>,
+        horizontal: bool,
+        vertical: bool,
+    ) -> Option<Faces> {
+        resize_faces(resize, direction, horizontal, vertical, || Ok(NORTH)).ok()
+    }
+
+    fn bounds((min, max, clamped): (BlockPos, BlockPos, bool)) -> ([i32; 6], bool) {
+        ([min.0.x, min.0.y, min.0.z, max.0.x, max.0.y, max.0.z], clamped)
+    }
+
+    #[test]
+    fn restriction_flags_and_directions_pick_faces() {
+        let all = Faces::Axes { horizontal: true, vertical: true };
+        let horizontal = Faces::Axes { horizontal: true, vertical: false };
+        let vertical = Faces::Axes { horizontal: false, vertical: true };
+        // (command, direction, -h, -v, faces or None for an error)
+        let cases = [
+            (Resize::Expand, None, false, false, Some(Faces::Toward(NORTH))),
+            (Resize::Expand, Some(UP), false, false, Some(Faces::Toward(UP))),
+            (Resize::Expand, None, true, false, Some(horizontal)),
+            (Resize::Expand, None, false, true, Some(vertical)),
+            (Resize::Expand, None, true, true, Some(all)),
+            (Resize::Expand, Some(UP), false, true, None),
+            (Resize::Contract, Some(UP), true, false, None),
+            (Resize::Contract, None, false, true, Some(vertical)),
+            (Resize::Outset, None, false, false, Some(all)),
+            (Resize::Outset, None, true, false, Some(horizontal)),
+            (Resize::Inset, None, false, true, Some(vertical)),
+        ];
+        for (resize, direction, h, v, expected) in cases {
+            let label = format!("{resize:?} {direction:?} -h={h} -v={v}");
+            assert_eq!(faces(resize, direction, h, v), expected, "{label}");
+        }
+    }
+
+    #[test]
+    fn each_command_moves_the_right_faces() {
+        let horizontal = Faces::Axes { horizontal: true, vertical: false };
+        let vertical = Faces::Axes { horizontal: false, vertical: true };
+        // Growth per face: west, east, down, up, north, south
+        let cases = [
+            (Resize::Expand, Faces::Toward(NORTH), [0, 0, 0, 0, 3, 0]),
+            (Resize::Expand, Faces::Toward(Vector3::new(1, 0, 1)), [0, 3, 0, 0, 0, 3]),
+            (Resize::Contract, Faces::Toward(NORTH), [0, 0, 0, 0, 0, -3]),
+            (Resize::Contract, Faces::Toward(UP), [0, 0, -3, 0, 0, 0]),
+            (Resize::Expand, vertical, [0, 0, 3, 3, 0, 0]),
+            (Resize::Outset, horizontal, [3, 3, 0, 0, 3, 3]),
+            (Resize::Outset, Faces::Axes { horizontal: true, vertical: true }, [3; 6]),
+            (Resize::Inset, vertical, [0, 0, -3, -3, 0, 0]),
+        ];
+        for (resize, faces, expected) in cases {
+            assert_eq!(face_growth(resize, 3, faces), expected, "{resize:?} {faces:?}");
+        }
+    }
+
+    #[test]
+    fn resizing_keeps_the_build_height() {
+        let height = (-64, 319);
+        let (min, max) = (pos(0, 60, 0), pos(4, 70, 4));
+
+        let grown = resized(&min, &max, [1, 2, 3, 4, 5, 6], height);
+        assert_eq!(bounds(grown), ([-1, 57, -5, 6, 74, 10], false));
+
+        // Vertical growth stops at the world's limits
+        let tall = resized(&min, &max, [0, 0, 500, 500, 0, 0], height);
+        assert_eq!(bounds(tall), ([0, -64, 0, 4, 319, 4], false));
+    }
+
+    #[test]
+    fn negative_amounts_do_the_inverse_and_never_invert_the_selection() {
+        let height = (-64, 319);
+        let all = Faces::Axes { horizontal: true, vertical: true };
+        let north = Faces::Toward(NORTH);
+        let east = Faces::Toward(Vector3::new(1, 0, 0));
+        // (selection min, max, command, amount, faces, resulting min and max, clamped)
+        let cases = [
+            // A negative amount matches the inverse command with the positive one
+            ((0, 0, 0), (9, 9, 9), Resize::Outset, -3, all, [3, 3, 3, 6, 6, 6], false),
+            ((0, 0, 0), (9, 9, 9), Resize::Inset, 3, all, [3, 3, 3, 6, 6, 6], false),
+            ((0, 0, 0), (9, 9, 9), Resize::Inset, -2, all, [-2, -2, -2, 11, 11, 11], false),
+            // and undoes the positive one on the same face
+            ((0, 0, 0), (9, 9, 9), Resize::Expand, -4, north, [0, 0, 4, 9, 9, 9], false),
+            ((0, 0, 0), (9, 9, 9), Resize::Contract, -4, north, [0, 0, 0, 9, 9, 13], false),
+            // Shrinking to exactly one layer is not a clamp
+            ((0, 0, 0), (8, 8, 8), Resize::Inset, 4, all, [4, 4, 4, 4, 4, 4], false),
+            // Both faces passing each other meet in the middle
+            ((0, 0, 0), (9, 9, 9), Resize::Outset, -7, all, [4, 4, 4, 4, 4, 4], true),
+            ((-5, 0, 0), (-2, 0, 0), Resize::Inset, 3, all, [-4, 0, 0, -4, 0, 0], true),
+            // One moving face stops at the other
+            ((0, 0, 0), (9, 9, 9), Resize::Expand, -20, north, [0, 0, 9, 9, 9, 9], true),
+            ((0, 0, 0), (9, 9, 9), Resize::Contract, 20, east, [9, 0, 0, 9, 9, 9], true),
+            ((0, 0, 0), (9, 9, 9), Resize::Expand, -20, east, [0, 0, 0, 0, 9, 9], true),
+            // A one-block-thick selection can't shrink at all
+            ((0, 5, 0), (9, 5, 9), Resize::Inset, 1, all, [1, 5, 1, 8, 5, 8], true),
+            ((0, 5, 0), (9, 5, 9), Resize::Expand, -1, Faces::Toward(UP), [0, 5, 0, 9, 5, 9], true),
+        ];
+        for (low, high, resize, amount, faces, expected, clamped) in cases {
+            let (min, max) = (pos(low.0, low.1, low.2), pos(high.0, high.1, high.2));
+            let growth = face_growth(resize, amount, faces);
+            let label = format!("{resize:?} {amount} {faces:?} on {low:?}..{high:?}");
+            assert_eq!(bounds(resized(&min, &max, growth, height)), (expected, clamped), "{label}");
+        }
+    }
+}
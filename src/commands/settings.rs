@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{simple::SimpleArgConsumer, ConsumedArgs, FindArg},
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
+    server::Server,
+};
+
+use crate::config;
+use crate::effects::FxMode;
+use crate::messages::msg;
+use crate::notify::{notify, Level, MessageMode};
+use crate::state::{sender_uuid, PLAYER_DATA};
+
+/// Argument name for the message mode in `msg`.
+pub const ARG_MESSAGE_MODE: &str = "mode";
+/// Argument name for the effects mode in `fx`.
+pub const ARG_FX_MODE: &str = "mode";
+
+// ============================================================================
+// //fast
+// ============================================================================
+
+pub struct FastExecutor;
+
+#[async_trait]
+impl CommandExecutor for FastExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+
+            let fast_mode = {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player_id).or_default();
+                data.fast_mode = !data.fast_mode;
+                data.fast_mode
+            };
+
+            let message = if fast_mode { msg!("fast_on") } else { msg!("fast_off") };
+            notify(sender, Level::Success, message).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we msg <chat|actionbar|quiet>
+// ============================================================================
+
+/// Chooses where routine feedback goes. Errors and requested output always use chat.
+pub struct MessageModeExecutor;
+
+#[async_trait]
+impl CommandExecutor for MessageModeExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let input = SimpleArgConsumer::find_arg(args, ARG_MESSAGE_MODE)?;
+            let mode = MessageMode::parse(input)
+                .ok_or_else(|| CommandError::CommandFailed(msg!("unknown_message_mode", input)))?;
+
+            {
+                let mut state = PLAYER_DATA.lock();
+                state.entry(player_id).or_default().message_mode = mode;
+            }
+
+            // Always confirmed in chat, or switching to quiet would be silent
+            sender.send_message(msg!("msg_mode_set", mode.name())).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we fx <off|complete|batch>
+// ============================================================================
+
+/// Chooses the sounds and particles the player gets for their own operations: none, a cue
+/// when one finishes, or also a few particles while it runs.
+pub struct FxExecutor;
+
+#[async_trait]
+impl CommandExecutor for FxExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player_id = sender_uuid(sender)?;
+            let input = SimpleArgConsumer::find_arg(args, ARG_FX_MODE)?;
+            let mode = FxMode::parse(input)
+                .ok_or_else(|| CommandError::CommandFailed(msg!("unknown_fx_mode", input)))?;
+
+            {
+                let mut state = PLAYER_DATA.lock();
+                state.entry(player_id).or_default().fx_mode = mode;
+            }
+
+            notify(sender, Level::Success, msg!("fx_mode_set", mode.name())).await;
+            if mode != FxMode::Off && !config::get().effects_enabled {
+                notify(sender, Level::Warning, msg!("fx_disabled")).await;
+            }
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the settings commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(literal("fast").execute(FastExecutor))
+        .then(
+            literal("msg")
+                .then(argument(ARG_MESSAGE_MODE, SimpleArgConsumer).execute(MessageModeExecutor)),
+        )
+        .then(literal("fx").then(argument(ARG_FX_MODE, SimpleArgConsumer).execute(FxExecutor)))
+}
@@ -0,0 +1,206 @@
+use std::path::PathBuf;
+
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{simple::SimpleArgConsumer, ConsumedArgs, FindArg},
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+
+use crate::cooldowns;
+use crate::engine::EditSession;
+use crate::messages::{format_count, msg, msg_string};
+use crate::notify::{notify, Level, Listing};
+use crate::permissions::{require_permission, SNAPSHOT};
+use crate::snapshot::{list_snapshots, region_dir, SnapshotReader};
+use crate::state::{
+    check_selection_size, get_selection, placement_flags, placement_note, sender_uuid,
+    sender_world, PLAYER_DATA, SNAPSHOTS_DIR,
+};
+
+use super::flag_error;
+
+/// Argument name for the snapshot in `snapshot use`.
+pub const ARG_SNAPSHOT_NAME: &str = "name";
+
+fn get_snapshots_dir() -> Result<PathBuf, CommandError> {
+    SNAPSHOTS_DIR
+        .get()
+        .cloned()
+        .ok_or(CommandError::CommandFailed(msg!("snapshots_dir_missing")))
+}
+
+// ============================================================================
+// /we snapshot list
+// ============================================================================
+
+/// Lists available world backups, newest first.
+pub struct SnapshotListExecutor;
+
+#[async_trait]
+impl CommandExecutor for SnapshotListExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, SNAPSHOT).await?;
+
+            let snapshots = list_snapshots(&get_snapshots_dir()?).map_err(flag_error)?;
+            if snapshots.is_empty() {
+                notify(sender, Level::Warning, msg!("snapshot_none")).await;
+                return Ok(0);
+            }
+
+            let mut listing = Listing::new(msg!("snapshot_header", snapshots.len()));
+            for name in &snapshots {
+                listing.push(msg!("list_entry", name));
+            }
+            listing.send(sender).await;
+
+            Ok(snapshots.len() as i32)
+        })
+    }
+}
+
+// ============================================================================
+// /we snapshot use <name>
+// ============================================================================
+
+/// Chooses the backup `/we restore` reads from.
+pub struct SnapshotUseExecutor;
+
+#[async_trait]
+impl CommandExecutor for SnapshotUseExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, SNAPSHOT).await?;
+
+            let name = SimpleArgConsumer::find_arg(args, ARG_SNAPSHOT_NAME)?;
+            let player_id = sender_uuid(sender)?;
+            region_dir(&get_snapshots_dir()?, name).map_err(flag_error)?;
+
+            {
+                let mut state = PLAYER_DATA.lock();
+                state.entry(player_id).or_default().snapshot = Some(name.to_string());
+            }
+
+            notify(sender, Level::Success, msg!("snapshot_using", name)).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we restore
+// ============================================================================
+
+/// Restores the selection from the chosen snapshot. Positions whose chunk is missing
+/// from the backup are left untouched.
+pub struct RestoreExecutor;
+
+#[async_trait]
+impl CommandExecutor for RestoreExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            require_permission(sender, SNAPSHOT).await?;
+
+            let player_id = sender_uuid(sender)?;
+            let world = sender_world(sender)?;
+            let (min, max) = get_selection(sender)?;
+            check_selection_size(&min, &max)?;
+
+            let name = {
+                let state = PLAYER_DATA.lock();
+                state.get(&player_id).and_then(|data| data.snapshot.clone())
+            }
+            .ok_or(CommandError::CommandFailed(msg!("snapshot_none_chosen")))?;
+            let dir = region_dir(&get_snapshots_dir()?, &name).map_err(flag_error)?;
+
+            let flags = placement_flags(&player_id, false);
+            let mut reader = SnapshotReader::new(dir);
+            let mut skipped = 0u64;
+            let command = format!("//restore {name}");
+            let mut session = EditSession::new(world, player_id, command, flags)?;
+            cooldowns::take_edit(sender, &min, &max)?;
+            session.backup(sender, &min, &max).await?;
+            let result = async {
+                for x in min.0.x..=max.0.x {
+                    for z in min.0.z..=max.0.z {
+                        for y in min.0.y..=max.0.y {
+                            let pos = BlockPos(Vector3::new(x, y, z));
+                            let Some(state) = reader.block_state(&pos)? else {
+                                skipped += 1;
+                                continue;
+                            };
+                            let old_state = session.get_block(&pos).await?;
+                            if old_state != state {
+                                session.set_block_from(pos, old_state, state).await?;
+                            }
+                        }
+                    }
+                }
+                Ok(())
+            }
+            .await;
+            let done = session.finish(result)?;
+
+            let skipped_note = if skipped > 0 {
+                msg_string!("restore_skipped", format_count(skipped))
+            } else {
+                String::new()
+            };
+            notify(
+                sender,
+                Level::Success,
+                msg!(
+                    "restore_done",
+                    format_count(done.blocks),
+                    name,
+                    done.time(),
+                    skipped_note,
+                    placement_note(flags)
+                ),
+            )
+            .await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the snapshot commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("snapshot").then(literal("list").execute(SnapshotListExecutor)).then(
+            literal("use")
+                .then(argument(ARG_SNAPSHOT_NAME, SimpleArgConsumer).execute(SnapshotUseExecutor)),
+        ),
+    )
+    .then(literal("restore").execute(RestoreExecutor))
+}
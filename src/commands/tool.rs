@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{block::BlockArgumentConsumer, simple::SimpleArgConsumer, ConsumedArgs, FindArg},
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
+    entity::player::Player,
+    server::Server,
+};
+
+use crate::messages::msg;
+use crate::notify::{notify, Level, Listing};
+use crate::state::{sender_player, LineTool, PLAYER_DATA};
+use crate::tools::{configured_tools, Tool};
+
+/// Argument name for the block drawn by the line tool.
+pub const ARG_LINE_BLOCK: &str = "block";
+/// Argument name for the thickness of the line tool.
+pub const ARG_LINE_THICKNESS: &str = "thickness";
+
+/// Registry name of the item in the player's main hand.
+async fn held_item(player: &Player) -> Result<String, CommandError> {
+    let held = player.inventory().held_item();
+    let stack = held.lock().await;
+    if stack.is_empty() {
+        return Err(CommandError::CommandFailed(msg!("tool_hold_item")));
+    }
+    Ok(stack.item.registry_key.to_string())
+}
+
+// ============================================================================
+// /we tool <inspect|wand|none>
+// ============================================================================
+
+/// Binds `tool` to the item in the player's main hand, or unbinds it when `None`.
+pub struct ToolExecutor {
+    pub tool: Option<Tool>,
+}
+
+#[async_trait]
+impl CommandExecutor for ToolExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player = sender_player(sender)?;
+            let item = held_item(&player).await?;
+
+            let previous = {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player.gameprofile.id).or_default();
+                match self.tool {
+                    Some(tool) => data.tools.insert(item.clone(), tool),
+                    None => data.tools.remove(&item),
+                }
+            };
+
+            let message = match (self.tool, previous) {
+                (Some(tool), _) => msg!("tool_bound", tool.name(), item),
+                (None, Some(tool)) => msg!("tool_unbound", tool.name(), item),
+                (None, None) => msg!("tool_none", item),
+            };
+            notify(sender, Level::Success, message).await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we tool line <block> [thickness]
+// ============================================================================
+
+/// Binds the line tool to the held item and sets what it draws. The anchor is cleared, so
+/// the next click starts a new path.
+pub struct ToolLineExecutor;
+
+#[async_trait]
+impl CommandExecutor for ToolLineExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player = sender_player(sender)?;
+            let block = BlockArgumentConsumer::find_arg(args, ARG_LINE_BLOCK)?;
+            let thickness = match SimpleArgConsumer::find_arg(args, ARG_LINE_THICKNESS) {
+                Ok(value) => value
+                    .parse::<i32>()
+                    .ok()
+                    .filter(|thickness| *thickness >= 1)
+                    .ok_or(CommandError::CommandFailed(msg!("invalid_thickness", value)))?,
+                Err(_) => 1,
+            };
+            let item = held_item(&player).await?;
+
+            {
+                let mut state = PLAYER_DATA.lock();
+                let data = state.entry(player.gameprofile.id).or_default();
+                data.tools.insert(item.clone(), Tool::Line);
+                data.line_tool = Some(LineTool {
+                    state_id: block.default_state.id,
+                    thickness,
+                    anchor: None,
+                });
+            }
+
+            notify(sender, Level::Success, msg!("tool_line_bound", item, block.name, thickness))
+                .await;
+
+            Ok(1)
+        })
+    }
+}
+
+// ============================================================================
+// /we tool list
+// ============================================================================
+
+/// Lists the player's own bindings followed by the server-wide ones they don't override.
+pub struct ToolListExecutor;
+
+#[async_trait]
+impl CommandExecutor for ToolListExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        _args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let player = sender_player(sender)?;
+            let mut own: Vec<(String, Tool)> = {
+                let state = PLAYER_DATA.lock();
+                state.get(&player.gameprofile.id).map_or_else(Vec::new, |data| {
+                    data.tools.iter().map(|(item, tool)| (item.clone(), *tool)).collect()
+                })
+            };
+            own.sort_by(|a, b| a.0.cmp(&b.0));
+            let configured: Vec<(String, Tool)> = configured_tools()
+                .into_iter()
+                .filter(|(item, _)| !own.iter().any(|(bound, _)| bound == item))
+                .collect();
+
+            if own.is_empty() && configured.is_empty() {
+                notify(sender, Level::Info, msg!("tool_list_empty")).await;
+                return Ok(0);
+            }
+            let mut listing = Listing::new(msg!("tool_list_header"));
+            for (item, tool) in &own {
+                listing.push(msg!("tool_list_entry", item, tool.name()));
+            }
+            for (item, tool) in &configured {
+                listing.push(msg!("tool_list_default", item, tool.name()));
+            }
+            listing.send(sender).await;
+
+            Ok((own.len() + configured.len()) as i32)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the tool commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("tool")
+            .then(literal("inspect").execute(ToolExecutor { tool: Some(Tool::Inspect) }))
+            .then(literal("wand").execute(ToolExecutor { tool: Some(Tool::Wand) }))
+            .then(literal("line").then(
+                argument(ARG_LINE_BLOCK, BlockArgumentConsumer).execute(ToolLineExecutor).then(
+                    argument(ARG_LINE_THICKNESS, SimpleArgConsumer).execute(ToolLineExecutor),
+                ),
+            ))
+            .then(literal("none").execute(ToolExecutor { tool: None }))
+            .then(literal("list").execute(ToolListExecutor)),
+    )
+}
@@ -0,0 +1,528 @@
+use async_trait::async_trait;
+use pumpkin::{
+    command::{
+        args::{
+            block::BlockArgumentConsumer, message::MsgArgConsumer, simple::SimpleArgConsumer,
+            ConsumedArgs, FindArg,
+        },
+        dispatcher::CommandError,
+        tree::{
+            builder::{argument, literal},
+            CommandTree,
+        },
+        CommandExecutor, CommandResult, CommandSender,
+    },
+    server::Server,
+};
+use pumpkin_data::Block;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+
+use crate::config;
+use crate::engine::{raycast_block, BlockAccess, EditSession, EditSummary};
+use crate::messages::{format_count, msg};
+use crate::notify::{notify, Level};
+use crate::state::{
+    check_selection_size, placement_flags, placement_note, sender_block_pos, sender_look,
+    sender_uuid, sender_world,
+};
+
+use super::{flag_error, flag_tokens, parse_radius, ARG_FLAGS};
+
+/// Argument name for the optional radius of radius commands.
+pub const ARG_SCAN_RADIUS: &str = "radius";
+/// Argument name for the block in `removenear`.
+pub const ARG_NEAR_BLOCK: &str = "block";
+/// Argument name for the block in `col` and `pillar`.
+pub const ARG_COLUMN_BLOCK: &str = "block";
+/// Argument name for the height of `pillar`.
+pub const ARG_HEIGHT: &str = "height";
+
+/// Radius used by `ex` when none is given.
+const DEFAULT_EX_RADIUS: u32 = 40;
+/// Radius used by `removenear` when none is given.
+const DEFAULT_REMOVENEAR_RADIUS: u32 = 50;
+
+/// Radius used by `drain`, `fixwater` and `fixlava` when none is given.
+const DEFAULT_FLUID_RADIUS: u32 = 10;
+
+/// Height of `col` when none is given.
+const DEFAULT_COLUMN_HEIGHT: i32 = 5;
+/// How far `-t` and `sel contains` look for the targeted block.
+pub const TARGET_RANGE: f64 = 128.0;
+
+/// Blocks put out by `ex`.
+const FIRE_BLOCKS: &[&str] = &["fire", "soul_fire"];
+
+/// Whether `state_id` is any state of `fluid`: a source, one of the flow levels, or falling.
+fn is_fluid(state_id: u16, fluid: &Block) -> bool {
+    Block::from_state_id(state_id).id == fluid.id
+}
+
+/// What `drain` turns `state_id` into: air for any state of water or lava.
+fn drained(state_id: u16) -> Option<u16> {
+    (is_fluid(state_id, &Block::WATER) || is_fluid(state_id, &Block::LAVA))
+        .then_some(Block::AIR.default_state.id)
+}
+
+/// What `fixwater` or `fixlava` turns `state_id` into: for any state of `fluid`, its default
+/// state, the level 0 source block.
+fn to_source(state_id: u16, fluid: &Block) -> Option<u16> {
+    is_fluid(state_id, fluid).then_some(fluid.default_state.id)
+}
+
+/// Optional radius argument, defaulting (and capped) by the configured maximum.
+fn radius_arg(args: &ConsumedArgs<'_>, default: u32) -> Result<u32, CommandError> {
+    let max = config::get().max_scan_radius;
+    match SimpleArgConsumer::find_arg(args, ARG_SCAN_RADIUS) {
+        Ok(value) => parse_radius(value, max),
+        Err(_) => Ok(default.min(max)),
+    }
+}
+
+/// Scan the cube of `radius` around the player and write `replace(state)` wherever it
+/// returns a new state, as one undoable operation. Shared by the radius commands.
+async fn radius_operation(
+    sender: &CommandSender,
+    command: String,
+    radius: u32,
+    replace: impl Fn(u16) -> Option<u16> + Send + Sync,
+) -> Result<(EditSummary, String), CommandError> {
+    let player_id = sender_uuid(sender)?;
+    let center = sender_block_pos(sender)?;
+    let world = sender_world(sender)?;
+    let flags = placement_flags(&player_id, false);
+    let r = radius as i32;
+
+    let mut session = EditSession::new(world, player_id, command, flags)?;
+    let result = async {
+        for x in center.0.x - r..=center.0.x + r {
+            for y in center.0.y - r..=center.0.y + r {
+                for z in center.0.z - r..=center.0.z + r {
+                    let pos = BlockPos(Vector3::new(x, y, z));
+                    let old_state = session.get_block(&pos).await?;
+                    if let Some(new_state) = replace(old_state).filter(|s| *s != old_state) {
+                        session.set_block_from(pos, old_state, new_state).await?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+    Ok((session.finish(result)?, placement_note(flags)))
+}
+
+// ============================================================================
+// //ex [radius]
+// ============================================================================
+
+/// Extinguishes fire and soul fire around the player.
+pub struct ExtinguishExecutor;
+
+#[async_trait]
+impl CommandExecutor for ExtinguishExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let radius = radius_arg(args, DEFAULT_EX_RADIUS)?;
+            let air = Block::AIR.default_state.id;
+
+            let (done, note) = radius_operation(sender, format!("//ex {radius}"), radius, |state| {
+                FIRE_BLOCKS
+                    .contains(&Block::from_state_id(state).name)
+                    .then_some(air)
+            })
+            .await?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("ex_done", format_count(done.blocks), done.time(), note),
+            )
+            .await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //removenear <block> [radius]
+// ============================================================================
+
+/// Removes every block of one type around the player.
+pub struct RemoveNearExecutor;
+
+#[async_trait]
+impl CommandExecutor for RemoveNearExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_NEAR_BLOCK)?;
+            let radius = radius_arg(args, DEFAULT_REMOVENEAR_RADIUS)?;
+            let air = Block::AIR.default_state.id;
+
+            let command = format!("//removenear {} {radius}", block.name);
+            let (done, note) = radius_operation(sender, command, radius, |state| {
+                (Block::from_state_id(state).id == block.id).then_some(air)
+            })
+            .await?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("removenear_done", format_count(done.blocks), done.time(), note),
+            )
+            .await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //drain [radius], //fixwater [radius], //fixlava [radius]
+// ============================================================================
+
+/// Removes water and lava around the player, flowing as well as source blocks.
+pub struct DrainExecutor;
+
+#[async_trait]
+impl CommandExecutor for DrainExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let radius = radius_arg(args, DEFAULT_FLUID_RADIUS)?;
+
+            let command = format!("//drain {radius}");
+            let (done, note) = radius_operation(sender, command, radius, drained).await?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("drain_done", format_count(done.blocks), done.time(), note),
+            )
+            .await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+/// Turns flowing water (or lava) around the player back into source blocks, so broken
+/// surfaces stop re-propagating.
+pub struct FixFluidExecutor {
+    pub lava: bool,
+}
+
+#[async_trait]
+impl CommandExecutor for FixFluidExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let radius = radius_arg(args, DEFAULT_FLUID_RADIUS)?;
+            let (fluid, name) = if self.lava {
+                (&Block::LAVA, "fixlava")
+            } else {
+                (&Block::WATER, "fixwater")
+            };
+            let (done, note) =
+                radius_operation(sender, format!("//{name} {radius}"), radius, |state| {
+                    to_source(state, fluid)
+                })
+                .await?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("fix_fluid_done", format_count(done.blocks), done.time(), note),
+            )
+            .await;
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// //col <block> [height] [-t], //pillar <block> <radius> <height> [-t]
+// ============================================================================
+
+fn parse_height(value: &str) -> Result<i32, CommandError> {
+    value
+        .parse::<i32>()
+        .ok()
+        .filter(|height| *height >= 1)
+        .ok_or(CommandError::CommandFailed(msg!("invalid_height", value)))
+}
+
+/// Block a column stands on: the targeted block with `-t`, otherwise the block under the player.
+async fn column_base(
+    sender: &CommandSender,
+    world: &dyn BlockAccess,
+    target: bool,
+) -> Result<BlockPos, CommandError> {
+    if !target {
+        let feet = sender_block_pos(sender)?;
+        return Ok(BlockPos(Vector3::new(feet.0.x, feet.0.y - 1, feet.0.z)));
+    }
+    let (eye, direction) = sender_look(sender)?;
+    raycast_block(world, eye, direction, TARGET_RANGE)
+        .await
+        .map_err(flag_error)?
+        .ok_or(CommandError::CommandFailed(msg!("no_target_block", TARGET_RANGE)))
+}
+
+/// Fill a vertical cylinder of `radius` (0 for a single column) on top of the base block,
+/// `height` blocks tall and clamped to the world's build height, as one undoable operation.
+/// Returns the summary, the placement note and the height actually built.
+async fn column_operation(
+    sender: &CommandSender,
+    command: String,
+    state_id: u16,
+    radius: i32,
+    height: i32,
+    target: bool,
+) -> Result<(EditSummary, String, i32), CommandError> {
+    let player_id = sender_uuid(sender)?;
+    let world = sender_world(sender)?;
+    let base = column_base(sender, world.as_ref(), target).await?;
+
+    let (_, max_y) = world.height_range();
+    let bottom = base.0.y + 1;
+    let top = base.0.y.saturating_add(height).min(max_y);
+    if bottom > top {
+        return Err(CommandError::CommandFailed(msg!("column_no_room", base.0.y)));
+    }
+    check_selection_size(
+        &BlockPos(Vector3::new(base.0.x - radius, bottom, base.0.z - radius)),
+        &BlockPos(Vector3::new(base.0.x + radius, top, base.0.z + radius)),
+    )?;
+
+    let flags = placement_flags(&player_id, false);
+    let mut session = EditSession::new(world, player_id, command, flags)?;
+    let result = async {
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                if dx * dx + dz * dz > radius * radius {
+                    continue;
+                }
+                for y in bottom..=top {
+                    let pos = BlockPos(Vector3::new(base.0.x + dx, y, base.0.z + dz));
+                    session.set_block(pos, state_id).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+    .await;
+    Ok((session.finish(result)?, placement_note(flags), top - bottom + 1))
+}
+
+/// Places a 1×1 column on the block under the player, or on the targeted block with `-t`.
+pub struct ColumnExecutor;
+
+#[async_trait]
+impl CommandExecutor for ColumnExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_COLUMN_BLOCK)?;
+            let flag_input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+            let mut height = DEFAULT_COLUMN_HEIGHT;
+            let mut target = false;
+            for flag in flag_tokens(&flag_input) {
+                match flag {
+                    "-t" => target = true,
+                    value if value.starts_with(|c: char| c.is_ascii_digit()) => {
+                        height = parse_height(value)?;
+                    }
+                    other => return Err(CommandError::CommandFailed(msg!("unknown_flag", other))),
+                }
+            }
+
+            let mut command = format!("//col {} {height}", block.name);
+            if target {
+                command.push_str(" -t");
+            }
+            let (done, note, built) =
+                column_operation(sender, command, block.default_state.id, 0, height, target)
+                    .await?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("col_done", format_count(done.blocks), done.time(), note),
+            )
+            .await;
+            if built < height {
+                notify(sender, Level::Warning, msg!("height_clamped", built)).await;
+            }
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+/// Places a round pillar of the given radius on the block under the player, or on the
+/// targeted block with `-t`.
+pub struct PillarExecutor;
+
+#[async_trait]
+impl CommandExecutor for PillarExecutor {
+    fn execute<'a>(
+        &'a self,
+        sender: &'a CommandSender,
+        _server: &'a Server,
+        args: &'a ConsumedArgs<'a>,
+    ) -> CommandResult<'a> {
+        Box::pin(async move {
+            let block = BlockArgumentConsumer::find_arg(args, ARG_COLUMN_BLOCK)?;
+            let radius = parse_radius(
+                SimpleArgConsumer::find_arg(args, ARG_SCAN_RADIUS)?,
+                config::get().max_scan_radius,
+            )?;
+            let height = parse_height(SimpleArgConsumer::find_arg(args, ARG_HEIGHT)?)?;
+            let flag_input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+            let mut target = false;
+            for flag in flag_tokens(&flag_input) {
+                match flag {
+                    "-t" => target = true,
+                    other => return Err(CommandError::CommandFailed(msg!("unknown_flag", other))),
+                }
+            }
+
+            let mut command = format!("//pillar {} {radius} {height}", block.name);
+            if target {
+                command.push_str(" -t");
+            }
+            let state_id = block.default_state.id;
+            let (done, note, built) =
+                column_operation(sender, command, state_id, radius as i32, height, target).await?;
+
+            notify(
+                sender,
+                Level::Success,
+                msg!("pillar_done", format_count(done.blocks), done.time(), note),
+            )
+            .await;
+            if built < height {
+                notify(sender, Level::Warning, msg!("height_clamped", built)).await;
+            }
+
+            Ok(done.blocks as i32)
+        })
+    }
+}
+
+// ============================================================================
+// Command tree
+// ============================================================================
+
+/// Add the utility commands to the `/we` tree.
+pub(super) fn register(tree: CommandTree) -> CommandTree {
+    tree.then(
+        literal("ex")
+            .execute(ExtinguishExecutor)
+            .then(argument(ARG_SCAN_RADIUS, SimpleArgConsumer).execute(ExtinguishExecutor)),
+    )
+    .then(
+        literal("removenear").then(
+            argument(ARG_NEAR_BLOCK, BlockArgumentConsumer)
+                .execute(RemoveNearExecutor)
+                .then(argument(ARG_SCAN_RADIUS, SimpleArgConsumer).execute(RemoveNearExecutor)),
+        ),
+    )
+    .then(
+        literal("drain")
+            .execute(DrainExecutor)
+            .then(argument(ARG_SCAN_RADIUS, SimpleArgConsumer).execute(DrainExecutor)),
+    )
+    .then(literal("fixwater").execute(FixFluidExecutor { lava: false }).then(
+        argument(ARG_SCAN_RADIUS, SimpleArgConsumer).execute(FixFluidExecutor { lava: false }),
+    ))
+    .then(literal("fixlava").execute(FixFluidExecutor { lava: true }).then(
+        argument(ARG_SCAN_RADIUS, SimpleArgConsumer).execute(FixFluidExecutor { lava: true }),
+    ))
+    .then(
+        literal("col").then(
+            argument(ARG_COLUMN_BLOCK, BlockArgumentConsumer)
+                .execute(ColumnExecutor)
+                .then(argument(ARG_FLAGS, MsgArgConsumer).execute(ColumnExecutor)),
+        ),
+    )
+    .then(
+        literal("pillar").then(
+            argument(ARG_COLUMN_BLOCK, BlockArgumentConsumer).then(
+                argument(ARG_SCAN_RADIUS, SimpleArgConsumer).then(
+                    argument(ARG_HEIGHT, SimpleArgConsumer)
+                        .execute(PillarExecutor)
+                        .then(argument(ARG_FLAGS, MsgArgConsumer).execute(PillarExecutor)),
+                ),
+            ),
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mask::Mask;
+    use crate::testing::pos;
+    use crate::transform::with_property;
+
+    fn level(fluid: &Block, level: u8) -> u16 {
+        with_property(fluid.default_state.id, "level", &level.to_string())
+            .expect("fluids have a level property")
+    }
+
+    #[test]
+    fn drain_matches_every_fluid_level() {
+        let air = Block::AIR.default_state.id;
+        for n in 0..16 {
+            assert_eq!(drained(level(&Block::WATER, n)), Some(air), "water level {n}");
+            assert_eq!(drained(level(&Block::LAVA, n)), Some(air), "lava level {n}");
+        }
+        assert_eq!(drained(Block::STONE.default_state.id), None);
+        assert_eq!(drained(air), None);
+    }
+
+    #[test]
+    fn fix_turns_every_level_into_a_source() {
+        for n in 0..16 {
+            let water = Some(Block::WATER.default_state.id);
+            assert_eq!(to_source(level(&Block::WATER, n), &Block::WATER), water, "level {n}");
+            // Each command only touches its own fluid
+            assert_eq!(to_source(level(&Block::LAVA, n), &Block::WATER), None);
+        }
+        assert_eq!(to_source(Block::STONE.default_state.id, &Block::LAVA), None);
+    }
+
+    #[test]
+    fn fluid_mask_matches_flowing_levels() {
+        let mask = Mask::parse("water").expect("water is a block");
+        let selection = (pos(0, 0, 0), pos(1, 1, 1));
+        assert!(mask.matches(&pos(0, 0, 0), level(&Block::WATER, 7), &selection));
+        assert!(!mask.matches(&pos(0, 0, 0), level(&Block::LAVA, 7), &selection));
+    }
+}
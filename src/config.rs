@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::state::StateRwLock;
+
+/// File name of the plugin configuration inside the plugin data folder.
+const CONFIG_FILE: &str = "config.toml";
+
+/// Default `surface_transparent`: tree parts, plants and snow layers.
+const DEFAULT_SURFACE_TRANSPARENT: &[&str] = &[
+    "*_leaves",
+    "*_log",
+    "*_wood",
+    "*_stem",
+    "*_hyphae",
+    "*_sapling",
+    "*_mushroom",
+    "*_tulip",
+    "short_grass",
+    "tall_grass",
+    "fern",
+    "large_fern",
+    "dead_bush",
+    "vine",
+    "snow",
+    "dandelion",
+    "poppy",
+    "blue_orchid",
+    "allium",
+    "azure_bluet",
+    "oxeye_daisy",
+    "cornflower",
+    "lily_of_the_valley",
+    "sunflower",
+    "lilac",
+    "rose_bush",
+    "peony",
+    "sugar_cane",
+    "bamboo",
+    "sweet_berry_bush",
+];
+
+/// Default `protected_blocks`: the world floor, barriers and End portal parts.
+const DEFAULT_PROTECTED_BLOCKS: &[&str] = &["bedrock", "barrier", "end_portal_frame", "end_portal"];
+
+/// How often a player may run one category of expensive command: at most `uses` runs within
+/// any `seconds`. `uses = 0` means no limit.
+#[derive(Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RateLimit {
+    pub uses: u32,
+    pub seconds: u64,
+}
+
+/// Plugin configuration, loaded from `config.toml` in the plugin data folder.
+/// Missing keys fall back to their defaults.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Operations taking longer than this many milliseconds are logged with their bounding box.
+    pub slow_operation_ms: u64,
+    /// Maximum number of entries in the server-wide shared clipboard store.
+    pub max_shared_clipboards: usize,
+    /// Maximum number of blocks in a single shared clipboard.
+    pub max_shared_clipboard_blocks: usize,
+    /// Shared clipboards not published or fetched for this many minutes are dropped.
+    pub shared_clipboard_idle_minutes: u64,
+    /// Largest radius accepted by `butcher` and `remove`.
+    pub max_entity_radius: u32,
+    /// Largest radius accepted by block-scanning radius commands (`ex`, `drain`, ...).
+    pub max_scan_radius: u32,
+    /// Directory of saved schematics, relative to the plugin data folder unless absolute.
+    /// Created on load if missing; `/we reload` switches to a new one.
+    pub schematics_dir: String,
+    /// Directory of world backups for `/we snapshot` and `/we restore`, relative to the
+    /// plugin data folder unless absolute. Each snapshot is a subdirectory of region files.
+    pub snapshots_dir: String,
+    /// Record which player changed each block, for the inspect tool.
+    pub tracking_enabled: bool,
+    /// Tracked edits older than this many days are dropped.
+    pub tracking_retention_days: u32,
+    /// Number of edits remembered per block; older ones are forgotten.
+    pub tracking_entries_per_block: usize,
+    /// Language of player-facing messages: the name of a file in the `lang` folder of the
+    /// plugin data folder, without `.toml`. Messages it doesn't define fall back to English.
+    pub language: String,
+    /// Save each player's undo history to disk when they leave, and load it again the next
+    /// time they undo, so it survives reconnects and restarts.
+    pub history_persist: bool,
+    /// Number of most recent undo entries saved per player.
+    pub history_persist_entries: usize,
+    /// Undo entries changing more blocks than this are not saved.
+    pub history_persist_max_blocks: usize,
+    /// Strokes of a brush or other rapid-fire tool finishing within this many milliseconds of
+    /// the previous one are merged into its undo entry; 0 keeps every stroke separate.
+    pub stroke_group_ms: u64,
+    /// Blocks that surface scans (such as `schem heightmap-export`) look through to find the
+    /// terrain below: names, optionally with a leading or trailing `*`.
+    pub surface_transparent: Vec<String>,
+    /// Blocks that `paste`, `set` and `clear` never overwrite, whatever their flags, unless
+    /// the player has the bypass permission. Same pattern syntax as `surface_transparent`.
+    pub protected_blocks: Vec<String>,
+    /// Run `/we fixlight` on the changed area after operations of at least
+    /// `auto_fixlight_min_blocks` blocks.
+    pub auto_fixlight: bool,
+    pub auto_fixlight_min_blocks: usize,
+    /// Worlds where WorldEdit changes nothing, for anyone. Selections still work there.
+    /// Toggled with `/we world disable|enable`, which saves this list.
+    pub disabled_worlds: Vec<String>,
+    /// Cancel a player's running operation when they disconnect instead of finishing it.
+    /// Either way the changes made are kept in their undo history.
+    pub cancel_on_disconnect: bool,
+    /// Largest number of blocks `/we clearchunk` may clear at once. Separate from the
+    /// selection limit since one full-height chunk column alone can be close to it.
+    pub max_clearchunk_blocks: u64,
+    /// Schematics deleted with `/we schem delete` stay in the `trash` folder this many days,
+    /// then are removed on the next plugin load.
+    pub schematic_trash_days: u32,
+    /// Before an edit of a region of at least this many blocks, the region is saved as a
+    /// schematic under `schematics/backups/<player>/`. 0 disables automatic backups.
+    pub auto_backup_min_blocks: u64,
+    /// Automatic backups kept per player; the oldest are deleted as new ones are taken.
+    pub backup_keep: usize,
+    /// Automatic backups older than this many days are deleted; 0 keeps them regardless of age.
+    pub backup_days: u32,
+    /// Schematic file reads and writes that may run at once across the server; further ones
+    /// wait for a free slot. Read when the first schematic is loaded or saved.
+    pub schematic_io_permits: usize,
+    /// Send the sounds and particles players choose with `/we fx`. `false` turns them off
+    /// for everyone, e.g. on a server with little bandwidth to spare.
+    pub effects_enabled: bool,
+    /// Rate limit on region edits of at least `heavy_edit_min_blocks` blocks, per player.
+    pub heavy_edit_limit: RateLimit,
+    pub heavy_edit_min_blocks: u64,
+    /// Rate limit on `paste` and `schem paste`, per player.
+    pub paste_limit: RateLimit,
+    /// Rate limit on schematic loads and saves, per player.
+    pub schematic_io_limit: RateLimit,
+    /// Largest clipboard `copy` and `schem load` produce, in blocks (air included). Separate
+    /// from the edit limit that `paste` checks, so a large area can be copied to save it as
+    /// a schematic without allowing a paste of that size.
+    pub max_clipboard_blocks: u64,
+    /// Largest volume, in blocks, a schematic file may declare. Checked against the header
+    /// before anything is decoded, whoever loads the file and however, so a malformed or
+    /// malicious file can't make the server allocate gigabytes.
+    pub max_schematic_volume: u64,
+    /// Ceiling in MiB on the memory held by all clipboards and undo histories together.
+    /// Copies and schematic loads that would exceed it are rejected up front; 0 disables it.
+    pub max_memory_mb: u64,
+    /// Tools bound to items for every player, as item registry name -> tool name. A
+    /// player's own `/we tool` bindings take precedence.
+    pub tool_bindings: BTreeMap<String, String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            slow_operation_ms: 2_000,
+            max_shared_clipboards: 20,
+            max_shared_clipboard_blocks: 100_000,
+            shared_clipboard_idle_minutes: 60,
+            max_entity_radius: 256,
+            max_scan_radius: 64,
+            schematics_dir: "schematics".to_string(),
+            snapshots_dir: "snapshots".to_string(),
+            tracking_enabled: true,
+            tracking_retention_days: 30,
+            tracking_entries_per_block: 5,
+            language: "en".to_string(),
+            history_persist: true,
+            history_persist_entries: 5,
+            history_persist_max_blocks: 100_000,
+            stroke_group_ms: 2_000,
+            surface_transparent: DEFAULT_SURFACE_TRANSPARENT
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            protected_blocks: DEFAULT_PROTECTED_BLOCKS
+                .iter()
+                .map(|pattern| pattern.to_string())
+                .collect(),
+            auto_fixlight: false,
+            auto_fixlight_min_blocks: 10_000,
+            disabled_worlds: Vec::new(),
+            cancel_on_disconnect: false,
+            max_clearchunk_blocks: 1_000_000,
+            schematic_trash_days: 7,
+            auto_backup_min_blocks: 0,
+            backup_keep: 10,
+            backup_days: 7,
+            schematic_io_permits: 2,
+            effects_enabled: true,
+            heavy_edit_limit: RateLimit { uses: 0, seconds: 60 },
+            heavy_edit_min_blocks: 10_000,
+            paste_limit: RateLimit { uses: 0, seconds: 60 },
+            schematic_io_limit: RateLimit { uses: 0, seconds: 60 },
+            max_clipboard_blocks: 500_000,
+            max_schematic_volume: 20_000_000,
+            max_memory_mb: 1024,
+            tool_bindings: BTreeMap::from([(
+                "minecraft:wooden_axe".to_string(),
+                "wand".to_string(),
+            )]),
+        }
+    }
+}
+
+static CONFIG: LazyLock<StateRwLock<Config>> =
+    LazyLock::new(|| StateRwLock::new(Config::default()));
+
+/// Current configuration.
+pub fn get() -> Config {
+    CONFIG.read().clone()
+}
+
+/// Change the configuration and write it back to `data_folder`. The new configuration takes
+/// effect even if writing fails.
+pub fn update(data_folder: &Path, change: impl FnOnce(&mut Config)) -> Result<(), String> {
+    let config = {
+        let mut config = CONFIG.write();
+        change(&mut config);
+        config.clone()
+    };
+    let text = toml::to_string_pretty(&config)
+        .map_err(|e| format!("Failed to serialize config: {e}"))?;
+    fs::write(data_folder.join(CONFIG_FILE), text)
+        .map_err(|e| format!("Failed to write {CONFIG_FILE}: {e}"))
+}
+
+/// Check that the configuration file in `data_folder` still parses, without applying it. A
+/// missing file is fine: `load` writes the defaults.
+pub fn validate(data_folder: &Path) -> Result<(), String> {
+    let path = data_folder.join(CONFIG_FILE);
+    if !path.exists() {
+        return Ok(());
+    }
+    let text =
+        fs::read_to_string(&path).map_err(|e| format!("Failed to read {CONFIG_FILE}: {e}"))?;
+    toml::from_str::<Config>(&text)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to parse {CONFIG_FILE}: {e}"))
+}
+
+/// Load the configuration from `data_folder`, writing a default file if none exists.
+pub fn load(data_folder: &Path) -> Result<(), String> {
+    let path = data_folder.join(CONFIG_FILE);
+
+    let config = if path.exists() {
+        let text =
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read {CONFIG_FILE}: {e}"))?;
+        toml::from_str(&text).map_err(|e| format!("Failed to parse {CONFIG_FILE}: {e}"))?
+    } else {
+        let config = Config::default();
+        let text = toml::to_string_pretty(&config)
+            .map_err(|e| format!("Failed to serialize default config: {e}"))?;
+        fs::write(&path, text).map_err(|e| format!("Failed to write {CONFIG_FILE}: {e}"))?;
+        config
+    };
+
+    *CONFIG.write() = config;
+    Ok(())
+}
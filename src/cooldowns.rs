@@ -0,0 +1,206 @@
+//! Per-player rate limits on expensive commands. Each category allows a budget of runs
+//! within a sliding window; a refused command tells the player how long until the oldest
+//! run leaves the window and another is allowed.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use pumpkin::command::{dispatcher::CommandError, CommandSender};
+use pumpkin_util::math::position::BlockPos;
+use uuid::Uuid;
+
+use crate::config::{self, Config, RateLimit};
+use crate::messages::{msg, msg_string};
+use crate::state::{selection_volume, StateLock};
+
+/// Kinds of command that share one rate limit.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category {
+    /// Region edits of at least `heavy_edit_min_blocks` blocks.
+    HeavyEdit,
+    /// `paste` and `schem paste`.
+    Paste,
+    /// Schematic loads and saves.
+    SchematicIo,
+}
+
+impl Category {
+    pub const ALL: [Category; 3] = [Category::HeavyEdit, Category::Paste, Category::SchematicIo];
+
+    /// The category's name as shown to players.
+    pub fn name(self) -> String {
+        match self {
+            Category::HeavyEdit => msg_string!("cooldown_heavy_edit"),
+            Category::Paste => msg_string!("cooldown_paste"),
+            Category::SchematicIo => msg_string!("cooldown_schematic_io"),
+        }
+    }
+
+    pub fn limit(self, config: &Config) -> RateLimit {
+        match self {
+            Category::HeavyEdit => config.heavy_edit_limit,
+            Category::Paste => config.paste_limit,
+            Category::SchematicIo => config.schematic_io_limit,
+        }
+    }
+}
+
+/// Start times of each player's runs per category still inside the category's window,
+/// oldest first.
+static RECENT_RUNS: LazyLock<StateLock<HashMap<(Uuid, Category), VecDeque<Instant>>>> =
+    LazyLock::new(|| StateLock::new(HashMap::new()));
+
+/// A player's standing in one category, as shown by `/we cooldowns`.
+pub struct Budget {
+    pub category: Category,
+    pub limit: RateLimit,
+    /// Runs still allowed right now.
+    pub left: u32,
+    /// Time until the oldest run leaves the window and frees one use; `None` if none is used.
+    pub next: Option<Duration>,
+}
+
+/// Drop the runs that have left the window of `limit` at `now`, then return how long until
+/// another run is allowed, or `None` if one is allowed now.
+fn wait_time(runs: &mut VecDeque<Instant>, limit: RateLimit, now: Instant) -> Option<Duration> {
+    let window = Duration::from_secs(limit.seconds);
+    while runs.front().is_some_and(|start| now.duration_since(*start) >= window) {
+        runs.pop_front();
+    }
+    let uses = limit.uses as usize;
+    if runs.len() < uses {
+        return None;
+    }
+    // Enough of the oldest runs must leave the window to get below the budget, which may
+    // have been lowered by a reload
+    runs.get(runs.len() - uses).map(|start| window - now.duration_since(*start))
+}
+
+/// Count a run of `category` for the sender, or refuse it with the time until the next run
+/// is allowed. The console and categories with `uses = 0` are not limited.
+pub fn take(sender: &CommandSender, category: Category) -> Result<(), CommandError> {
+    let Some(player) = sender.as_player() else {
+        return Ok(());
+    };
+    let limit = category.limit(&config::get());
+    if limit.uses == 0 {
+        return Ok(());
+    }
+    let now = Instant::now();
+    let mut recent = RECENT_RUNS.lock();
+    let runs = recent.entry((player.gameprofile.id, category)).or_default();
+    if let Some(wait) = wait_time(runs, limit, now) {
+        return Err(CommandError::CommandFailed(msg!(
+            "cooldown_active",
+            category.name(),
+            format_wait(wait)
+        )));
+    }
+    runs.push_back(now);
+    Ok(())
+}
+
+/// Count a region edit of `min..=max` as a heavy edit if it spans at least
+/// `heavy_edit_min_blocks` blocks; smaller edits are never limited.
+pub fn take_edit(
+    sender: &CommandSender,
+    min: &BlockPos,
+    max: &BlockPos,
+) -> Result<(), CommandError> {
+    if (selection_volume(min, max) as u64) < config::get().heavy_edit_min_blocks {
+        return Ok(());
+    }
+    take(sender, Category::HeavyEdit)
+}
+
+/// The player's budget in every category.
+pub fn budgets(player_id: Uuid) -> Vec<Budget> {
+    let config = config::get();
+    let now = Instant::now();
+    let mut recent = RECENT_RUNS.lock();
+    Category::ALL
+        .into_iter()
+        .map(|category| {
+            let limit = category.limit(&config);
+            let window = Duration::from_secs(limit.seconds);
+            let (used, next) = match recent.get_mut(&(player_id, category)) {
+                Some(runs) => {
+                    wait_time(runs, limit, now);
+                    let next = runs.front().map(|oldest| window - now.duration_since(*oldest));
+                    (runs.len() as u32, next)
+                }
+                None => (0, None),
+            };
+            Budget { category, limit, left: limit.uses.saturating_sub(used), next }
+        })
+        .collect()
+}
+
+/// Format a remaining cooldown, rounded up to whole seconds ("12s", "2m 5s").
+pub fn format_wait(wait: Duration) -> String {
+    let secs = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+    if secs < 60 { format!("{secs}s") } else { format!("{}m {}s", secs / 60, secs % 60) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(n: u64) -> Duration {
+        Duration::from_secs(n)
+    }
+
+    /// Try a run at `now`, recording it when allowed, like `take`.
+    fn run(runs: &mut VecDeque<Instant>, limit: RateLimit, now: Instant) -> Option<Duration> {
+        let wait = wait_time(runs, limit, now);
+        if wait.is_none() {
+            runs.push_back(now);
+        }
+        wait
+    }
+
+    #[test]
+    fn budget_refills_as_runs_leave_the_window() {
+        let limit = RateLimit { uses: 2, seconds: 60 };
+        let start = Instant::now();
+        let mut runs = VecDeque::new();
+
+        assert_eq!(run(&mut runs, limit, start), None);
+        assert_eq!(run(&mut runs, limit, start + secs(10)), None);
+        // The budget is spent until the first run is a minute old
+        assert_eq!(run(&mut runs, limit, start + secs(15)), Some(secs(45)));
+        assert_eq!(run(&mut runs, limit, start + secs(59)), Some(secs(1)));
+        assert_eq!(runs.len(), 2, "refused runs are not counted");
+
+        // One use is back, and the next waits for the second run
+        assert_eq!(run(&mut runs, limit, start + secs(60)), None);
+        assert_eq!(run(&mut runs, limit, start + secs(61)), Some(secs(9)));
+
+        // Long after, the whole budget is back
+        assert_eq!(run(&mut runs, limit, start + secs(300)), None);
+        assert_eq!(runs.len(), 1);
+        assert_eq!(run(&mut runs, limit, start + secs(301)), None);
+    }
+
+    #[test]
+    fn lowered_budget_waits_for_enough_runs_to_leave() {
+        let start = Instant::now();
+        let mut runs: VecDeque<Instant> = (0..3).map(|i| start + secs(i * 10)).collect();
+
+        // A reload cut the budget from 3 to 1: all three runs must leave, not just the oldest
+        let limit = RateLimit { uses: 1, seconds: 60 };
+        assert_eq!(wait_time(&mut runs, limit, start + secs(30)), Some(secs(50)));
+        assert_eq!(wait_time(&mut runs, limit, start + secs(70)), Some(secs(10)));
+        assert_eq!(runs.len(), 1);
+        assert_eq!(wait_time(&mut runs, limit, start + secs(80)), None);
+        assert_eq!(runs.len(), 0);
+    }
+
+    #[test]
+    fn waits_round_up_to_whole_seconds() {
+        assert_eq!(format_wait(Duration::from_millis(11_200)), "12s");
+        assert_eq!(format_wait(secs(59)), "59s");
+        assert_eq!(format_wait(secs(125)), "2m 5s");
+    }
+}
@@ -0,0 +1,241 @@
+//! Self-test run by `/we diag`, for a quick health check after an upgrade. Each check
+//! exercises one part of the plugin and reports a [`Check`]; new regression canaries are
+//! added to [`run`].
+
+use pumpkin::command::{dispatcher::CommandError, CommandSender};
+use pumpkin_data::Block;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+
+use crate::config;
+use crate::engine::{restore, BlockAccess, EditSession};
+use crate::messages::{format_count, msg_string};
+use crate::schematic::{self, Compression, Resolved};
+use crate::state::{
+    memory_usage, placement_flags, schematic_io_waiting, schematics_dir, sender_block_pos,
+    sender_uuid, sender_world, ClipboardData, DATA_FOLDER, PLAYER_DATA,
+};
+use crate::stats::running_operations;
+
+/// Block states the registry must resolve, each with properties that differ from its block's
+/// default state so a silent fallback to the default is caught.
+const KNOWN_STATES: &[&str] = &[
+    "minecraft:oak_stairs[facing=east,half=top,shape=straight,waterlogged=false]",
+    "minecraft:oak_log[axis=x]",
+    "minecraft:redstone_wire[east=side,north=none,power=7,south=up,west=none]",
+    "minecraft:chest[facing=south,type=left,waterlogged=false]",
+    "minecraft:lantern[hanging=true,waterlogged=false]",
+];
+
+/// Edge length of the cube placed and reverted by the set/undo check.
+const CYCLE_SIZE: i32 = 3;
+/// Command recorded for the set/undo check's history entry.
+const CYCLE_COMMAND: &str = "/we diag";
+
+pub enum Outcome {
+    Pass,
+    Fail,
+    /// The check doesn't apply to this sender, e.g. a world edit run from the console.
+    Skipped,
+}
+
+/// The result of one check: its name and a short detail, or the reason it failed.
+pub struct Check {
+    pub name: String,
+    pub outcome: Outcome,
+    pub detail: String,
+}
+
+impl Check {
+    fn from_result(name: String, result: Result<String, String>) -> Self {
+        match result {
+            Ok(detail) => Self { name, outcome: Outcome::Pass, detail },
+            Err(detail) => Self { name, outcome: Outcome::Fail, detail },
+        }
+    }
+}
+
+/// Run every check in order. A check that fails doesn't stop the ones after it.
+pub async fn run(sender: &CommandSender) -> Vec<Check> {
+    vec![
+        Check::from_result(msg_string!("diag_schematics_dir"), schematics_dir_writable()),
+        Check::from_result(msg_string!("diag_config"), config_parses()),
+        Check::from_result(msg_string!("diag_round_trip"), schematic_round_trip()),
+        set_undo_cycle(sender).await,
+        Check::from_result(msg_string!("diag_block_states"), block_states()),
+        Check::from_result(msg_string!("diag_memory"), memory()),
+    ]
+}
+
+/// The schematics directory is set and a file can be written to it.
+fn schematics_dir_writable() -> Result<String, String> {
+    let dir = schematics_dir().ok_or_else(|| msg_string!("schematics_dir_missing"))?;
+    schematic::check_writable(&dir)?;
+    Ok(dir.display().to_string())
+}
+
+/// `config.toml` on disk still parses, so the next reload or restart won't fail.
+fn config_parses() -> Result<String, String> {
+    let data_folder = DATA_FOLDER.get().ok_or("not loaded")?;
+    config::validate(data_folder)?;
+    Ok(msg_string!("diag_config_ok"))
+}
+
+/// A small clipboard encoded as a `.schem` in memory decodes to the same blocks.
+fn schematic_round_trip() -> Result<String, String> {
+    let blocks = KNOWN_STATES
+        .iter()
+        .enumerate()
+        .map(|(i, state)| {
+            let i = i as i32;
+            let state_id = schematic::resolve_block_state(state)
+                .ok_or_else(|| msg_string!("diag_unknown_state", state))?;
+            Ok((Vector3::new(i, i % 2, i % 3), state_id))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let clipboard = ClipboardData { blocks, biomes: None, source: None };
+
+    let root = schematic::encode_schematic(&clipboard, None)?;
+    let bytes = schematic::compound_bytes(root, Compression::Gzip)?;
+    let size = bytes.len();
+    let decoded = schematic::decode_schematic(&schematic::read_root_compound(bytes)?)?;
+
+    let sorted = |blocks: &[(Vector3<i32>, u16)]| {
+        let mut blocks: Vec<_> =
+            blocks.iter().map(|(pos, state)| ((pos.x, pos.y, pos.z), *state)).collect();
+        blocks.sort_unstable();
+        blocks
+    };
+    if sorted(&clipboard.blocks) != sorted(&decoded.blocks) {
+        return Err(msg_string!(
+            "diag_round_trip_mismatch",
+            clipboard.blocks.len(),
+            decoded.blocks.len()
+        ));
+    }
+    Ok(msg_string!("diag_round_trip_ok", clipboard.blocks.len(), format_count(size)))
+}
+
+/// Fill a cube standing on the sender's feet with glass through an edit session, then take
+/// the entry off their history and restore it, checking both steps block by block.
+async fn set_undo_cycle(sender: &CommandSender) -> Check {
+    let name = msg_string!("diag_set_undo");
+    if sender.as_player().is_none() {
+        let detail = msg_string!("diag_needs_player");
+        return Check { name, outcome: Outcome::Skipped, detail };
+    }
+    Check::from_result(name, run_set_undo(sender).await)
+}
+
+async fn run_set_undo(sender: &CommandSender) -> Result<String, String> {
+    let refused = |_: CommandError| msg_string!("diag_set_undo_refused");
+    let player_id = sender_uuid(sender).map_err(refused)?;
+    let world = sender_world(sender).map_err(refused)?;
+    let feet = sender_block_pos(sender).map_err(refused)?;
+    let (min_y, max_y) = world.height_range();
+    if feet.0.y < min_y || feet.0.y + CYCLE_SIZE - 1 > max_y {
+        return Err(msg_string!("diag_set_undo_height"));
+    }
+
+    let half = CYCLE_SIZE / 2;
+    let mut positions = Vec::new();
+    for y in 0..CYCLE_SIZE {
+        for x in -half..CYCLE_SIZE - half {
+            for z in -half..CYCLE_SIZE - half {
+                positions.push(BlockPos(Vector3::new(feet.0.x + x, feet.0.y + y, feet.0.z + z)));
+            }
+        }
+    }
+    let mut original = Vec::with_capacity(positions.len());
+    for pos in &positions {
+        original.push(world.read_state(pos).await?);
+    }
+
+    let glass = Block::GLASS.default_state.id;
+    let flags = placement_flags(&player_id, false);
+    let mut session =
+        EditSession::new(world.clone(), player_id, CYCLE_COMMAND, flags).map_err(refused)?;
+    let written = async {
+        for pos in &positions {
+            session.set_block(*pos, glass).await?;
+        }
+        Ok(())
+    }
+    .await;
+    session.commit();
+
+    // Counted before anything is restored so a failure below still reverts the cube
+    let mut placed = 0;
+    for pos in &positions {
+        if world.read_state(pos).await.ok() == Some(glass) {
+            placed += 1;
+        }
+    }
+    let entry = {
+        let mut state = PLAYER_DATA.lock();
+        let history = &mut state.entry(player_id).or_default().history;
+        let newest = history.entries().next();
+        let ours = newest.is_some_and(|entry| entry.info.command == CYCLE_COMMAND);
+        if ours { history.pop_undo() } else { None }
+    };
+    // An empty session records nothing, so the newest entry is the admin's own
+    let Some(entry) = entry else {
+        written?;
+        return Err(msg_string!("diag_set_undo_no_entry"));
+    };
+    let (_, restored) = restore(world.as_ref(), &entry.changes, flags).await;
+    written?;
+    restored?;
+    if placed != positions.len() {
+        return Err(msg_string!("diag_set_undo_not_placed", placed, positions.len()));
+    }
+
+    let mut reverted = 0;
+    for (pos, state) in positions.iter().zip(&original) {
+        if world.read_state(pos).await.ok() == Some(*state) {
+            reverted += 1;
+        }
+    }
+    if reverted != positions.len() {
+        return Err(msg_string!("diag_set_undo_not_reverted", reverted, positions.len()));
+    }
+    Ok(msg_string!("diag_set_undo_ok", positions.len(), feet.0.x, feet.0.y, feet.0.z))
+}
+
+/// Every state in [`KNOWN_STATES`] resolves exactly, to a non-default state that converts
+/// back to the same id.
+fn block_states() -> Result<String, String> {
+    for state in KNOWN_STATES {
+        let state_id = match schematic::resolve_palette_entry(state) {
+            Resolved::Exact(state_id) => state_id,
+            Resolved::Default(_) => return Err(msg_string!("diag_state_mismatch", state)),
+            Resolved::Unknown => return Err(msg_string!("diag_unknown_state", state)),
+        };
+        let round_trip = schematic::resolve_block_state(&schematic::build_block_state_string(
+            state_id,
+        ));
+        if state_id == Block::from_state_id(state_id).default_state.id
+            || round_trip != Some(state_id)
+        {
+            return Err(msg_string!("diag_state_mismatch", state));
+        }
+    }
+    Ok(msg_string!("diag_block_states_ok", KNOWN_STATES.len()))
+}
+
+/// Memory held by clipboards and histories is under `max_memory_mb`; the detail also shows
+/// how many operations are running and waiting for schematic I/O.
+fn memory() -> Result<String, String> {
+    let (clipboard_bytes, history_bytes) = memory_usage();
+    let detail = msg_string!(
+        "diag_memory_detail",
+        format_count(clipboard_bytes / 1024),
+        format_count(history_bytes / 1024),
+        running_operations(),
+        schematic_io_waiting()
+    );
+    let limit = config::get().max_memory_mb * 1024 * 1024;
+    if limit > 0 && (clipboard_bytes + history_bytes) as u64 >= limit {
+        return Err(detail);
+    }
+    Ok(detail)
+}
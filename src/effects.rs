@@ -0,0 +1,86 @@
+//! Completion and progress effects chosen with `/we fx`: a sound and particles sent only to
+//! the player whose operation it is. Nothing is spawned in the world.
+
+use pumpkin::entity::player::Player;
+use pumpkin_data::particle::Particle;
+use pumpkin_data::sound::{Sound, SoundCategory};
+use pumpkin_protocol::{codec::var_int::VarInt, java::client::play::CParticle};
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+
+/// Blocks an operation writes between two progress effects in `batch` mode.
+pub const BATCH_BLOCKS: usize = 4096;
+/// Particles in the burst played when an operation finishes.
+const COMPLETE_PARTICLES: i32 = 24;
+/// Particles in each progress effect.
+const BATCH_PARTICLES: i32 = 3;
+
+/// Which effects a player gets for their operations, chosen with `/we fx`.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum FxMode {
+    #[default]
+    Off,
+    /// A sound and a particle burst when an operation finishes.
+    Complete,
+    /// Like `Complete`, plus a few particles every [`BATCH_BLOCKS`] blocks while it runs.
+    Batch,
+}
+
+impl FxMode {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.to_ascii_lowercase().as_str() {
+            "off" => Some(Self::Off),
+            "complete" => Some(Self::Complete),
+            "batch" => Some(Self::Batch),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Complete => "complete",
+            Self::Batch => "batch",
+        }
+    }
+}
+
+/// Center of the block at `pos`.
+fn block_center(pos: &BlockPos) -> Vector3<f64> {
+    Vector3::new(f64::from(pos.0.x) + 0.5, f64::from(pos.0.y) + 0.5, f64::from(pos.0.z) + 0.5)
+}
+
+/// Play the completion cue at `position`, the center of the changed area, to `player` only.
+pub async fn complete(player: &Player, position: Vector3<f64>) {
+    player
+        .play_sound(
+            Sound::BlockAmethystBlockChime as u16,
+            SoundCategory::Blocks,
+            &position,
+            0.8,
+            1.2,
+            0.0,
+        )
+        .await;
+    send_particles(player, position, Vector3::new(0.6, 0.6, 0.6), COMPLETE_PARTICLES).await;
+}
+
+/// Show a small puff at `pos`, the block an operation has just reached, to `player` only.
+pub async fn batch(player: &Player, pos: BlockPos) {
+    send_particles(player, block_center(&pos), Vector3::new(0.3, 0.3, 0.3), BATCH_PARTICLES)
+        .await;
+}
+
+/// Send `count` sparkle particles spread by `offset` around `position` to `player`'s client.
+async fn send_particles(player: &Player, position: Vector3<f64>, offset: Vector3<f32>, count: i32) {
+    let packet = CParticle::new(
+        false,
+        false,
+        position,
+        offset,
+        0.0,
+        count,
+        VarInt(Particle::HappyVillager as i32),
+        &[],
+    );
+    player.client.enqueue_packet(&packet).await;
+}
@@ -0,0 +1,1002 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use pumpkin::{
+    command::{dispatcher::CommandError, CommandSender},
+    entity::player::Player,
+    world::World,
+};
+use pumpkin_data::chunk::Biome;
+use pumpkin_nbt::compound::NbtCompound;
+use pumpkin_util::math::{position::BlockPos, vector2::Vector2, vector3::Vector3};
+use pumpkin_world::{block::entities::block_entity_from_nbt, world::BlockFlags};
+use uuid::Uuid;
+
+use crate::config;
+use crate::effects::{self, FxMode};
+use crate::history::{biome_cell, BiomeChange, HistoryEntry, OperationInfo, UndoEntry};
+use crate::history_store;
+use crate::mask::BlockNames;
+use crate::messages::{format_count, format_count_short, msg, msg_string};
+use crate::notify::{notify, Level};
+use crate::schematic::{self, Compression, SchematicMetadata};
+use crate::state::{
+    check_world_enabled, has_departed, schematic_io, schematics_dir, selection_volume,
+    ClipboardData, OperationGuard, PLAYER_DATA,
+};
+use crate::stats::{self, format_duration, RunningOperation};
+use crate::support::needs_support;
+use crate::tracking;
+use crate::transform::is_air;
+
+// ============================================================================
+// Block Access
+// ============================================================================
+
+/// Minimal world interface used by edit operations.
+/// Reads and writes are fallible so a failure midway through an operation can be reported
+/// without losing the undo data for the blocks already changed.
+#[async_trait]
+pub trait BlockAccess: Send + Sync {
+    /// Name of the world, used in history descriptors.
+    fn world_name(&self) -> String;
+
+    /// Lowest and highest buildable Y.
+    fn height_range(&self) -> (i32, i32);
+
+    async fn read_state(&self, pos: &BlockPos) -> Result<u16, String>;
+
+    async fn write_state(&self, pos: &BlockPos, state_id: u16, flags: BlockFlags)
+        -> Result<(), String>;
+
+    /// Full NBT of the block entity at `pos`, if there is one.
+    async fn read_block_entity(&self, pos: &BlockPos) -> Result<Option<NbtCompound>, String>;
+
+    /// Remove the block entity at `pos` and, when `nbt` is given, recreate it from that data.
+    async fn write_block_entity(
+        &self,
+        pos: &BlockPos,
+        nbt: Option<NbtCompound>,
+    ) -> Result<(), String>;
+
+    /// Biome id at `pos`. Biomes are stored per 4×4×4 cell.
+    async fn read_biome(&self, pos: &BlockPos) -> Result<u8, String>;
+
+    /// Set the biome of the cell containing `pos`.
+    async fn write_biome(&self, pos: &BlockPos, biome: u8) -> Result<(), String>;
+
+    /// Resend chunks (x, z) to players so they redraw changed biomes.
+    async fn resend_chunks(&self, chunks: &[(i32, i32)]);
+
+    /// The player with `id` if they are in this world, to send them `/we fx` effects.
+    async fn player(&self, id: Uuid) -> Option<Arc<Player>>;
+}
+
+#[async_trait]
+impl BlockAccess for World {
+    fn world_name(&self) -> String {
+        self.get_world_name().to_string()
+    }
+
+    fn height_range(&self) -> (i32, i32) {
+        let min_y = self.dimension_type.min_y;
+        (min_y, min_y + self.dimension_type.height - 1)
+    }
+
+    async fn read_state(&self, pos: &BlockPos) -> Result<u16, String> {
+        Ok(self.get_block_state_id(pos).await)
+    }
+
+    async fn write_state(
+        &self,
+        pos: &BlockPos,
+        state_id: u16,
+        flags: BlockFlags,
+    ) -> Result<(), String> {
+        self.set_block_state(pos, state_id, flags).await;
+        Ok(())
+    }
+
+    async fn read_block_entity(&self, pos: &BlockPos) -> Result<Option<NbtCompound>, String> {
+        let Some(block_entity) = self.get_block_entity(pos).await else {
+            return Ok(None);
+        };
+        let mut nbt = NbtCompound::new();
+        block_entity.write_internal(&mut nbt).await;
+        Ok(Some(nbt))
+    }
+
+    async fn write_block_entity(
+        &self,
+        pos: &BlockPos,
+        nbt: Option<NbtCompound>,
+    ) -> Result<(), String> {
+        self.remove_block_entity(pos).await;
+        if let Some(nbt) = nbt {
+            let block_entity = block_entity_from_nbt(&nbt).ok_or_else(|| {
+                format!(
+                    "Unsupported block entity data at ({}, {}, {})",
+                    pos.0.x, pos.0.y, pos.0.z
+                )
+            })?;
+            self.add_block_entity(block_entity).await;
+        }
+        Ok(())
+    }
+
+    async fn read_biome(&self, pos: &BlockPos) -> Result<u8, String> {
+        Ok(self.get_biome(pos).await.id)
+    }
+
+    async fn write_biome(&self, pos: &BlockPos, biome: u8) -> Result<(), String> {
+        let biome = Biome::from_id(biome).ok_or_else(|| format!("Unknown biome id {biome}"))?;
+        self.set_biome(pos, biome).await;
+        Ok(())
+    }
+
+    async fn resend_chunks(&self, chunks: &[(i32, i32)]) {
+        for &(x, z) in chunks {
+            self.resend_chunk(Vector2::new(x, z)).await;
+        }
+    }
+
+    async fn player(&self, id: Uuid) -> Option<Arc<Player>> {
+        self.get_player_by_uuid(id).await
+    }
+}
+
+// ============================================================================
+// Edit Session
+// ============================================================================
+
+/// Outcome of a committed edit session.
+pub struct EditSummary {
+    pub blocks: usize,
+    pub elapsed: Duration,
+    /// Writes skipped because the block there is protected, see [`EditSession::protect`].
+    pub protected: usize,
+}
+
+impl EditSummary {
+    /// Elapsed time formatted for completion messages ("840ms", "1.25s").
+    pub fn time(&self) -> String {
+        format_duration(self.elapsed)
+    }
+}
+
+/// A single block-modifying operation: applies writes and records undo data,
+/// then commits the recorded changes to the player's history — including when
+/// the operation fails partway through.
+pub struct EditSession {
+    world: Arc<dyn BlockAccess>,
+    player_id: Uuid,
+    command: String,
+    flags: BlockFlags,
+    undo: UndoEntry,
+    started: Instant,
+    /// Stroke group to merge into, see [`Self::group_strokes`].
+    stroke_group: Option<String>,
+    /// Blocks left in place instead of overwritten, see [`Self::protect`].
+    protected: Option<BlockNames>,
+    protected_skipped: usize,
+    /// Effects the player chose with `/we fx`, `Off` when the config disables them.
+    fx: FxMode,
+    running: RunningOperation,
+    _guard: OperationGuard,
+}
+
+impl EditSession {
+    /// Start an operation for `player_id`. Fails if the player already has one running.
+    pub fn new(
+        world: Arc<dyn BlockAccess>,
+        player_id: Uuid,
+        command: impl Into<String>,
+        flags: BlockFlags,
+    ) -> Result<Self, CommandError> {
+        let world_name = world.world_name();
+        check_world_enabled(&world_name)?;
+        let guard = OperationGuard::acquire(player_id)?;
+        let command = command.into();
+        let fx = if config::get().effects_enabled {
+            PLAYER_DATA.lock().get(&player_id).map_or(FxMode::Off, |data| data.fx_mode)
+        } else {
+            FxMode::Off
+        };
+        Ok(Self {
+            world,
+            player_id,
+            running: RunningOperation::start(player_id, &command, world_name),
+            command,
+            flags,
+            undo: UndoEntry::new(),
+            started: Instant::now(),
+            stroke_group: None,
+            protected: None,
+            protected_skipped: 0,
+            fx,
+            _guard: guard,
+        })
+    }
+
+    /// Merge this operation into the previous undo entry when it is a recent stroke of the
+    /// same `group` (see `stroke_group_ms`), for tools that edit on every click.
+    pub fn group_strokes(&mut self, group: impl Into<String>) {
+        self.stroke_group = Some(group.into());
+    }
+
+    /// Leave blocks on the list in place: writes over them are skipped and counted in
+    /// [`EditSummary::protected`]. `None` (a player with the bypass permission) protects nothing.
+    pub fn protect(&mut self, blocks: Option<BlockNames>) {
+        self.protected = blocks;
+    }
+
+    /// Save the region `min..=max` as a schematic under `schematics/backups/<player>/` before
+    /// this session overwrites it, if it spans at least `auto_backup_min_blocks` positions.
+    /// The region is read here and encoded and written on a blocking thread; the player's
+    /// oldest backups are pruned afterwards. Fails the operation if the backup can't be saved.
+    pub async fn backup(
+        &self,
+        sender: &CommandSender,
+        min: &BlockPos,
+        max: &BlockPos,
+    ) -> Result<(), CommandError> {
+        let config = config::get();
+        let volume = selection_volume(min, max) as u64;
+        if config.auto_backup_min_blocks == 0 || volume < config.auto_backup_min_blocks {
+            return Ok(());
+        }
+        let Some(schematics_dir) = schematics_dir() else {
+            return Err(CommandError::CommandFailed(msg!("schematics_dir_missing")));
+        };
+        notify(sender, Level::Progress, msg!("backup_progress", format_count_short(volume))).await;
+
+        let failed = |e: String| CommandError::CommandFailed(msg!("backup_failed", e));
+        let blocks = read_region(self.world.as_ref(), min, max, min).await.map_err(failed)?;
+        let clipboard = ClipboardData { blocks, biomes: None, source: None };
+        let metadata = sender.as_player().map(|player| {
+            SchematicMetadata::new(player.gameprofile.name.clone(), Some(self.command.clone()))
+        });
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+        let dir = schematic::backup_dir(schematics_dir, &self.player_id);
+        let path = dir.join(schematic::backup_file_name(secs, min.0, &self.world.world_name()));
+        let (keep, days) = (config.backup_keep, config.backup_days);
+        schematic_io(sender, move || {
+            fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            schematic::save_schematic(&path, &clipboard, Compression::Gzip, metadata.as_ref())?;
+            schematic::prune_backups(&dir, keep, days);
+            Ok(())
+        })
+        .await
+        .map_err(failed)
+    }
+
+    /// Whether a write over `old_state` must be skipped, counting it if so.
+    fn skip_protected(&mut self, old_state: u16) -> bool {
+        let skip = self.protected.as_ref().is_some_and(|blocks| blocks.contains(old_state));
+        if skip {
+            self.protected_skipped += 1;
+        }
+        skip
+    }
+
+    /// Read the current state at `pos`.
+    pub async fn get_block(&self, pos: &BlockPos) -> Result<u16, String> {
+        self.world.read_state(pos).await
+    }
+
+    /// Full NBT of the block entity currently at `pos`, if there is one.
+    pub async fn get_block_entity(&self, pos: &BlockPos) -> Result<Option<NbtCompound>, String> {
+        self.world.read_block_entity(pos).await
+    }
+
+    /// Write `state_id` at `pos` like [`Self::set_block_from`], then create a block entity
+    /// for the new block from `nbt`. Undo restores the original block entity.
+    pub async fn set_block_with_entity(
+        &mut self,
+        pos: BlockPos,
+        old_state: u16,
+        state_id: u16,
+        nbt: NbtCompound,
+    ) -> Result<(), String> {
+        if self.skip_protected(old_state) {
+            return Ok(());
+        }
+        self.write_block(pos, old_state, state_id).await?;
+        self.world.write_block_entity(&pos, Some(nbt)).await
+    }
+
+    /// Write `state_id` at `pos`, recording the previous state for undo.
+    pub async fn set_block(&mut self, pos: BlockPos, state_id: u16) -> Result<(), String> {
+        let old_state = self.world.read_state(&pos).await?;
+        self.set_block_from(pos, old_state, state_id).await
+    }
+
+    /// Write `state_id` at `pos` when the caller has already read its current state.
+    /// A block entity at the position is captured for undo and removed, so no orphaned
+    /// data (chest contents, sign text) survives under the new block.
+    pub async fn set_block_from(
+        &mut self,
+        pos: BlockPos,
+        old_state: u16,
+        state_id: u16,
+    ) -> Result<(), String> {
+        if self.skip_protected(old_state) {
+            return Ok(());
+        }
+        self.write_block(pos, old_state, state_id).await
+    }
+
+    async fn write_block(
+        &mut self,
+        pos: BlockPos,
+        old_state: u16,
+        state_id: u16,
+    ) -> Result<(), String> {
+        self.check_cancelled()?;
+        let block_entity = self.world.read_block_entity(&pos).await?;
+        let had_block_entity = block_entity.is_some();
+        if !self.undo.contains(&pos) {
+            self.undo.record(pos, old_state, block_entity);
+            self.running.set_blocks(self.undo.len());
+            if self.fx == FxMode::Batch && self.undo.len() % effects::BATCH_BLOCKS == 0 {
+                if let Some(player) = self.world.player(self.player_id).await {
+                    effects::batch(&player, pos).await;
+                }
+            }
+        }
+        // Also on a later write to the same position: the entity may be one this session
+        // placed, e.g. an overlapping stack copy, and undo already holds the original
+        if had_block_entity {
+            self.world.write_block_entity(&pos, None).await?;
+        }
+        self.world.write_state(&pos, state_id, self.flags).await
+    }
+
+    /// Stop with an error once an admin has cancelled the operation; the changes made so
+    /// far are committed like any other failure.
+    fn check_cancelled(&self) -> Result<(), String> {
+        if self.running.is_cancelled() {
+            return Err(msg_string!("operation_cancelled"));
+        }
+        Ok(())
+    }
+
+    /// Set the biome of the cell containing `pos`, recording the previous biome for undo.
+    pub async fn set_biome(&mut self, pos: BlockPos, biome: u8) -> Result<(), String> {
+        self.check_cancelled()?;
+        if !self.undo.contains_biome(&pos) {
+            let old_biome = self.world.read_biome(&pos).await?;
+            self.undo.record_biome(pos, old_biome);
+        }
+        self.world.write_biome(&pos, biome).await
+    }
+
+    /// Resend the chunks whose biomes this session changed so clients redraw their colors.
+    pub async fn refresh_biomes(&self) {
+        resend_biome_chunks(self.world.as_ref(), self.undo.biome_changes()).await;
+    }
+
+    /// Commit the recorded changes to the player's history.
+    /// Operations slower than the configured threshold are logged with their bounding box.
+    pub fn commit(self) -> EditSummary {
+        let count = self.undo.len();
+        let elapsed = self.started.elapsed();
+        stats::record_operation(self.player_id, &self.command, count, elapsed);
+        let config = config::get();
+
+        if elapsed.as_millis() >= u128::from(config.slow_operation_ms) {
+            if let Some((min, max)) = self.undo.bounds() {
+                log::info!(
+                    "Slow operation '{}' by {}: {count} block(s) in {} within ({}, {}, {}) to ({}, {}, {})",
+                    self.command,
+                    self.player_id,
+                    format_duration(elapsed),
+                    min.0.x,
+                    min.0.y,
+                    min.0.z,
+                    max.0.x,
+                    max.0.y,
+                    max.0.z
+                );
+            }
+        }
+
+        if self.fx != FxMode::Off {
+            if let Some((min, max)) = self.undo.bounds() {
+                let (world, player_id) = (self.world.clone(), self.player_id);
+                let middle = |low: i32, high: i32| f64::from(low + high) / 2.0 + 0.5;
+                let center = Vector3::new(
+                    middle(min.0.x, max.0.x),
+                    middle(min.0.y, max.0.y),
+                    middle(min.0.z, max.0.z),
+                );
+                tokio::spawn(async move {
+                    if let Some(player) = world.player(player_id).await {
+                        effects::complete(&player, center).await;
+                    }
+                });
+            }
+        }
+
+        if config.auto_fixlight && count >= config.auto_fixlight_min_blocks {
+            if let Some((min, max)) = self.undo.bounds() {
+                let world = self.world.clone();
+                tokio::spawn(async move {
+                    resend_region(world.as_ref(), &min, &max).await;
+                });
+            }
+        }
+
+        let world = self.world.world_name();
+        tracking::record(&world, self.player_id, &self.command, &self.undo.positions());
+        let info = OperationInfo::new(self.command, count, world);
+
+        {
+            let mut state = PLAYER_DATA.lock();
+            let data = state.entry(self.player_id).or_default();
+            let entry = HistoryEntry {
+                info,
+                changes: self.undo,
+            };
+            match &self.stroke_group {
+                Some(group) => {
+                    let window = Duration::from_millis(config.stroke_group_ms);
+                    data.history.record_stroke(entry, group, window);
+                }
+                None => data.history.record(entry),
+            }
+        }
+        // The player's history was saved when they left; add this entry to it
+        if has_departed(&self.player_id) {
+            history_store::save_on_leave(self.player_id);
+        }
+        EditSummary {
+            blocks: count,
+            elapsed,
+            protected: self.protected_skipped,
+        }
+    }
+
+    /// Commit the session and map the operation's result to the executor's result.
+    /// On failure the partial changes are still committed so `/we undo` can revert them.
+    pub fn finish(self, result: Result<(), String>) -> Result<EditSummary, CommandError> {
+        let summary = self.commit();
+        result.map(|()| summary).map_err(|e| {
+            CommandError::CommandFailed(msg!("operation_failed", format_count(summary.blocks), e))
+        })
+    }
+}
+
+// ============================================================================
+// Region Reads
+// ============================================================================
+
+/// Chunk columns [`read_region`] reads at the same time.
+const READ_CONCURRENCY: usize = 8;
+
+/// Read every block in the cuboid `min..=max` as (offset from `origin`, state id), in X, Y, Z
+/// order. Shared by `//copy` (anchored at the player) and `schem save -s` (anchored at `min`).
+///
+/// The cuboid is read one chunk column at a time with up to [`READ_CONCURRENCY`] columns in
+/// flight, so a large copy doesn't wait on each block in turn; the result is put back in
+/// order, so it is the same however the reads interleave.
+pub async fn read_region(
+    world: &dyn BlockAccess,
+    min: &BlockPos,
+    max: &BlockPos,
+    origin: &BlockPos,
+) -> Result<Vec<(Vector3<i32>, u16)>, String> {
+    let (min, max) = (min.0, max.0);
+    if min.x > max.x || min.y > max.y || min.z > max.z {
+        return Ok(Vec::new());
+    }
+    let height = (max.y - min.y + 1) as usize;
+    let length = (max.z - min.z + 1) as usize;
+    let index = |pos: Vector3<i32>| {
+        ((pos.x - min.x) as usize * height + (pos.y - min.y) as usize) * length
+            + (pos.z - min.z) as usize
+    };
+
+    let columns = (min.x >> 4..=max.x >> 4)
+        .flat_map(|chunk_x| (min.z >> 4..=max.z >> 4).map(move |chunk_z| (chunk_x, chunk_z)));
+    let mut batches = stream::iter(columns)
+        .map(|(chunk_x, chunk_z)| read_chunk_column(world, min, max, chunk_x, chunk_z))
+        .buffer_unordered(READ_CONCURRENCY);
+    let mut states = vec![0u16; (max.x - min.x + 1) as usize * height * length];
+    while let Some(batch) = batches.next().await {
+        for (pos, state_id) in batch? {
+            states[index(pos)] = state_id;
+        }
+    }
+
+    let offsets = (min.x..=max.x).flat_map(|x| {
+        (min.y..=max.y).flat_map(move |y| (min.z..=max.z).map(move |z| Vector3::new(x, y, z)))
+    });
+    Ok(offsets
+        .zip(states)
+        .map(|(pos, state_id)| {
+            (Vector3::new(pos.x - origin.0.x, pos.y - origin.0.y, pos.z - origin.0.z), state_id)
+        })
+        .collect())
+}
+
+/// Read the part of the cuboid `min..=max` inside one chunk column, block by block.
+async fn read_chunk_column(
+    world: &dyn BlockAccess,
+    min: Vector3<i32>,
+    max: Vector3<i32>,
+    chunk_x: i32,
+    chunk_z: i32,
+) -> Result<Vec<(Vector3<i32>, u16)>, String> {
+    let (min_x, max_x) = ((chunk_x << 4).max(min.x), ((chunk_x << 4) + 15).min(max.x));
+    let (min_z, max_z) = ((chunk_z << 4).max(min.z), ((chunk_z << 4) + 15).min(max.z));
+    let mut blocks = Vec::new();
+    for x in min_x..=max_x {
+        for y in min.y..=max.y {
+            for z in min_z..=max_z {
+                let pos = Vector3::new(x, y, z);
+                blocks.push((pos, world.read_state(&BlockPos(pos)).await?));
+            }
+        }
+    }
+    Ok(blocks)
+}
+
+/// Block states of a cuboid plus a one-block border, read once up front with
+/// [`read_region`]'s batched reads so `set` and `replace` don't wait on a read before every
+/// write, and exposure tests (`-x`) cost no extra world reads per block.
+pub struct Neighborhood {
+    min: Vector3<i32>,
+    height: usize,
+    length: usize,
+    states: Vec<u16>,
+}
+
+impl Neighborhood {
+    pub async fn read(
+        world: &dyn BlockAccess,
+        min: &BlockPos,
+        max: &BlockPos,
+    ) -> Result<Self, String> {
+        let min = BlockPos(Vector3::new(min.0.x - 1, min.0.y - 1, min.0.z - 1));
+        let max = BlockPos(Vector3::new(max.0.x + 1, max.0.y + 1, max.0.z + 1));
+        let states = read_region(world, &min, &max, &min)
+            .await?
+            .into_iter()
+            .map(|(_, state_id)| state_id)
+            .collect();
+        Ok(Self {
+            min: min.0,
+            height: (max.0.y - min.0.y + 1) as usize,
+            length: (max.0.z - min.0.z + 1) as usize,
+            states,
+        })
+    }
+
+    /// State at `pos` before the operation, which must lie within the cuboid or its border.
+    pub fn state(&self, pos: &BlockPos) -> u16 {
+        let x = (pos.0.x - self.min.x) as usize;
+        let y = (pos.0.y - self.min.y) as usize;
+        let z = (pos.0.z - self.min.z) as usize;
+        self.states[(x * self.height + y) * self.length + z]
+    }
+
+    /// Whether any of the six blocks next to `pos` is air.
+    pub fn is_exposed(&self, pos: &BlockPos) -> bool {
+        let p = pos.0;
+        [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)]
+            .into_iter()
+            .any(|(dx, dy, dz)| {
+                is_air(self.state(&BlockPos(Vector3::new(p.x + dx, p.y + dy, p.z + dz))))
+            })
+    }
+}
+
+/// Step length when tracing a line of sight, in blocks.
+const RAYCAST_STEP: f64 = 0.1;
+
+/// First non-air block along the ray from `origin` in `direction` (a unit vector),
+/// within `range` blocks.
+pub async fn raycast_block(
+    world: &dyn BlockAccess,
+    origin: Vector3<f64>,
+    direction: Vector3<f64>,
+    range: f64,
+) -> Result<Option<BlockPos>, String> {
+    let mut last = None;
+    let mut distance = 0.0;
+    while distance <= range {
+        let pos = BlockPos(Vector3::new(
+            (origin.x + direction.x * distance).floor() as i32,
+            (origin.y + direction.y * distance).floor() as i32,
+            (origin.z + direction.z * distance).floor() as i32,
+        ));
+        distance += RAYCAST_STEP;
+        let key = (pos.0.x, pos.0.y, pos.0.z);
+        if last == Some(key) {
+            continue;
+        }
+        last = Some(key);
+        if !is_air(world.read_state(&pos).await?) {
+            return Ok(Some(pos));
+        }
+    }
+    Ok(None)
+}
+
+/// Read the biome of every block in the cuboid `min..=max`, in the same order as
+/// [`read_region`]. Each biome cell is read once.
+pub async fn read_region_biomes(
+    world: &dyn BlockAccess,
+    min: &BlockPos,
+    max: &BlockPos,
+) -> Result<Vec<u8>, String> {
+    let mut cells: HashMap<(i32, i32, i32), u8> = HashMap::new();
+    let mut biomes = Vec::new();
+    for x in min.0.x..=max.0.x {
+        for y in min.0.y..=max.0.y {
+            for z in min.0.z..=max.0.z {
+                let pos = BlockPos(Vector3::new(x, y, z));
+                let biome = match cells.get(&biome_cell(&pos)) {
+                    Some(biome) => *biome,
+                    None => {
+                        let biome = world.read_biome(&pos).await?;
+                        cells.insert(biome_cell(&pos), biome);
+                        biome
+                    }
+                };
+                biomes.push(biome);
+            }
+        }
+    }
+    Ok(biomes)
+}
+
+/// Y of the topmost block of each X/Z column in `min..=max` that `transparent` doesn't pass
+/// through, or `None` for columns without one, in X-major order. Each column is scanned
+/// downward from `max` and stops at the first such block.
+pub async fn column_tops(
+    world: &dyn BlockAccess,
+    min: &BlockPos,
+    max: &BlockPos,
+    transparent: impl Fn(u16) -> bool,
+) -> Result<Vec<Option<i32>>, String> {
+    let mut tops = Vec::new();
+    for x in min.0.x..=max.0.x {
+        for z in min.0.z..=max.0.z {
+            let mut top = None;
+            for y in (min.0.y..=max.0.y).rev() {
+                let state_id = world.read_state(&BlockPos(Vector3::new(x, y, z))).await?;
+                if !transparent(state_id) {
+                    top = Some(y);
+                    break;
+                }
+            }
+            tops.push(top);
+        }
+    }
+    Ok(tops)
+}
+
+/// Resend every chunk overlapping `min..=max` so clients redraw it, lighting included, from
+/// the server's data. Returns the number of chunk sections covered.
+pub async fn resend_region(world: &dyn BlockAccess, min: &BlockPos, max: &BlockPos) -> usize {
+    let chunks: Vec<(i32, i32)> = (min.0.x >> 4..=max.0.x >> 4)
+        .flat_map(|x| (min.0.z >> 4..=max.0.z >> 4).map(move |z| (x, z)))
+        .collect();
+    world.resend_chunks(&chunks).await;
+    let sections = (max.0.y >> 4) - (min.0.y >> 4) + 1;
+    chunks.len() * sections as usize
+}
+
+/// Resend every chunk containing one of `changes`.
+async fn resend_biome_chunks(world: &dyn BlockAccess, changes: &[BiomeChange]) {
+    let chunks: HashSet<(i32, i32)> =
+        changes.iter().map(|change| (change.pos.0.x >> 4, change.pos.0.z >> 4)).collect();
+    if !chunks.is_empty() {
+        world.resend_chunks(&chunks.into_iter().collect::<Vec<_>>()).await;
+    }
+}
+
+// ============================================================================
+// Undo / Redo
+// ============================================================================
+
+/// Write every recorded change back in restore order, block entities and biomes included.
+/// Returns the entry that reverses this restoration (for redo, or undo after redo),
+/// together with the outcome; on failure the reverse entry covers the blocks already restored.
+pub async fn restore(
+    world: &dyn BlockAccess,
+    changes: &UndoEntry,
+    flags: BlockFlags,
+) -> (UndoEntry, Result<(), String>) {
+    let mut reverse = UndoEntry::new();
+    let result = async {
+        for change in changes.restore_order() {
+            let current_state = world.read_state(&change.pos).await?;
+            let current_entity = world.read_block_entity(&change.pos).await?;
+            let had_entity = current_entity.is_some();
+            reverse.record(change.pos, current_state, current_entity);
+
+            world.write_state(&change.pos, change.state, flags).await?;
+            if had_entity || change.block_entity.is_some() {
+                world
+                    .write_block_entity(&change.pos, change.block_entity.clone())
+                    .await?;
+            }
+        }
+        for change in changes.biome_changes().iter().rev() {
+            let current_biome = world.read_biome(&change.pos).await?;
+            reverse.record_biome(change.pos, current_biome);
+            world.write_biome(&change.pos, change.biome).await?;
+        }
+        Ok(())
+    }
+    .await;
+    resend_biome_chunks(world, reverse.biome_changes()).await;
+    (reverse, result)
+}
+
+// ============================================================================
+// Write Ordering
+// ============================================================================
+
+/// Sort writes into a physically safe placement order.
+///
+/// Free-standing blocks go first, then blocks that need support, so torches, sand and
+/// doors always find their neighbors already placed. Within each group writes are grouped
+/// by chunk for locality and placed bottom-up.
+pub fn sort_for_placement(blocks: &mut [(BlockPos, u16)]) {
+    blocks.sort_by_cached_key(|(pos, state_id)| {
+        (
+            needs_support(*state_id),
+            pos.0.x >> 4,
+            pos.0.z >> 4,
+            pos.0.y,
+            pos.0.x,
+            pos.0.z,
+        )
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use pumpkin_data::Block;
+    use pumpkin_nbt::tag::NbtTag;
+
+    use super::*;
+    use crate::testing::{air, cuboid, player, pop_undo, pos, start, FakeWorld};
+
+    fn stone() -> u16 {
+        Block::STONE.default_state.id
+    }
+
+    fn glass() -> u16 {
+        Block::GLASS.default_state.id
+    }
+
+    #[tokio::test]
+    async fn undo_restores_blocks_and_block_entities() {
+        let world = FakeWorld::new("overworld");
+        world.fill(pos(0, 0, 0), pos(2, 1, 2), stone());
+        let chest = pos(1, 1, 1);
+        let mut nbt = NbtCompound::new();
+        nbt.put("id", NbtTag::String("minecraft:chest".to_string()));
+        world.set_block_entity(chest, nbt);
+        let before = world.snapshot();
+
+        let player_id = player(1);
+        let mut session = start(&world, player_id, "//set glass");
+        for pos in cuboid(pos(0, 0, 0), pos(2, 2, 2)) {
+            session.set_block(pos, glass()).await.unwrap();
+        }
+        assert_eq!(session.commit().blocks, 27);
+        assert!(cuboid(pos(0, 0, 0), pos(2, 2, 2)).into_iter().all(|p| world.get(p) == glass()));
+        assert!(!world.has_block_entity(chest), "the chest's contents outlived the chest");
+
+        let entry = pop_undo(player_id);
+        assert_eq!(entry.info.block_count, 27);
+        let flags = BlockFlags::FORCE_STATE;
+        let (reverse, result) = restore(world.as_ref(), &entry.changes, flags).await;
+        result.unwrap();
+        assert_eq!(world.snapshot(), before);
+        assert!(world.has_block_entity(chest));
+
+        // The reverse entry is the redo, putting the glass back
+        assert_eq!(reverse.len(), 27);
+        let (_, result) = restore(world.as_ref(), &reverse, flags).await;
+        result.unwrap();
+        assert_eq!(world.get(pos(0, 2, 0)), glass());
+    }
+
+    #[tokio::test]
+    async fn clearing_a_chest_and_undoing_keeps_its_items() {
+        let world = FakeWorld::new("overworld");
+        let chest = pos(0, 64, 0);
+        world.set(chest, Block::CHEST.default_state.id);
+        let mut item = NbtCompound::new();
+        item.put("id", NbtTag::String("minecraft:diamond".to_string()));
+        let mut nbt = NbtCompound::new();
+        nbt.put("id", NbtTag::String("minecraft:chest".to_string()));
+        nbt.put("Items", NbtTag::List(vec![NbtTag::Compound(item)]));
+        world.set_block_entity(chest, nbt);
+
+        let player_id = player(4);
+        let mut session = start(&world, player_id, "//clear");
+        session.set_block(chest, air()).await.unwrap();
+        session.commit();
+        assert!(!world.has_block_entity(chest));
+
+        let entry = pop_undo(player_id);
+        let flags = BlockFlags::FORCE_STATE;
+        let (redo, result) = restore(world.as_ref(), &entry.changes, flags).await;
+        result.unwrap();
+        assert_eq!(world.get(chest), Block::CHEST.default_state.id);
+        let restored = world.read_block_entity(&chest).await.unwrap().expect("chest data");
+        assert_eq!(restored.get_list("Items").map(Vec::len), Some(1));
+
+        // Redo clears the chest and its data again
+        let (_, result) = restore(world.as_ref(), &redo, flags).await;
+        result.unwrap();
+        assert_eq!(world.get(chest), air());
+        assert!(!world.has_block_entity(chest));
+    }
+
+    #[tokio::test]
+    async fn writing_a_block_twice_undoes_to_the_original() {
+        let world = FakeWorld::new("overworld");
+        world.fill(pos(0, 0, 0), pos(1, 0, 0), stone());
+        let before = world.snapshot();
+
+        // Like move: clear the source, then write the destination over part of it
+        let player_id = player(3);
+        let mut session = start(&world, player_id, "//move 1 east");
+        session.set_block(pos(0, 0, 0), air()).await.unwrap();
+        session.set_block(pos(1, 0, 0), air()).await.unwrap();
+        session.set_block(pos(1, 0, 0), glass()).await.unwrap();
+        session.set_block(pos(2, 0, 0), glass()).await.unwrap();
+        session.commit();
+
+        let entry = pop_undo(player_id);
+        assert_eq!(entry.changes.len(), 3);
+        let (_, result) = restore(world.as_ref(), &entry.changes, BlockFlags::FORCE_STATE).await;
+        result.unwrap();
+        assert_eq!(world.snapshot(), before);
+    }
+
+    #[tokio::test]
+    async fn overwriting_a_placed_chest_removes_its_entity() {
+        let world = FakeWorld::new("overworld");
+        let (chest, spot) = (Block::CHEST.default_state.id, pos(0, 64, 0));
+        let mut nbt = NbtCompound::new();
+        nbt.put("id", NbtTag::String("minecraft:chest".to_string()));
+
+        // An earlier copy places a chest, a later overlapping one puts stone there
+        let player_id = player(9);
+        let mut session = start(&world, player_id, "//stack 2 up");
+        session.set_block_with_entity(spot, air(), chest, nbt).await.unwrap();
+        session.set_block(spot, stone()).await.unwrap();
+        session.commit();
+        assert_eq!(world.get(spot), stone());
+        assert!(!world.has_block_entity(spot), "the chest's data outlived the chest");
+
+        // Undo still restores what was there before the session: nothing
+        let entry = pop_undo(player_id);
+        let (_, result) = restore(world.as_ref(), &entry.changes, BlockFlags::FORCE_STATE).await;
+        result.unwrap();
+        assert_eq!(world.get(spot), air());
+        assert!(!world.has_block_entity(spot));
+    }
+
+    #[tokio::test]
+    async fn failed_operation_keeps_undo_for_what_it_changed() {
+        let world = FakeWorld::new("overworld");
+        world.fill(pos(0, 0, 0), pos(9, 0, 0), stone());
+        let before = world.snapshot();
+        world.fail_writes_after(4);
+
+        let player_id = player(2);
+        let mut session = start(&world, player_id, "//set air");
+        let result = async {
+            for pos in cuboid(pos(0, 0, 0), pos(9, 0, 0)) {
+                session.set_block(pos, air()).await?;
+            }
+            Ok(())
+        }
+        .await;
+        assert!(session.finish(result).is_err());
+        assert_eq!(world.get(pos(3, 0, 0)), air());
+        assert_eq!(world.get(pos(4, 0, 0)), stone());
+
+        world.fail_writes_after(usize::MAX);
+        let entry = pop_undo(player_id);
+        let (_, result) = restore(world.as_ref(), &entry.changes, BlockFlags::FORCE_STATE).await;
+        result.unwrap();
+        assert_eq!(world.snapshot(), before);
+    }
+
+    #[tokio::test]
+    async fn region_reads_keep_their_order_across_chunk_borders() {
+        let world = FakeWorld::new("overworld");
+        let (min, max) = (pos(14, 0, -2), pos(17, 1, 1));
+        let positions = cuboid(min, max);
+        for (i, pos) in positions.iter().enumerate() {
+            world.set(*pos, if i % 3 == 0 { stone() } else { glass() });
+        }
+
+        let blocks = read_region(world.as_ref(), &min, &max, &pos(15, 0, 0)).await.unwrap();
+        let blocks: Vec<_> = blocks
+            .into_iter()
+            .map(|(offset, state)| ((offset.x, offset.y, offset.z), state))
+            .collect();
+        let expected: Vec<_> =
+            positions.iter().map(|p| ((p.0.x - 15, p.0.y, p.0.z), world.get(*p))).collect();
+        assert_eq!(blocks, expected);
+    }
+
+    #[tokio::test]
+    async fn second_operation_is_rejected_while_first_runs() {
+        let world = FakeWorld::new("overworld");
+        world.fill(pos(0, 0, 0), pos(3, 0, 0), stone());
+        let before = world.snapshot();
+        let flags = BlockFlags::FORCE_STATE;
+
+        let player_id = player(5);
+        let mut first = start(&world, player_id, "//set glass");
+        first.set_block(pos(0, 0, 0), glass()).await.unwrap();
+        assert!(EditSession::new(world.clone(), player_id, "//set air", flags).is_err());
+        assert!(EditSession::new(world.clone(), player(6), "//set air", flags).is_ok());
+        first.set_block(pos(1, 0, 0), glass()).await.unwrap();
+        first.commit();
+
+        // Only the first operation made history, and undoing it restores the original
+        let entry = pop_undo(player_id);
+        assert_eq!(entry.changes.len(), 2);
+        let (_, result) = restore(world.as_ref(), &entry.changes, flags).await;
+        result.unwrap();
+        assert_eq!(world.snapshot(), before);
+
+        // Ending the operation, failed or not, lets the next one start
+        let failed = start(&world, player_id, "//set air");
+        assert!(failed.finish(Err("stopped".to_string())).is_err());
+        drop(start(&world, player_id, "//set air"));
+    }
+
+    #[test]
+    fn supported_blocks_are_placed_after_their_supports() {
+        let torch = Block::TORCH.default_state.id;
+        let sand = Block::SAND.default_state.id;
+        // A torch on a wall and sand on a pillar, listed top-down as a clipboard may hold them
+        let mut writes = vec![
+            (pos(0, 3, 0), torch),
+            (pos(5, 3, 0), sand),
+            (pos(0, 2, 0), stone()),
+            (pos(5, 2, 0), stone()),
+            (pos(5, 1, 0), stone()),
+            (pos(20, 0, 0), stone()),
+        ];
+        sort_for_placement(&mut writes);
+
+        let index = |x, y| writes.iter().position(|(at, _)| (at.0.x, at.0.y) == (x, y)).unwrap();
+        assert!(index(0, 3) > index(0, 2));
+        assert!(index(5, 3) > index(5, 2));
+        let supported = writes.iter().position(|(_, state)| *state == torch || *state == sand);
+        assert_eq!(supported, Some(4), "all free-standing blocks go first");
+        // Free-standing blocks by chunk, then bottom-up
+        let order: Vec<_> = writes[..4].iter().map(|(p, _)| (p.0.x, p.0.y)).collect();
+        assert_eq!(order, [(5, 1), (0, 2), (5, 2), (20, 0)]);
+    }
+
+    #[tokio::test]
+    async fn region_read_failure_is_reported() {
+        let world = FakeWorld::new("overworld");
+        world.break_block(pos(20, 5, 3));
+        assert!(read_region(world.as_ref(), &pos(16, 0, 0), &pos(31, 10, 15), &pos(0, 0, 0))
+            .await
+            .is_err());
+    }
+}
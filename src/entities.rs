@@ -0,0 +1,151 @@
+use std::sync::Arc;
+
+use pumpkin::{entity::EntityBase, world::World};
+use pumpkin_util::math::vector3::Vector3;
+
+/// Hostile mobs removed by `butcher` by default.
+const HOSTILE: &[&str] = &[
+    "zombie",
+    "zombie_villager",
+    "husk",
+    "drowned",
+    "skeleton",
+    "stray",
+    "bogged",
+    "wither_skeleton",
+    "creeper",
+    "spider",
+    "cave_spider",
+    "enderman",
+    "endermite",
+    "silverfish",
+    "witch",
+    "slime",
+    "magma_cube",
+    "phantom",
+    "blaze",
+    "ghast",
+    "guardian",
+    "elder_guardian",
+    "shulker",
+    "vex",
+    "vindicator",
+    "evoker",
+    "pillager",
+    "ravager",
+    "illusioner",
+    "piglin_brute",
+    "hoglin",
+    "zoglin",
+    "zombified_piglin",
+    "warden",
+    "breeze",
+    "creaking",
+];
+
+/// Passive animals, removed by `butcher -a`.
+const ANIMALS: &[&str] = &[
+    "cow",
+    "mooshroom",
+    "pig",
+    "sheep",
+    "chicken",
+    "rabbit",
+    "horse",
+    "donkey",
+    "mule",
+    "llama",
+    "trader_llama",
+    "camel",
+    "goat",
+    "cat",
+    "ocelot",
+    "wolf",
+    "fox",
+    "panda",
+    "polar_bear",
+    "parrot",
+    "turtle",
+    "frog",
+    "tadpole",
+    "axolotl",
+    "armadillo",
+    "sniffer",
+    "bee",
+    "strider",
+    "dolphin",
+    "cod",
+    "salmon",
+    "tropical_fish",
+    "pufferfish",
+    "squid",
+    "glow_squid",
+];
+
+/// Ambient mobs, removed by `butcher -b`.
+const AMBIENT: &[&str] = &["bat"];
+
+/// What a living entity counts as for `butcher`.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MobKind {
+    Hostile,
+    Animal,
+    Ambient,
+}
+
+/// Entity types `remove` can despawn, as (argument name, entity type names).
+pub const REMOVABLE: &[(&str, &[&str])] = &[
+    ("items", &["item"]),
+    ("arrows", &["arrow", "spectral_arrow", "trident"]),
+    ("xp", &["experience_orb"]),
+    ("falling", &["falling_block"]),
+    ("tnt", &["tnt"]),
+];
+
+/// Entity type name without namespace, e.g. `zombie`.
+pub fn type_name(entity: &dyn EntityBase) -> &'static str {
+    let name = entity.get_entity().entity_type.resource_name;
+    name.strip_prefix("minecraft:").unwrap_or(name)
+}
+
+/// Butcher category of an entity type, if it is a mob at all.
+pub fn mob_kind(type_name: &str) -> Option<MobKind> {
+    if HOSTILE.contains(&type_name) {
+        Some(MobKind::Hostile)
+    } else if ANIMALS.contains(&type_name) {
+        Some(MobKind::Animal)
+    } else if AMBIENT.contains(&type_name) {
+        Some(MobKind::Ambient)
+    } else {
+        None
+    }
+}
+
+/// Whether the entity carries a custom name (name tag).
+pub fn is_named(entity: &dyn EntityBase) -> bool {
+    entity.get_entity().custom_name.is_some()
+}
+
+/// Non-player entities within `radius` blocks (cube) of `center`.
+pub async fn entities_near(
+    world: &World,
+    center: Vector3<f64>,
+    radius: f64,
+) -> Vec<Arc<dyn EntityBase>> {
+    let entities = world.entities.read().await;
+    entities
+        .values()
+        .filter(|entity| {
+            let pos = entity.get_entity().pos.load();
+            (pos.x - center.x).abs() <= radius
+                && (pos.y - center.y).abs() <= radius
+                && (pos.z - center.z).abs() <= radius
+        })
+        .cloned()
+        .collect()
+}
+
+/// Despawn `entity` without drops.
+pub async fn despawn(entity: &dyn EntityBase) {
+    entity.get_entity().remove().await;
+}
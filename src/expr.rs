@@ -0,0 +1,433 @@
+//! Small arithmetic/logic expression language shared by masks and shape commands.
+//!
+//! Grammar (lowest to highest precedence): `||`, `&&`, `== !=`, `< <= > >=`, `+ -`,
+//! `* / %`, unary `- !`, `^` (right-associative), then numbers, variables, `pi`, `e`,
+//! parenthesised expressions and function calls. Comparisons and logic yield 1 or 0;
+//! any non-zero value counts as true.
+//!
+//! A [`Program`] is a `;`-separated list of assignments (`y += 0.2*sin(x*10)`, with
+//! `= += -= *= /=`) and swaps (`swap x z`) over the same variables.
+
+use crate::messages::msg_string;
+
+/// Functions callable from expressions, as (name, argument count).
+const FUNCTIONS: &[(&str, usize)] = &[
+    ("sin", 1),
+    ("cos", 1),
+    ("tan", 1),
+    ("asin", 1),
+    ("acos", 1),
+    ("atan", 1),
+    ("atan2", 2),
+    ("sqrt", 1),
+    ("abs", 1),
+    ("floor", 1),
+    ("ceil", 1),
+    ("round", 1),
+    ("exp", 1),
+    ("ln", 1),
+    ("log10", 1),
+    ("min", 2),
+    ("max", 2),
+    ("pow", 2),
+];
+
+// ============================================================================
+// Tokens
+// ============================================================================
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    Num(f64),
+    Ident(String),
+    Op(&'static str),
+}
+
+/// Multi-character operators first so `<=` wins over `<`.
+const OPERATORS: &[&str] = &[
+    "&&", "||", "==", "!=", "<=", ">=", "+=", "-=", "*=", "/=", "+", "-", "*", "/", "%", "^",
+    "<", ">", "!", "=", "(", ")", ",", ";",
+];
+
+/// Assignment operators accepted in programs.
+const ASSIGNMENTS: &[&str] = &["=", "+=", "-=", "*=", "/="];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source.trim_start();
+
+    while !rest.is_empty() {
+        let c = rest.chars().next().unwrap_or_default();
+        if c.is_ascii_digit() || c == '.' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .unwrap_or(rest.len());
+            let number = &rest[..end];
+            let value = number.parse().map_err(|_| msg_string!("expr_invalid_number", number))?;
+            tokens.push(Token::Num(value));
+            rest = &rest[end..];
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let end = rest
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..end].to_string()));
+            rest = &rest[end..];
+        } else if let Some(op) = OPERATORS.iter().copied().find(|op| rest.starts_with(*op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+        } else {
+            return Err(msg_string!("expr_unexpected_character", c));
+        }
+        rest = rest.trim_start();
+    }
+    Ok(tokens)
+}
+
+// ============================================================================
+// Syntax Tree
+// ============================================================================
+
+#[derive(Clone)]
+enum Node {
+    Num(f64),
+    /// Index into the variable values passed to `eval`.
+    Var(usize),
+    Neg(Box<Node>),
+    Not(Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+    Call(&'static str, Vec<Node>),
+}
+
+fn truth(value: f64) -> bool {
+    value != 0.0 && !value.is_nan()
+}
+
+fn flag(condition: bool) -> f64 {
+    if condition {
+        1.0
+    } else {
+        0.0
+    }
+}
+
+impl Node {
+    fn eval(&self, values: &[f64]) -> f64 {
+        match self {
+            Self::Num(n) => *n,
+            Self::Var(index) => values[*index],
+            Self::Neg(inner) => -inner.eval(values),
+            Self::Not(inner) => flag(!truth(inner.eval(values))),
+            Self::Binary(op, lhs, rhs) => {
+                let a = lhs.eval(values);
+                // Short-circuit logic
+                match *op {
+                    "&&" => return flag(truth(a) && truth(rhs.eval(values))),
+                    "||" => return flag(truth(a) || truth(rhs.eval(values))),
+                    _ => {}
+                }
+                let b = rhs.eval(values);
+                match *op {
+                    "+" => a + b,
+                    "-" => a - b,
+                    "*" => a * b,
+                    "/" => a / b,
+                    "%" => a.rem_euclid(b),
+                    "^" => a.powf(b),
+                    "==" => flag(a == b),
+                    "!=" => flag(a != b),
+                    "<" => flag(a < b),
+                    "<=" => flag(a <= b),
+                    ">" => flag(a > b),
+                    ">=" => flag(a >= b),
+                    _ => f64::NAN,
+                }
+            }
+            Self::Call(name, args) => {
+                let a = args.first().map_or(0.0, |arg| arg.eval(values));
+                let b = args.get(1).map_or(0.0, |arg| arg.eval(values));
+                match *name {
+                    "sin" => a.sin(),
+                    "cos" => a.cos(),
+                    "tan" => a.tan(),
+                    "asin" => a.asin(),
+                    "acos" => a.acos(),
+                    "atan" => a.atan(),
+                    "atan2" => a.atan2(b),
+                    "sqrt" => a.sqrt(),
+                    "abs" => a.abs(),
+                    "floor" => a.floor(),
+                    "ceil" => a.ceil(),
+                    "round" => a.round(),
+                    "exp" => a.exp(),
+                    "ln" => a.ln(),
+                    "log10" => a.log10(),
+                    "min" => a.min(b),
+                    "max" => a.max(b),
+                    "pow" => a.powf(b),
+                    _ => f64::NAN,
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Parser
+// ============================================================================
+
+/// Binary operator levels, lowest precedence first.
+const BINARY_LEVELS: &[&[&str]] = &[
+    &["||"],
+    &["&&"],
+    &["==", "!="],
+    &["<", "<=", ">", ">="],
+    &["+", "-"],
+    &["*", "/", "%"],
+];
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    variables: &'a [&'a str],
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, op: &'static str) -> bool {
+        if self.peek() == Some(&Token::Op(op)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, op: &'static str) -> Result<(), String> {
+        if self.eat(op) {
+            Ok(())
+        } else {
+            Err(msg_string!("expr_expected", op))
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<Node, String> {
+        let Some(ops) = BINARY_LEVELS.get(level) else {
+            return self.unary();
+        };
+        let mut lhs = self.binary(level + 1)?;
+        while let Some(Token::Op(op)) = self.peek().cloned() {
+            if !ops.contains(&op) {
+                break;
+            }
+            self.pos += 1;
+            let rhs = self.binary(level + 1)?;
+            lhs = Node::Binary(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat("-") {
+            Ok(Node::Neg(Box::new(self.unary()?)))
+        } else if self.eat("!") {
+            Ok(Node::Not(Box::new(self.unary()?)))
+        } else {
+            self.eat("+");
+            self.power()
+        }
+    }
+
+    fn power(&mut self) -> Result<Node, String> {
+        let base = self.primary()?;
+        if self.eat("^") {
+            // Right-associative, and binds tighter than unary minus on its left
+            let exponent = self.unary()?;
+            Ok(Node::Binary("^", Box::new(base), Box::new(exponent)))
+        } else {
+            Ok(base)
+        }
+    }
+
+    fn primary(&mut self) -> Result<Node, String> {
+        match self.next() {
+            Some(Token::Num(n)) => Ok(Node::Num(n)),
+            Some(Token::Op("(")) => {
+                let inner = self.binary(0)?;
+                self.expect(")")?;
+                Ok(inner)
+            }
+            Some(Token::Ident(name)) => self.identifier(&name),
+            Some(Token::Op(op)) => Err(msg_string!("expr_unexpected", op)),
+            None => Err(msg_string!("expr_unexpected_end")),
+        }
+    }
+
+    fn identifier(&mut self, name: &str) -> Result<Node, String> {
+        if let Some(&(function, arity)) = FUNCTIONS.iter().find(|(f, _)| *f == name) {
+            self.expect("(")?;
+            let mut args = Vec::new();
+            if !self.eat(")") {
+                loop {
+                    args.push(self.binary(0)?);
+                    if self.eat(")") {
+                        break;
+                    }
+                    self.expect(",")?;
+                }
+            }
+            if args.len() != arity {
+                return Err(msg_string!("expr_arity", function, arity));
+            }
+            return Ok(Node::Call(function, args));
+        }
+        if let Some(index) = self.variables.iter().position(|v| *v == name) {
+            return Ok(Node::Var(index));
+        }
+        match name {
+            "pi" => Ok(Node::Num(std::f64::consts::PI)),
+            "e" => Ok(Node::Num(std::f64::consts::E)),
+            _ => Err(msg_string!("expr_unknown_name", name)),
+        }
+    }
+}
+
+/// Parse a complete expression from `tokens`, rejecting trailing input.
+fn parse_tokens(tokens: Vec<Token>, variables: &[&str]) -> Result<Node, String> {
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        variables,
+    };
+    let root = parser.binary(0)?;
+    if let Some(token) = parser.peek() {
+        let found = match token {
+            Token::Num(n) => n.to_string(),
+            Token::Ident(name) => name.clone(),
+            Token::Op(op) => (*op).to_string(),
+        };
+        return Err(msg_string!("expr_trailing", found));
+    }
+    Ok(root)
+}
+
+// ============================================================================
+// Expression
+// ============================================================================
+
+/// A parsed expression over a fixed list of variables.
+#[derive(Clone)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    /// Parse `source`, resolving variable names against `variables`.
+    /// Values are passed to [`Expression::eval`] in the same order.
+    pub fn parse(source: &str, variables: &[&str]) -> Result<Self, String> {
+        let tokens = tokenize(source)?;
+        if tokens.is_empty() {
+            return Err(msg_string!("expr_empty"));
+        }
+        parse_tokens(tokens, variables).map(|root| Self { root })
+    }
+
+    pub fn eval(&self, values: &[f64]) -> f64 {
+        self.root.eval(values)
+    }
+
+    /// Evaluate as a condition: non-zero (and not NaN) is true.
+    pub fn test(&self, values: &[f64]) -> bool {
+        truth(self.eval(values))
+    }
+}
+
+// ============================================================================
+// Program
+// ============================================================================
+
+enum Statement {
+    /// Variable index, assignment operator, value.
+    Assign(usize, &'static str, Node),
+    Swap(usize, usize),
+}
+
+/// A parsed sequence of assignments and swaps that updates variables in place.
+pub struct Program {
+    statements: Vec<Statement>,
+}
+
+impl Program {
+    /// Parse `source`; only names in `variables` can be assigned or read.
+    pub fn parse(source: &str, variables: &[&str]) -> Result<Self, String> {
+        let variable = |token: Option<&Token>| match token {
+            Some(Token::Ident(name)) => variables
+                .iter()
+                .position(|v| *v == name.as_str())
+                .ok_or_else(|| msg_string!("expr_unknown_variable", name)),
+            _ => Err(msg_string!("expr_expected_variable")),
+        };
+
+        let tokens = tokenize(source)?;
+        let mut statements = Vec::new();
+        for part in tokens.split(|token| *token == Token::Op(";")) {
+            if part.is_empty() {
+                continue;
+            }
+            if part[0] == Token::Ident("swap".to_string()) {
+                if part.len() != 3 {
+                    return Err(msg_string!("expr_swap_arity"));
+                }
+                statements.push(Statement::Swap(
+                    variable(part.get(1))?,
+                    variable(part.get(2))?,
+                ));
+                continue;
+            }
+
+            let target = variable(part.first())?;
+            let op = match part.get(1) {
+                Some(Token::Op(op)) if ASSIGNMENTS.contains(op) => *op,
+                _ => return Err(msg_string!("expr_expected_assignment")),
+            };
+            if part.len() < 3 {
+                return Err(msg_string!("expr_missing_value", op));
+            }
+            let value = parse_tokens(part[2..].to_vec(), variables)?;
+            statements.push(Statement::Assign(target, op, value));
+        }
+
+        if statements.is_empty() {
+            return Err(msg_string!("expr_empty"));
+        }
+        Ok(Self { statements })
+    }
+
+    /// Run every statement in order, updating `values`.
+    pub fn run(&self, values: &mut [f64]) {
+        for statement in &self.statements {
+            match statement {
+                Statement::Assign(target, op, value) => {
+                    let v = value.eval(values);
+                    let current = values[*target];
+                    values[*target] = match *op {
+                        "+=" => current + v,
+                        "-=" => current - v,
+                        "*=" => current * v,
+                        "/=" => current / v,
+                        _ => v,
+                    };
+                }
+                Statement::Swap(a, b) => values.swap(*a, *b),
+            }
+        }
+    }
+}
@@ -0,0 +1,289 @@
+use pumpkin::command::{
+    args::{message::MsgArgConsumer, ConsumedArgs, FindArg},
+    dispatcher::CommandError,
+};
+
+use crate::commands::ARG_FLAGS;
+use crate::messages::msg;
+use crate::transform::parse_direction;
+
+/// A flag a command accepts: a bare switch like `-u`, or `-m <mask>` when it takes a value.
+pub struct FlagSpec {
+    pub flag: &'static str,
+    /// Name of the value shown in usage, or `None` for a switch.
+    pub value: Option<&'static str>,
+}
+
+impl FlagSpec {
+    pub const fn switch(flag: &'static str) -> Self {
+        Self { flag, value: None }
+    }
+
+    pub const fn with_value(flag: &'static str, value: &'static str) -> Self {
+        Self { flag, value: Some(value) }
+    }
+}
+
+/// The flags given to one command, checked against that command's specs.
+pub struct Flags {
+    given: Vec<(&'static str, Option<String>)>,
+}
+
+impl Flags {
+    /// Parse the trailing `ARG_FLAGS` argument; a command run without it has no flags.
+    pub fn from_args(args: &ConsumedArgs<'_>, specs: &[FlagSpec]) -> Result<Self, CommandError> {
+        let input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+        Self::parse(&input, specs)
+    }
+
+    /// Like [`Self::from_args`], but first takes up to `max` leading values that aren't flags,
+    /// for optional arguments such as the height in `dome <block> <radius> [height] [-h]`.
+    /// Numbers and directions count as values even with a leading `-`, so `move 5 -1,0,1` works.
+    pub fn from_args_with_values(
+        args: &ConsumedArgs<'_>,
+        max: usize,
+        specs: &[FlagSpec],
+    ) -> Result<(Vec<String>, Self), CommandError> {
+        let input = MsgArgConsumer::find_arg(args, ARG_FLAGS).unwrap_or_default();
+        Self::parse_with_values(&input, max, specs)
+    }
+
+    /// Parse a flag string after up to `max` leading values. See [`Self::from_args_with_values`].
+    pub fn parse_with_values(
+        input: &str,
+        max: usize,
+        specs: &[FlagSpec],
+    ) -> Result<(Vec<String>, Self), CommandError> {
+        let mut tokens = flag_tokens(input);
+        let count = tokens.iter().take(max).take_while(|token| is_value(token)).count();
+        let values = tokens.drain(..count).map(str::to_string).collect();
+        Ok((values, Self::from_tokens(tokens, specs)?))
+    }
+
+    /// Parse the switches in front of a free-text argument, such as `-r` in
+    /// `deform [-r] <expression>`, up to the first token without a `-`. Returns the flags and
+    /// the rest of `input` as typed. Only switches fit here: a value would read as the text.
+    pub fn parse_leading<'i>(
+        input: &'i str,
+        specs: &[FlagSpec],
+    ) -> Result<(Self, &'i str), CommandError> {
+        let mut tokens = Vec::new();
+        let mut rest = input.trim_start();
+        while rest.starts_with('-') {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            tokens.push(&rest[..end]);
+            rest = rest[end..].trim_start();
+        }
+        Ok((Self::from_tokens(tokens, specs)?, rest))
+    }
+
+    /// Parse a flag string. See [`Self::from_tokens`].
+    pub fn parse(input: &str, specs: &[FlagSpec]) -> Result<Self, CommandError> {
+        Self::from_tokens(flag_tokens(input), specs)
+    }
+
+    /// Check flag tokens against `specs`, taking the next token as the value of flags that
+    /// need one. Switches can be combined, `-ua` for `-u -a`. Unknown flags and missing values
+    /// are errors that list what the command accepts.
+    fn from_tokens(tokens: Vec<&str>, specs: &[FlagSpec]) -> Result<Self, CommandError> {
+        let mut given = Vec::new();
+        let mut tokens = tokens.into_iter();
+        while let Some(token) = tokens.next() {
+            let group = match combined(token, specs) {
+                Some(group) => group,
+                None => vec![specs.iter().find(|spec| spec.flag == token).ok_or_else(|| {
+                    CommandError::CommandFailed(msg!("unknown_flag_valid", token, usage(specs)))
+                })?],
+            };
+            for spec in group {
+                let value = match spec.value {
+                    Some(name) => Some(tokens.next().map(str::to_string).ok_or_else(|| {
+                        CommandError::CommandFailed(msg!("missing_flag_value", name, spec.flag))
+                    })?),
+                    None => None,
+                };
+                given.push((spec.flag, value));
+            }
+        }
+        Ok(Self { given })
+    }
+
+    /// Whether the flag was given.
+    pub fn has(&self, flag: &str) -> bool {
+        self.given.iter().any(|(given, _)| *given == flag)
+    }
+
+    /// The value of a flag that takes one; the last occurrence wins.
+    pub fn value(&self, flag: &str) -> Option<&str> {
+        self.given
+            .iter()
+            .rev()
+            .find(|(given, _)| *given == flag)
+            .and_then(|(_, value)| value.as_deref())
+    }
+}
+
+/// Whether a leading token is a value rather than a flag: anything without a `-`, and negative
+/// numbers or direction vectors such as `-3` and `-1,0,1`.
+fn is_value(token: &str) -> bool {
+    !token.starts_with('-') || token.parse::<i32>().is_ok() || parse_direction(token).is_ok()
+}
+
+/// The flags of a combined token such as `-ua`, one per letter. Only the last may take a value,
+/// so `-am stone` works but `-ma stone` doesn't. `None` if the token isn't such a group.
+fn combined<'s>(token: &str, specs: &'s [FlagSpec]) -> Option<Vec<&'s FlagSpec>> {
+    let letters = token.strip_prefix('-')?;
+    if letters.chars().count() < 2 || specs.iter().any(|spec| spec.flag == token) {
+        return None;
+    }
+    let group = letters
+        .chars()
+        .map(|letter| {
+            let mut buf = [0; 4];
+            let name: &str = letter.encode_utf8(&mut buf);
+            specs.iter().find(|spec| spec.flag.strip_prefix('-') == Some(name))
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let (_, switches) = group.split_last()?;
+    switches.iter().all(|spec| spec.value.is_none()).then_some(group)
+}
+
+/// The accepted flags as shown in errors, e.g. `-u, -x, -m <mask>`.
+fn usage(specs: &[FlagSpec]) -> String {
+    specs
+        .iter()
+        .map(|spec| match spec.value {
+            Some(value) => format!("{} <{value}>", spec.flag),
+            None => spec.flag.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Split a flag string on whitespace, keeping double-quoted values together (quotes removed).
+pub fn flag_tokens(input: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut rest = input.trim_start();
+    while !rest.is_empty() {
+        let (token, remainder) = if let Some(quoted) = rest.strip_prefix('"') {
+            match quoted.find('"') {
+                Some(end) => (&quoted[..end], &quoted[end + 1..]),
+                None => (quoted, ""),
+            }
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            (&rest[..end], &rest[end..])
+        };
+        tokens.push(token);
+        rest = remainder.trim_start();
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::flags;
+
+    const SPECS: &[FlagSpec] =
+        &[FlagSpec::switch("-a"), FlagSpec::switch("-b"), FlagSpec::with_value("-m", "mask")];
+
+    #[test]
+    fn switches_and_values() {
+        let parsed = flags("-a -m stone,dirt", SPECS);
+        assert!(parsed.has("-a"));
+        assert!(!parsed.has("-b"));
+        assert_eq!(parsed.value("-m"), Some("stone,dirt"));
+    }
+
+    #[test]
+    fn no_input_means_no_flags() {
+        let parsed = flags("  ", SPECS);
+        assert!(!parsed.has("-a"));
+        assert_eq!(parsed.value("-m"), None);
+    }
+
+    #[test]
+    fn unknown_flags_are_rejected() {
+        assert!(Flags::parse("-z", SPECS).is_err());
+        assert!(Flags::parse("-a stone", SPECS).is_err());
+        assert!(Flags::parse("--a", SPECS).is_err());
+    }
+
+    #[test]
+    fn value_flags_need_a_value() {
+        assert!(Flags::parse("-m", SPECS).is_err());
+        assert!(Flags::parse("-a -m", SPECS).is_err());
+        assert_eq!(flags("-m \"stone dirt\" -a", SPECS).value("-m"), Some("stone dirt"));
+        assert_eq!(flags("-m -a", SPECS).value("-m"), Some("-a"));
+    }
+
+    #[test]
+    fn duplicate_flags_keep_the_last_value() {
+        let parsed = flags("-m stone -a -a -m dirt", SPECS);
+        assert!(parsed.has("-a"));
+        assert_eq!(parsed.value("-m"), Some("dirt"));
+    }
+
+    #[test]
+    fn combined_short_flags() {
+        let parsed = flags("-ab", SPECS);
+        assert!(parsed.has("-a") && parsed.has("-b"));
+
+        let parsed = flags("-bam glass", SPECS);
+        assert!(parsed.has("-a") && parsed.has("-b"));
+        assert_eq!(parsed.value("-m"), Some("glass"));
+
+        assert!(Flags::parse("-ma glass", SPECS).is_err());
+        assert!(Flags::parse("-az", SPECS).is_err());
+        assert!(Flags::parse("-am", SPECS).is_err());
+    }
+
+    #[test]
+    fn leading_values_before_flags() {
+        let (values, parsed) = match Flags::parse_with_values("5 -a", 2, SPECS) {
+            Ok(parsed) => parsed,
+            Err(_) => panic!("'5 -a' was rejected"),
+        };
+        assert_eq!(values, ["5"]);
+        assert!(parsed.has("-a"));
+
+        assert!(Flags::parse_with_values("5 6 -a", 1, SPECS).is_err());
+    }
+
+    #[test]
+    fn switches_before_free_text() {
+        let Ok((parsed, rest)) = Flags::parse_leading(" -a -b  y -= 0.2", SPECS) else {
+            panic!("'-a -b' was rejected");
+        };
+        assert!(parsed.has("-a") && parsed.has("-b"));
+        assert_eq!(rest, "y -= 0.2");
+
+        let Ok((parsed, rest)) = Flags::parse_leading("swap x -a", SPECS) else {
+            panic!("text without flags was rejected");
+        };
+        assert!(!parsed.has("-a"));
+        assert_eq!(rest, "swap x -a");
+
+        assert!(Flags::parse_leading("-z y = 1", SPECS).is_err());
+    }
+
+    #[test]
+    fn negative_values_are_not_flags() {
+        for (input, value) in [("-1,0,1 -a", "-1,0,1"), ("-3 -a", "-3"), ("0,-1,0 -a", "0,-1,0")] {
+            let (values, parsed) = match Flags::parse_with_values(input, 1, SPECS) {
+                Ok(parsed) => parsed,
+                Err(_) => panic!("'{input}' was rejected"),
+            };
+            assert_eq!(values, [value]);
+            assert!(parsed.has("-a"));
+        }
+
+        let (values, parsed) = match Flags::parse_with_values("-a", 1, SPECS) {
+            Ok(parsed) => parsed,
+            Err(_) => panic!("'-a' was rejected"),
+        };
+        assert!(values.is_empty());
+        assert!(parsed.has("-a"));
+    }
+}
@@ -0,0 +1,96 @@
+//! Terrain heightmaps: grayscale images of column heights, and the smoothing behind
+//! `//smooth`.
+
+use std::path::PathBuf;
+
+/// Gray levels for the ground height of each column, one byte per pixel with X across and Z
+/// down. `tops` is in X-major order as returned by `engine::column_tops`. Heights are scaled
+/// so `min_y` is black and `max_y` white; columns without ground are black as well.
+pub fn render(
+    tops: &[Option<i32>],
+    width: usize,
+    length: usize,
+    min_y: i32,
+    max_y: i32,
+) -> Vec<u8> {
+    let range = i64::from(max_y - min_y).max(1);
+    let mut pixels = vec![0u8; width * length];
+    for x in 0..width {
+        for z in 0..length {
+            if let Some(top) = tops[x * length + z] {
+                let level = i64::from(top - min_y) * 255 / range;
+                pixels[z * width + x] = level.clamp(0, 255) as u8;
+            }
+        }
+    }
+    pixels
+}
+
+/// Blur column heights `iterations` times with a 3×3 kernel weighted 1-2-1 along each axis.
+/// `heights` is in X-major order like `engine::column_tops`. Columns without a height are
+/// holes: they stay `None` and are left out of their neighbors' averages, so the surface is
+/// interpolated across them instead of dragged down to them.
+pub fn smooth(
+    heights: &[Option<f64>],
+    width: usize,
+    length: usize,
+    iterations: u32,
+) -> Vec<Option<f64>> {
+    let mut current = heights.to_vec();
+    for _ in 0..iterations {
+        let mut next = current.clone();
+        for x in 0..width {
+            for z in 0..length {
+                if current[x * length + z].is_none() {
+                    continue;
+                }
+                let mut sum = 0.0;
+                let mut weights = 0.0;
+                for (nx, wx) in [(x.checked_sub(1), 1.0), (Some(x), 2.0), (Some(x + 1), 1.0)] {
+                    for (nz, wz) in [(z.checked_sub(1), 1.0), (Some(z), 2.0), (Some(z + 1), 1.0)] {
+                        let (Some(nx), Some(nz)) = (nx, nz) else {
+                            continue;
+                        };
+                        if nx >= width || nz >= length {
+                            continue;
+                        }
+                        if let Some(height) = current[nx * length + nz] {
+                            sum += height * wx * wz;
+                            weights += wx * wz;
+                        }
+                    }
+                }
+                next[x * length + z] = Some(sum / weights);
+            }
+        }
+        current = next;
+    }
+    current
+}
+
+/// Encode 8-bit grayscale pixels as a PNG file.
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    let mut encoder = png::Encoder::new(&mut data, width, height);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    writer.write_image_data(pixels).map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+/// Encode and write a heightmap image off the server thread.
+pub async fn write_png(
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+) -> Result<(), String> {
+    tokio::task::spawn_blocking(move || {
+        let data = encode_png(width, height, &pixels)?;
+        std::fs::write(&path, data).map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
@@ -0,0 +1,425 @@
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, SystemTime};
+
+use pumpkin_nbt::compound::NbtCompound;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+
+use crate::messages::format_count;
+
+/// Maximum number of undo entries kept per player.
+pub const MAX_HISTORY: usize = 15;
+
+/// The state of one position before it was modified.
+pub struct BlockChange {
+    pub pos: BlockPos,
+    pub state: u16,
+    /// Block entity data (chest contents, sign text, ...) present before the change.
+    pub block_entity: Option<NbtCompound>,
+}
+
+/// The biome of a biome cell (4×4×4 blocks) before it was modified.
+pub struct BiomeChange {
+    /// Any position inside the cell.
+    pub pos: BlockPos,
+    pub biome: u8,
+}
+
+/// Blocks changed by a single operation, kept in the order they were first modified.
+///
+/// Each position is recorded once with the state it had before the operation touched it,
+/// so an operation that writes the same block twice (e.g. clear source, then write destination)
+/// still restores the true original. Undo applies the list strictly in reverse.
+#[derive(Default)]
+pub struct UndoEntry {
+    changes: Vec<BlockChange>,
+    seen: HashSet<(i32, i32, i32)>,
+    biomes: Vec<BiomeChange>,
+    seen_cells: HashSet<(i32, i32, i32)>,
+}
+
+/// Key of the biome cell containing `pos`.
+pub fn biome_cell(pos: &BlockPos) -> (i32, i32, i32) {
+    (pos.0.x >> 2, pos.0.y >> 2, pos.0.z >> 2)
+}
+
+impl UndoEntry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the state (and block entity) of `pos` before it is modified.
+    /// Only the first (oldest) state per position is kept; later records are ignored.
+    pub fn record(&mut self, pos: BlockPos, old_state: u16, block_entity: Option<NbtCompound>) {
+        if self.seen.insert((pos.0.x, pos.0.y, pos.0.z)) {
+            self.changes.push(BlockChange {
+                pos,
+                state: old_state,
+                block_entity,
+            });
+        }
+    }
+
+    /// Record the biome of the cell containing `pos` before it is modified.
+    /// Only the first biome per cell is kept.
+    pub fn record_biome(&mut self, pos: BlockPos, old_biome: u8) {
+        if self.seen_cells.insert(biome_cell(&pos)) {
+            self.biomes.push(BiomeChange {
+                pos,
+                biome: old_biome,
+            });
+        }
+    }
+
+    /// Whether the biome cell containing `pos` has already been recorded.
+    pub fn contains_biome(&self, pos: &BlockPos) -> bool {
+        self.seen_cells.contains(&biome_cell(pos))
+    }
+
+    /// Recorded biome changes in the order they were first modified.
+    pub fn biome_changes(&self) -> &[BiomeChange] {
+        &self.biomes
+    }
+
+    /// Whether `pos` has already been recorded in this entry.
+    pub fn contains(&self, pos: &BlockPos) -> bool {
+        self.seen.contains(&(pos.0.x, pos.0.y, pos.0.z))
+    }
+
+    /// Number of distinct positions recorded.
+    pub fn len(&self) -> usize {
+        self.changes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && self.biomes.is_empty()
+    }
+
+    /// Bounding box (min, max) of all recorded positions.
+    pub fn bounds(&self) -> Option<(BlockPos, BlockPos)> {
+        let first = self.changes.first()?.pos;
+        Some(self.changes.iter().fold((first, first), |(min, max), change| {
+            let p = change.pos.0;
+            (
+                BlockPos(Vector3::new(min.0.x.min(p.x), min.0.y.min(p.y), min.0.z.min(p.z))),
+                BlockPos(Vector3::new(max.0.x.max(p.x), max.0.y.max(p.y), max.0.z.max(p.z))),
+            )
+        }))
+    }
+
+    /// Recorded positions in the order they were first modified.
+    pub fn positions(&self) -> Vec<BlockPos> {
+        self.changes.iter().map(|change| change.pos).collect()
+    }
+
+    /// Recorded changes in the order they were first modified.
+    pub fn changes(&self) -> &[BlockChange] {
+        &self.changes
+    }
+
+    /// Append the changes of a later operation. Positions and biome cells already recorded keep
+    /// their older state, so undoing the combined entry restores what was there before both.
+    pub fn merge(&mut self, later: UndoEntry) {
+        for change in later.changes {
+            self.record(change.pos, change.state, change.block_entity);
+        }
+        for change in later.biomes {
+            self.record_biome(change.pos, change.biome);
+        }
+    }
+
+    /// Keep only the changes at positions not in `positions` and biome cells not in `cells`,
+    /// for undoing an older entry around newer ones. Returns the rest and how many block
+    /// positions were left out.
+    pub fn without(
+        self,
+        positions: &HashSet<(i32, i32, i32)>,
+        cells: &HashSet<(i32, i32, i32)>,
+    ) -> (UndoEntry, usize) {
+        let mut kept = UndoEntry::new();
+        let mut skipped = 0;
+        for change in self.changes {
+            if positions.contains(&(change.pos.0.x, change.pos.0.y, change.pos.0.z)) {
+                skipped += 1;
+            } else {
+                kept.record(change.pos, change.state, change.block_entity);
+            }
+        }
+        for change in self.biomes {
+            if !cells.contains(&biome_cell(&change.pos)) {
+                kept.record_biome(change.pos, change.biome);
+            }
+        }
+        (kept, skipped)
+    }
+
+    /// Recorded changes in the order they must be restored (most recent modification first).
+    pub fn restore_order(&self) -> impl Iterator<Item = &BlockChange> {
+        self.changes.iter().rev()
+    }
+}
+
+/// Describes the operation a history entry came from.
+#[derive(Clone)]
+pub struct OperationInfo {
+    /// Per-player sequence number shown in `/we history` and taken by `undo -i`, given when
+    /// the entry is recorded; 0 before that.
+    pub id: u64,
+    /// Command as typed, e.g. `//set stone`.
+    pub command: String,
+    pub block_count: usize,
+    pub world: String,
+    /// When the operation finished; for a stroke group, when its last stroke finished.
+    pub timestamp: SystemTime,
+    /// Label shared by the strokes merged into this entry, e.g. `brush sphere`.
+    pub group: Option<String>,
+    /// Number of operations merged into this entry.
+    pub strokes: usize,
+}
+
+impl OperationInfo {
+    pub fn new(command: impl Into<String>, block_count: usize, world: impl Into<String>) -> Self {
+        Self {
+            id: 0,
+            command: command.into(),
+            block_count,
+            world: world.into(),
+            timestamp: SystemTime::now(),
+            group: None,
+            strokes: 1,
+        }
+    }
+
+    /// What the entry undoes: the command, or `brush sphere ×14` for a stroke group.
+    pub fn label(&self) -> String {
+        match &self.group {
+            Some(group) if self.strokes > 1 => format!("{group} ×{}", self.strokes),
+            _ => self.command.clone(),
+        }
+    }
+}
+
+/// Format an elapsed duration as a short relative time ("12s ago", "2m ago", "3h ago").
+pub fn format_ago(age: Duration) -> String {
+    let secs = age.as_secs();
+    if secs < 60 {
+        format!("{secs}s ago")
+    } else if secs < 3600 {
+        format!("{}m ago", secs / 60)
+    } else if secs < 86400 {
+        format!("{}h ago", secs / 3600)
+    } else {
+        format!("{}d ago", secs / 86400)
+    }
+}
+
+/// One undoable (or redoable) operation.
+pub struct HistoryEntry {
+    pub info: OperationInfo,
+    pub changes: UndoEntry,
+}
+
+impl HistoryEntry {
+    /// One-line summary, e.g. `//set stone — 12,400 blocks — 2m ago — world`. Biome changes
+    /// are counted separately, as `800 blocks, 12 biome cells`, or alone when no block changed.
+    pub fn describe(&self) -> String {
+        let age = self.info.timestamp.elapsed().unwrap_or_default();
+        let size = match (self.info.block_count, self.changes.biome_changes().len()) {
+            (blocks, 0) => format!("{} blocks", format_count(blocks)),
+            (0, cells) => format!("{} biome cells", format_count(cells)),
+            (blocks, cells) => {
+                format!("{} blocks, {} biome cells", format_count(blocks), format_count(cells))
+            }
+        };
+        format!("{} — {size} — {} — {}", self.info.label(), format_ago(age), self.info.world)
+    }
+}
+
+/// Block positions and biome cells changed by a set of history entries.
+#[derive(Default)]
+pub struct Overlap {
+    pub positions: HashSet<(i32, i32, i32)>,
+    pub cells: HashSet<(i32, i32, i32)>,
+}
+
+/// Per-player undo and redo stacks.
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<HistoryEntry>,
+    redo: Vec<HistoryEntry>,
+    /// Id of the most recently recorded entry.
+    last_id: u64,
+}
+
+impl History {
+    /// Record a newly completed operation. Clears the redo stack; empty operations are dropped.
+    pub fn record(&mut self, mut entry: HistoryEntry) {
+        if entry.changes.is_empty() {
+            return;
+        }
+        self.redo.clear();
+        self.last_id += 1;
+        entry.info.id = self.last_id;
+        self.push_undo(entry);
+    }
+
+    /// Record one stroke of a rapid-fire source such as a brush. If the newest undo entry holds
+    /// strokes of the same `group` in the same world and its last stroke finished less than
+    /// `window` ago, the stroke is merged into it so one undo reverts the whole burst.
+    pub fn record_stroke(&mut self, mut entry: HistoryEntry, group: &str, window: Duration) {
+        if entry.changes.is_empty() {
+            return;
+        }
+        if let Some(last) = self.undo.back_mut() {
+            let recent = last.info.timestamp.elapsed().is_ok_and(|age| age < window);
+            if recent
+                && last.info.group.as_deref() == Some(group)
+                && last.info.world == entry.info.world
+            {
+                last.changes.merge(entry.changes);
+                last.info.block_count = last.changes.len();
+                last.info.strokes += entry.info.strokes;
+                last.info.timestamp = entry.info.timestamp;
+                self.redo.clear();
+                return;
+            }
+        }
+        entry.info.group = Some(group.to_string());
+        self.record(entry);
+    }
+
+    /// Push an entry onto the undo stack without touching redo (used by redo itself).
+    pub fn push_undo(&mut self, entry: HistoryEntry) {
+        self.undo.push_back(entry);
+        while self.undo.len() > MAX_HISTORY {
+            self.undo.pop_front();
+        }
+    }
+
+    pub fn pop_undo(&mut self) -> Option<HistoryEntry> {
+        self.undo.pop_back()
+    }
+
+    /// Remove the undo entry with `id`, along with the block positions and biome cells that
+    /// newer undo entries changed, which undoing it alone must leave alone.
+    pub fn take_undo_by_id(&mut self, id: u64) -> Option<(HistoryEntry, Overlap)> {
+        let index = self.undo.iter().position(|entry| entry.info.id == id)?;
+        let mut overlap = Overlap::default();
+        for newer in self.undo.iter().skip(index + 1) {
+            overlap.positions.extend(newer.changes.seen.iter().copied());
+            overlap.cells.extend(newer.changes.seen_cells.iter().copied());
+        }
+        Some((self.undo.remove(index)?, overlap))
+    }
+
+    /// Remove and return all undo entries, oldest first.
+    pub fn take_undo(&mut self) -> Vec<HistoryEntry> {
+        self.undo.drain(..).collect()
+    }
+
+    /// Put entries older than everything on the undo stack back underneath it (oldest first),
+    /// dropping the oldest if the stack would exceed `MAX_HISTORY`. Entries recorded since a
+    /// restart are numbered from 1 again, so they are renumbered to follow the restored ones.
+    pub fn restore_older(&mut self, entries: Vec<HistoryEntry>) {
+        let restored_last = entries.iter().map(|entry| entry.info.id).max().unwrap_or(0);
+        let newer_first = self.undo.iter().chain(&self.redo).map(|entry| entry.info.id).min();
+        if newer_first.is_some_and(|first| first <= restored_last) {
+            for entry in self.undo.iter_mut().chain(&mut self.redo) {
+                entry.info.id += restored_last;
+            }
+            self.last_id += restored_last;
+        }
+        self.last_id = self.last_id.max(restored_last);
+        for entry in entries.into_iter().rev() {
+            if self.undo.len() >= MAX_HISTORY {
+                break;
+            }
+            self.undo.push_front(entry);
+        }
+    }
+
+    pub fn push_redo(&mut self, entry: HistoryEntry) {
+        self.redo.push(entry);
+    }
+
+    pub fn pop_redo(&mut self) -> Option<HistoryEntry> {
+        self.redo.pop()
+    }
+
+    /// Undo entries, newest first.
+    pub fn entries(&self) -> impl Iterator<Item = &HistoryEntry> {
+        self.undo.iter().rev()
+    }
+
+    pub fn redo_len(&self) -> usize {
+        self.redo.len()
+    }
+
+    /// Total positions held across the undo and redo stacks.
+    pub fn stored_blocks(&self) -> usize {
+        self.undo.iter().chain(self.redo.iter()).map(|entry| entry.changes.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::pos;
+
+    fn states(changes: impl Iterator<Item = (i32, u16)>) -> Vec<(i32, u16)> {
+        changes.collect()
+    }
+
+    #[test]
+    fn a_position_keeps_its_oldest_state() {
+        let mut entry = UndoEntry::new();
+        entry.record(pos(0, 64, 0), 1, None);
+        entry.record(pos(1, 64, 0), 2, None);
+        // Written again later in the same operation, e.g. move clearing then filling it
+        entry.record(pos(0, 64, 0), 3, None);
+
+        assert_eq!(entry.len(), 2);
+        let order = entry.changes().iter().map(|change| (change.pos.0.x, change.state));
+        assert_eq!(states(order), [(0, 1), (1, 2)]);
+    }
+
+    #[test]
+    fn changes_restore_newest_first() {
+        let mut entry = UndoEntry::new();
+        for x in 0..4 {
+            entry.record(pos(x, 64, 0), x as u16, None);
+        }
+        let order = entry.restore_order().map(|change| (change.pos.0.x, change.state));
+        assert_eq!(states(order), [(3, 3), (2, 2), (1, 1), (0, 0)]);
+    }
+
+    #[test]
+    fn merged_entries_restore_what_was_there_before_both() {
+        let mut first = UndoEntry::new();
+        first.record(pos(0, 64, 0), 1, None);
+        first.record_biome(pos(0, 64, 0), 5);
+        let mut later = UndoEntry::new();
+        later.record(pos(0, 64, 0), 7, None);
+        later.record(pos(1, 64, 0), 8, None);
+        later.record_biome(pos(1, 64, 1), 9);
+        later.record_biome(pos(8, 64, 0), 6);
+
+        first.merge(later);
+        let order = first.restore_order().map(|change| (change.pos.0.x, change.state));
+        assert_eq!(states(order), [(1, 8), (0, 1)]);
+        let biomes: Vec<u8> = first.biome_changes().iter().map(|change| change.biome).collect();
+        assert_eq!(biomes, [5, 6]);
+    }
+
+    #[test]
+    fn without_leaves_out_positions_changed_since() {
+        let mut entry = UndoEntry::new();
+        for x in 0..3 {
+            entry.record(pos(x, 64, 0), 1, None);
+        }
+        let newer = HashSet::from([(1, 64, 0), (9, 64, 0)]);
+        let (kept, skipped) = entry.without(&newer, &HashSet::new());
+        assert_eq!(skipped, 1);
+        assert!(kept.contains(&pos(0, 64, 0)) && kept.contains(&pos(2, 64, 0)));
+        assert!(!kept.contains(&pos(1, 64, 0)));
+    }
+}
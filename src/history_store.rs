@@ -0,0 +1,461 @@
+//! Undo history saved to disk when a player leaves and loaded back on their next undo, so a
+//! reconnect or server restart doesn't lose it.
+//!
+//! Each player's entries go in one gzipped NBT file, `history/<uuid>.nbt`. Block states are
+//! stored as a palette of state strings plus varint indices into it. Files carry a format
+//! version: older versions are upgraded on load, while unreadable files and versions from a
+//! newer plugin are logged and discarded instead of failing the command.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::time::{Duration, UNIX_EPOCH};
+
+use pumpkin_nbt::compound::NbtCompound;
+use pumpkin_nbt::nbt_compress::{read_gzip_compound_tag, write_gzip_compound_tag};
+use pumpkin_nbt::tag::NbtTag;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+use uuid::Uuid;
+
+use crate::config;
+use crate::history::{HistoryEntry, OperationInfo, UndoEntry};
+use crate::schematic::{
+    biome_name, build_block_state_string, decode_varints, encode_varints, resolve_biome,
+    resolve_block_state,
+};
+use crate::state::PLAYER_DATA;
+
+/// Version written to new files.
+///
+/// 1. Initial format.
+/// 2. Every entry has `Strokes`, the number of operations merged into it.
+/// 3. Every entry has `Id`, its per-player sequence number.
+const FORMAT_VERSION: i32 = 3;
+
+/// Upgrades from each version to the next: `MIGRATIONS[0]` turns version 1 into 2, and so on.
+/// Each one rewrites the root compound in place.
+const MIGRATIONS: [fn(&mut NbtCompound) -> Result<(), String>; FORMAT_VERSION as usize - 1] =
+    [migrate_v1, migrate_v2];
+
+/// Saved history directory, set by `init`.
+static HISTORY_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Create the saved history directory.
+pub fn init(dir: PathBuf) -> Result<(), String> {
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create history directory: {e}"))?;
+    let _ = HISTORY_DIR.set(dir);
+    Ok(())
+}
+
+fn history_path(player_id: Uuid) -> Option<PathBuf> {
+    HISTORY_DIR.get().map(|dir| dir.join(format!("{player_id}.nbt")))
+}
+
+// ============================================================================
+// Migration
+// ============================================================================
+
+/// Upgrade a file's root compound to `FORMAT_VERSION`. Versions from a newer plugin can't be
+/// read and are an error.
+fn migrate(root: &mut NbtCompound) -> Result<(), String> {
+    let version = root.get_int("Version").unwrap_or(0);
+    if version < 1 || version > FORMAT_VERSION {
+        return Err(format!("unsupported version {version}"));
+    }
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize - 1) {
+        migration(root).map_err(|e| format!("upgrading from version {}: {e}", from + 1))?;
+    }
+    root.put_int("Version", FORMAT_VERSION);
+    Ok(())
+}
+
+/// Version 1 → 2: entries without `Strokes` (all of them, since grouping only wrote it on
+/// grouped entries) hold a single operation.
+fn migrate_v1(root: &mut NbtCompound) -> Result<(), String> {
+    let entries = root
+        .get_list("Entries")
+        .ok_or("Missing Entries")?
+        .iter()
+        .map(|tag| match tag {
+            NbtTag::Compound(entry) if entry.get_int("Strokes").is_none() => {
+                let mut entry = entry.clone();
+                entry.put("Strokes", NbtTag::Int(1));
+                NbtTag::Compound(entry)
+            }
+            other => other.clone(),
+        })
+        .collect();
+    root.put("Entries", NbtTag::List(entries));
+    Ok(())
+}
+
+/// Version 2 → 3: entries are numbered 1, 2, ... in file order, which is oldest first.
+fn migrate_v2(root: &mut NbtCompound) -> Result<(), String> {
+    let entries = root
+        .get_list("Entries")
+        .ok_or("Missing Entries")?
+        .iter()
+        .enumerate()
+        .map(|(i, tag)| match tag {
+            NbtTag::Compound(entry) => {
+                let mut entry = entry.clone();
+                entry.put("Id", NbtTag::Long(i as i64 + 1));
+                NbtTag::Compound(entry)
+            }
+            other => other.clone(),
+        })
+        .collect();
+    root.put("Entries", NbtTag::List(entries));
+    Ok(())
+}
+
+// ============================================================================
+// Encoding
+// ============================================================================
+
+fn encode_entry(entry: &HistoryEntry) -> NbtCompound {
+    let mut palette: Vec<u16> = Vec::new();
+    let mut palette_index: HashMap<u16, usize> = HashMap::new();
+    let mut positions = Vec::with_capacity(entry.changes.len() * 3);
+    let mut indices = Vec::with_capacity(entry.changes.len());
+    let mut block_entities = Vec::new();
+
+    for (i, change) in entry.changes.changes().iter().enumerate() {
+        let index = *palette_index.entry(change.state).or_insert_with(|| {
+            palette.push(change.state);
+            palette.len() - 1
+        });
+        indices.push(index as i32);
+        positions.extend([change.pos.0.x, change.pos.0.y, change.pos.0.z]);
+        if let Some(nbt) = &change.block_entity {
+            let mut block_entity = NbtCompound::new();
+            block_entity.put_int("Index", i as i32);
+            block_entity.put_component("Data", nbt.clone());
+            block_entities.push(NbtTag::Compound(block_entity));
+        }
+    }
+
+    let mut biome_positions = Vec::with_capacity(entry.changes.biome_changes().len() * 3);
+    let mut biomes = Vec::with_capacity(entry.changes.biome_changes().len());
+    for change in entry.changes.biome_changes() {
+        biome_positions.extend([change.pos.0.x, change.pos.0.y, change.pos.0.z]);
+        biomes.push(NbtTag::String(biome_name(change.biome)));
+    }
+
+    let timestamp = entry
+        .info
+        .timestamp
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as i64);
+    let palette = palette
+        .into_iter()
+        .map(|state| NbtTag::String(build_block_state_string(state)))
+        .collect();
+
+    let mut compound = NbtCompound::new();
+    compound.put("Id", NbtTag::Long(entry.info.id as i64));
+    compound.put("Command", NbtTag::String(entry.info.command.clone()));
+    compound.put("World", NbtTag::String(entry.info.world.clone()));
+    compound.put("Timestamp", NbtTag::Long(timestamp));
+    compound.put("Strokes", NbtTag::Int(entry.info.strokes as i32));
+    if let Some(group) = &entry.info.group {
+        compound.put("Group", NbtTag::String(group.clone()));
+    }
+    compound.put("Palette", NbtTag::List(palette));
+    compound.put("Positions", NbtTag::IntArray(positions));
+    compound.put("States", NbtTag::ByteArray(encode_varints(&indices).into_boxed_slice()));
+    compound.put("BlockEntities", NbtTag::List(block_entities));
+    compound.put("BiomePositions", NbtTag::IntArray(biome_positions));
+    compound.put("Biomes", NbtTag::List(biomes));
+    compound
+}
+
+fn decode_entry(compound: &NbtCompound) -> Result<HistoryEntry, String> {
+    let command = compound.get_string("Command").ok_or("Missing Command")?;
+    let world = compound.get_string("World").ok_or("Missing World")?;
+    let timestamp = compound.get_long("Timestamp").ok_or("Missing Timestamp")?;
+    let positions = compound.get_int_array("Positions").ok_or("Missing Positions")?;
+    let states = compound
+        .get("States")
+        .and_then(|t| t.extract_byte_array())
+        .ok_or("Missing States")?;
+
+    let palette = compound
+        .get_list("Palette")
+        .ok_or("Missing Palette")?
+        .iter()
+        .map(|tag| match tag {
+            NbtTag::String(name) => {
+                resolve_block_state(name).ok_or_else(|| format!("Unknown block state {name}"))
+            }
+            _ => Err("Invalid palette entry".to_string()),
+        })
+        .collect::<Result<Vec<u16>, String>>()?;
+
+    let count = positions.len() / 3;
+    let indices = decode_varints(states, count)?;
+    if positions.len() % 3 != 0 || indices.len() != count {
+        return Err(format!(
+            "{} position values but {} states",
+            positions.len(),
+            indices.len()
+        ));
+    }
+
+    let mut block_entities: Vec<Option<NbtCompound>> = vec![None; count];
+    for tag in compound.get_list("BlockEntities").map_or(&[][..], Vec::as_slice) {
+        let NbtTag::Compound(block_entity) = tag else {
+            continue;
+        };
+        let (Some(index), Some(data)) =
+            (block_entity.get_int("Index"), block_entity.get_compound("Data"))
+        else {
+            continue;
+        };
+        if let Some(slot) = block_entities.get_mut(index as usize) {
+            *slot = Some(data.clone());
+        }
+    }
+
+    let mut changes = UndoEntry::new();
+    for (i, (pos, block_entity)) in positions.chunks(3).zip(block_entities).enumerate() {
+        let state = palette
+            .get(indices[i] as usize)
+            .copied()
+            .ok_or_else(|| format!("Palette index {} out of range", indices[i]))?;
+        changes.record(BlockPos(Vector3::new(pos[0], pos[1], pos[2])), state, block_entity);
+    }
+
+    // Biomes are optional so entries without them stay compact
+    if let (Some(positions), Some(biomes)) =
+        (compound.get_int_array("BiomePositions"), compound.get_list("Biomes"))
+    {
+        for (pos, tag) in positions.chunks(3).zip(biomes) {
+            let (NbtTag::String(name), [x, y, z]) = (tag, pos) else {
+                return Err("Invalid biome entry".to_string());
+            };
+            let biome = resolve_biome(name).ok_or_else(|| format!("Unknown biome {name}"))?;
+            changes.record_biome(BlockPos(Vector3::new(*x, *y, *z)), biome);
+        }
+    }
+
+    Ok(HistoryEntry {
+        info: OperationInfo {
+            id: compound.get_long("Id").ok_or("Missing Id")?.max(0) as u64,
+            command: command.to_string(),
+            block_count: changes.len(),
+            world: world.to_string(),
+            timestamp: UNIX_EPOCH + Duration::from_secs(timestamp.max(0) as u64),
+            group: compound.get_string("Group").map(str::to_string),
+            strokes: compound.get_int("Strokes").ok_or("Missing Strokes")?.max(1) as usize,
+        },
+        changes,
+    })
+}
+
+// ============================================================================
+// Save / Load
+// ============================================================================
+
+/// Save the newest `history_persist_entries` of `entries` (oldest first) for `player_id`,
+/// skipping entries over `history_persist_max_blocks`. Replaces any earlier file.
+fn save(player_id: Uuid, entries: &[HistoryEntry]) {
+    let config = config::get();
+    let Some(path) = history_path(player_id) else {
+        return;
+    };
+
+    let kept: Vec<&HistoryEntry> = entries
+        .iter()
+        .filter(|entry| entry.changes.len() <= config.history_persist_max_blocks)
+        .collect();
+    let kept = &kept[kept.len().saturating_sub(config.history_persist_entries)..];
+    if kept.is_empty() {
+        let _ = fs::remove_file(&path);
+        return;
+    }
+
+    let mut root = NbtCompound::new();
+    root.put_int("Version", FORMAT_VERSION);
+    root.put(
+        "Entries",
+        NbtTag::List(kept.iter().map(|entry| NbtTag::Compound(encode_entry(entry))).collect()),
+    );
+
+    let result = fs::File::create(&path)
+        .map_err(|e| e.to_string())
+        .and_then(|file| write_gzip_compound_tag(root, file).map_err(|e| e.to_string()));
+    match result {
+        Ok(()) => log::debug!("Saved {} history entries for {player_id}", kept.len()),
+        Err(e) => log::warn!("Failed to save history for {player_id}: {e}"),
+    }
+}
+
+/// Read and delete the saved history of `player_id`, oldest first. Missing, corrupt or
+/// outdated files yield no entries.
+fn load(player_id: Uuid) -> Vec<HistoryEntry> {
+    let Some(path) = history_path(player_id) else {
+        return Vec::new();
+    };
+    let Ok(data) = fs::read(&path) else {
+        return Vec::new();
+    };
+    let _ = fs::remove_file(&path);
+
+    let root = match read_gzip_compound_tag(Cursor::new(data)) {
+        Ok(root) => root,
+        Err(e) => {
+            log::warn!("Discarding unreadable history file {}: {e}", path.display());
+            return Vec::new();
+        }
+    };
+    let mut root = root;
+    if let Err(e) = migrate(&mut root) {
+        log::warn!("Discarding history file {}: {e}", path.display());
+        return Vec::new();
+    }
+
+    let Some(tags) = root.get_list("Entries") else {
+        return Vec::new();
+    };
+    tags.iter()
+        .filter_map(|tag| {
+            let NbtTag::Compound(compound) = tag else {
+                return None;
+            };
+            decode_entry(compound)
+                .map_err(|e| log::warn!("Skipping history entry in {}: {e}", path.display()))
+                .ok()
+        })
+        .collect()
+}
+
+/// Move the undo entries of a player who is leaving from memory to disk.
+pub fn save_on_leave(player_id: Uuid) {
+    if !config::get().history_persist {
+        return;
+    }
+    let entries = {
+        let mut state = PLAYER_DATA.lock();
+        match state.get_mut(&player_id) {
+            Some(data) => data.history.take_undo(),
+            None => return,
+        }
+    };
+    // Entries saved on an earlier leave and never reloaded are older than these
+    let mut all = load(player_id);
+    all.extend(entries);
+    save(player_id, &all);
+}
+
+/// Put any saved history of `player_id` back underneath their in-memory undo stack.
+pub fn reload(player_id: Uuid) {
+    let entries = load(player_id);
+    if entries.is_empty() {
+        return;
+    }
+    log::debug!("Reloaded {} history entries for {player_id}", entries.len());
+
+    let mut state = PLAYER_DATA.lock();
+    state.entry(player_id).or_default().history.restore_older(entries);
+}
+
+#[cfg(test)]
+mod tests {
+    use pumpkin_data::Block;
+
+    use super::*;
+    use crate::testing::pos;
+
+    fn entry(id: u64, x: i32) -> HistoryEntry {
+        let mut changes = UndoEntry::new();
+        changes.record(pos(x, 64, 0), Block::STONE.default_state.id, None);
+        let mut info = OperationInfo::new("//set stone", changes.len(), "overworld");
+        info.id = id;
+        HistoryEntry { info, changes }
+    }
+
+    fn file(version: i32, entries: Vec<NbtCompound>) -> NbtCompound {
+        let mut root = NbtCompound::new();
+        root.put_int("Version", version);
+        root.put("Entries", NbtTag::List(entries.into_iter().map(NbtTag::Compound).collect()));
+        root
+    }
+
+    /// An entry as version 1 wrote it: no `Strokes` and no `Id`.
+    fn v1_entry(x: i32) -> NbtCompound {
+        let mut compound = NbtCompound::new();
+        compound.put("Command", NbtTag::String("//set stone".to_string()));
+        compound.put("World", NbtTag::String("overworld".to_string()));
+        compound.put("Timestamp", NbtTag::Long(0));
+        compound.put("Palette", NbtTag::List(vec![NbtTag::String("minecraft:stone".into())]));
+        compound.put("Positions", NbtTag::IntArray(vec![x, 64, 0]));
+        compound.put("States", NbtTag::ByteArray(encode_varints(&[0]).into_boxed_slice()));
+        compound
+    }
+
+    /// An entry as version 2 wrote it: `Strokes` but no `Id`.
+    fn v2_entry(x: i32) -> NbtCompound {
+        let mut compound = v1_entry(x);
+        compound.put("Strokes", NbtTag::Int(1));
+        compound
+    }
+
+    fn decode_all(root: &NbtCompound) -> Vec<(u64, usize, i32)> {
+        root.get_list("Entries")
+            .expect("entries are kept")
+            .iter()
+            .map(|tag| {
+                let NbtTag::Compound(compound) = tag else { panic!("entry is not a compound") };
+                let entry = decode_entry(compound).expect("migrated entry decodes");
+                let x = entry.changes.changes()[0].pos.0.x;
+                (entry.info.id, entry.info.strokes, x)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn current_version_round_trips() {
+        let mut root =
+            file(FORMAT_VERSION, vec![encode_entry(&entry(7, 1)), encode_entry(&entry(9, 2))]);
+        assert!(migrate(&mut root).is_ok());
+        assert_eq!(root.get_int("Version"), Some(FORMAT_VERSION));
+        assert_eq!(decode_all(&root), [(7, 1, 1), (9, 1, 2)]);
+    }
+
+    #[test]
+    fn previous_version_is_numbered_oldest_first() {
+        let mut root = file(FORMAT_VERSION - 1, vec![v2_entry(1), v2_entry(2), v2_entry(3)]);
+        assert!(migrate(&mut root).is_ok());
+        assert_eq!(root.get_int("Version"), Some(FORMAT_VERSION));
+        assert_eq!(decode_all(&root), [(1, 1, 1), (2, 1, 2), (3, 1, 3)]);
+    }
+
+    #[test]
+    fn first_version_runs_every_migration() {
+        let mut root = file(1, vec![v1_entry(1), v1_entry(2)]);
+        assert!(migrate(&mut root).is_ok());
+        assert_eq!(decode_all(&root), [(1, 1, 1), (2, 1, 2)]);
+    }
+
+    #[test]
+    fn unknown_versions_are_rejected() {
+        for version in [0, FORMAT_VERSION + 1] {
+            let mut root = file(version, vec![encode_entry(&entry(1, 1))]);
+            assert!(migrate(&mut root).is_err(), "version {version}");
+        }
+        // Files without a version predate the format and can't be read either
+        let mut root = NbtCompound::new();
+        root.put("Entries", NbtTag::List(Vec::new()));
+        assert!(migrate(&mut root).is_err());
+    }
+
+    #[test]
+    fn unmigrated_entries_fail_to_decode() {
+        // Guards the migrations: without them an old entry is missing required fields
+        assert!(decode_entry(&v2_entry(1)).is_err());
+        assert!(decode_entry(&v1_entry(1)).is_err());
+    }
+}
@@ -1,14 +1,40 @@
 //! Pumpkin WorldEdit plugin: region selection, block operations, clipboard, undo, and schematics (.schem / .litematic).
 
 mod commands;
+mod config;
+mod cooldowns;
+mod diag;
+mod effects;
+mod engine;
+mod entities;
+mod expr;
+mod flags;
+mod heightmap;
+mod history;
+mod history_store;
+mod listeners;
+mod mask;
+mod messages;
+mod notify;
+mod permissions;
+mod preview;
 mod schematic;
+mod shapes;
+mod shared;
+mod snapshot;
 mod state;
+mod stats;
+mod support;
+#[cfg(test)]
+mod testing;
+mod tools;
+mod tracking;
+mod transform;
 
 use std::sync::Arc;
 
-use pumpkin::plugin::Context;
+use pumpkin::plugin::{Context, EventPriority};
 use pumpkin_api_macros::{plugin_impl, plugin_method};
-use pumpkin_util::permission::{Permission, PermissionDefault};
 
 #[plugin_method]
 async fn on_load(&mut self, server: Arc<Context>) -> Result<(), String> {
@@ -16,37 +42,70 @@ async fn on_load(&mut self, server: Arc<Context>) -> Result<(), String> {
 
     log::info!("Pumpkin WorldEdit plugin loading...");
 
-    // Set up schematics directory
-    let schematics_dir = server.get_data_folder().join("schematics");
-    if !schematics_dir.exists() {
-        std::fs::create_dir_all(&schematics_dir)
-            .map_err(|e| format!("Failed to create schematics directory: {e}"))?;
+    // Load configuration
+    let data_folder = server.get_data_folder();
+    std::fs::create_dir_all(&data_folder)
+        .map_err(|e| format!("Failed to create plugin data folder: {e}"))?;
+    config::load(&data_folder)?;
+    messages::load(&data_folder, &config::get().language)?;
+    tools::validate_bindings();
+    let _ = state::DATA_FOLDER.set(data_folder.clone());
+
+    // Set up schematics directory; every schematic command depends on it, so refuse to load
+    // without a writable one
+    let schematics_dir = schematic::prepare_dir(&data_folder, &config::get().schematics_dir)
+        .map_err(|e| format!("Failed to prepare schematics directory: {e}"))?;
+    let pruned = schematic::prune_trash(&schematics_dir, config::get().schematic_trash_days);
+    if pruned > 0 {
+        log::info!("Pruned {pruned} schematic(s) from the trash");
     }
-    let _ = state::SCHEMATICS_DIR.set(schematics_dir.clone());
+    state::set_schematics_dir(schematics_dir.clone());
     log::info!("Schematics directory: {}", schematics_dir.display());
 
+    // Set up scripts directory
+    let scripts_dir = data_folder.join("scripts");
+    if !scripts_dir.exists() {
+        std::fs::create_dir_all(&scripts_dir)
+            .map_err(|e| format!("Failed to create scripts directory: {e}"))?;
+    }
+    let _ = state::SCRIPTS_DIR.set(scripts_dir);
+
+    // Snapshots are only read, so the directory is not created here
+    let snapshots_dir = data_folder.join(config::get().snapshots_dir);
+    let _ = state::SNAPSHOTS_DIR.set(snapshots_dir);
+
+    // Load the change index used by the inspect tool
+    tracking::init(data_folder.join("tracking"))?;
+    history_store::init(data_folder.join("history"))?;
+
     // Build command tree
     let command = commands::build_command_tree();
 
-    // Register permission
-    let permission = Permission::new(
-        "pumpkin-worldedit:command.we",
-        "Allows the player to use WorldEdit commands",
-        PermissionDefault::Op(pumpkin_util::permission::PermissionLvl::Two),
-    );
-
-    server
-        .register_permission(permission)
-        .await
-        .map_err(|e| format!("Failed to register permission: {e}"))?;
+    // Register permissions
+    permissions::register_all(&server).await?;
 
     // Register command
+    server.register_command(command, permissions::COMMAND).await;
+
+    // Route right-clicks with bound items to their tools
+    server
+        .register_event(Arc::new(tools::ToolListener), EventPriority::Highest, true)
+        .await;
+    // Remember the last command for /we repeat
+    server
+        .register_event(Arc::new(listeners::LastCommandListener), EventPriority::Lowest, false)
+        .await;
+    // Save undo history of players who leave
+    server
+        .register_event(Arc::new(listeners::SaveHistoryListener), EventPriority::Lowest, false)
+        .await;
+    // Resume feedback for players who left mid-operation and came back
     server
-        .register_command(command, "pumpkin-worldedit:command.we")
+        .register_event(Arc::new(listeners::ReturnListener), EventPriority::Lowest, false)
         .await;
 
     log::info!(
-        "Pumpkin WorldEdit loaded! Commands: /we <pos1|pos2|set|replace|walls|copy|paste|undo|size|clear|hollow|schem>"
+        "Pumpkin WorldEdit loaded! Commands: /we <pos1|pos2|sel|count|distr|blockinfo|set|replace|walls|setstate|move|stack|copy|paste|rotate|flip|undo|redo|history|repeat|size|clear|clearchunk|hollow|smooth|update|fixlight|fixfloat|deform|ex|removenear|drain|fixwater|fixlava|col|pillar|sphere|ellipsoid|dome|bowl|tube|ring|fast|msg|fx|tool|butcher|remove|stats|version|reload|diag|status|cooldowns|queue|world|clipboard|schem|snapshot|restore|run>"
     );
 
     Ok(())
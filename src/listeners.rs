@@ -0,0 +1,79 @@
+//! Server event listeners that feed per-player state.
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use pumpkin::{
+    plugin::{
+        player::{
+            player_command_send::PlayerCommandSendEvent, player_join::PlayerJoinEvent,
+            player_leave::PlayerLeaveEvent,
+        },
+        EventHandler,
+    },
+    server::Server,
+};
+
+use crate::commands::COMMAND_NAMES;
+use crate::config;
+use crate::history_store;
+use crate::state::{mark_departed, mark_returned, PLAYER_DATA};
+use crate::stats::{active_operations, cancel_operation};
+
+/// Subcommands that `/we repeat` never replays.
+const NOT_REPEATABLE: &[&str] = &["undo", "redo", "history", "help", "repeat"];
+
+/// Remembers each player's last repeatable WorldEdit command for `/we repeat`.
+pub struct LastCommandListener;
+
+#[async_trait]
+impl EventHandler<PlayerCommandSendEvent> for LastCommandListener {
+    async fn handle(&self, _server: &Arc<Server>, event: &PlayerCommandSendEvent) {
+        let command = event.command.trim().trim_start_matches('/');
+        let mut words = command.split_whitespace();
+        if !words.next().is_some_and(|name| COMMAND_NAMES.contains(&name)) {
+            return;
+        }
+        match words.next() {
+            Some(subcommand) if !NOT_REPEATABLE.contains(&subcommand) => {}
+            _ => return,
+        }
+
+        let mut state = PLAYER_DATA.lock();
+        let data = state.entry(event.player.gameprofile.id).or_default();
+        data.last_command = Some(command.to_string());
+    }
+}
+
+/// Saves a leaving player's undo history to disk so it can be undone after they return.
+///
+/// Operations they started keep running in the world they were started in (as they do when
+/// a player changes worlds) unless `cancel_on_disconnect` is set. Their feedback is dropped,
+/// and the history entry they commit is saved to disk on top of what is saved here.
+pub struct SaveHistoryListener;
+
+#[async_trait]
+impl EventHandler<PlayerLeaveEvent> for SaveHistoryListener {
+    async fn handle(&self, _server: &Arc<Server>, event: &PlayerLeaveEvent) {
+        let player_id = event.player.gameprofile.id;
+        mark_departed(player_id);
+        if config::get().cancel_on_disconnect {
+            for operation in active_operations() {
+                if operation.player_id == player_id {
+                    cancel_operation(operation.id);
+                }
+            }
+        }
+        history_store::save_on_leave(player_id);
+    }
+}
+
+/// Sends feedback to a returning player again.
+pub struct ReturnListener;
+
+#[async_trait]
+impl EventHandler<PlayerJoinEvent> for ReturnListener {
+    async fn handle(&self, _server: &Arc<Server>, event: &PlayerJoinEvent) {
+        mark_returned(event.player.gameprofile.id);
+    }
+}
@@ -0,0 +1,203 @@
+use pumpkin_data::tag::{get_tag_values, RegistryKey};
+use pumpkin_data::Block;
+use pumpkin_util::math::position::BlockPos;
+
+use crate::config;
+use crate::expr::Expression;
+use crate::messages::msg_string;
+use crate::transform::is_air;
+
+/// Variables available to expression masks: absolute block coordinates, then the same
+/// coordinates normalized to -1..1 across the selection.
+const MASK_VARIABLES: &[&str] = &["x", "y", "z", "nx", "ny", "nz"];
+
+/// Restricts an operation to matching positions.
+///
+/// Syntax: `stone,dirt,#logs` (block types and tags), `=<expression>` (e.g. `=(y>70)&&(x+z)%2==0`),
+/// and a leading `!` to negate either.
+pub enum Mask {
+    /// Matches any of the listed block types, ignoring block state.
+    Blocks(Vec<u16>),
+    /// Matches positions where the expression is non-zero.
+    Expr(Expression),
+    Not(Box<Mask>),
+}
+
+/// Look up a block type by name, with or without the `minecraft:` namespace.
+fn resolve_block(name: &str) -> Result<&'static Block, String> {
+    let bare = name.strip_prefix("minecraft:").unwrap_or(name);
+    Block::from_name(bare)
+        .or_else(|| Block::from_name(&format!("minecraft:{bare}")))
+        .ok_or_else(|| msg_string!("unknown_block", name))
+}
+
+/// The blocks of a block tag such as `logs` or `minecraft:logs`.
+fn resolve_tag(name: &str) -> Result<Vec<&'static Block>, String> {
+    let bare = name.strip_prefix("minecraft:").unwrap_or(name);
+    let values = get_tag_values(RegistryKey::Block, &format!("minecraft:{bare}"))
+        .ok_or_else(|| msg_string!("unknown_block_tag", name))?;
+    values.iter().map(|value| resolve_block(value)).collect()
+}
+
+/// Parse a comma-separated list of block types and `#` block tags such as
+/// `stone,andesite,#minecraft:logs`, dropping repeats and keeping the order given.
+pub fn parse_block_list(input: &str) -> Result<Vec<&'static Block>, String> {
+    let mut blocks: Vec<&'static Block> = Vec::new();
+    for name in input.split(',') {
+        let name = name.trim();
+        let found = match name.strip_prefix('#') {
+            Some(tag) => resolve_tag(tag)?,
+            None => vec![resolve_block(name)?],
+        };
+        for block in found {
+            if !blocks.iter().any(|known| known.id == block.id) {
+                blocks.push(block);
+            }
+        }
+    }
+    Ok(blocks)
+}
+
+/// Map `value` from `min..=max` onto -1..1 (0 when the range is a single block).
+pub fn normalize(value: i32, min: i32, max: i32) -> f64 {
+    if max == min {
+        0.0
+    } else {
+        2.0 * f64::from(value - min) / f64::from(max - min) - 1.0
+    }
+}
+
+impl Mask {
+    /// Parse a mask, reporting unknown blocks and expression errors up front.
+    pub fn parse(input: &str) -> Result<Self, String> {
+        let input = input.trim();
+        if let Some(rest) = input.strip_prefix('!') {
+            return Ok(Self::Not(Box::new(Self::parse(rest)?)));
+        }
+        if let Some(source) = input.strip_prefix('=') {
+            return Expression::parse(source, MASK_VARIABLES)
+                .map(Self::Expr)
+                .map_err(|e| msg_string!("invalid_mask_expression", e));
+        }
+        if input.is_empty() {
+            return Err(msg_string!("empty_mask"));
+        }
+        let blocks = parse_block_list(input)?;
+        Ok(Self::Blocks(blocks.iter().map(|block| block.id).collect()))
+    }
+
+    /// Whether the block at `pos`, currently `state_id`, passes the mask.
+    /// `selection` (min, max) is used for the normalized expression variables.
+    pub fn matches(&self, pos: &BlockPos, state_id: u16, selection: &(BlockPos, BlockPos)) -> bool {
+        match self {
+            Self::Blocks(ids) => ids.contains(&Block::from_state_id(state_id).id),
+            Self::Expr(expression) => {
+                let (min, max) = selection;
+                let p = pos.0;
+                expression.test(&[
+                    f64::from(p.x),
+                    f64::from(p.y),
+                    f64::from(p.z),
+                    normalize(p.x, min.0.x, max.0.x),
+                    normalize(p.y, min.0.y, max.0.y),
+                    normalize(p.z, min.0.z, max.0.z),
+                ])
+            }
+            Self::Not(inner) => !inner.matches(pos, state_id, selection),
+        }
+    }
+}
+
+/// A list of block names from the config, such as `surface_transparent` or `protected_blocks`.
+pub struct BlockNames {
+    patterns: Vec<String>,
+}
+
+impl BlockNames {
+    /// Patterns are block names with or without the `minecraft:` namespace, optionally with a
+    /// leading or trailing `*` wildcard, e.g. `*_leaves`.
+    pub fn new(patterns: Vec<String>) -> Self {
+        let patterns = patterns
+            .into_iter()
+            .map(|pattern| pattern.trim().trim_start_matches("minecraft:").to_string())
+            .collect();
+        Self { patterns }
+    }
+
+    /// Whether the block of `state_id` is on the list, whatever its state.
+    pub fn contains(&self, state_id: u16) -> bool {
+        let name = Block::from_state_id(state_id).name;
+        self.patterns.iter().any(|pattern| {
+            match (pattern.strip_prefix('*'), pattern.strip_suffix('*')) {
+                (Some(suffix), _) => name.ends_with(suffix),
+                (None, Some(prefix)) => name.starts_with(prefix),
+                (None, None) => name == pattern,
+            }
+        })
+    }
+}
+
+/// Blocks that terrain-surface scans look through to find the ground below: vegetation, tree
+/// parts and snow layers, as configured in `surface_transparent`. Air always counts.
+pub struct SurfaceTransparency {
+    blocks: BlockNames,
+}
+
+impl SurfaceTransparency {
+    /// The current `surface_transparent` list.
+    pub fn from_config() -> Self {
+        Self::new(config::get().surface_transparent)
+    }
+
+    /// See [`BlockNames::new`] for the pattern syntax.
+    pub fn new(patterns: Vec<String>) -> Self {
+        Self { blocks: BlockNames::new(patterns) }
+    }
+
+    /// Whether a surface scan passes through `state_id`.
+    pub fn is_transparent(&self, state_id: u16) -> bool {
+        is_air(state_id) || self.blocks.contains(state_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::column_tops;
+    use crate::testing::{pos, FakeWorld};
+
+    fn transparency() -> SurfaceTransparency {
+        SurfaceTransparency::new(vec!["*_leaves".into(), "minecraft:short_grass".into()])
+    }
+
+    #[test]
+    fn listed_blocks_are_transparent_and_others_are_not() {
+        let transparency = transparency();
+        assert!(transparency.is_transparent(Block::AIR.default_state.id));
+        assert!(transparency.is_transparent(Block::OAK_LEAVES.default_state.id));
+        assert!(transparency.is_transparent(Block::BIRCH_LEAVES.default_state.id));
+        assert!(transparency.is_transparent(Block::SHORT_GRASS.default_state.id));
+        assert!(!transparency.is_transparent(Block::OAK_LOG.default_state.id));
+        assert!(!transparency.is_transparent(Block::GRASS_BLOCK.default_state.id));
+    }
+
+    #[tokio::test]
+    async fn surface_scan_skips_listed_blocks_and_stops_at_others() {
+        let world = FakeWorld::new("overworld");
+        // Column 0: grass block under grass under leaves; column 1: the same under a log
+        for z in 0..2 {
+            world.set(pos(0, 60, z), Block::GRASS_BLOCK.default_state.id);
+            world.set(pos(0, 61, z), Block::SHORT_GRASS.default_state.id);
+            world.set(pos(0, 63, z), Block::OAK_LEAVES.default_state.id);
+        }
+        world.set(pos(0, 64, 1), Block::OAK_LOG.default_state.id);
+
+        let transparency = transparency();
+        let tops = column_tops(world.as_ref(), &pos(0, 50, 0), &pos(0, 70, 1), |state| {
+            transparency.is_transparent(state)
+        })
+        .await
+        .unwrap();
+        assert_eq!(tops, [Some(60), Some(64)]);
+    }
+}
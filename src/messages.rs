@@ -0,0 +1,231 @@
+//! Message catalog: every user-facing message is a keyed template with a color.
+//!
+//! English is embedded in the plugin; `lang/<language>.toml` in the data folder overrides
+//! individual messages, and keys it doesn't define fall back to English. Templates use
+//! positional placeholders (`{0}`, `{1}`, ...).
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+use std::sync::LazyLock;
+
+use pumpkin_util::text::{color::NamedColor, TextComponent};
+use serde::Deserialize;
+
+use crate::state::StateRwLock;
+
+/// Embedded English catalog, also the fallback for missing keys.
+const ENGLISH: &str = include_str!("../lang/en.toml");
+/// Language files written to the data folder on first load, as (file name, contents).
+const BUNDLED: &[(&str, &str)] = &[
+    ("en.toml", ENGLISH),
+    ("de.toml", include_str!("../lang/de.toml")),
+];
+
+#[derive(Clone, Deserialize)]
+struct Message {
+    text: String,
+    color: Option<String>,
+}
+
+static CATALOG: LazyLock<StateRwLock<HashMap<String, Message>>> =
+    LazyLock::new(|| StateRwLock::new(parse(ENGLISH).expect("embedded English catalog is valid")));
+
+fn parse(source: &str) -> Result<HashMap<String, Message>, String> {
+    toml::from_str(source).map_err(|e| e.to_string())
+}
+
+fn parse_color(name: &str) -> Option<NamedColor> {
+    Some(match name {
+        "black" => NamedColor::Black,
+        "dark_blue" => NamedColor::DarkBlue,
+        "dark_green" => NamedColor::DarkGreen,
+        "dark_aqua" => NamedColor::DarkAqua,
+        "dark_red" => NamedColor::DarkRed,
+        "dark_purple" => NamedColor::DarkPurple,
+        "gold" => NamedColor::Gold,
+        "gray" => NamedColor::Gray,
+        "dark_gray" => NamedColor::DarkGray,
+        "blue" => NamedColor::Blue,
+        "green" => NamedColor::Green,
+        "aqua" => NamedColor::Aqua,
+        "red" => NamedColor::Red,
+        "light_purple" => NamedColor::LightPurple,
+        "yellow" => NamedColor::Yellow,
+        "white" => NamedColor::White,
+        _ => return None,
+    })
+}
+
+/// Load `lang/<language>.toml` from `data_folder` over the embedded English catalog,
+/// writing the bundled language files there first if they are missing.
+pub fn load(data_folder: &Path, language: &str) -> Result<(), String> {
+    let lang_dir = data_folder.join("lang");
+    fs::create_dir_all(&lang_dir).map_err(|e| format!("Failed to create lang directory: {e}"))?;
+    for (file_name, contents) in BUNDLED {
+        let path = lang_dir.join(file_name);
+        if !path.exists() {
+            fs::write(&path, contents)
+                .map_err(|e| format!("Failed to write {}: {e}", path.display()))?;
+        }
+    }
+
+    let mut catalog = parse(ENGLISH)?;
+    let path = lang_dir.join(format!("{language}.toml"));
+    match fs::read_to_string(&path) {
+        Ok(source) => {
+            let overrides = parse(&source).map_err(|e| format!("Invalid {}: {e}", path.display()))?;
+            for (key, message) in &overrides {
+                if !catalog.contains_key(key) {
+                    log::warn!("{}: unknown message key '{key}'", path.display());
+                }
+                if message.color.as_deref().is_some_and(|c| parse_color(c).is_none()) {
+                    log::warn!("{}: unknown color for '{key}'", path.display());
+                }
+            }
+            catalog.extend(overrides);
+        }
+        Err(_) => log::warn!("Language file {} not found, using English", path.display()),
+    }
+
+    *CATALOG.write() = catalog;
+    log::info!("Messages loaded for language '{language}'");
+    Ok(())
+}
+
+/// Fill `{0}`, `{1}`, ... in the template for `key`. Unknown keys yield the key itself.
+pub fn string(key: &str, args: &[&dyn Display]) -> String {
+    let catalog = CATALOG.read();
+    let Some(message) = catalog.get(key) else {
+        return key.to_string();
+    };
+    let mut text = message.text.clone();
+    for (i, arg) in args.iter().enumerate() {
+        text = text.replace(&format!("{{{i}}}"), &arg.to_string());
+    }
+    text
+}
+
+/// A count, volume, limit or size with the catalog's thousands separator (`number_separator`),
+/// e.g. `124,000` in English and `124.000` in German.
+pub fn format_count(value: impl TryInto<i128>) -> String {
+    group_digits(value.try_into().unwrap_or(i128::MAX), &string("number_separator", &[]))
+}
+
+/// A count shortened to three significant digits for progress messages, e.g. `124k` or
+/// `1.2M`, with the catalog's `decimal_separator`. Below 1000 it is shown in full.
+pub fn format_count_short(value: impl TryInto<i128>) -> String {
+    abbreviate(value.try_into().unwrap_or(i128::MAX), &string("decimal_separator", &[]))
+}
+
+/// `value` with `separator` between each group of three digits.
+fn group_digits(value: i128, separator: &str) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let mut grouped = String::new();
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i) % 3 == 0 {
+            grouped.push_str(separator);
+        }
+        grouped.push(digit);
+    }
+    if value < 0 { format!("-{grouped}") } else { grouped }
+}
+
+/// `value` in thousands (`k`), millions (`M`) and so on, with one decimal below 10 units.
+fn abbreviate(value: i128, decimal: &str) -> String {
+    const UNITS: [&str; 6] = ["k", "M", "G", "T", "P", "E"];
+    let sign = if value < 0 { "-" } else { "" };
+    let magnitude = value.unsigned_abs();
+    if magnitude < 1000 {
+        return format!("{sign}{magnitude}");
+    }
+    // Round in the unit as shown, moving up a unit when that reaches 1000 (999,500 is 1M)
+    let mut unit = 0;
+    let mut divisor: u128 = 1000;
+    loop {
+        let tenths = magnitude.saturating_mul(10).saturating_add(divisor / 2) / divisor;
+        if tenths < 100 {
+            let (whole, fraction) = (tenths / 10, tenths % 10);
+            return if fraction > 0 {
+                format!("{sign}{whole}{decimal}{fraction}{}", UNITS[unit])
+            } else {
+                format!("{sign}{whole}{}", UNITS[unit])
+            };
+        }
+        let whole = magnitude.saturating_add(divisor / 2) / divisor;
+        if whole < 1000 || unit + 1 == UNITS.len() {
+            return format!("{sign}{whole}{}", UNITS[unit]);
+        }
+        unit += 1;
+        divisor *= 1000;
+    }
+}
+
+/// The message for `key` with its placeholders filled, in its catalog color.
+pub fn text(key: &str, args: &[&dyn Display]) -> TextComponent {
+    let color = CATALOG
+        .read()
+        .get(key)
+        .and_then(|message| message.color.as_deref().and_then(parse_color));
+    let component = TextComponent::text(string(key, args));
+    match color {
+        Some(color) => component.color_named(color),
+        None => component,
+    }
+}
+
+/// `msg!("key", arg0, arg1)`: the catalog message as a colored `TextComponent`.
+macro_rules! msg {
+    ($key:expr $(, $arg:expr)* $(,)?) => {
+        $crate::messages::text($key, &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+
+/// `msg_string!("key", arg0)`: the catalog message as plain text, for message fragments.
+macro_rules! msg_string {
+    ($key:expr $(, $arg:expr)* $(,)?) => {
+        $crate::messages::string($key, &[$(&$arg as &dyn std::fmt::Display),*])
+    };
+}
+
+pub(crate) use {msg, msg_string};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::panic_holding;
+
+    #[test]
+    fn counts_are_grouped() {
+        assert_eq!(format_count(0), "0");
+        assert_eq!(format_count(999), "999");
+        assert_eq!(format_count(1_000), "1,000");
+        assert_eq!(format_count(999_500), "999,500");
+        assert_eq!(format_count(1_234_567), "1,234,567");
+        assert_eq!(format_count(-1_234_567), "-1,234,567");
+        assert_eq!(group_digits(1_234_567, "."), "1.234.567");
+    }
+
+    #[test]
+    fn short_counts_carry_into_the_next_unit() {
+        assert_eq!(format_count_short(0), "0");
+        assert_eq!(format_count_short(999), "999");
+        assert_eq!(format_count_short(1_000), "1k");
+        assert_eq!(format_count_short(1_250), "1.3k");
+        assert_eq!(format_count_short(124_000), "124k");
+        assert_eq!(format_count_short(999_499), "999k");
+        assert_eq!(format_count_short(999_500), "1M");
+        assert_eq!(format_count_short(1_234_567), "1.2M");
+        assert_eq!(format_count_short(-1_234_567), "-1.2M");
+        assert_eq!(abbreviate(9_960, ","), "10k");
+        assert_eq!(abbreviate(1_500_000, ","), "1,5M");
+    }
+
+    #[test]
+    fn catalog_recovers_after_a_panic() {
+        panic_holding(|| CATALOG.write());
+        assert_eq!(string("no_such_message", &[]), "no_such_message");
+        assert!(CATALOG.read().contains_key("operation_failed"));
+    }
+}
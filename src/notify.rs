@@ -0,0 +1,148 @@
+//! Command feedback, routed through each player's message mode (`/we msg`).
+
+use pumpkin::command::{dispatcher::CommandError, CommandSender};
+use pumpkin::entity::player::Player;
+use pumpkin_util::text::TextComponent;
+
+use crate::messages::msg;
+use crate::state::{has_departed, PLAYER_DATA};
+
+/// What kind of feedback a message is, which decides whether the message mode applies.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Level {
+    /// Routine confirmation (block counts, positions set). Follows the message mode.
+    Success,
+    /// Progress of a running operation. Follows the message mode.
+    Progress,
+    /// Output the player asked for (lists, history, sizes). Always sent to chat.
+    Info,
+    /// Something the player should notice ("nothing found"). Always sent to chat.
+    Warning,
+}
+
+/// Where routine feedback goes, chosen with `/we msg`.
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+pub enum MessageMode {
+    #[default]
+    Chat,
+    ActionBar,
+    Quiet,
+}
+
+impl MessageMode {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.to_ascii_lowercase().as_str() {
+            "chat" => Some(Self::Chat),
+            "actionbar" => Some(Self::ActionBar),
+            "quiet" => Some(Self::Quiet),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Chat => "chat",
+            Self::ActionBar => "actionbar",
+            Self::Quiet => "quiet",
+        }
+    }
+}
+
+/// Send command feedback. Success and progress messages follow the player's message mode;
+/// everything else, and all feedback to the console, goes to chat. Errors are returned as
+/// `CommandError`s and always shown in chat by the dispatcher.
+pub async fn notify(sender: &CommandSender, level: Level, message: TextComponent) {
+    match sender.as_player() {
+        Some(player) => notify_player(&player, level, message).await,
+        None => sender.send_message(message).await,
+    }
+}
+
+/// Send feedback to a player outside a command, such as from a tool. Nothing is sent to a
+/// player who has left, e.g. when an operation they started finishes after they disconnected.
+pub async fn notify_player(player: &Player, level: Level, message: TextComponent) {
+    if has_departed(&player.gameprofile.id) {
+        return;
+    }
+    let mode = if matches!(level, Level::Success | Level::Progress) {
+        PLAYER_DATA
+            .lock()
+            .get(&player.gameprofile.id)
+            .map_or(MessageMode::Chat, |data| data.message_mode)
+    } else {
+        MessageMode::Chat
+    };
+    match mode {
+        MessageMode::Chat => player.send_system_message(&message).await,
+        MessageMode::ActionBar => player.send_system_message_raw(&message, true).await,
+        MessageMode::Quiet => {}
+    }
+}
+
+/// The output of a list command (header, entries, page footer), sent to chat as one
+/// multi-line message. Sending a line at a time costs a packet each, and on busy servers
+/// other chat can arrive between the lines.
+pub struct Listing {
+    header: Vec<TextComponent>,
+    entries: Vec<TextComponent>,
+}
+
+impl Listing {
+    pub fn new(header: TextComponent) -> Self {
+        Self { header: vec![header], entries: Vec::new() }
+    }
+
+    /// Add a line under the header that is shown on every page.
+    pub fn intro(&mut self, line: TextComponent) {
+        self.header.push(line);
+    }
+
+    /// Add an entry.
+    pub fn push(&mut self, entry: TextComponent) {
+        self.entries.push(entry);
+    }
+
+    /// Send the header and every entry.
+    pub async fn send(self, sender: &CommandSender) {
+        notify(sender, Level::Info, join_lines(self.header.into_iter().chain(self.entries))).await;
+    }
+
+    /// Send the header and one page of `page_size` entries. `page` is the page argument as
+    /// typed (the first page if absent); when there is more than one page a footer names
+    /// `command` as the way to see the others.
+    pub async fn send_page(
+        self,
+        sender: &CommandSender,
+        page: Option<&str>,
+        page_size: usize,
+        command: &str,
+    ) -> Result<(), CommandError> {
+        let pages = self.entries.len().div_ceil(page_size).max(1);
+        let page = match page {
+            Some(value) => value
+                .parse::<usize>()
+                .ok()
+                .filter(|page| (1..=pages).contains(page))
+                .ok_or(CommandError::CommandFailed(msg!("page_range", pages)))?,
+            None => 1,
+        };
+        let shown = self.entries.into_iter().skip((page - 1) * page_size).take(page_size);
+        let footer = (pages > 1).then(|| msg!("list_page", page, pages, command));
+        notify(sender, Level::Info, join_lines(self.header.into_iter().chain(shown).chain(footer)))
+            .await;
+        Ok(())
+    }
+}
+
+/// One component showing `lines` on separate lines. The root is unstyled so each line keeps
+/// only its own color.
+fn join_lines(lines: impl IntoIterator<Item = TextComponent>) -> TextComponent {
+    let mut message = TextComponent::text("");
+    for (i, line) in lines.into_iter().enumerate() {
+        if i > 0 {
+            message = message.add_child(TextComponent::text("\n"));
+        }
+        message = message.add_child(line);
+    }
+    message
+}
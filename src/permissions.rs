@@ -0,0 +1,120 @@
+use pumpkin::command::{dispatcher::CommandError, CommandSender};
+use pumpkin::plugin::Context;
+use pumpkin_util::permission::{Permission, PermissionDefault, PermissionLvl};
+
+use crate::config;
+use crate::mask::BlockNames;
+use crate::messages::msg;
+
+/// Base permission for every `/we` command.
+pub const COMMAND: &str = "pumpkin-worldedit:command.we";
+/// Publishing clipboards to the server-wide shared store.
+pub const CLIPBOARD_PUBLISH: &str = "pumpkin-worldedit:clipboard.publish";
+/// Fetching clipboards from the server-wide shared store.
+pub const CLIPBOARD_FETCH: &str = "pumpkin-worldedit:clipboard.fetch";
+/// Killing mobs with `butcher`.
+pub const BUTCHER: &str = "pumpkin-worldedit:command.butcher";
+/// Despawning items, arrows and other loose entities with `remove`.
+pub const REMOVE: &str = "pumpkin-worldedit:command.remove";
+/// Listing world backups and restoring blocks from them.
+pub const SNAPSHOT: &str = "pumpkin-worldedit:command.snapshot";
+/// Seeing who edited a block with the inspect tool.
+pub const INSPECT: &str = "pumpkin-worldedit:tool.inspect";
+/// Running script files with `run`.
+pub const RUN: &str = "pumpkin-worldedit:command.run";
+/// Overwriting blocks on the `protected_blocks` list with paste, set and clear.
+pub const BYPASS_PROTECTED: &str = "pumpkin-worldedit:bypass.protected-blocks";
+/// Loading and pasting schematics larger than the block limit.
+pub const BYPASS_BLOCK_LIMIT: &str = "pumpkin-worldedit:bypass.block-limit";
+/// Server-wide administration (statistics, diagnostics, other players' data).
+pub const ADMIN: &str = "pumpkin-worldedit:admin";
+
+/// Register all plugin permissions with the server.
+pub async fn register_all(server: &Context) -> Result<(), String> {
+    let permissions = [
+        Permission::new(
+            COMMAND,
+            "Allows the player to use WorldEdit commands",
+            PermissionDefault::Op(PermissionLvl::Two),
+        ),
+        Permission::new(
+            CLIPBOARD_PUBLISH,
+            "Allows the player to publish clipboards to the shared store",
+            PermissionDefault::Op(PermissionLvl::Two),
+        ),
+        Permission::new(
+            CLIPBOARD_FETCH,
+            "Allows the player to fetch clipboards from the shared store",
+            PermissionDefault::Op(PermissionLvl::Two),
+        ),
+        Permission::new(
+            BUTCHER,
+            "Allows the player to kill mobs around them with butcher",
+            PermissionDefault::Op(PermissionLvl::Two),
+        ),
+        Permission::new(
+            REMOVE,
+            "Allows the player to despawn items and other entities with remove",
+            PermissionDefault::Op(PermissionLvl::Two),
+        ),
+        Permission::new(
+            SNAPSHOT,
+            "Allows the player to restore blocks from world backups",
+            PermissionDefault::Op(PermissionLvl::Two),
+        ),
+        Permission::new(
+            INSPECT,
+            "Allows the player to see who edited a block with the inspect tool",
+            PermissionDefault::Op(PermissionLvl::Two),
+        ),
+        Permission::new(
+            RUN,
+            "Allows the player to run WorldEdit script files",
+            PermissionDefault::Op(PermissionLvl::Three),
+        ),
+        Permission::new(
+            BYPASS_PROTECTED,
+            "Allows the player to overwrite protected blocks such as bedrock",
+            PermissionDefault::Op(PermissionLvl::Four),
+        ),
+        Permission::new(
+            BYPASS_BLOCK_LIMIT,
+            "Allows the player to load and paste schematics larger than the block limit",
+            PermissionDefault::Op(PermissionLvl::Four),
+        ),
+        Permission::new(
+            ADMIN,
+            "Allows the player to use WorldEdit administration commands",
+            PermissionDefault::Op(PermissionLvl::Three),
+        ),
+    ];
+
+    for permission in permissions {
+        server
+            .register_permission(permission)
+            .await
+            .map_err(|e| format!("Failed to register permission: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Fail with a red error unless the sender has `permission`.
+pub async fn require_permission(
+    sender: &CommandSender,
+    permission: &str,
+) -> Result<(), CommandError> {
+    if sender.has_permission(permission).await {
+        Ok(())
+    } else {
+        Err(CommandError::CommandFailed(msg!("no_permission")))
+    }
+}
+
+/// Blocks `sender` may not overwrite with paste, set and clear: the `protected_blocks` list,
+/// or `None` with the bypass permission.
+pub async fn protected_blocks(sender: &CommandSender) -> Option<BlockNames> {
+    if sender.has_permission(BYPASS_PROTECTED).await {
+        return None;
+    }
+    Some(BlockNames::new(config::get().protected_blocks))
+}
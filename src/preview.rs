@@ -0,0 +1,53 @@
+//! Client-side previews of region commands: fake block changes sent only to the player who
+//! asked, put back to the real blocks after a few seconds. Nothing in the world is edited.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use pumpkin::entity::player::Player;
+use pumpkin_data::Block;
+use pumpkin_protocol::{codec::var_int::VarInt, java::client::play::CBlockUpdate};
+use pumpkin_util::math::position::BlockPos;
+
+use crate::engine::BlockAccess;
+
+/// How long a preview stays visible before the real blocks are sent again.
+pub const PREVIEW_DURATION: Duration = Duration::from_secs(10);
+/// Most fake block changes sent for one preview; larger previews are thinned out evenly.
+pub const MAX_PREVIEW_BLOCKS: usize = 4096;
+
+/// State shown at previewed positions: the block the command would place, or glass where it
+/// would place air so the removed blocks still stand out.
+pub fn preview_state(state_id: u16) -> u16 {
+    if state_id == Block::AIR.default_state.id { Block::GLASS.default_state.id } else { state_id }
+}
+
+/// Show `state_id` at `positions` to `player` only, keeping every n-th position if there are
+/// more than [`MAX_PREVIEW_BLOCKS`]. After [`PREVIEW_DURATION`] the blocks actually there are
+/// sent in their place. Returns how many positions are shown.
+pub async fn show(player: Arc<Player>, positions: Vec<BlockPos>, state_id: u16) -> usize {
+    let step = positions.len().div_ceil(MAX_PREVIEW_BLOCKS).max(1);
+    let shown: Vec<BlockPos> = positions.into_iter().step_by(step).collect();
+    for pos in &shown {
+        send_block(&player, *pos, state_id).await;
+    }
+
+    let count = shown.len();
+    tokio::spawn(async move {
+        tokio::time::sleep(PREVIEW_DURATION).await;
+        let world = player.world().clone();
+        for pos in shown {
+            // A failed read leaves the fake block until the chunk is sent again
+            if let Ok(real) = world.read_state(&pos).await {
+                send_block(&player, pos, real).await;
+            }
+        }
+    });
+    count
+}
+
+/// Send one block change to `player`'s client without touching the world.
+async fn send_block(player: &Player, pos: BlockPos, state_id: u16) {
+    let packet = CBlockUpdate::new(pos, VarInt(i32::from(state_id)));
+    player.client.enqueue_packet(&packet).await;
+}
@@ -1,19 +1,67 @@
 use std::collections::HashMap;
 use std::fs;
-use std::io::Cursor;
-use std::path::Path;
-
-use pumpkin_data::Block;
+use std::io::{self, Cursor, Read, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use pumpkin_data::{chunk::Biome, Block};
 use pumpkin_nbt::compound::NbtCompound;
+use pumpkin_nbt::deserializer::NbtReadHelper;
 use pumpkin_nbt::nbt_compress::{read_gzip_compound_tag, write_gzip_compound_tag};
 use pumpkin_nbt::tag::NbtTag;
+use pumpkin_nbt::Nbt;
 use pumpkin_util::math::vector3::Vector3;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
 
-use crate::state::ClipboardData;
+use crate::config;
+use crate::messages::{format_count, msg_string};
+use crate::state::{ClipboardData, StateLock};
 
 /// Data version for Minecraft 1.21.11 (used when saving schematics).
 const MC_DATA_VERSION: i32 = 4671;
 
+/// Blocks decoded as a fallback because this server's block registry didn't match their
+/// palette entry, see [`Resolved`].
+#[derive(Clone, Copy, Default)]
+pub struct RegistryFallbacks {
+    /// Blocks of a known type placed as its default state.
+    pub defaulted: usize,
+    /// Blocks of an unknown type, or with no palette entry, left out as air.
+    pub unknown: usize,
+}
+
+impl RegistryFallbacks {
+    pub fn is_empty(&self) -> bool {
+        self.defaulted == 0 && self.unknown == 0
+    }
+
+    pub fn add(&mut self, other: RegistryFallbacks) {
+        self.defaulted += other.defaulted;
+        self.unknown += other.unknown;
+    }
+
+    /// The state for one block of palette entry `entry` (`None` if the index has no entry),
+    /// counting it if it falls back.
+    fn state(&mut self, entry: Option<Resolved>, air_state_id: u16) -> u16 {
+        match entry {
+            Some(Resolved::Exact(state_id)) => state_id,
+            Some(Resolved::Default(state_id)) => {
+                self.defaulted += 1;
+                state_id
+            }
+            Some(Resolved::Unknown) | None => {
+                self.unknown += 1;
+                air_state_id
+            }
+        }
+    }
+}
+
 /// Represents a loaded schematic (in-memory).
 #[allow(dead_code)] // offset used for paste origin; reserved for future use
 pub struct SchematicData {
@@ -24,6 +72,168 @@ pub struct SchematicData {
     pub offset: Vector3<i32>,
     /// (relative position, block state id). Air blocks are omitted.
     pub blocks: Vec<(Vector3<i32>, u16)>,
+    /// Biome id of each entry in `blocks`, when the file has a `Biomes` section.
+    pub biomes: Option<Vec<u8>>,
+    /// Provenance from the file's `Metadata` compound, if it has any.
+    pub metadata: Option<SchematicMetadata>,
+    /// Blocks the server's registry couldn't match exactly.
+    pub fallbacks: RegistryFallbacks,
+}
+
+/// Provenance stored in a schematic's `Metadata` compound. Every field is optional since
+/// files written by other tools carry any subset of them.
+#[derive(Clone, Default)]
+pub struct SchematicMetadata {
+    /// Display name.
+    pub name: Option<String>,
+    pub author: Option<String>,
+    /// Creation time in milliseconds since the Unix epoch.
+    pub date: Option<i64>,
+    /// Tool that wrote the file, e.g. `Pumpkin WorldEdit 0.1.0`.
+    pub generator: Option<String>,
+}
+
+/// Key of the compound under `Metadata` holding this plugin's version.
+const METADATA_PLUGIN_KEY: &str = "PumpkinWorldEdit";
+
+impl SchematicMetadata {
+    /// Metadata for a file saved now by `author`.
+    pub fn new(author: String, name: Option<String>) -> Self {
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+        Self {
+            name,
+            author: Some(author),
+            date: Some(date),
+            generator: Some(format!("Pumpkin WorldEdit {}", env!("CARGO_PKG_VERSION"))),
+        }
+    }
+
+    /// Read the Sponge (`Name`, `Author`, `Date`) or Litematica (`TimeCreated`) fields.
+    /// Returns `None` when the compound carries none of them.
+    fn read(compound: &NbtCompound) -> Option<Self> {
+        let generator = [(METADATA_PLUGIN_KEY, "Pumpkin WorldEdit"), ("WorldEdit", "WorldEdit")]
+            .into_iter()
+            .find_map(|(key, tool)| {
+                let version = compound.get_compound(key)?.get_string("Version")?;
+                Some(format!("{tool} {version}"))
+            });
+        let metadata = Self {
+            name: compound.get_string("Name").map(str::to_string),
+            author: compound.get_string("Author").map(str::to_string),
+            date: compound.get_long("Date").or_else(|| compound.get_long("TimeCreated")),
+            generator,
+        };
+        let empty = metadata.name.is_none()
+            && metadata.author.is_none()
+            && metadata.date.is_none()
+            && metadata.generator.is_none();
+        (!empty).then_some(metadata)
+    }
+
+    fn write(&self) -> NbtCompound {
+        let mut compound = NbtCompound::new();
+        if let Some(name) = &self.name {
+            compound.put("Name", NbtTag::String(name.clone()));
+        }
+        if let Some(author) = &self.author {
+            compound.put("Author", NbtTag::String(author.clone()));
+        }
+        if let Some(date) = self.date {
+            compound.put("Date", NbtTag::Long(date));
+        }
+        let mut plugin = NbtCompound::new();
+        plugin.put("Version", NbtTag::String(env!("CARGO_PKG_VERSION").to_string()));
+        compound.put_component(METADATA_PLUGIN_KEY, plugin);
+        compound
+    }
+}
+
+/// Compression applied to the NBT payload of a schematic file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zlib,
+    None,
+}
+
+impl Compression {
+    /// Sniff the compression from the leading bytes of a file.
+    /// Gzip starts with 0x1f 0x8b, zlib with 0x78; anything else is treated as plain NBT.
+    fn detect(data: &[u8]) -> Self {
+        match data {
+            [0x1f, 0x8b, ..] => Compression::Gzip,
+            [0x78, ..] => Compression::Zlib,
+            _ => Compression::None,
+        }
+    }
+}
+
+// ============================================================================
+// NBT Reading / Writing
+// ============================================================================
+
+/// Parse the root compound of an uncompressed NBT payload.
+fn read_plain_compound_tag(data: Vec<u8>) -> Result<NbtCompound, String> {
+    Nbt::read(&mut NbtReadHelper::new(Cursor::new(data)))
+        .map(|nbt| nbt.root_tag)
+        .map_err(|e| format!("Failed to parse NBT data: {e}"))
+}
+
+/// Parse the root compound of a schematic file, picking the decoder from the file header.
+pub fn read_root_compound(data: Vec<u8>) -> Result<NbtCompound, String> {
+    match Compression::detect(&data) {
+        Compression::Gzip => read_gzip_compound_tag(Cursor::new(data))
+            .map_err(|e| format!("Failed to parse gzipped NBT data: {e}")),
+        Compression::Zlib => {
+            let mut inflated = Vec::new();
+            ZlibDecoder::new(data.as_slice())
+                .read_to_end(&mut inflated)
+                .map_err(|e| format!("Failed to inflate zlib data: {e}"))?;
+            read_plain_compound_tag(inflated)
+        }
+        Compression::None => read_plain_compound_tag(data),
+    }
+}
+
+/// Write a root compound to `path` using the requested compression.
+fn write_root_compound(
+    path: &Path,
+    root: NbtCompound,
+    compression: Compression,
+) -> Result<(), String> {
+    let file =
+        fs::File::create(path).map_err(|e| format!("Failed to create schematic file: {e}"))?;
+    write_compound(file, root, compression)
+}
+
+/// Encode a root compound with the requested compression, as it would be written to a file.
+pub fn compound_bytes(root: NbtCompound, compression: Compression) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    write_compound(&mut bytes, root, compression)?;
+    Ok(bytes)
+}
+
+fn write_compound(
+    mut writer: impl Write,
+    root: NbtCompound,
+    compression: Compression,
+) -> Result<(), String> {
+    match compression {
+        Compression::Gzip => write_gzip_compound_tag(root, writer)
+            .map_err(|e| format!("Failed to write schematic: {e}")),
+        Compression::Zlib => {
+            let mut encoder = ZlibEncoder::new(writer, flate2::Compression::default());
+            encoder
+                .write_all(&Nbt::new(String::new(), root).write())
+                .and_then(|_| encoder.finish().map(|_| ()))
+                .map_err(|e| format!("Failed to write schematic: {e}"))
+        }
+        Compression::None => writer
+            .write_all(&Nbt::new(String::new(), root).write())
+            .map_err(|e| format!("Failed to write schematic: {e}")),
+    }
 }
 
 // ============================================================================
@@ -56,34 +266,75 @@ fn parse_block_state_string(s: &str) -> (&str, Vec<(&str, &str)>) {
     }
 }
 
-/// Resolve a block state string from a schematic palette to a Pumpkin block state ID.
-fn resolve_block_state(block_state_str: &str) -> Option<u16> {
+/// A block state string resolved against this server's block registry, which can lack
+/// blocks and property values from files saved on a newer Minecraft version.
+#[derive(Clone, Copy)]
+pub enum Resolved {
+    Exact(u16),
+    /// A known block whose properties the registry rejected: the block's default state.
+    Default(u16),
+    /// A block the registry doesn't have.
+    Unknown,
+}
+
+/// Resolve a block state string from a schematic palette, logging a warning if it falls
+/// back. Resolve each palette entry once: matching properties scans the block's states.
+pub fn resolve_palette_entry(block_state_str: &str) -> Resolved {
     let (name, props) = parse_block_state_string(block_state_str);
-    let block = Block::from_name(name)?;
+    let Some(block) = Block::from_name(name) else {
+        log::warn!("Unknown block state {block_state_str}, using air");
+        return Resolved::Unknown;
+    };
 
     if props.is_empty() || block.states.len() <= 1 {
         // No properties or block doesn't have variants → use default state
-        Some(block.default_state.id)
-    } else {
-        // Try to resolve with properties; fall back to default if it panics
-        let result = std::panic::catch_unwind(|| {
-            let block_props = block.from_properties(&props);
-            block_props.to_state_id(block)
-        });
-        match result {
-            Ok(state_id) => Some(state_id),
-            Err(_) => {
-                log::warn!(
-                    "Failed to resolve properties for {block_state_str}, using default state"
-                );
-                Some(block.default_state.id)
-            }
+        return Resolved::Exact(block.default_state.id);
+    }
+    match state_from_properties(block, &props) {
+        Some(state_id) => Resolved::Exact(state_id),
+        None => {
+            log::warn!("Failed to resolve properties for {block_state_str}, using default state");
+            Resolved::Default(block.default_state.id)
         }
     }
 }
 
+/// Resolve a block state string to a Pumpkin block state ID, taking the block's default
+/// state if its properties are rejected. `None` if the block is unknown.
+pub fn resolve_block_state(block_state_str: &str) -> Option<u16> {
+    match resolve_palette_entry(block_state_str) {
+        Resolved::Exact(state_id) | Resolved::Default(state_id) => Some(state_id),
+        Resolved::Unknown => None,
+    }
+}
+
+/// Resolve `block` with the given properties to a state ID, or `None` if the block has no
+/// such property or none of its states has the value. Properties left out keep their
+/// default. The block's own states are searched rather than building the state from the
+/// properties, which panics on values the registry doesn't know.
+pub fn state_from_properties(block: &Block, props: &[(&str, &str)]) -> Option<u16> {
+    let Some(defaults) = block.properties(block.default_state.id) else {
+        return props.is_empty().then_some(block.default_state.id);
+    };
+    let defaults = defaults.to_props();
+    if props.iter().any(|(key, _)| !defaults.iter().any(|(name, _)| name == key)) {
+        return None;
+    }
+    let wanted: Vec<(&str, &str)> = defaults
+        .iter()
+        .map(|(name, default)| {
+            // The last occurrence wins, as in a block state string
+            let given = props.iter().rev().find(|(key, _)| key == name);
+            (*name, given.map_or(*default, |(_, value)| *value))
+        })
+        .collect();
+    block.states.iter().map(|state| state.id).find(|state_id| {
+        block.properties(*state_id).is_some_and(|props| props.to_props() == wanted)
+    })
+}
+
 /// Build a block state string (for schematic palette) from a Pumpkin state ID.
-fn build_block_state_string(state_id: u16) -> String {
+pub fn build_block_state_string(state_id: u16) -> String {
     let block = Block::from_state_id(state_id);
     let name = format!("minecraft:{}", block.name);
 
@@ -107,8 +358,20 @@ fn build_block_state_string(state_id: u16) -> String {
 // Varint Encoding / Decoding
 // ============================================================================
 
+/// Biome registry name (`minecraft:plains`) for a biome id.
+pub fn biome_name(id: u8) -> String {
+    let name = Biome::from_id(id).map_or("plains", |biome| biome.registry_id);
+    format!("minecraft:{name}")
+}
+
+/// Look up a biome id by name, with or without the `minecraft:` namespace.
+pub fn resolve_biome(name: &str) -> Option<u8> {
+    let bare = name.strip_prefix("minecraft:").unwrap_or(name);
+    Biome::from_name(bare).map(|biome| biome.id)
+}
+
 /// Decode varint-encoded integers from a byte array.
-fn decode_varints(data: &[u8], expected_count: usize) -> Result<Vec<i32>, String> {
+pub fn decode_varints(data: &[u8], expected_count: usize) -> Result<Vec<i32>, String> {
     let mut result = Vec::with_capacity(expected_count);
     let mut i = 0;
 
@@ -141,7 +404,7 @@ fn decode_varints(data: &[u8], expected_count: usize) -> Result<Vec<i32>, String
 }
 
 /// Encode integers as a varint byte array.
-fn encode_varints(values: &[i32]) -> Vec<u8> {
+pub fn encode_varints(values: &[i32]) -> Vec<u8> {
     let mut result = Vec::new();
 
     for &value in values {
@@ -213,32 +476,143 @@ fn unpack_packed_long_array(
 // Load Schematic (Sponge .schem)
 // ============================================================================
 
-/// Load a schematic from a `.schem` or `.litematic` file.
-/// Format is detected automatically (Litematica has "Regions", Sponge has "Schematic" or flat Palette).
-pub fn load_schematic(path: &Path) -> Result<SchematicData, String> {
-    // Read file into memory, then wrap in Cursor (read_gzip_compound_tag needs Read + Seek)
+/// Size of a schematic as read from its header, before any block data is decoded.
+pub struct SchematicInfo {
+    /// Number of positions in all regions, air included.
+    pub volume: u64,
+    /// Number of block state palette entries.
+    pub palette: usize,
+}
+
+/// Rough size of one decoded palette entry (block state string and map slot).
+const PALETTE_ENTRY_BYTES: u64 = 64;
+
+impl SchematicInfo {
+    /// Upper estimate of the memory decoding the schematic into a clipboard needs: per
+    /// position a palette index, a schematic block entry and the clipboard copy of it.
+    pub fn estimated_bytes(&self) -> u64 {
+        let entry = size_of::<(Vector3<i32>, u16)>() as u64;
+        let per_position = 2 * entry + size_of::<i32>() as u64;
+        self.volume * per_position + self.palette as u64 * PALETTE_ENTRY_BYTES
+    }
+}
+
+/// Check the dimensions of a schematic or Litematica region before anything is allocated for
+/// them: each must be at least 1 and together they may span at most `limit` positions
+/// (`max_schematic_volume`). `what` names the checked part in errors. Returns the volume.
+fn check_dimensions(
+    what: &str,
+    [width, height, length]: [u64; 3],
+    limit: u64,
+) -> Result<u64, String> {
+    if width == 0 || height == 0 || length == 0 {
+        return Err(format!("{what} has a zero dimension ({width}x{height}x{length})"));
+    }
+    check_volume(what, width.saturating_mul(height).saturating_mul(length), limit)
+}
+
+/// Check a schematic's total volume against `limit` (`max_schematic_volume`).
+fn check_volume(what: &str, volume: u64, limit: u64) -> Result<u64, String> {
+    if volume > limit {
+        return Err(format!(
+            "{what} spans {} blocks, more than max_schematic_volume ({})",
+            format_count(volume),
+            format_count(limit)
+        ));
+    }
+    Ok(volume)
+}
+
+/// Dimensions of a Litematica region size, which may be negative along any axis.
+fn region_dimensions(size: Vector3<i32>) -> [u64; 3] {
+    [size.x, size.y, size.z].map(|value| u64::from(value.unsigned_abs()))
+}
+
+/// Whether the root compound is a Litematica file: it has "Regions" and no "Schematic".
+fn is_litematic(root: &NbtCompound) -> bool {
+    root.get_compound("Regions").is_some() && root.get_compound("Schematic").is_none()
+}
+
+/// Sponge format version and the compound holding the schematic data.
+/// v3 nests everything under "Schematic", v2 is flat.
+fn sponge_root(root: &NbtCompound) -> (i32, &NbtCompound) {
+    match root.get_compound("Schematic") {
+        Some(schematic) => (schematic.get_int("Version").unwrap_or(3), schematic),
+        None => (root.get_int("Version").unwrap_or(2), root),
+    }
+}
+
+/// The min corner relative to the copy origin from WorldEdit's `Metadata.WEOffsetX/Y/Z`.
+fn worldedit_offset(data_root: &NbtCompound) -> Option<Vector3<i32>> {
+    let metadata = data_root.get_compound("Metadata")?;
+    Some(Vector3::new(
+        metadata.get_int("WEOffsetX")?,
+        metadata.get_int("WEOffsetY")?,
+        metadata.get_int("WEOffsetZ")?,
+    ))
+}
+
+/// Read and parse the root compound of a `.schem` or `.litematic` file, detecting the
+/// compression (gzip, zlib or uncompressed NBT).
+pub fn read_schematic_file(path: &Path) -> Result<NbtCompound, String> {
     let data = fs::read(path).map_err(|e| format!("Failed to read schematic file: {e}"))?;
-    let root = read_gzip_compound_tag(Cursor::new(data))
-        .map_err(|e| format!("Failed to parse NBT data: {e}"))?;
+    read_root_compound(data)
+}
 
-    // Litematica: root has "Regions" compound and no "Schematic"
-    let has_regions = root.get_compound("Regions").is_some();
-    let has_schematic = root.get_compound("Schematic").is_some();
-    if has_regions && !has_schematic {
-        log::info!("Detected Litematica format");
-        return load_litematic(&root);
+/// Read the dimensions and palette size of a parsed schematic without decoding its blocks.
+pub fn read_schematic_info(root: &NbtCompound) -> Result<SchematicInfo, String> {
+    let limit = config::get().max_schematic_volume;
+    if is_litematic(root) {
+        let regions = root.get_compound("Regions").ok_or("Missing Regions compound")?;
+        let mut info = SchematicInfo { volume: 0, palette: 0 };
+        for (name, tag) in &regions.child_tags {
+            let NbtTag::Compound(region) = tag else {
+                continue;
+            };
+            if let Some(size) = get_region_size(region).or_else(|| get_size_from_metadata(root)) {
+                let what = format!("Region '{name}'");
+                info.volume += check_dimensions(&what, region_dimensions(size), limit)?;
+            }
+            info.palette +=
+                region.get_compound("BlockStatePalette").map_or(0, |p| p.child_tags.len());
+        }
+        check_volume("Schematic", info.volume, limit)?;
+        return Ok(info);
     }
 
-    // Sponge schematic (.schem) v2 or v3
-    // Detect version: v3 nests everything under "Schematic", v2 is flat
-    let (version, data_root);
-    if let Some(schematic) = root.get_compound("Schematic") {
-        version = schematic.get_int("Version").unwrap_or(3);
-        data_root = schematic;
+    let (version, data_root) = sponge_root(root);
+    let dimension = |key: &str| {
+        data_root
+            .get_short(key)
+            .map(|value| u64::from(value as u16))
+            .ok_or(format!("Missing {key} tag"))
+    };
+    let volume = check_dimensions(
+        "Schematic",
+        [dimension("Width")?, dimension("Height")?, dimension("Length")?],
+        limit,
+    )?;
+    let palette = if version >= 3 {
+        data_root.get_compound("Blocks").and_then(|blocks| blocks.get_compound("Palette"))
     } else {
-        version = root.get_int("Version").unwrap_or(2);
-        data_root = &root;
+        data_root.get_compound("Palette")
     };
+    Ok(SchematicInfo {
+        volume,
+        palette: palette.map_or(0, |p| p.child_tags.len()),
+    })
+}
+
+/// Decode a parsed schematic. The format is detected automatically (Litematica has
+/// "Regions", Sponge has "Schematic" or a flat Palette).
+pub fn decode_schematic(root: &NbtCompound) -> Result<SchematicData, String> {
+    if is_litematic(root) {
+        log::info!("Detected Litematica format");
+        return load_litematic(root);
+    }
+
+    // Sponge schematic (.schem) v2 or v3
+    let (version, data_root) = sponge_root(root);
 
     log::info!("Loading schematic (version {version})");
 
@@ -254,17 +628,19 @@ pub fn load_schematic(path: &Path) -> Result<SchematicData, String> {
         .ok_or("Missing Length tag")? as u16;
 
     log::info!("Schematic dimensions: {width}x{height}x{length}");
-
-    // Read offset (optional)
-    let offset = if let Some(offset_arr) = data_root.get_int_array("Offset") {
-        if offset_arr.len() >= 3 {
-            Vector3::new(offset_arr[0], offset_arr[1], offset_arr[2])
-        } else {
-            Vector3::new(0, 0, 0)
-        }
-    } else {
-        Vector3::new(0, 0, 0)
-    };
+    let limit = config::get().max_schematic_volume;
+    check_dimensions("Schematic", [width, height, length].map(u64::from), limit)?;
+
+    // Read offset (optional). WorldEdit stores the region's world position in Offset and
+    // the min corner relative to the copy origin in its metadata; only the latter places a
+    // paste where it was copied from.
+    let offset = worldedit_offset(data_root)
+        .or_else(|| {
+            let offset_arr = data_root.get_int_array("Offset")?;
+            (offset_arr.len() >= 3)
+                .then(|| Vector3::new(offset_arr[0], offset_arr[1], offset_arr[2]))
+        })
+        .unwrap_or(Vector3::new(0, 0, 0));
 
     // Read palette and block data depending on version
     let (palette_compound, block_data_bytes) = if version >= 3 {
@@ -292,11 +668,11 @@ pub fn load_schematic(path: &Path) -> Result<SchematicData, String> {
         (palette, data)
     };
 
-    // Build palette map: palette index → block state string
-    let mut palette_map: HashMap<i32, String> = HashMap::new();
+    // Build palette map: palette index → resolved block state
+    let mut palette_map: HashMap<i32, Resolved> = HashMap::new();
     for (name, tag) in &palette_compound.child_tags {
         if let NbtTag::Int(index) = tag {
-            palette_map.insert(*index, name.clone());
+            palette_map.insert(*index, resolve_palette_entry(name));
         }
     }
 
@@ -313,12 +689,20 @@ pub fn load_schematic(path: &Path) -> Result<SchematicData, String> {
         ));
     }
 
+    let biome_grid = if version >= 3 {
+        read_biome_grid(data_root, expected_blocks)?
+    } else {
+        None
+    };
+
     // Resolve palette entries to state IDs
     let air_state_id = Block::from_name("minecraft:air")
         .map(|b| b.default_state.id)
         .unwrap_or(0);
 
     let mut blocks = Vec::new();
+    let mut biomes = biome_grid.as_ref().map(|_| Vec::new());
+    let mut fallbacks = RegistryFallbacks::default();
 
     for (i, &palette_index) in block_indices.iter().enumerate() {
         // Schematic index: x + z * Width + y * Width * Length
@@ -327,15 +711,7 @@ pub fn load_schematic(path: &Path) -> Result<SchematicData, String> {
         let z = (remainder / width as usize) as i32;
         let x = (remainder % width as usize) as i32;
 
-        let state_id = if let Some(block_state_str) = palette_map.get(&palette_index) {
-            resolve_block_state(block_state_str).unwrap_or_else(|| {
-                log::warn!("Unknown block state: {block_state_str}, using air");
-                air_state_id
-            })
-        } else {
-            log::warn!("Palette index {palette_index} not found, using air");
-            air_state_id
-        };
+        let state_id = fallbacks.state(palette_map.get(&palette_index).copied(), air_state_id);
 
         // Skip air blocks to save memory
         if state_id != air_state_id {
@@ -343,6 +719,9 @@ pub fn load_schematic(path: &Path) -> Result<SchematicData, String> {
                 Vector3::new(x + offset.x, y + offset.y, z + offset.z),
                 state_id,
             ));
+            if let (Some(biomes), Some(grid)) = (&mut biomes, &biome_grid) {
+                biomes.push(grid[i]);
+            }
         }
     }
 
@@ -357,9 +736,53 @@ pub fn load_schematic(path: &Path) -> Result<SchematicData, String> {
         length,
         offset,
         blocks,
+        biomes,
+        metadata: data_root.get_compound("Metadata").and_then(SchematicMetadata::read),
+        fallbacks,
     })
 }
 
+/// Read the optional v3 `Biomes` section: one biome id per block, in block data order.
+/// Unknown biome names fall back to plains.
+fn read_biome_grid(data_root: &NbtCompound, expected: usize) -> Result<Option<Vec<u8>>, String> {
+    let Some(section) = data_root.get_compound("Biomes") else {
+        return Ok(None);
+    };
+    let palette = section
+        .get_compound("Palette")
+        .ok_or("Missing Biomes.Palette compound")?;
+    let data = section
+        .get("Data")
+        .and_then(|t| t.extract_byte_array())
+        .ok_or("Missing Biomes.Data byte array")?;
+
+    let plains = resolve_biome("plains").unwrap_or(0);
+    let mut palette_map: HashMap<i32, u8> = HashMap::new();
+    for (name, tag) in &palette.child_tags {
+        if let NbtTag::Int(index) = tag {
+            let biome = resolve_biome(name).unwrap_or_else(|| {
+                log::warn!("Unknown biome: {name}, using plains");
+                plains
+            });
+            palette_map.insert(*index, biome);
+        }
+    }
+
+    let indices = decode_varints(data, expected)?;
+    if indices.len() != expected {
+        return Err(format!(
+            "Biome data count mismatch: expected {expected}, got {}",
+            indices.len()
+        ));
+    }
+    Ok(Some(
+        indices
+            .iter()
+            .map(|index| palette_map.get(index).copied().unwrap_or(plains))
+            .collect(),
+    ))
+}
+
 // ============================================================================
 // Load Litematica (.litematic)
 // ============================================================================
@@ -445,8 +868,11 @@ fn load_litematic(root: &NbtCompound) -> Result<SchematicData, String> {
         .unwrap_or(0);
 
     let mut all_blocks: Vec<(Vector3<i32>, u16)> = Vec::new();
+    let mut fallbacks = RegistryFallbacks::default();
     let mut global_min: Option<Vector3<i32>> = None;
     let mut global_max: Option<Vector3<i32>> = None;
+    let mut total_volume = 0u64;
+    let limit = config::get().max_schematic_volume;
 
     for (region_name, region_tag) in &regions.child_tags {
         let NbtTag::Compound(region) = region_tag else {
@@ -463,13 +889,13 @@ fn load_litematic(root: &NbtCompound) -> Result<SchematicData, String> {
         let size_x = size.x;
         let size_y = size.y;
         let size_z = size.z;
+        let what = format!("Region '{region_name}'");
+        total_volume += check_dimensions(&what, region_dimensions(size), limit)?;
+        check_volume("Schematic", total_volume, limit)?;
         let w_abs = size_x.unsigned_abs() as usize;
         let h_abs = size_y.unsigned_abs() as usize;
         let l_abs = size_z.unsigned_abs() as usize;
         let block_count = w_abs * h_abs * l_abs;
-        if block_count == 0 {
-            continue;
-        }
 
         let region_blocks_start = all_blocks.len();
 
@@ -477,11 +903,11 @@ fn load_litematic(root: &NbtCompound) -> Result<SchematicData, String> {
             .get_compound("BlockStatePalette")
             .ok_or("Region missing BlockStatePalette")?;
 
-        // Build palette: index -> block state string (Litematica stores string -> int in NBT)
-        let mut palette_by_index: HashMap<i32, String> = HashMap::new();
+        // Build palette: index -> resolved block state (Litematica stores string -> int in NBT)
+        let mut palette_by_index: HashMap<i32, Resolved> = HashMap::new();
         for (block_state_str, tag) in &palette_compound.child_tags {
             if let NbtTag::Int(index) = tag {
-                palette_by_index.insert(*index, block_state_str.clone());
+                palette_by_index.insert(*index, resolve_palette_entry(block_state_str));
             }
         }
 
@@ -525,11 +951,8 @@ fn load_litematic(root: &NbtCompound) -> Result<SchematicData, String> {
             let schematic_z = base_z + local_z;
 
             let palette_index = palette_indices[i] as i32;
-            let block_state_str = palette_by_index.get(&palette_index).map(String::as_str);
-            let state_id = match block_state_str {
-                Some(s) => resolve_block_state(s).unwrap_or(air_state_id),
-                None => air_state_id,
-            };
+            let entry = palette_by_index.get(&palette_index).copied();
+            let state_id = fallbacks.state(entry, air_state_id);
 
             if state_id != air_state_id {
                 let v = Vector3::new(schematic_x, schematic_y, schematic_z);
@@ -588,6 +1011,9 @@ fn load_litematic(root: &NbtCompound) -> Result<SchematicData, String> {
         length,
         offset: min,
         blocks,
+        biomes: None,
+        metadata: root.get_compound("Metadata").and_then(SchematicMetadata::read),
+        fallbacks,
     })
 }
 
@@ -595,6 +1021,8 @@ fn load_litematic(root: &NbtCompound) -> Result<SchematicData, String> {
 pub fn schematic_to_clipboard(schem: &SchematicData) -> ClipboardData {
     ClipboardData {
         blocks: schem.blocks.clone(),
+        biomes: schem.biomes.clone(),
+        source: None,
     }
 }
 
@@ -602,10 +1030,28 @@ pub fn schematic_to_clipboard(schem: &SchematicData) -> ClipboardData {
 // Save Schematic
 // ============================================================================
 
-/// Save clipboard data as a `.schem` file (Sponge Schematic v3 format).
-pub fn save_schematic(path: &Path, clipboard: &ClipboardData) -> Result<(), String> {
+/// Save clipboard data as a `.schem` file (Sponge Schematic v3 format). The `Metadata`
+/// compound is only written when `metadata` is given.
+pub fn save_schematic(
+    path: &Path,
+    clipboard: &ClipboardData,
+    compression: Compression,
+    metadata: Option<&SchematicMetadata>,
+) -> Result<(), String> {
+    let root = encode_schematic(clipboard, metadata)?;
+    write_root_compound(path, root, compression)?;
+    invalidate_schematic_list();
+    log::info!("Saved schematic: {}", path.display());
+    Ok(())
+}
+
+/// Build the root compound of a `.schem` file for clipboard data, see [`save_schematic`].
+pub fn encode_schematic(
+    clipboard: &ClipboardData,
+    metadata: Option<&SchematicMetadata>,
+) -> Result<NbtCompound, String> {
     if clipboard.blocks.is_empty() {
-        return Err("Clipboard is empty".to_string());
+        return Err(msg_string!("clipboard_empty"));
     }
 
     // Calculate bounding box
@@ -696,21 +1142,561 @@ pub fn save_schematic(path: &Path, clipboard: &ClipboardData) -> Result<(), Stri
     schematic.put_short("Height", height as i16);
     schematic.put_short("Length", length as i16);
     schematic.put("Offset", NbtTag::IntArray(vec![min.x, min.y, min.z]));
+    // WorldEdit places pastes by these rather than Offset
+    let mut metadata = metadata.map_or_else(NbtCompound::new, SchematicMetadata::write);
+    metadata.put_int("WEOffsetX", min.x);
+    metadata.put_int("WEOffsetY", min.y);
+    metadata.put_int("WEOffsetZ", min.z);
+    schematic.put_component("Metadata", metadata);
     schematic.put_component("Blocks", blocks_compound);
+    if let Some(biomes) = &clipboard.biomes {
+        let section = biome_section(clipboard, biomes, min, width, height, length);
+        schematic.put_component("Biomes", section);
+    }
+
+    log::debug!("Encoded schematic: {width}x{height}x{length} ({next_index} palette entries)");
 
     let mut root = NbtCompound::new();
     root.put_component("Schematic", schematic);
+    Ok(root)
+}
 
-    // Write gzipped NBT to file
-    let file =
-        fs::File::create(path).map_err(|e| format!("Failed to create schematic file: {e}"))?;
+/// Build the v3 `Biomes` section from per-block biome ids. Positions without a clipboard
+/// entry take the biome of the first entry.
+fn biome_section(
+    clipboard: &ClipboardData,
+    biomes: &[u8],
+    min: Vector3<i32>,
+    width: u16,
+    height: u16,
+    length: u16,
+) -> NbtCompound {
+    let (width, length) = (width as usize, length as usize);
+    let default = biomes.first().copied().unwrap_or(0);
+    let mut grid = vec![default; width * height as usize * length];
+    for ((pos, _), biome) in clipboard.blocks.iter().zip(biomes) {
+        let (x, y, z) = (
+            (pos.x - min.x) as usize,
+            (pos.y - min.y) as usize,
+            (pos.z - min.z) as usize,
+        );
+        grid[x + z * width + y * width * length] = *biome;
+    }
 
-    write_gzip_compound_tag(root, file).map_err(|e| format!("Failed to write schematic: {e}"))?;
+    let mut palette: HashMap<u8, i32> = HashMap::new();
+    let data: Vec<i32> = grid
+        .iter()
+        .map(|biome| {
+            let next = palette.len() as i32;
+            *palette.entry(*biome).or_insert(next)
+        })
+        .collect();
 
-    log::info!(
-        "Saved schematic: {width}x{height}x{length} ({} palette entries)",
-        next_index
-    );
+    let mut palette_compound = NbtCompound::new();
+    for (biome, index) in &palette {
+        palette_compound.put(&biome_name(*biome), NbtTag::Int(*index));
+    }
+    let mut section = NbtCompound::new();
+    section.put_component("Palette", palette_compound);
+    section.put("Data", NbtTag::ByteArray(encode_varints(&data).into_boxed_slice()));
+    section
+}
+
+// ============================================================================
+// Tiles
+// ============================================================================
+
+/// Version written to new tile manifests.
+const TILE_MANIFEST_VERSION: u32 = 1;
+
+/// Layout of a selection saved as a grid of schematics by `/we schem savetiles`, stored
+/// next to them as `<name>.tiles.json`.
+#[derive(Serialize, Deserialize)]
+pub struct TileManifest {
+    pub version: u32,
+    /// Edge length of a tile along X and Z; tiles on the far edges may be smaller.
+    pub tile_size: i32,
+    /// Number of tiles along X and Z.
+    pub columns: i32,
+    pub rows: i32,
+    /// Size of the whole saved region as [x, y, z].
+    pub size: [i32; 3],
+    pub tiles: Vec<TileEntry>,
+}
+
+/// One tile: its schematic file and where its min corner sits relative to the region's.
+#[derive(Serialize, Deserialize)]
+pub struct TileEntry {
+    pub file: String,
+    pub x: i32,
+    pub z: i32,
+}
+
+impl TileManifest {
+    pub fn new(tile_size: i32, size: [i32; 3]) -> Self {
+        Self {
+            version: TILE_MANIFEST_VERSION,
+            tile_size,
+            columns: size[0].div_ceil(tile_size),
+            rows: size[2].div_ceil(tile_size),
+            size,
+            tiles: Vec::new(),
+        }
+    }
+}
+
+/// File name of the tile in grid column `column` and row `row` of `name`.
+pub fn tile_file_name(name: &str, column: i32, row: i32) -> String {
+    format!("{name}_{column}_{row}.schem")
+}
+
+pub fn tile_manifest_path(schematics_dir: &Path, name: &str) -> PathBuf {
+    schematics_dir.join(format!("{name}.tiles.json"))
+}
+
+pub fn write_tile_manifest(path: &Path, manifest: &TileManifest) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(manifest).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+/// Read a tile manifest, rejecting versions from a newer plugin.
+pub fn read_tile_manifest(path: &Path) -> Result<TileManifest, String> {
+    let json = fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let manifest: TileManifest = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+    if manifest.version > TILE_MANIFEST_VERSION {
+        return Err(msg_string!("tile_manifest_version", manifest.version));
+    }
+    Ok(manifest)
+}
+
+// ============================================================================
+// Directory
+// ============================================================================
 
+/// File written and deleted in the schematics directory to check that it is writable.
+const PROBE_FILE: &str = ".write-test";
+
+/// Resolve the configured schematics directory (relative to `data_folder` unless absolute),
+/// create it if missing and check that files can be written there. Returns the absolute
+/// path, or an error naming the directory and what failed.
+pub fn prepare_dir(data_folder: &Path, configured: &str) -> Result<PathBuf, String> {
+    let dir = data_folder.join(configured);
+    fs::create_dir_all(&dir).map_err(|e| format!("can't create {}: {e}", dir.display()))?;
+    let dir = dir.canonicalize().map_err(|e| format!("can't resolve {}: {e}", dir.display()))?;
+    check_writable(&dir)?;
+    Ok(dir)
+}
+
+/// Write and delete a probe file in `dir` to check that files can be created there.
+pub fn check_writable(dir: &Path) -> Result<(), String> {
+    let probe = dir.join(PROBE_FILE);
+    fs::write(&probe, b"")
+        .and_then(|()| fs::remove_file(&probe))
+        .map_err(|e| format!("{} is not writable: {e}", dir.display()))
+}
+
+// ============================================================================
+// Trash
+// ============================================================================
+
+/// Subfolder of the schematics directory that deleted schematics are moved into.
+const TRASH_DIR: &str = "trash";
+
+/// Split a trashed file name `<stem>.<unix seconds>.<extension>` into its parts.
+fn parse_trash_name(file_name: &str) -> Option<(&str, u64, &str)> {
+    let (rest, extension) = file_name.rsplit_once('.')?;
+    let (stem, secs) = rest.rsplit_once('.')?;
+    Some((stem, secs.parse().ok()?, extension))
+}
+
+/// Move `path` into the trash, suffixed with the current time so repeated deletes of the
+/// same name don't collide.
+pub fn trash_file(schematics_dir: &Path, path: &Path) -> Result<(), String> {
+    let trash = schematics_dir.join(TRASH_DIR);
+    fs::create_dir_all(&trash).map_err(|e| format!("Failed to create trash folder: {e}"))?;
+    let stem = path.file_stem().and_then(|s| s.to_str()).ok_or("Invalid file name")?;
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("schem");
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    fs::rename(path, trash.join(format!("{stem}.{secs}.{extension}")))
+        .map_err(|e| e.to_string())?;
+    invalidate_schematic_list();
     Ok(())
 }
+
+/// Move the most recently trashed schematic named `stem` back into the schematics
+/// directory, returning its restored path. Fails if a schematic of that name exists again.
+pub fn restore_file(schematics_dir: &Path, stem: &str) -> Result<Option<PathBuf>, String> {
+    let trash = schematics_dir.join(TRASH_DIR);
+    let Ok(entries) = fs::read_dir(&trash) else {
+        return Ok(None);
+    };
+    let newest = entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_str()?.to_string();
+            let (name, secs, extension) = parse_trash_name(&file_name)?;
+            (name == stem).then(|| (secs, format!("{name}.{extension}"), entry.path()))
+        })
+        .max_by_key(|(secs, _, _)| *secs);
+    let Some((_, file_name, trashed)) = newest else {
+        return Ok(None);
+    };
+
+    let target = schematics_dir.join(file_name);
+    if target.exists() {
+        return Err(format!("'{}' already exists", target.display()));
+    }
+    fs::rename(&trashed, &target).map_err(|e| e.to_string())?;
+    invalidate_schematic_list();
+    Ok(Some(target))
+}
+
+/// Delete trashed schematics older than `retention_days`. Returns how many were removed.
+pub fn prune_trash(schematics_dir: &Path, retention_days: u32) -> usize {
+    let Ok(entries) = fs::read_dir(schematics_dir.join(TRASH_DIR)) else {
+        return 0;
+    };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let cutoff = now.saturating_sub(u64::from(retention_days) * 24 * 60 * 60);
+    entries
+        .flatten()
+        .filter(|entry| {
+            let file_name = entry.file_name();
+            file_name
+                .to_str()
+                .and_then(parse_trash_name)
+                .is_some_and(|(_, secs, _)| secs < cutoff)
+        })
+        .filter(|entry| match fs::remove_file(entry.path()) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Failed to prune {}: {e}", entry.path().display());
+                false
+            }
+        })
+        .count()
+}
+
+// ============================================================================
+// Backups
+// ============================================================================
+
+/// Subfolder of the schematics directory holding automatic backups, one folder per player.
+const BACKUP_DIR: &str = "backups";
+
+/// An automatic backup of a region taken before a large edit.
+pub struct BackupEntry {
+    pub path: PathBuf,
+    /// When it was taken, in seconds since the Unix epoch.
+    pub secs: u64,
+    /// World position of the region's min corner.
+    pub origin: Vector3<i32>,
+    pub world: String,
+    /// File size in bytes.
+    pub size: u64,
+}
+
+/// Folder of one player's backups.
+pub fn backup_dir(schematics_dir: &Path, player_id: &Uuid) -> PathBuf {
+    schematics_dir.join(BACKUP_DIR).join(player_id.to_string())
+}
+
+/// File name of a backup: `<unix seconds>_<x>_<y>_<z>_<world>.schem`. The world comes last
+/// since its name may contain underscores.
+pub fn backup_file_name(secs: u64, origin: Vector3<i32>, world: &str) -> String {
+    format!("{secs}_{}_{}_{}_{world}.schem", origin.x, origin.y, origin.z)
+}
+
+/// Split a backup file name back into its time, origin and world.
+fn parse_backup_name(file_name: &str) -> Option<(u64, Vector3<i32>, String)> {
+    let mut parts = file_name.strip_suffix(".schem")?.splitn(5, '_');
+    let secs = parts.next()?.parse().ok()?;
+    let mut coordinate = || parts.next()?.parse::<i32>().ok();
+    let origin = Vector3::new(coordinate()?, coordinate()?, coordinate()?);
+    Some((secs, origin, parts.next()?.to_string()))
+}
+
+/// A player's backups, newest first. A missing folder is an empty list.
+pub fn list_backups(dir: &Path) -> Vec<BackupEntry> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<BackupEntry> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let (secs, origin, world) = parse_backup_name(entry.file_name().to_str()?)?;
+            let size = entry.metadata().ok()?.len();
+            Some(BackupEntry { path: entry.path(), secs, origin, world, size })
+        })
+        .collect();
+    backups.sort_by(|a, b| b.secs.cmp(&a.secs));
+    backups
+}
+
+/// Delete a player's backups beyond the newest `keep` or older than `max_days` (0 keeps
+/// them regardless of age). Returns how many were removed.
+pub fn prune_backups(dir: &Path, keep: usize, max_days: u32) -> usize {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs());
+    let cutoff = now.saturating_sub(u64::from(max_days) * 24 * 60 * 60);
+    list_backups(dir)
+        .into_iter()
+        .enumerate()
+        .filter(|(index, backup)| *index >= keep || (max_days > 0 && backup.secs < cutoff))
+        .filter(|(_, backup)| match fs::remove_file(&backup.path) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Failed to prune backup {}: {e}", backup.path.display());
+                false
+            }
+        })
+        .count()
+}
+
+// ============================================================================
+// NBT dump
+// ============================================================================
+
+/// Longest string value shown in an NBT dump before it is cut off, in characters.
+const DUMP_STRING_CHARS: usize = 64;
+
+/// The tag tree of `root` for triaging files that fail to load: one line per tag with its
+/// name, type and value, indented by depth. Arrays show only their length and long strings
+/// are cut off, so the dump reflects the structure rather than the block data.
+pub fn dump_nbt(root: &NbtCompound) -> Vec<String> {
+    let mut lines = Vec::new();
+    for (name, tag) in &root.child_tags {
+        dump_tag(name, tag, 0, &mut lines);
+    }
+    lines
+}
+
+fn dump_tag(name: &str, tag: &NbtTag, depth: usize, lines: &mut Vec<String>) {
+    let summary = match tag {
+        NbtTag::End => "End".to_string(),
+        NbtTag::Byte(value) => format!("Byte = {value}"),
+        NbtTag::Short(value) => format!("Short = {value}"),
+        NbtTag::Int(value) => format!("Int = {value}"),
+        NbtTag::Long(value) => format!("Long = {value}"),
+        NbtTag::Float(value) => format!("Float = {value}"),
+        NbtTag::Double(value) => format!("Double = {value}"),
+        NbtTag::String(value) if value.chars().count() > DUMP_STRING_CHARS => {
+            let shown: String = value.chars().take(DUMP_STRING_CHARS).collect();
+            format!("String = \"{shown}...\" ({} chars)", value.chars().count())
+        }
+        NbtTag::String(value) => format!("String = \"{value}\""),
+        NbtTag::ByteArray(values) => format!("ByteArray [{} bytes]", values.len()),
+        NbtTag::IntArray(values) => format!("IntArray [{} ints]", values.len()),
+        NbtTag::LongArray(values) => format!("LongArray [{} longs]", values.len()),
+        NbtTag::List(items) => format!("List [{} entries]", items.len()),
+        NbtTag::Compound(compound) => format!("Compound {{{} tags}}", compound.child_tags.len()),
+    };
+    lines.push(format!("{}{name}: {summary}", "  ".repeat(depth)));
+
+    match tag {
+        NbtTag::List(items) => {
+            for (index, item) in items.iter().enumerate() {
+                dump_tag(&format!("[{index}]"), item, depth + 1, lines);
+            }
+        }
+        NbtTag::Compound(compound) => {
+            for (child, item) in &compound.child_tags {
+                dump_tag(child, item, depth + 1, lines);
+            }
+        }
+        _ => {}
+    }
+}
+
+// ============================================================================
+// Listing
+// ============================================================================
+
+/// How long a directory scan is reused before the next `/we schem list` scans again.
+const LIST_CACHE_TTL: Duration = Duration::from_secs(30);
+
+/// A saved schematic as found by the last directory scan.
+pub struct SchematicEntry {
+    /// File name without its extension.
+    pub name: String,
+    /// File size in bytes.
+    pub size: u64,
+    pub modified: SystemTime,
+}
+
+/// The last scan of the schematics directory and when it was taken.
+static LIST_CACHE: StateLock<Option<(Instant, Arc<Vec<SchematicEntry>>)>> = StateLock::new(None);
+
+/// Read the schematics directory, sorted by name. A missing directory is an empty list.
+fn scan_schematics(schematics_dir: &Path) -> io::Result<Vec<SchematicEntry>> {
+    let entries = match fs::read_dir(schematics_dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut schematics: Vec<SchematicEntry> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.path();
+            if !path.extension().is_some_and(|ext| ext == "schem" || ext == "litematic") {
+                return None;
+            }
+            let metadata = entry.metadata().ok()?;
+            Some(SchematicEntry {
+                name: path.file_stem()?.to_string_lossy().to_string(),
+                size: metadata.len(),
+                modified: metadata.modified().unwrap_or(UNIX_EPOCH),
+            })
+        })
+        .collect();
+    schematics.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(schematics)
+}
+
+/// The saved schematics, from the cache while it is fresh or else from a scan on a
+/// blocking thread so a slow filesystem doesn't stall the command task.
+pub async fn list_schematics(schematics_dir: &Path) -> Result<Arc<Vec<SchematicEntry>>, String> {
+    if let Some((scanned, schematics)) = LIST_CACHE.lock().as_ref() {
+        if scanned.elapsed() < LIST_CACHE_TTL {
+            return Ok(schematics.clone());
+        }
+    }
+
+    let dir = schematics_dir.to_path_buf();
+    let schematics = tokio::task::spawn_blocking(move || scan_schematics(&dir))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    let schematics = Arc::new(schematics);
+    *LIST_CACHE.lock() = Some((Instant::now(), schematics.clone()));
+    Ok(schematics)
+}
+
+/// Drop the cached listing after a schematic was saved, deleted or restored.
+pub fn invalidate_schematic_list() {
+    *LIST_CACHE.lock() = None;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::{property, with_property};
+
+    fn sample() -> NbtCompound {
+        let mut root = NbtCompound::new();
+        root.put_int("Version", 3);
+        root.put("Name", NbtTag::String("castle".to_string()));
+        root
+    }
+
+    #[test]
+    fn compressed_roots_read_back() {
+        for compression in [Compression::Gzip, Compression::Zlib, Compression::None] {
+            let bytes = compound_bytes(sample(), compression).unwrap();
+            assert_eq!(Compression::detect(&bytes), compression);
+            let root = read_root_compound(bytes).unwrap();
+            assert_eq!(root.get_int("Version"), Some(3));
+            assert_eq!(root.get_string("Name"), Some("castle"));
+        }
+    }
+
+    #[test]
+    fn unknown_headers_are_read_as_plain_nbt() {
+        assert_eq!(Compression::detect(&[0x0a, 0x00, 0x00]), Compression::None);
+        assert_eq!(Compression::detect(&[0x1f, 0x00]), Compression::None);
+        assert_eq!(Compression::detect(&[0x1f]), Compression::None);
+        assert_eq!(Compression::detect(&[]), Compression::None);
+        assert!(read_root_compound(vec![0xff, 0x01, 0x02]).is_err());
+    }
+
+    #[test]
+    fn palette_entries_fall_back_without_panicking() {
+        let log = Block::OAK_LOG.default_state.id;
+        match resolve_palette_entry("minecraft:oak_log[axis=x]") {
+            Resolved::Exact(state_id) => {
+                assert_eq!(property(state_id, "axis"), Some("x"));
+            }
+            _ => panic!("oak_log[axis=x] did not resolve exactly"),
+        }
+        for entry in ["minecraft:oak_log[axis=sideways]", "minecraft:oak_log[color=red]"] {
+            assert!(matches!(resolve_palette_entry(entry), Resolved::Default(id) if id == log));
+        }
+        for entry in ["minecraft:future_block", "minecraft:future_block[facing=north]", ""] {
+            assert!(matches!(resolve_palette_entry(entry), Resolved::Unknown));
+        }
+        assert_eq!(resolve_block_state("minecraft:oak_log[axis=sideways]"), Some(log));
+        assert_eq!(resolve_block_state("minecraft:future_block"), None);
+    }
+
+    #[test]
+    fn dimensions_are_checked_against_the_limit() {
+        assert_eq!(check_dimensions("Schematic", [2, 3, 4], 24), Ok(24));
+        assert!(check_dimensions("Schematic", [2, 3, 4], 23).is_err());
+        assert!(check_dimensions("Schematic", [0, 3, 4], 100).is_err());
+        assert!(check_dimensions("Schematic", [2, 0, 4], 100).is_err());
+        assert!(check_volume("Schematic", 101, 100).is_err());
+    }
+
+    #[test]
+    fn negative_region_sizes_count_by_magnitude() {
+        let dimensions = region_dimensions(Vector3::new(-2, 3, -4));
+        assert_eq!(dimensions, [2, 3, 4]);
+        assert_eq!(check_dimensions("Region", dimensions, 24), Ok(24));
+        assert!(check_dimensions("Region", region_dimensions(Vector3::new(-2, 0, 4)), 24).is_err());
+    }
+
+    #[test]
+    fn huge_dimensions_saturate_instead_of_overflowing() {
+        assert!(check_dimensions("Schematic", [u64::MAX, u64::MAX, 2], u64::MAX - 1).is_err());
+        let extreme = region_dimensions(Vector3::new(i32::MIN, i32::MIN, i32::MIN));
+        assert_eq!(extreme, [1 << 31; 3]);
+        assert!(check_dimensions("Region", extreme, 20_000_000).is_err());
+    }
+
+    /// Each block of a clipboard or loaded schematic with its biome, by position.
+    fn placed(
+        blocks: &[(Vector3<i32>, u16)],
+        biomes: &[u8],
+    ) -> HashMap<(i32, i32, i32), (u16, u8)> {
+        blocks
+            .iter()
+            .zip(biomes)
+            .map(|((pos, state_id), biome)| ((pos.x, pos.y, pos.z), (*state_id, *biome)))
+            .collect()
+    }
+
+    #[test]
+    fn saved_schematics_load_back_block_for_block() {
+        let stairs = with_property(Block::OAK_STAIRS.default_state.id, "facing", "east").unwrap();
+        let (plains, desert) = (resolve_biome("plains").unwrap(), resolve_biome("desert").unwrap());
+        let clipboard = ClipboardData {
+            blocks: vec![
+                (Vector3::new(-2, 0, -1), Block::STONE.default_state.id),
+                (Vector3::new(0, 0, 0), stairs),
+                (Vector3::new(1, 2, 1), Block::GLASS.default_state.id),
+            ],
+            biomes: Some(vec![plains, desert, plains]),
+            source: None,
+        };
+        let metadata = SchematicMetadata::new("Alex".to_string(), Some("tower".to_string()));
+        let path =
+            std::env::temp_dir().join(format!("worldedit-test-{}.schem", std::process::id()));
+
+        for compression in [Compression::Gzip, Compression::Zlib, Compression::None] {
+            save_schematic(&path, &clipboard, compression, Some(&metadata)).unwrap();
+            let root = read_schematic_file(&path);
+            let _ = fs::remove_file(&path);
+            let schem = decode_schematic(&root.unwrap()).unwrap();
+
+            assert_eq!((schem.width, schem.height, schem.length), (4, 3, 3));
+            // Air isn't stored, so exactly the clipboard's blocks come back, in place
+            let biomes = schem.biomes.as_deref().unwrap_or_default();
+            assert_eq!(
+                placed(&schem.blocks, biomes),
+                placed(&clipboard.blocks, &[plains, desert, plains])
+            );
+            let name = schem.metadata.and_then(|metadata| metadata.name);
+            assert_eq!(name.as_deref(), Some("tower"), "{compression:?}");
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use pumpkin_util::math::vector3::Vector3;
+
+/// Which part of an ellipsoid to generate, split at the center layer. Both halves include
+/// the center layer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Half {
+    Whole,
+    /// The top half, for domes and roofs.
+    Upper,
+    /// The bottom half, for bowls and craters.
+    Lower,
+}
+
+/// An axis-aligned ellipsoid around the origin with per-axis radii in blocks. A sphere is
+/// the case of three equal radii.
+pub struct Ellipsoid {
+    pub radii: Vector3<i32>,
+    pub half: Half,
+    /// Only the outer shell, one block thick.
+    pub hollow: bool,
+}
+
+impl Ellipsoid {
+    pub fn new(radii: Vector3<i32>, half: Half, hollow: bool) -> Self {
+        Self { radii, half, hollow }
+    }
+
+    /// Whether the block at `offset` lies inside the whole ellipsoid. Radii are widened by half
+    /// a block so the outermost layer reaches exactly `radius` blocks from the center.
+    fn contains(&self, offset: Vector3<i32>) -> bool {
+        let axis = |value: i32, radius: i32| {
+            let scaled = f64::from(value) / (f64::from(radius) + 0.5);
+            scaled * scaled
+        };
+        axis(offset.x, self.radii.x) + axis(offset.y, self.radii.y) + axis(offset.z, self.radii.z)
+            <= 1.0
+    }
+
+    /// Whether a block inside the ellipsoid has a face neighbor outside it. The cut of a half
+    /// is not a surface, so a hollow dome is open underneath.
+    fn is_shell(&self, offset: Vector3<i32>) -> bool {
+        [(1, 0, 0), (-1, 0, 0), (0, 1, 0), (0, -1, 0), (0, 0, 1), (0, 0, -1)]
+            .into_iter()
+            .any(|(dx, dy, dz)| {
+                !self.contains(Vector3::new(offset.x + dx, offset.y + dy, offset.z + dz))
+            })
+    }
+
+    /// Smallest and largest offsets the shape can cover.
+    pub fn bounds(&self) -> (Vector3<i32>, Vector3<i32>) {
+        let Vector3 { x: rx, y: ry, z: rz } = self.radii;
+        let (bottom, top) = match self.half {
+            Half::Whole => (-ry, ry),
+            Half::Upper => (0, ry),
+            Half::Lower => (-ry, 0),
+        };
+        (Vector3::new(-rx, bottom, -rz), Vector3::new(rx, top, rz))
+    }
+
+    /// Offsets from the center of every block in the shape, bottom layer first so blocks
+    /// are placed on top of what is already there.
+    pub fn offsets(&self) -> Vec<Vector3<i32>> {
+        let (min, max) = self.bounds();
+        let mut offsets = Vec::new();
+        for y in min.y..=max.y {
+            for x in min.x..=max.x {
+                for z in min.z..=max.z {
+                    let offset = Vector3::new(x, y, z);
+                    if self.contains(offset) && (!self.hollow || self.is_shell(offset)) {
+                        offsets.push(offset);
+                    }
+                }
+            }
+        }
+        offsets
+    }
+}
+
+/// A vertical tube: a ring of `radius` blocks, `thickness` blocks wide measured inwards,
+/// stacked `height` layers up from the origin. A ring is a tube one layer high.
+pub struct Tube {
+    pub radius: i32,
+    pub thickness: i32,
+    pub height: i32,
+}
+
+impl Tube {
+    pub fn new(radius: i32, thickness: i32, height: i32) -> Self {
+        Self { radius, thickness, height }
+    }
+
+    /// Smallest and largest offsets the shape can cover.
+    pub fn bounds(&self) -> (Vector3<i32>, Vector3<i32>) {
+        let r = self.radius;
+        (Vector3::new(-r, 0, -r), Vector3::new(r, self.height - 1, r))
+    }
+
+    /// Offsets from the origin of every block in the tube, bottom layer first.
+    pub fn offsets(&self) -> Vec<Vector3<i32>> {
+        let ring = ring(self.radius, self.thickness);
+        (0..self.height)
+            .flat_map(|y| ring.iter().map(move |&(x, z)| Vector3::new(x, y, z)))
+            .collect()
+    }
+}
+
+/// Whether the column at (`x`, `z`) lies in the disk of `radius` around the origin, widened
+/// by half a block like [`Ellipsoid`]'s radii. A negative radius is an empty disk.
+fn in_disk(x: i32, z: i32, radius: i32) -> bool {
+    let widened = f64::from(radius) + 0.5;
+    radius >= 0 && f64::from(x * x + z * z) <= widened * widened
+}
+
+/// (x, z) columns of a ring `thickness` blocks wide: those in the disk of `radius` with a side
+/// neighbor outside the disk of `radius - thickness + 1`. Taking the boundary of a disk this
+/// way leaves no gap at any radius, which thresholding the distance from the center does at
+/// small ones. A thickness beyond the radius fills the disk.
+fn ring(radius: i32, thickness: i32) -> Vec<(i32, i32)> {
+    let inner = radius - thickness + 1;
+    let mut columns = Vec::new();
+    for x in -radius..=radius {
+        for z in -radius..=radius {
+            let edge = [(1, 0), (-1, 0), (0, 1), (0, -1)]
+                .into_iter()
+                .any(|(dx, dz)| !in_disk(x + dx, z + dz, inner));
+            if in_disk(x, z, radius) && edge {
+                columns.push((x, z));
+            }
+        }
+    }
+    columns
+}
+
+/// Blocks on the straight line from `start` to `end`, both included: one per step along the
+/// longest axis, with the other two axes rounded to the nearest block.
+pub fn line(start: Vector3<i32>, end: Vector3<i32>) -> Vec<Vector3<i32>> {
+    let delta = Vector3::new(end.x - start.x, end.y - start.y, end.z - start.z);
+    let steps = delta.x.abs().max(delta.y.abs()).max(delta.z.abs());
+    if steps == 0 {
+        return vec![start];
+    }
+    let along = |from: i32, by: i32, step: i32| {
+        from + (f64::from(by) * f64::from(step) / f64::from(steps)).round() as i32
+    };
+    (0..=steps)
+        .map(|step| {
+            Vector3::new(
+                along(start.x, delta.x, step),
+                along(start.y, delta.y, step),
+                along(start.z, delta.z, step),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const SIDES: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+    const DIAGONALS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, 1), (-1, -1)];
+
+    /// Whether every column of `columns` can be reached from the first one through `steps`.
+    fn connected(columns: &HashSet<(i32, i32)>, steps: &[(i32, i32)]) -> bool {
+        let Some(&start) = columns.iter().next() else {
+            return false;
+        };
+        let mut seen = HashSet::from([start]);
+        let mut pending = vec![start];
+        while let Some((x, z)) = pending.pop() {
+            for &(dx, dz) in steps {
+                let next = (x + dx, z + dz);
+                if columns.contains(&next) && seen.insert(next) {
+                    pending.push(next);
+                }
+            }
+        }
+        seen.len() == columns.len()
+    }
+
+    /// Whether walking side to side from outside the ring's bounds never reaches the center,
+    /// i.e. the ring has no gap water or a player could pass through.
+    fn sealed(columns: &HashSet<(i32, i32)>, radius: i32) -> bool {
+        let limit = radius + 1;
+        let start = (-limit, -limit);
+        let mut seen = HashSet::from([start]);
+        let mut pending = vec![start];
+        while let Some((x, z)) = pending.pop() {
+            for (dx, dz) in SIDES {
+                let next = (x + dx, z + dz);
+                let inside = next.0.abs() <= limit && next.1.abs() <= limit;
+                if inside && !columns.contains(&next) && seen.insert(next) {
+                    pending.push(next);
+                }
+            }
+        }
+        !seen.contains(&(0, 0))
+    }
+
+    #[test]
+    fn thin_rings_are_connected_and_gap_free() {
+        let steps: Vec<_> = SIDES.into_iter().chain(DIAGONALS).collect();
+        for radius in 1..=64 {
+            let columns: HashSet<_> = ring(radius, 1).into_iter().collect();
+            assert!(connected(&columns, &steps), "ring of radius {radius} is broken");
+            assert!(sealed(&columns, radius), "ring of radius {radius} has a gap");
+        }
+    }
+
+    #[test]
+    fn thick_rings_are_connected_side_to_side() {
+        for radius in 2..=64 {
+            for thickness in 2..=radius.min(3) {
+                let columns: HashSet<_> = ring(radius, thickness).into_iter().collect();
+                assert!(connected(&columns, &SIDES), "ring {radius}/{thickness} is broken");
+                assert!(sealed(&columns, radius), "ring {radius}/{thickness} has a gap");
+            }
+        }
+    }
+
+    #[test]
+    fn ring_stays_within_radius() {
+        for radius in 1..=64 {
+            for (x, z) in ring(radius, 1) {
+                assert!(in_disk(x, z, radius));
+                assert!(x.abs() <= radius && z.abs() <= radius);
+            }
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::config;
+use crate::messages::{format_count, msg_string};
+use crate::state::{ClipboardData, StateLock};
+
+/// Maximum length of a shared clipboard name.
+const MAX_NAME_LEN: usize = 32;
+
+/// A clipboard published to the server-wide store.
+pub struct SharedClipboard {
+    pub owner: Uuid,
+    pub owner_name: String,
+    pub clipboard: ClipboardData,
+    /// Last publish or fetch; entries idle longer than the configured time expire.
+    last_used: Instant,
+}
+
+/// Listing entry for `/we clipboard shared`.
+pub struct SharedInfo {
+    pub name: String,
+    pub owner_name: String,
+    pub blocks: usize,
+    pub idle: Duration,
+}
+
+/// Server-wide named clipboards, keyed by lowercase name.
+static SHARED_CLIPBOARDS: LazyLock<StateLock<HashMap<String, SharedClipboard>>> =
+    LazyLock::new(|| StateLock::new(HashMap::new()));
+
+/// Drop entries that have not been published or fetched within the configured idle time.
+fn expire(store: &mut HashMap<String, SharedClipboard>) {
+    let idle_limit = Duration::from_secs(config::get().shared_clipboard_idle_minutes * 60);
+    store.retain(|_, entry| entry.last_used.elapsed() < idle_limit);
+}
+
+/// Check that `name` is usable as a shared clipboard name.
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        return Err(msg_string!("shared_name_length", MAX_NAME_LEN));
+    }
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return Err(msg_string!("shared_name_chars"));
+    }
+    Ok(())
+}
+
+/// Publish a clipboard under `name`. An existing entry can only be replaced by its owner
+/// (or an admin when `is_admin` is set). Size and count are bounded by the configuration.
+pub fn publish(
+    name: &str,
+    owner: Uuid,
+    owner_name: String,
+    clipboard: ClipboardData,
+    is_admin: bool,
+) -> Result<(), String> {
+    validate_name(name)?;
+    let config = config::get();
+    let blocks = clipboard.blocks.len();
+    if blocks > config.max_shared_clipboard_blocks {
+        return Err(msg_string!(
+            "shared_too_large",
+            format_count(blocks),
+            format_count(config.max_shared_clipboard_blocks)
+        ));
+    }
+
+    let key = name.to_lowercase();
+    let mut store = SHARED_CLIPBOARDS.lock();
+    expire(&mut store);
+
+    match store.get(&key) {
+        Some(existing) if existing.owner != owner && !is_admin => {
+            return Err(msg_string!("shared_owned", name, existing.owner_name));
+        }
+        None if store.len() >= config.max_shared_clipboards => {
+            return Err(msg_string!("shared_store_full", config.max_shared_clipboards));
+        }
+        _ => {}
+    }
+
+    store.insert(
+        key,
+        SharedClipboard {
+            owner,
+            owner_name,
+            clipboard,
+            last_used: Instant::now(),
+        },
+    );
+    Ok(())
+}
+
+/// Copy of the shared clipboard `name`, refreshing its idle timer.
+pub fn fetch(name: &str) -> Option<ClipboardData> {
+    let mut store = SHARED_CLIPBOARDS.lock();
+    expire(&mut store);
+    let entry = store.get_mut(&name.to_lowercase())?;
+    entry.last_used = Instant::now();
+    Some(entry.clipboard.clone())
+}
+
+/// Remove the shared clipboard `name`. Only its owner or an admin may remove it.
+pub fn remove(name: &str, requester: Uuid, is_admin: bool) -> Result<(), String> {
+    let key = name.to_lowercase();
+    let mut store = SHARED_CLIPBOARDS.lock();
+    expire(&mut store);
+    let entry = store.get(&key).ok_or_else(|| msg_string!("shared_not_found", name))?;
+    if entry.owner != requester && !is_admin {
+        return Err(msg_string!("shared_owned", name, entry.owner_name));
+    }
+    store.remove(&key);
+    Ok(())
+}
+
+/// All live entries, sorted by name.
+pub fn list() -> Vec<SharedInfo> {
+    let mut store = SHARED_CLIPBOARDS.lock();
+    expire(&mut store);
+    let mut entries: Vec<SharedInfo> = store
+        .iter()
+        .map(|(name, entry)| SharedInfo {
+            name: name.clone(),
+            owner_name: entry.owner_name.clone(),
+            blocks: entry.clipboard.blocks.len(),
+            idle: entry.last_used.elapsed(),
+        })
+        .collect();
+    entries.sort_by(|a, b| a.name.cmp(&b.name));
+    entries
+}
@@ -0,0 +1,241 @@
+//! Read-only access to world backups stored as Anvil region files.
+//!
+//! Only the modern chunk format (1.18+, `sections` with paletted `block_states`) is supported.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use pumpkin_nbt::compound::NbtCompound;
+use pumpkin_nbt::tag::NbtTag;
+use pumpkin_util::math::position::BlockPos;
+
+use crate::messages::msg_string;
+use crate::schematic::{read_root_compound, resolve_block_state};
+
+/// Size of one sector in a region file.
+const SECTOR_SIZE: u64 = 4096;
+/// Minimum bits per block state index in a section.
+const MIN_BITS_PER_ENTRY: u32 = 4;
+
+// ============================================================================
+// Snapshot Discovery
+// ============================================================================
+
+/// Region directory of snapshot `name`: `<name>/region` if present, otherwise `<name>` itself.
+pub fn region_dir(snapshots_dir: &Path, name: &str) -> Result<PathBuf, String> {
+    if name.contains(['/', '\\']) || name.contains("..") {
+        return Err(msg_string!("snapshot_invalid_name", name));
+    }
+    let root = snapshots_dir.join(name);
+    if !root.is_dir() {
+        return Err(msg_string!("snapshot_not_found", name));
+    }
+    let region = root.join("region");
+    Ok(if region.is_dir() { region } else { root })
+}
+
+/// Snapshot names (subdirectories of `snapshots_dir`), newest first.
+pub fn list_snapshots(snapshots_dir: &Path) -> Result<Vec<String>, String> {
+    if !snapshots_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let entries =
+        fs::read_dir(snapshots_dir).map_err(|e| msg_string!("snapshots_dir_unreadable", e))?;
+
+    let mut snapshots: Vec<(String, std::time::SystemTime)> = entries
+        .flatten()
+        .filter(|entry| entry.path().is_dir())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_str()?.to_string();
+            let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+            Some((name, modified))
+        })
+        .collect();
+    snapshots.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+    Ok(snapshots.into_iter().map(|(name, _)| name).collect())
+}
+
+// ============================================================================
+// Sections
+// ============================================================================
+
+/// One 16×16×16 section: resolved palette plus per-block palette indices.
+struct Section {
+    /// `None` for palette entries that don't exist in this server's registry.
+    palette: Vec<Option<u16>>,
+    /// Empty when the palette has a single entry.
+    indices: Vec<u16>,
+}
+
+impl Section {
+    fn parse(section: &NbtCompound) -> Result<Option<(i32, Self)>, String> {
+        let Some(y) = section.get_byte("Y") else {
+            return Ok(None);
+        };
+        let Some(block_states) = section.get_compound("block_states") else {
+            return Ok(None);
+        };
+        let palette_tags = block_states
+            .get_list("palette")
+            .ok_or_else(|| msg_string!("snapshot_section_invalid"))?;
+
+        let palette: Vec<Option<u16>> = palette_tags
+            .iter()
+            .map(|tag| match tag {
+                NbtTag::Compound(entry) => palette_entry_state(entry),
+                _ => None,
+            })
+            .collect();
+
+        let indices = match block_states.get("data").and_then(|t| t.extract_long_array()) {
+            Some(data) if palette.len() > 1 => unpack_section_indices(data, palette.len())?,
+            _ => Vec::new(),
+        };
+        Ok(Some((i32::from(y), Self { palette, indices })))
+    }
+
+    fn state(&self, x: usize, y: usize, z: usize) -> Option<u16> {
+        let index = if self.indices.is_empty() {
+            0
+        } else {
+            self.indices[(y * 16 + z) * 16 + x] as usize
+        };
+        self.palette.get(index).copied().flatten()
+    }
+}
+
+/// Resolve a palette entry `{Name, Properties}` to a state ID.
+fn palette_entry_state(entry: &NbtCompound) -> Option<u16> {
+    let name = entry.get_string("Name")?;
+    let props: Vec<String> = entry
+        .get_compound("Properties")
+        .map(|props| {
+            props
+                .child_tags
+                .iter()
+                .filter_map(|(key, value)| match value {
+                    NbtTag::String(value) => Some(format!("{key}={value}")),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if props.is_empty() {
+        resolve_block_state(name)
+    } else {
+        resolve_block_state(&format!("{name}[{}]", props.join(",")))
+    }
+}
+
+/// Unpack 4096 palette indices. Since 1.16 entries never span two longs.
+fn unpack_section_indices(data: &[i64], palette_len: usize) -> Result<Vec<u16>, String> {
+    let bits = (usize::BITS - (palette_len - 1).leading_zeros()).max(MIN_BITS_PER_ENTRY);
+    let per_long = (64 / bits) as usize;
+    if data.len() * per_long < 4096 {
+        return Err(msg_string!("snapshot_section_invalid"));
+    }
+    let mask = (1u64 << bits) - 1;
+    Ok((0..4096)
+        .map(|i| {
+            let long = data[i / per_long] as u64;
+            ((long >> ((i % per_long) as u32 * bits)) & mask) as u16
+        })
+        .collect())
+}
+
+// ============================================================================
+// Region Files
+// ============================================================================
+
+/// Read and parse chunk (`cx`, `cz`) from its region file; `None` if it was never saved.
+fn read_chunk(region_dir: &Path, cx: i32, cz: i32) -> Result<Option<NbtCompound>, String> {
+    let path = region_dir.join(format!("r.{}.{}.mca", cx >> 5, cz >> 5));
+    if !path.exists() {
+        return Ok(None);
+    }
+    let mut file = fs::File::open(&path)
+        .map_err(|e| msg_string!("snapshot_region_unreadable", path.display(), e))?;
+
+    // Header: one big-endian u32 per chunk, 3 bytes sector offset + 1 byte sector count
+    let header_index = ((cx & 31) + (cz & 31) * 32) as u64;
+    let mut location = [0u8; 4];
+    file.seek(SeekFrom::Start(header_index * 4))
+        .and_then(|_| file.read_exact(&mut location))
+        .map_err(|e| msg_string!("snapshot_region_unreadable", path.display(), e))?;
+    let offset = u64::from(u32::from_be_bytes([0, location[0], location[1], location[2]]));
+    if offset == 0 {
+        return Ok(None);
+    }
+
+    // Chunk: u32 length (including the compression byte), compression byte, payload
+    let mut prefix = [0u8; 5];
+    file.seek(SeekFrom::Start(offset * SECTOR_SIZE))
+        .and_then(|_| file.read_exact(&mut prefix))
+        .map_err(|e| msg_string!("snapshot_chunk_unreadable", cx, cz, e))?;
+    let length = u32::from_be_bytes([prefix[0], prefix[1], prefix[2], prefix[3]]) as usize;
+    let compression = prefix[4];
+    if !(1..=3).contains(&compression) {
+        return Err(msg_string!("snapshot_chunk_compression", cx, cz, compression));
+    }
+    let mut payload = vec![0u8; length.saturating_sub(1)];
+    file.read_exact(&mut payload)
+        .map_err(|e| msg_string!("snapshot_chunk_unreadable", cx, cz, e))?;
+
+    read_root_compound(payload).map(Some)
+}
+
+/// Lazily loads chunks from a snapshot's region files and answers block-state queries.
+pub struct SnapshotReader {
+    region_dir: PathBuf,
+    /// Parsed sections per chunk, keyed by section Y; `None` for chunks missing in the backup.
+    chunks: HashMap<(i32, i32), Option<HashMap<i32, Section>>>,
+}
+
+impl SnapshotReader {
+    pub fn new(region_dir: PathBuf) -> Self {
+        Self {
+            region_dir,
+            chunks: HashMap::new(),
+        }
+    }
+
+    fn load_chunk(&self, cx: i32, cz: i32) -> Result<Option<HashMap<i32, Section>>, String> {
+        let Some(root) = read_chunk(&self.region_dir, cx, cz)? else {
+            return Ok(None);
+        };
+        if root.get_compound("Level").is_some() {
+            return Err(msg_string!("snapshot_old_format"));
+        }
+        let Some(section_tags) = root.get_list("sections") else {
+            return Ok(None);
+        };
+        let mut sections = HashMap::new();
+        for tag in section_tags {
+            if let NbtTag::Compound(section) = tag {
+                if let Some((y, section)) = Section::parse(section)? {
+                    sections.insert(y, section);
+                }
+            }
+        }
+        Ok(Some(sections))
+    }
+
+    /// Block state at `pos` in the backup, or `None` when its chunk or section is missing
+    /// (or the block is unknown to this server).
+    pub fn block_state(&mut self, pos: &BlockPos) -> Result<Option<u16>, String> {
+        let (x, y, z) = (pos.0.x, pos.0.y, pos.0.z);
+        let key = (x >> 4, z >> 4);
+        if !self.chunks.contains_key(&key) {
+            let chunk = self.load_chunk(key.0, key.1)?;
+            self.chunks.insert(key, chunk);
+        }
+        let Some(Some(sections)) = self.chunks.get(&key) else {
+            return Ok(None);
+        };
+        Ok(sections.get(&(y >> 4)).and_then(|section| {
+            section.state((x & 15) as usize, (y & 15) as usize, (z & 15) as usize)
+        }))
+    }
+}
@@ -1,34 +1,141 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::mem::size_of;
 use std::path::PathBuf;
-use std::sync::{LazyLock, Mutex, OnceLock};
+use std::sync::{LazyLock, Mutex, MutexGuard, OnceLock, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
 use pumpkin::{
     command::{dispatcher::CommandError, CommandSender},
+    entity::player::Player,
     world::World,
 };
-use pumpkin_util::{
-    math::{position::BlockPos, vector3::Vector3},
-    text::{color::NamedColor, TextComponent},
-};
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+use pumpkin_world::world::BlockFlags;
 use std::sync::Arc;
+use tokio::sync::Semaphore;
 use uuid::Uuid;
 
+use crate::config;
+use crate::effects::FxMode;
+use crate::history::{BlockChange, History};
+use crate::messages::{format_count, msg, msg_string};
+use crate::notify::{notify, Level, MessageMode};
+use crate::tools::Tool;
+
 /// Maximum number of blocks that can be modified in a single operation.
 pub const MAX_BLOCKS: i64 = 100_000;
 
-/// Schematics directory path, set during plugin load.
-pub static SCHEMATICS_DIR: OnceLock<PathBuf> = OnceLock::new();
+/// Number of earlier selections kept for `/we sel undo`.
+const SELECTION_HISTORY: usize = 5;
+
+/// Plugin data folder holding `config.toml` and `lang/`, set during plugin load.
+pub static DATA_FOLDER: OnceLock<PathBuf> = OnceLock::new();
+
+/// Schematics directory path, set during plugin load and changed by `/we reload`.
+static SCHEMATICS_DIR: StateLock<Option<PathBuf>> = StateLock::new(None);
+
+/// Script directory path for `/we run`, set during plugin load.
+pub static SCRIPTS_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// World backup (snapshot) directory path, set during plugin load.
+pub static SNAPSHOTS_DIR: OnceLock<PathBuf> = OnceLock::new();
 
 // ============================================================================
 // Data Structures
 // ============================================================================
 
-/// Per-player WorldEdit state: selection, clipboard, and undo history.
+/// Per-player WorldEdit state: selection, clipboard, and undo/redo history.
 pub struct PlayerState {
     pub pos1: Option<BlockPos>,
     pub pos2: Option<BlockPos>,
+    /// World pos1 and pos2 were set in. Setting a corner in another world clears the other
+    /// corner, so both always share this world.
+    pub selection_world: Option<String>,
+    /// Earlier selections, oldest first, for `/we sel undo`.
+    pub selection_undo: VecDeque<SavedSelection>,
+    /// Selections left with `/we sel undo`, most recent last, for `/we sel redo`.
+    pub selection_redo: Vec<SavedSelection>,
     pub clipboard: Option<ClipboardData>,
-    pub undo_data: Option<Vec<(BlockPos, u16)>>,
+    pub history: History,
+    /// Force-place blocks without neighbor updates (default). Toggled with `/we fast`.
+    pub fast_mode: bool,
+    /// Snapshot chosen with `/we snapshot use`, read by `/we restore`.
+    pub snapshot: Option<String>,
+    /// Tools bound with `/we tool`, keyed by item registry name.
+    pub tools: HashMap<String, Tool>,
+    /// Settings of the line tool, set with `/we tool line`.
+    pub line_tool: Option<LineTool>,
+    /// Last repeatable `/we` command as typed (without the slash), for `/we repeat`.
+    pub last_command: Option<String>,
+    /// Where routine feedback goes. Set with `/we msg`.
+    pub message_mode: MessageMode,
+    /// Sounds and particles for the player's operations. Set with `/we fx`.
+    pub fx_mode: FxMode,
+}
+
+/// A selection kept for `/we sel undo`: pos1, pos2 and the world they were set in.
+pub type SavedSelection = (Option<BlockPos>, Option<BlockPos>, Option<String>);
+
+impl PlayerState {
+    /// Change the selection, remembering the previous one for `/we sel undo`. Selections
+    /// undone before are forgotten, like redo history after a new edit.
+    pub fn set_selection(
+        &mut self,
+        pos1: Option<BlockPos>,
+        pos2: Option<BlockPos>,
+        world: Option<String>,
+    ) {
+        let previous = self.saved_selection();
+        if previous == (pos1, pos2, world.clone()) {
+            return;
+        }
+        if (previous.0, previous.1) != (None, None) {
+            if self.selection_undo.len() == SELECTION_HISTORY {
+                self.selection_undo.pop_front();
+            }
+            self.selection_undo.push_back(previous);
+        }
+        self.selection_redo.clear();
+        (self.pos1, self.pos2, self.selection_world) = (pos1, pos2, world);
+    }
+
+    /// Set pos1 (`first`) or pos2 to `pos` in `world`. If the other corner was set in a
+    /// different world it is cleared, and that world is returned.
+    pub fn set_corner(&mut self, first: bool, pos: BlockPos, world: &str) -> Option<String> {
+        let other_world = self.selection_world.clone().filter(|selected| selected != world);
+        let other = match (&other_world, first) {
+            (Some(_), _) => None,
+            (None, true) => self.pos2,
+            (None, false) => self.pos1,
+        };
+        let (pos1, pos2) = if first { (Some(pos), other) } else { (other, Some(pos)) };
+        self.set_selection(pos1, pos2, Some(world.to_string()));
+        other_world
+    }
+
+    fn saved_selection(&self) -> SavedSelection {
+        (self.pos1, self.pos2, self.selection_world.clone())
+    }
+
+    /// Go back to the previous selection, keeping the current one for `/we sel redo`.
+    /// Returns `false` if there is none.
+    pub fn undo_selection(&mut self) -> bool {
+        let Some(previous) = self.selection_undo.pop_back() else {
+            return false;
+        };
+        self.selection_redo.push(self.saved_selection());
+        (self.pos1, self.pos2, self.selection_world) = previous;
+        true
+    }
+
+    /// Return to the selection last left with `/we sel undo`. Returns `false` if there is none.
+    pub fn redo_selection(&mut self) -> bool {
+        let Some(next) = self.selection_redo.pop() else {
+            return false;
+        };
+        self.selection_undo.push_back(self.saved_selection());
+        (self.pos1, self.pos2, self.selection_world) = next;
+        true
+    }
 }
 
 impl Default for PlayerState {
@@ -36,38 +143,315 @@ impl Default for PlayerState {
         Self {
             pos1: None,
             pos2: None,
+            selection_world: None,
+            selection_undo: VecDeque::new(),
+            selection_redo: Vec::new(),
             clipboard: None,
-            undo_data: None,
+            history: History::default(),
+            fast_mode: true,
+            snapshot: None,
+            tools: HashMap::new(),
+            line_tool: None,
+            last_command: None,
+            message_mode: MessageMode::Chat,
+            fx_mode: FxMode::Off,
         }
     }
 }
 
+/// What the line tool draws, and where its next segment starts.
+#[derive(Clone, Copy)]
+pub struct LineTool {
+    pub state_id: u16,
+    /// 1 for a single-block line; larger values draw a round beam this many blocks across
+    /// each way from the center line, counting the center.
+    pub thickness: i32,
+    /// The last clicked block. The next right-click draws from here.
+    pub anchor: Option<BlockPos>,
+}
+
 /// Blocks stored in the clipboard as (offset from player position, block state id).
+#[derive(Clone)]
 pub struct ClipboardData {
     pub blocks: Vec<(Vector3<i32>, u16)>,
+    /// Biome id of each entry in `blocks` (same order), when copied with `-b`.
+    pub biomes: Option<Vec<u8>>,
+    /// Schematic file the clipboard was loaded from, e.g. `house.schem`. Cleared when the
+    /// file is deleted or no longer found.
+    pub source: Option<String>,
+}
+
+/// A mutex for plugin state that survives a panic while it is held. A std mutex would stay
+/// poisoned and fail every later command of every player; this one logs the panic once and
+/// hands out the data as the panicking code left it.
+///
+/// The guard is not `Send`, so holding it across an `.await` in a command executor fails to
+/// compile; lock inside a block and copy out what is needed.
+pub struct StateLock<T>(Mutex<T>);
+
+impl<T> StateLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self(Mutex::new(value))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, T> {
+        self.0.lock().unwrap_or_else(|poisoned| {
+            log::warn!("Recovering plugin state after a panic while it was locked");
+            self.0.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+/// The read-write counterpart of [`StateLock`], for state read by nearly every command and
+/// rarely replaced: the configuration and the message catalog.
+pub struct StateRwLock<T>(RwLock<T>);
+
+impl<T> StateRwLock<T> {
+    pub const fn new(value: T) -> Self {
+        Self(RwLock::new(value))
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, T> {
+        self.0.read().unwrap_or_else(|poisoned| {
+            log::warn!("Recovering plugin state after a panic while it was locked");
+            self.0.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, T> {
+        self.0.write().unwrap_or_else(|poisoned| {
+            log::warn!("Recovering plugin state after a panic while it was locked");
+            self.0.clear_poison();
+            poisoned.into_inner()
+        })
+    }
+}
+
+/// Clear the clipboard source of every player whose clipboard came from `filename`.
+pub fn forget_clipboard_source(filename: &str) {
+    let mut state = PLAYER_DATA.lock();
+    for clipboard in state.values_mut().filter_map(|data| data.clipboard.as_mut()) {
+        if clipboard.source.as_deref() == Some(filename) {
+            clipboard.source = None;
+        }
+    }
 }
 
 /// Global thread-safe storage for all player states.
-pub static PLAYER_DATA: LazyLock<Mutex<HashMap<Uuid, PlayerState>>> =
-    LazyLock::new(|| Mutex::new(HashMap::new()));
+pub static PLAYER_DATA: LazyLock<StateLock<HashMap<Uuid, PlayerState>>> =
+    LazyLock::new(|| StateLock::new(HashMap::new()));
+
+/// Players with a block-modifying operation in progress.
+static BUSY_PLAYERS: LazyLock<StateLock<HashSet<Uuid>>> =
+    LazyLock::new(|| StateLock::new(HashSet::new()));
+
+/// Marks a player's operation as in progress until dropped. Undo data is committed when an
+/// operation ends, so a second operation interleaving its writes with the first would record
+/// intermediate states and undo to the wrong blocks; it is rejected instead.
+pub struct OperationGuard(Uuid);
+
+impl OperationGuard {
+    pub fn acquire(player_id: Uuid) -> Result<Self, CommandError> {
+        if !BUSY_PLAYERS.lock().insert(player_id) {
+            return Err(CommandError::CommandFailed(msg!("operation_running")));
+        }
+        Ok(Self(player_id))
+    }
+}
+
+impl Drop for OperationGuard {
+    fn drop(&mut self) {
+        BUSY_PLAYERS.lock().remove(&self.0);
+    }
+}
+
+/// Players who left while the plugin was running and have not joined again. Operations they
+/// started keep running; their feedback is dropped and their history goes to disk.
+static DEPARTED_PLAYERS: LazyLock<StateLock<HashSet<Uuid>>> =
+    LazyLock::new(|| StateLock::new(HashSet::new()));
+
+pub fn mark_departed(player_id: Uuid) {
+    DEPARTED_PLAYERS.lock().insert(player_id);
+}
+
+pub fn mark_returned(player_id: Uuid) {
+    DEPARTED_PLAYERS.lock().remove(&player_id);
+}
+
+pub fn has_departed(player_id: &Uuid) -> bool {
+    DEPARTED_PLAYERS.lock().contains(player_id)
+}
+
+/// Bytes reserved by operations that are still building a clipboard.
+static RESERVED_BYTES: StateLock<u64> = StateLock::new(0);
+
+/// Approximate bytes held by all players' clipboards and histories, as (clipboards, histories).
+pub fn memory_usage() -> (usize, usize) {
+    let state = PLAYER_DATA.lock();
+    state.values().fold((0, 0), |(clipboards, histories), data| {
+        let clipboard = data
+            .clipboard
+            .as_ref()
+            .map_or(0, |c| {
+                c.blocks.len() * size_of::<(Vector3<i32>, u16)>()
+                    + c.biomes.as_ref().map_or(0, Vec::len)
+            });
+        let history = data.history.stored_blocks() * size_of::<BlockChange>();
+        (clipboards + clipboard, histories + history)
+    })
+}
+
+/// Memory set aside for a large allocation until dropped. Clipboards and histories plus all
+/// reservations, across every player, must stay under `max_memory_mb`; an operation that
+/// would exceed it is rejected before it allocates anything.
+pub struct MemoryReservation(u64);
+
+impl MemoryReservation {
+    pub fn acquire(bytes: u64) -> Result<Self, CommandError> {
+        let limit = config::get().max_memory_mb * 1024 * 1024;
+        // Measured first so PLAYER_DATA is never locked under RESERVED_BYTES. The reserved
+        // total is checked and raised under one lock, so concurrent reservations can't both
+        // fit in the same room
+        let stored = if limit > 0 {
+            let (clipboards, histories) = memory_usage();
+            (clipboards + histories) as u64
+        } else {
+            0
+        };
+        let mut reserved = RESERVED_BYTES.lock();
+        if limit > 0 {
+            let used = stored + *reserved;
+            if used.saturating_add(bytes) > limit {
+                return Err(CommandError::CommandFailed(msg!(
+                    "memory_limit",
+                    format_count(bytes.div_ceil(1024 * 1024)),
+                    format_count(used / (1024 * 1024)),
+                    format_count(limit / (1024 * 1024))
+                )));
+            }
+        }
+        *reserved += bytes;
+        Ok(Self(bytes))
+    }
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        *RESERVED_BYTES.lock() -= self.0;
+    }
+}
+
+/// Slots for schematic file reads and writes, shared by every player so a burst of loads
+/// can't take over the blocking thread pool and the disk. Sized once from the config.
+static SCHEMATIC_IO_PERMITS: LazyLock<usize> =
+    LazyLock::new(|| config::get().schematic_io_permits.max(1));
+static SCHEMATIC_IO: LazyLock<Semaphore> = LazyLock::new(|| Semaphore::new(*SCHEMATIC_IO_PERMITS));
+
+/// The schematics directory, `None` before the plugin has loaded.
+pub fn schematics_dir() -> Option<PathBuf> {
+    SCHEMATICS_DIR.lock().clone()
+}
+
+/// Set the schematics directory during plugin load.
+pub fn set_schematics_dir(dir: PathBuf) {
+    *SCHEMATICS_DIR.lock() = Some(dir);
+}
+
+/// Point schematic commands at `dir` while no schematic file is being read or written: every
+/// I/O slot is held during the switch, so none can start on the old directory meanwhile.
+/// Fails without changing anything if one is in flight.
+pub fn repoint_schematics_dir(dir: PathBuf) -> Result<(), String> {
+    let Ok(_all) = SCHEMATIC_IO.try_acquire_many(*SCHEMATIC_IO_PERMITS as u32) else {
+        return Err(msg_string!("schematics_dir_busy"));
+    };
+    *SCHEMATICS_DIR.lock() = Some(dir);
+    Ok(())
+}
+
+/// Senders waiting for a schematic I/O slot, in arrival order; `None` is the console.
+static SCHEMATIC_IO_WAITING: StateLock<Vec<Option<Uuid>>> = StateLock::new(Vec::new());
+
+/// Lists a sender as waiting for a schematic I/O slot until dropped.
+struct IoWaiting(Option<Uuid>);
+
+impl Drop for IoWaiting {
+    fn drop(&mut self) {
+        let mut waiting = SCHEMATIC_IO_WAITING.lock();
+        if let Some(index) = waiting.iter().position(|id| *id == self.0) {
+            waiting.remove(index);
+        }
+    }
+}
+
+/// Schematic operations running plus those queued before the `index`-th waiting one.
+fn io_ahead(index: usize) -> usize {
+    *SCHEMATIC_IO_PERMITS - SCHEMATIC_IO.available_permits() + index
+}
+
+/// Senders waiting for a schematic I/O slot.
+pub fn schematic_io_waiting() -> usize {
+    SCHEMATIC_IO_WAITING.lock().len()
+}
+
+/// How many schematic operations are ahead of the player's own if it is waiting for a
+/// schematic I/O slot.
+pub fn schematic_io_position(player_id: &Uuid) -> Option<usize> {
+    let waiting = SCHEMATIC_IO_WAITING.lock();
+    let index = waiting.iter().position(|id| *id == Some(*player_id))?;
+    Some(io_ahead(index))
+}
+
+/// Run the blocking schematic file read or write `task` on the blocking thread pool once a
+/// slot is free. A sender who has to wait is told how many operations are ahead of them.
+/// The slot is given back when the task ends, fails or the command is dropped.
+pub async fn schematic_io<T, F>(sender: &CommandSender, task: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    let _permit = match SCHEMATIC_IO.try_acquire() {
+        Ok(permit) => permit,
+        Err(_) => {
+            let player_id = sender.as_player().map(|player| player.gameprofile.id);
+            let ahead = {
+                let mut waiting = SCHEMATIC_IO_WAITING.lock();
+                waiting.push(player_id);
+                io_ahead(waiting.len() - 1)
+            };
+            let _waiting = IoWaiting(player_id);
+            notify(sender, Level::Progress, msg!("schematic_io_queued", format_count(ahead)))
+                .await;
+            SCHEMATIC_IO.acquire().await.map_err(|e| e.to_string())?
+        }
+    };
+    tokio::task::spawn_blocking(task).await.map_err(|e| e.to_string())?
+}
 
 // ============================================================================
 // Helper Functions
 // ============================================================================
 
-/// Get the normalized selection (min corner, max corner) for a player.
-pub fn get_selection(player_id: &Uuid) -> Result<(BlockPos, BlockPos), CommandError> {
-    let state = PLAYER_DATA.lock().unwrap();
-    let data = state.get(player_id).ok_or(CommandError::CommandFailed(
-        TextComponent::text("No selection set. Use //pos1 and //pos2 first.")
-            .color_named(NamedColor::Red),
-    ))?;
-    let pos1 = data.pos1.ok_or(CommandError::CommandFailed(
-        TextComponent::text("Position 1 not set. Use //pos1 first.").color_named(NamedColor::Red),
-    ))?;
-    let pos2 = data.pos2.ok_or(CommandError::CommandFailed(
-        TextComponent::text("Position 2 not set. Use //pos2 first.").color_named(NamedColor::Red),
-    ))?;
+/// Get the normalized selection (min corner, max corner) of the sending player. A selection
+/// made in another world than the one the player is in is refused.
+pub fn get_selection(sender: &CommandSender) -> Result<(BlockPos, BlockPos), CommandError> {
+    let player_id = sender_uuid(sender)?;
+    let world = sender_world(sender)?;
+    let state = PLAYER_DATA.lock();
+    let data = state.get(&player_id).ok_or(CommandError::CommandFailed(msg!("no_selection")))?;
+    let pos1 = data.pos1.ok_or(CommandError::CommandFailed(msg!("pos1_not_set")))?;
+    let pos2 = data.pos2.ok_or(CommandError::CommandFailed(msg!("pos2_not_set")))?;
+    if let Some(selected) = data.selection_world.as_deref() {
+        let current = world.get_world_name();
+        if selected != current {
+            return Err(CommandError::CommandFailed(msg!(
+                "selection_other_world",
+                selected,
+                current
+            )));
+        }
+    }
     Ok((
         BlockPos(Vector3::new(
             pos1.0.x.min(pos2.0.x),
@@ -82,11 +466,131 @@ pub fn get_selection(player_id: &Uuid) -> Result<(BlockPos, BlockPos), CommandEr
     ))
 }
 
+/// The region a selection covers. Selections are cuboids between pos1 and pos2; operations
+/// that follow the selection's outline (such as `//hollow`) go through `contains` rather than
+/// the bounding box so they keep working for other shapes.
+#[derive(Clone, Copy)]
+pub enum SelectionShape {
+    Cuboid { min: BlockPos, max: BlockPos },
+}
+
+impl SelectionShape {
+    /// Bounding box (min corner, max corner).
+    pub fn bounds(&self) -> (BlockPos, BlockPos) {
+        match self {
+            Self::Cuboid { min, max } => (*min, *max),
+        }
+    }
+
+    pub fn contains(&self, pos: &BlockPos) -> bool {
+        match self {
+            Self::Cuboid { min, max } => {
+                (min.0.x..=max.0.x).contains(&pos.0.x)
+                    && (min.0.y..=max.0.y).contains(&pos.0.y)
+                    && (min.0.z..=max.0.z).contains(&pos.0.z)
+            }
+        }
+    }
+
+    /// Whether `pos` and all six of its neighbors are inside the shape.
+    pub fn is_interior(&self, pos: &BlockPos) -> bool {
+        self.contains(pos) && !self.near_outside(pos, 1, &NEIGHBORS)
+    }
+
+    /// Whether `pos` is inside the shape and within `thickness` blocks of its outside along
+    /// X or Z, i.e. part of a wall that thick. Cuboids have a closed form; shapes without one
+    /// test `near_outside(pos, thickness, &NEIGHBORS[..4])`.
+    pub fn is_wall(&self, pos: &BlockPos, thickness: i32) -> bool {
+        let p = pos.0;
+        match self {
+            Self::Cuboid { min, max } => {
+                self.contains(pos)
+                    && (p.x - min.0.x < thickness
+                        || max.0.x - p.x < thickness
+                        || p.z - min.0.z < thickness
+                        || max.0.z - p.z < thickness)
+            }
+        }
+    }
+
+    /// Blocks from `pos` to the shape's nearest face. Inside, how many blocks it is in from
+    /// the outside (0 on the outer layer); outside, the largest per-axis gap to the shape.
+    pub fn face_distance(&self, pos: &BlockPos) -> i32 {
+        let p = pos.0;
+        match self {
+            Self::Cuboid { min, max } => {
+                let gaps = [
+                    (min.0.x - p.x, p.x - max.0.x),
+                    (min.0.y - p.y, p.y - max.0.y),
+                    (min.0.z - p.z, p.z - max.0.z),
+                ];
+                if self.contains(pos) {
+                    gaps.iter().map(|(below, above)| (-below).min(-above)).min().unwrap_or(0)
+                } else {
+                    gaps.iter().map(|(below, above)| (*below).max(*above)).max().unwrap_or(0)
+                }
+            }
+        }
+    }
+
+    /// Whether stepping up to `reach` blocks from `pos` in any of `directions` leaves the shape.
+    fn near_outside(&self, pos: &BlockPos, reach: i32, directions: &[(i32, i32, i32)]) -> bool {
+        let p = pos.0;
+        directions.iter().any(|(dx, dy, dz)| {
+            (1..=reach).any(|k| {
+                !self.contains(&BlockPos(Vector3::new(p.x + dx * k, p.y + dy * k, p.z + dz * k)))
+            })
+        })
+    }
+}
+
+/// Offsets of the six face neighbors, horizontal ones first.
+const NEIGHBORS: [(i32, i32, i32); 6] =
+    [(1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1), (0, 1, 0), (0, -1, 0)];
+
+/// Get the shape of the sending player's selection.
+pub fn get_selection_shape(sender: &CommandSender) -> Result<SelectionShape, CommandError> {
+    let (min, max) = get_selection(sender)?;
+    Ok(SelectionShape::Cuboid { min, max })
+}
+
+/// Block flags for a player's edits. Fast mode force-places blocks without neighbor updates;
+/// otherwise (or when the command was given `-u`) blocks are placed normally with physics.
+pub fn placement_flags(player_id: &Uuid, updates: bool) -> BlockFlags {
+    let fast_mode = PLAYER_DATA.lock().get(player_id).is_none_or(|data| data.fast_mode);
+    if fast_mode && !updates {
+        BlockFlags::FORCE_STATE
+    } else {
+        BlockFlags::NOTIFY_ALL
+    }
+}
+
+/// Suffix for completion messages telling the player the edit ran with physics.
+pub fn placement_note(flags: BlockFlags) -> String {
+    if flags.contains(BlockFlags::FORCE_STATE) {
+        String::new()
+    } else {
+        msg_string!("physics_note")
+    }
+}
+
+/// The sender as a player; the console and command blocks are refused with a message.
+pub fn sender_player(sender: &CommandSender) -> Result<Arc<Player>, CommandError> {
+    sender
+        .as_player()
+        .ok_or(CommandError::CommandFailed(msg!("requires_player")))
+}
+
+/// The sender's exact position, for senders that have one.
+pub fn sender_position(sender: &CommandSender) -> Result<Vector3<f64>, CommandError> {
+    sender
+        .position()
+        .ok_or(CommandError::CommandFailed(msg!("requires_position")))
+}
+
 /// Convert the sender's floating-point position to a block position.
 pub fn sender_block_pos(sender: &CommandSender) -> Result<BlockPos, CommandError> {
-    let pos = sender
-        .position()
-        .ok_or(CommandError::InvalidRequirement)?;
+    let pos = sender_position(sender)?;
     Ok(BlockPos(Vector3::new(
         pos.x.floor() as i32,
         pos.y.floor() as i32,
@@ -94,17 +598,50 @@ pub fn sender_block_pos(sender: &CommandSender) -> Result<BlockPos, CommandError
     )))
 }
 
+/// The player's eye position and look direction as a unit vector.
+pub fn sender_look(sender: &CommandSender) -> Result<(Vector3<f64>, Vector3<f64>), CommandError> {
+    let player = sender_player(sender)?;
+    let entity = &player.living_entity.entity;
+    let pos = entity.pos.load();
+    let eye = Vector3::new(pos.x, pos.y + f64::from(entity.standing_eye_height), pos.z);
+    let yaw = f64::from(entity.yaw.load()).to_radians();
+    let pitch = f64::from(entity.pitch.load()).to_radians();
+    let direction = Vector3::new(-yaw.sin() * pitch.cos(), -pitch.sin(), yaw.cos() * pitch.cos());
+    Ok((eye, direction))
+}
+
+/// Unit step along the axis the player is looking most along, e.g. `(0, 0, -1)` facing north.
+pub fn sender_facing(sender: &CommandSender) -> Result<Vector3<i32>, CommandError> {
+    let (_, look) = sender_look(sender)?;
+    let sign = |value: f64| if value < 0.0 { -1 } else { 1 };
+    let (x, y, z) = (look.x.abs(), look.y.abs(), look.z.abs());
+    Ok(if x >= y && x >= z {
+        Vector3::new(sign(look.x), 0, 0)
+    } else if y >= z {
+        Vector3::new(0, sign(look.y), 0)
+    } else {
+        Vector3::new(0, 0, sign(look.z))
+    })
+}
+
 /// Extract the player's UUID from the command sender.
 pub fn sender_uuid(sender: &CommandSender) -> Result<Uuid, CommandError> {
-    let player = sender
-        .as_player()
-        .ok_or(CommandError::InvalidRequirement)?;
-    Ok(player.gameprofile.id)
+    Ok(sender_player(sender)?.gameprofile.id)
 }
 
 /// Get the player's world from the command sender.
 pub fn sender_world(sender: &CommandSender) -> Result<Arc<World>, CommandError> {
-    sender.world().ok_or(CommandError::InvalidRequirement)
+    sender
+        .world()
+        .ok_or(CommandError::CommandFailed(msg!("world_unavailable")))
+}
+
+/// Refuse to change a world listed in `disabled_worlds`.
+pub fn check_world_enabled(world: &str) -> Result<(), CommandError> {
+    if config::get().disabled_worlds.iter().any(|name| name == world) {
+        return Err(CommandError::CommandFailed(msg!("world_disabled", world)));
+    }
+    Ok(())
 }
 
 /// Calculate the volume of a selection.
@@ -115,16 +652,124 @@ pub fn selection_volume(min: &BlockPos, max: &BlockPos) -> i64 {
     dx * dy * dz
 }
 
+/// Check that a clipboard of `volume` blocks does not exceed `max_clipboard_blocks`.
+pub fn check_clipboard_size(volume: u64) -> Result<(), CommandError> {
+    let limit = config::get().max_clipboard_blocks;
+    if volume > limit {
+        return Err(CommandError::CommandFailed(msg!(
+            "clipboard_limit",
+            format_count(volume),
+            format_count(limit)
+        )));
+    }
+    Ok(())
+}
+
 /// Check that the selection does not exceed the block limit.
 pub fn check_selection_size(min: &BlockPos, max: &BlockPos) -> Result<(), CommandError> {
     let volume = selection_volume(min, max);
     if volume > MAX_BLOCKS {
-        return Err(CommandError::CommandFailed(
-            TextComponent::text(format!(
-                "Selection too large ({volume} blocks). Maximum is {MAX_BLOCKS}."
-            ))
-            .color_named(NamedColor::Red),
-        ));
+        return Err(CommandError::CommandFailed(msg!(
+            "selection_too_large",
+            format_count(volume),
+            format_count(MAX_BLOCKS)
+        )));
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::stats::{record_operation, STATS};
+    use crate::testing::{cuboid, panic_holding, player, pos};
+
+    #[test]
+    fn cuboid_interior_is_what_hollow_always_removed() {
+        for (min, max) in [
+            (pos(0, 0, 0), pos(4, 3, 5)),
+            (pos(-3, 60, 2), pos(-1, 62, 4)),
+            (pos(0, 0, 0), pos(5, 0, 5)),
+            (pos(0, 0, 0), pos(1, 1, 1)),
+        ] {
+            let shape = SelectionShape::Cuboid { min, max };
+            let below = pos(min.0.x - 1, min.0.y - 1, min.0.z - 1);
+            let above = pos(max.0.x + 1, max.0.y + 1, max.0.z + 1);
+            for p in cuboid(below, above) {
+                // Before shapes: inside the bounding box and on none of its faces
+                let (x, y, z) = (p.0.x, p.0.y, p.0.z);
+                let on_face = x == min.0.x
+                    || x == max.0.x
+                    || y == min.0.y
+                    || y == max.0.y
+                    || z == min.0.z
+                    || z == max.0.z;
+                assert_eq!(shape.is_interior(&p), shape.contains(&p) && !on_face);
+            }
+        }
+    }
+
+    #[test]
+    fn cuboid_walls_match_the_neighbor_scan() {
+        let shape = SelectionShape::Cuboid { min: pos(0, 0, 0), max: pos(6, 2, 7) };
+        for thickness in 1..=4 {
+            for p in cuboid(pos(-1, 0, -1), pos(7, 2, 8)) {
+                let scanned =
+                    shape.contains(&p) && shape.near_outside(&p, thickness, &NEIGHBORS[..4]);
+                assert_eq!(shape.is_wall(&p, thickness), scanned);
+            }
+        }
+    }
+
+    #[test]
+    fn face_distance_counts_layers_in_and_gaps_out() {
+        let shape = SelectionShape::Cuboid { min: pos(0, 0, 0), max: pos(8, 8, 8) };
+        assert_eq!(shape.face_distance(&pos(0, 4, 4)), 0);
+        assert_eq!(shape.face_distance(&pos(4, 4, 4)), 4);
+        assert_eq!(shape.face_distance(&pos(2, 7, 3)), 1);
+        assert_eq!(shape.face_distance(&pos(-3, 4, 10)), 3);
+    }
+
+    #[test]
+    fn selections_up_to_the_block_limit_are_allowed() {
+        let limit = MAX_BLOCKS as i32;
+        assert!(check_selection_size(&pos(0, 64, 0), &pos(limit - 1, 64, 0)).is_ok());
+        assert!(check_selection_size(&pos(0, 64, 0), &pos(limit, 64, 0)).is_err());
+        // 47 x 47 x 47 is 103,823 blocks
+        assert!(check_selection_size(&pos(0, 0, 0), &pos(46, 46, 46)).is_err());
+    }
+
+    #[test]
+    fn lock_recovers_after_a_panic() {
+        let lock = StateLock::new(vec![1]);
+        std::thread::scope(|scope| {
+            let result = scope
+                .spawn(|| {
+                    let mut data = lock.lock();
+                    data.push(2);
+                    panic!("command panicked while holding plugin state");
+                })
+                .join();
+            assert!(result.is_err());
+        });
+
+        // Later commands still get the state, including writes made before the panic
+        assert_eq!(*lock.lock(), [1, 2]);
+        assert!(!lock.0.is_poisoned());
+        lock.lock().push(3);
+        assert_eq!(*lock.lock(), [1, 2, 3]);
+    }
+
+    #[test]
+    fn global_state_recovers_after_a_panic() {
+        // Every commit records its operation here
+        panic_holding(|| STATS.lock());
+        record_operation(player(8), "//set stone", 10, Duration::from_millis(5));
+        assert_eq!(STATS.lock().by_player.get(&player(8)).map(|c| c.blocks), Some(10));
+
+        panic_holding(|| PLAYER_DATA.lock());
+        assert!(!PLAYER_DATA.lock().contains_key(&player(8)));
+    }
+}
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, Instant};
+
+use uuid::Uuid;
+
+use crate::state::StateLock;
+
+/// Number of slowest operations kept for `/we stats`.
+const SLOWEST_KEPT: usize = 5;
+
+/// Operation and block counters for one command or player.
+#[derive(Clone, Copy, Default)]
+pub struct Counter {
+    pub operations: u64,
+    pub blocks: u64,
+}
+
+impl Counter {
+    fn add(&mut self, blocks: usize) {
+        self.operations += 1;
+        self.blocks += blocks as u64;
+    }
+}
+
+/// A completed operation kept in the slowest-operations list.
+#[derive(Clone)]
+pub struct TimedOperation {
+    pub command: String,
+    pub blocks: usize,
+    pub duration: Duration,
+}
+
+/// Server-wide operation statistics since start.
+#[derive(Default)]
+pub struct OperationStats {
+    pub total: Counter,
+    /// Keyed by command name (e.g. `//set`).
+    pub by_command: HashMap<String, Counter>,
+    pub by_player: HashMap<Uuid, Counter>,
+    /// Slowest operations, slowest first.
+    pub slowest: Vec<TimedOperation>,
+}
+
+pub static STATS: LazyLock<StateLock<OperationStats>> =
+    LazyLock::new(|| StateLock::new(OperationStats::default()));
+
+/// An edit session in progress, as shown by `/we status` and `/we queue`.
+pub struct ActiveOperation {
+    /// Number shown in `/we queue`, used to cancel the operation.
+    pub id: u64,
+    pub player_id: Uuid,
+    pub command: String,
+    /// Name of the world being changed.
+    pub world: String,
+    pub started: Instant,
+    blocks: AtomicUsize,
+    cancelled: AtomicBool,
+}
+
+impl ActiveOperation {
+    /// Blocks changed so far.
+    pub fn blocks(&self) -> usize {
+        self.blocks.load(Ordering::Relaxed)
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+/// Edit sessions started and not yet committed, oldest first.
+static RUNNING: StateLock<Vec<Arc<ActiveOperation>>> = StateLock::new(Vec::new());
+static NEXT_OPERATION_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Lists an operation as in progress until dropped.
+pub struct RunningOperation(Arc<ActiveOperation>);
+
+impl RunningOperation {
+    pub fn start(player_id: Uuid, command: &str, world: String) -> Self {
+        let operation = Arc::new(ActiveOperation {
+            id: NEXT_OPERATION_ID.fetch_add(1, Ordering::Relaxed),
+            player_id,
+            command: command.to_string(),
+            world,
+            started: Instant::now(),
+            blocks: AtomicUsize::new(0),
+            cancelled: AtomicBool::new(false),
+        });
+        RUNNING.lock().push(operation.clone());
+        Self(operation)
+    }
+
+    /// Update the number of blocks changed so far.
+    pub fn set_blocks(&self, blocks: usize) {
+        self.0.blocks.store(blocks, Ordering::Relaxed);
+    }
+
+    /// Whether `/we queue cancel` asked this operation to stop.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.is_cancelled()
+    }
+}
+
+impl Drop for RunningOperation {
+    fn drop(&mut self) {
+        RUNNING.lock().retain(|operation| operation.id != self.0.id);
+    }
+}
+
+/// Number of block-modifying operations currently in progress.
+pub fn running_operations() -> usize {
+    RUNNING.lock().len()
+}
+
+/// Operations currently in progress, oldest first.
+pub fn active_operations() -> Vec<Arc<ActiveOperation>> {
+    RUNNING.lock().clone()
+}
+
+/// Ask the operation with `id` to stop at its next block. Returns it if it was running.
+pub fn cancel_operation(id: u64) -> Option<Arc<ActiveOperation>> {
+    let running = RUNNING.lock();
+    let operation = running.iter().find(|operation| operation.id == id)?;
+    operation.cancelled.store(true, Ordering::Relaxed);
+    Some(operation.clone())
+}
+
+/// Ask every operation changing `world` to stop at its next block. Returns how many there were.
+pub fn cancel_world_operations(world: &str) -> usize {
+    let running = RUNNING.lock();
+    running
+        .iter()
+        .filter(|operation| operation.world == world)
+        .inspect(|operation| operation.cancelled.store(true, Ordering::Relaxed))
+        .count()
+}
+
+/// Record a completed block-modifying operation.
+pub fn record_operation(player_id: Uuid, command: &str, blocks: usize, duration: Duration) {
+    let name = command.split_whitespace().next().unwrap_or(command);
+
+    let mut stats = STATS.lock();
+    stats.total.add(blocks);
+    stats.by_command.entry(name.to_string()).or_default().add(blocks);
+    stats.by_player.entry(player_id).or_default().add(blocks);
+
+    let is_slow = stats.slowest.len() < SLOWEST_KEPT
+        || stats.slowest.last().is_some_and(|op| duration > op.duration);
+    if is_slow {
+        stats.slowest.push(TimedOperation {
+            command: command.to_string(),
+            blocks,
+            duration,
+        });
+        stats.slowest.sort_by(|a, b| b.duration.cmp(&a.duration));
+        stats.slowest.truncate(SLOWEST_KEPT);
+    }
+}
+
+/// Format a duration as milliseconds below one second, seconds otherwise ("840ms", "1.25s").
+pub fn format_duration(duration: Duration) -> String {
+    if duration.as_secs() == 0 {
+        format!("{}ms", duration.as_millis())
+    } else {
+        format!("{:.2}s", duration.as_secs_f64())
+    }
+}
@@ -0,0 +1,238 @@
+//! Blocks that hang on a neighbor, and where that neighbor is, for `fixfloat` and for placing
+//! them after their neighbors. Each family is one row of [`FAMILIES`], matched by block name,
+//! so covering another family is a new row.
+
+use pumpkin_data::Block;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+
+use crate::transform::{is_air, parse_direction, property};
+
+/// Where a family's supporting block is, relative to the block itself.
+#[derive(Clone, Copy)]
+enum Support {
+    /// The block below: floor torches, carpets, plants, snow layers, rails, pressure plates.
+    Below,
+    /// The block above: spore blossoms and hanging roots.
+    Above,
+    /// The block behind, opposite the `facing` property: wall torches, ladders, wall signs.
+    Behind,
+    /// Chosen by the `face` property: below on the floor, above on the ceiling, behind on a
+    /// wall. Buttons and levers.
+    Face,
+    /// Above if the `hanging` property is true, below otherwise. Lanterns.
+    Hanging,
+    /// Held up in a way `fixfloat` doesn't follow, such as vines on any side or lily pads on
+    /// water. They are only placed after their neighbors.
+    Unchecked,
+}
+
+/// One family of blocks that need support.
+struct Family {
+    /// Exact block names.
+    names: &'static [&'static str],
+    /// Name suffixes shared by the whole family, e.g. `_carpet`.
+    suffixes: &'static [&'static str],
+    support: Support,
+}
+
+impl Family {
+    fn matches(&self, name: &str) -> bool {
+        self.names.contains(&name) || self.suffixes.iter().any(|suffix| name.ends_with(suffix))
+    }
+}
+
+/// Every family of attached blocks. A block takes the first row it matches, so wall banners
+/// and hanging signs come before the standing banners and signs whose suffix they share.
+const FAMILIES: &[Family] = &[
+    Family {
+        names: &["torch", "soul_torch", "redstone_torch", "copper_torch"],
+        suffixes: &[],
+        support: Support::Below,
+    },
+    Family {
+        names: &[
+            "wall_torch",
+            "soul_wall_torch",
+            "redstone_wall_torch",
+            "copper_wall_torch",
+            "ladder",
+            "tripwire_hook",
+        ],
+        suffixes: &["_wall_sign", "_wall_banner"],
+        support: Support::Behind,
+    },
+    Family { names: &["lever"], suffixes: &["_button"], support: Support::Face },
+    Family {
+        names: &["vine", "tripwire", "lily_pad"],
+        suffixes: &["_wall_hanging_sign"],
+        support: Support::Unchecked,
+    },
+    Family { names: &[], suffixes: &["_hanging_sign"], support: Support::Above },
+    Family {
+        names: &[],
+        suffixes: &["_carpet", "_banner", "_sign", "_door", "_coral_fan"],
+        support: Support::Below,
+    },
+    Family {
+        names: &["rail", "redstone_wire", "repeater", "comparator"],
+        suffixes: &["_rail", "_pressure_plate"],
+        support: Support::Below,
+    },
+    Family { names: &["snow"], suffixes: &[], support: Support::Below },
+    Family {
+        names: &[
+            "short_grass",
+            "tall_grass",
+            "fern",
+            "large_fern",
+            "dead_bush",
+            "dandelion",
+            "poppy",
+            "blue_orchid",
+            "allium",
+            "azure_bluet",
+            "oxeye_daisy",
+            "cornflower",
+            "lily_of_the_valley",
+            "wither_rose",
+            "torchflower",
+            "sunflower",
+            "lilac",
+            "rose_bush",
+            "peony",
+            "pink_petals",
+            "sweet_berry_bush",
+            "brown_mushroom",
+            "red_mushroom",
+            "crimson_fungus",
+            "warped_fungus",
+            "crimson_roots",
+            "warped_roots",
+            "nether_sprouts",
+            "sugar_cane",
+            "cactus",
+            "bamboo",
+            "kelp",
+            "kelp_plant",
+            "seagrass",
+            "tall_seagrass",
+            "wheat",
+            "carrots",
+            "potatoes",
+            "beetroots",
+        ],
+        suffixes: &["_sapling", "_tulip"],
+        support: Support::Below,
+    },
+    Family { names: &["spore_blossom", "hanging_roots"], suffixes: &[], support: Support::Above },
+    Family { names: &["lantern", "soul_lantern"], suffixes: &[], support: Support::Hanging },
+];
+
+/// Blocks that fall when placed without support.
+const GRAVITY_BLOCKS: &[&str] = &[
+    "sand",
+    "red_sand",
+    "suspicious_sand",
+    "gravel",
+    "suspicious_gravel",
+    "anvil",
+    "chipped_anvil",
+    "damaged_anvil",
+    "dragon_egg",
+    "pointed_dripstone",
+    "scaffolding",
+];
+
+/// Whether a block needs a neighbor to stay in place: gravity blocks, concrete powder and
+/// every family of attached blocks.
+pub fn needs_support(state_id: u16) -> bool {
+    let name = Block::from_state_id(state_id).name;
+    GRAVITY_BLOCKS.contains(&name)
+        || name.ends_with("_concrete_powder")
+        || FAMILIES.iter().any(|family| family.matches(name))
+}
+
+/// Position of the block holding up the block `state_id` at `pos`, or `None` if it stands
+/// on its own.
+pub fn support_pos(state_id: u16, pos: &BlockPos) -> Option<BlockPos> {
+    let name = Block::from_state_id(state_id).name;
+    let family = FAMILIES.iter().find(|family| family.matches(name))?;
+    let (below, above) = (Vector3::new(0, -1, 0), Vector3::new(0, 1, 0));
+    let offset = match family.support {
+        Support::Below => below,
+        Support::Above => above,
+        Support::Behind => behind(state_id)?,
+        Support::Face => match property(state_id, "face")? {
+            "floor" => below,
+            "ceiling" => above,
+            _ => behind(state_id)?,
+        },
+        Support::Hanging => {
+            if property(state_id, "hanging") == Some("true") {
+                above
+            } else {
+                below
+            }
+        }
+        Support::Unchecked => return None,
+    };
+    Some(BlockPos(Vector3::new(pos.0.x + offset.x, pos.0.y + offset.y, pos.0.z + offset.z)))
+}
+
+/// Offset to the block a wall-mounted block hangs on: the opposite of its `facing`.
+fn behind(state_id: u16) -> Option<Vector3<i32>> {
+    let facing = parse_direction(property(state_id, "facing")?).ok()?;
+    Some(Vector3::new(-facing.x, -facing.y, -facing.z))
+}
+
+/// Whether `state_id` can hold up an attached block. Air and fluids can't; everything else,
+/// including other attached blocks such as the lower half of a tall flower, can.
+pub fn can_support(state_id: u16) -> bool {
+    !is_air(state_id)
+        && !matches!(Block::from_state_id(state_id).name, "water" | "lava" | "bubble_column")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::with_property;
+
+    #[test]
+    fn placement_order_and_fixfloat_share_one_list() {
+        let attached = [
+            &Block::TORCH,
+            &Block::WALL_TORCH,
+            &Block::OAK_DOOR,
+            &Block::OAK_SIGN,
+            &Block::OAK_HANGING_SIGN,
+            &Block::VINE,
+            &Block::LILY_PAD,
+            &Block::CACTUS,
+            &Block::WHITE_CARPET,
+        ];
+        for block in attached {
+            assert!(needs_support(block.default_state.id), "{} needs support", block.name);
+        }
+        // Gravity blocks are placed late but not checked by fixfloat
+        let origin = BlockPos(Vector3::new(0, 64, 0));
+        for block in [&Block::SAND, &Block::WHITE_CONCRETE_POWDER, &Block::ANVIL] {
+            assert!(needs_support(block.default_state.id), "{} falls", block.name);
+            assert!(support_pos(block.default_state.id, &origin).is_none());
+        }
+        for block in [&Block::STONE, &Block::OAK_PLANKS, &Block::GLASS, &Block::OAK_TRAPDOOR] {
+            assert!(!needs_support(block.default_state.id), "{} stands alone", block.name);
+        }
+    }
+
+    #[test]
+    fn supports_follow_the_block_state() {
+        let origin = BlockPos(Vector3::new(0, 64, 0));
+        let at = |state_id| support_pos(state_id, &origin).map(|pos| (pos.0.x, pos.0.y, pos.0.z));
+        assert_eq!(at(Block::TORCH.default_state.id), Some((0, 63, 0)));
+        assert_eq!(at(Block::OAK_HANGING_SIGN.default_state.id), Some((0, 65, 0)));
+        let wall_torch = with_property(Block::WALL_TORCH.default_state.id, "facing", "east");
+        assert_eq!(at(wall_torch.unwrap()), Some((-1, 64, 0)));
+        assert_eq!(at(Block::VINE.default_state.id), None);
+        assert_eq!(at(Block::STONE.default_state.id), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Test support: an in-memory world behind [`BlockAccess`] so edit sessions, undo and region
+//! reads run without a server, plus small builders shared by the tests of each module.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use pumpkin::entity::player::Player;
+use pumpkin_data::Block;
+use pumpkin_nbt::compound::NbtCompound;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+use pumpkin_world::world::BlockFlags;
+use uuid::Uuid;
+
+use crate::engine::{BlockAccess, EditSession};
+use crate::flags::{FlagSpec, Flags};
+use crate::history::{biome_cell, HistoryEntry};
+use crate::state::PLAYER_DATA;
+
+type Key = (i32, i32, i32);
+
+/// A world held in hash maps. Unset positions are air and unset biome cells are biome 0.
+/// Reads or writes can be made to fail to test how operations handle a world error midway.
+pub struct FakeWorld {
+    name: String,
+    blocks: Mutex<HashMap<Key, u16>>,
+    block_entities: Mutex<HashMap<Key, NbtCompound>>,
+    biomes: Mutex<HashMap<Key, u8>>,
+    /// Writes still allowed before every further write fails; `None` never fails.
+    writes_left: Mutex<Option<usize>>,
+    /// Reads at these positions fail.
+    broken: Mutex<Vec<Key>>,
+}
+
+impl FakeWorld {
+    pub fn new(name: &str) -> Arc<Self> {
+        Arc::new(Self {
+            name: name.to_string(),
+            blocks: Mutex::new(HashMap::new()),
+            block_entities: Mutex::new(HashMap::new()),
+            biomes: Mutex::new(HashMap::new()),
+            writes_left: Mutex::new(None),
+            broken: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Place `state_id` at `pos` directly, outside any edit session.
+    pub fn set(&self, pos: BlockPos, state_id: u16) {
+        let mut blocks = self.blocks.lock().unwrap();
+        if state_id == air() {
+            blocks.remove(&key(&pos));
+        } else {
+            blocks.insert(key(&pos), state_id);
+        }
+    }
+
+    /// Fill the cuboid `min..=max` with `state_id`.
+    pub fn fill(&self, min: BlockPos, max: BlockPos, state_id: u16) {
+        for pos in cuboid(min, max) {
+            self.set(pos, state_id);
+        }
+    }
+
+    pub fn get(&self, pos: BlockPos) -> u16 {
+        self.blocks.lock().unwrap().get(&key(&pos)).copied().unwrap_or_else(air)
+    }
+
+    /// Every non-air block, to compare whole worlds before and after an operation.
+    pub fn snapshot(&self) -> HashMap<(i32, i32, i32), u16> {
+        self.blocks.lock().unwrap().clone()
+    }
+
+    pub fn set_block_entity(&self, pos: BlockPos, nbt: NbtCompound) {
+        self.block_entities.lock().unwrap().insert(key(&pos), nbt);
+    }
+
+    pub fn has_block_entity(&self, pos: BlockPos) -> bool {
+        self.block_entities.lock().unwrap().contains_key(&key(&pos))
+    }
+
+    /// Let `count` more writes succeed, then fail every write after them.
+    pub fn fail_writes_after(&self, count: usize) {
+        *self.writes_left.lock().unwrap() = Some(count);
+    }
+
+    /// Make reads at `pos` fail.
+    pub fn break_block(&self, pos: BlockPos) {
+        self.broken.lock().unwrap().push(key(&pos));
+    }
+
+    fn take_write(&self, pos: &BlockPos) -> Result<(), String> {
+        match self.writes_left.lock().unwrap().as_mut() {
+            Some(0) => Err(format!("write refused at {:?}", key(pos))),
+            Some(left) => {
+                *left -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[async_trait]
+impl BlockAccess for FakeWorld {
+    fn world_name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn height_range(&self) -> (i32, i32) {
+        (-64, 319)
+    }
+
+    async fn read_state(&self, pos: &BlockPos) -> Result<u16, String> {
+        if self.broken.lock().unwrap().contains(&key(pos)) {
+            return Err(format!("read refused at {:?}", key(pos)));
+        }
+        Ok(self.get(*pos))
+    }
+
+    async fn write_state(
+        &self,
+        pos: &BlockPos,
+        state_id: u16,
+        _flags: BlockFlags,
+    ) -> Result<(), String> {
+        self.take_write(pos)?;
+        self.set(*pos, state_id);
+        Ok(())
+    }
+
+    async fn read_block_entity(&self, pos: &BlockPos) -> Result<Option<NbtCompound>, String> {
+        Ok(self.block_entities.lock().unwrap().get(&key(pos)).cloned())
+    }
+
+    async fn write_block_entity(
+        &self,
+        pos: &BlockPos,
+        nbt: Option<NbtCompound>,
+    ) -> Result<(), String> {
+        let mut block_entities = self.block_entities.lock().unwrap();
+        match nbt {
+            Some(nbt) => block_entities.insert(key(pos), nbt),
+            None => block_entities.remove(&key(pos)),
+        };
+        Ok(())
+    }
+
+    async fn read_biome(&self, pos: &BlockPos) -> Result<u8, String> {
+        Ok(self.biomes.lock().unwrap().get(&biome_cell(pos)).copied().unwrap_or(0))
+    }
+
+    async fn write_biome(&self, pos: &BlockPos, biome: u8) -> Result<(), String> {
+        self.biomes.lock().unwrap().insert(biome_cell(pos), biome);
+        Ok(())
+    }
+
+    async fn resend_chunks(&self, _chunks: &[(i32, i32)]) {}
+
+    async fn player(&self, _id: Uuid) -> Option<Arc<Player>> {
+        None
+    }
+}
+
+fn key(pos: &BlockPos) -> Key {
+    (pos.0.x, pos.0.y, pos.0.z)
+}
+
+pub fn pos(x: i32, y: i32, z: i32) -> BlockPos {
+    BlockPos(Vector3::new(x, y, z))
+}
+
+pub fn air() -> u16 {
+    Block::AIR.default_state.id
+}
+
+/// Every position of the cuboid `min..=max`, in X, Y, Z order like region reads.
+pub fn cuboid(min: BlockPos, max: BlockPos) -> Vec<BlockPos> {
+    let mut positions = Vec::new();
+    for x in min.0.x..=max.0.x {
+        for y in min.0.y..=max.0.y {
+            for z in min.0.z..=max.0.z {
+                positions.push(pos(x, y, z));
+            }
+        }
+    }
+    positions
+}
+
+/// A distinct player id per test, so tests running in parallel don't share history or the
+/// one-operation-per-player guard.
+pub fn player(n: u128) -> Uuid {
+    Uuid::from_u128(n)
+}
+
+/// An edit session for `command` on `world`, placing without neighbor updates.
+pub fn start(world: &Arc<FakeWorld>, player_id: Uuid, command: &str) -> EditSession {
+    match EditSession::new(world.clone(), player_id, command, BlockFlags::FORCE_STATE) {
+        Ok(session) => session,
+        Err(_) => panic!("session for '{command}' refused"),
+    }
+}
+
+/// Take the player's newest undo entry off their history.
+pub fn pop_undo(player_id: Uuid) -> HistoryEntry {
+    PLAYER_DATA
+        .lock()
+        .get_mut(&player_id)
+        .and_then(|data| data.history.pop_undo())
+        .expect("the session recorded an undo entry")
+}
+
+/// Panic on another thread while holding the guard `hold` returns, poisoning the lock it took.
+pub fn panic_holding<G>(hold: impl FnOnce() -> G + Send) {
+    let result = std::thread::scope(|scope| {
+        scope
+            .spawn(move || {
+                let _guard = hold();
+                panic!("command panicked while holding plugin state");
+            })
+            .join()
+    });
+    assert!(result.is_err());
+}
+
+/// Flags parsed from `input`, failing the test if `specs` reject them.
+pub fn flags(input: &str, specs: &[FlagSpec]) -> Flags {
+    match Flags::parse(input, specs) {
+        Ok(flags) => flags,
+        Err(_) => panic!("flags '{input}' were rejected"),
+    }
+}
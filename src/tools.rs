@@ -0,0 +1,284 @@
+//! Item-bound tools, triggered by right-clicking a block with the bound item.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use pumpkin::{
+    command::dispatcher::CommandError,
+    entity::player::Player,
+    plugin::{
+        player::player_interact_event::{InteractAction, PlayerInteractEvent},
+        Cancellable, EventHandler,
+    },
+    server::Server,
+};
+use pumpkin_data::item::Item;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+use uuid::Uuid;
+
+use crate::config;
+use crate::engine::{BlockAccess, EditSession, EditSummary};
+use crate::history::format_ago;
+use crate::messages::{format_count, msg};
+use crate::notify::{notify_player, Level};
+use crate::permissions::{COMMAND, INSPECT};
+use crate::shapes::{self, Ellipsoid, Half};
+use crate::state::{placement_flags, placement_note, LineTool, MAX_BLOCKS, PLAYER_DATA};
+use crate::tracking;
+
+/// A function that can be bound to an item with `/we tool` or `tool_bindings` in the config.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    /// Prints the WorldEdit edit history of the clicked block.
+    Inspect,
+    /// Sets position 1 on left-click and position 2 on right-click.
+    Wand,
+    /// Sets the line anchor on left-click; right-click draws from the anchor to the clicked
+    /// block and moves the anchor there.
+    Line,
+}
+
+impl Tool {
+    const ALL: [Self; 3] = [Self::Inspect, Self::Wand, Self::Line];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Inspect => "inspect",
+            Self::Wand => "wand",
+            Self::Line => "line",
+        }
+    }
+
+    /// Whether the tool acts on left-clicks too; other left-clicks are left to the game.
+    fn uses_left_click(self) -> bool {
+        matches!(self, Self::Wand | Self::Line)
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|tool| tool.name() == name)
+    }
+}
+
+// ============================================================================
+// Bindings
+// ============================================================================
+
+/// Item registry name without the `minecraft:` namespace, as held items report it.
+fn item_name(key: &str) -> &str {
+    key.strip_prefix("minecraft:").unwrap_or(key)
+}
+
+/// Server-wide bindings from `tool_bindings`, read from the live config so `/we reload`
+/// rebinds them. Entries naming an unknown item or tool are skipped.
+pub fn configured_tools() -> Vec<(String, Tool)> {
+    config::get()
+        .tool_bindings
+        .iter()
+        .filter(|(item, _)| Item::from_registry_key(item_name(item)).is_some())
+        .filter_map(|(item, tool)| Some((item_name(item).to_string(), Tool::from_name(tool)?)))
+        .collect()
+}
+
+/// Warn about `tool_bindings` entries naming an unknown item or tool.
+pub fn validate_bindings() {
+    for (item, tool) in &config::get().tool_bindings {
+        if Item::from_registry_key(item_name(item)).is_none() {
+            log::warn!("tool_bindings: unknown item '{item}', binding ignored");
+        } else if Tool::from_name(tool).is_none() {
+            log::warn!("tool_bindings: unknown tool '{tool}' for '{item}', binding ignored");
+        }
+    }
+}
+
+/// Tool bound to `item` for a player: their own `/we tool` binding, else the configured one.
+pub fn tool_for(player_id: &Uuid, item: &str) -> Option<Tool> {
+    let own = {
+        let state = PLAYER_DATA.lock();
+        state.get(player_id).and_then(|data| data.tools.get(item).copied())
+    };
+    own.or_else(|| {
+        configured_tools()
+            .into_iter()
+            .find(|(bound, _)| bound == item_name(item))
+            .map(|(_, tool)| tool)
+    })
+}
+
+// ============================================================================
+// Listener
+// ============================================================================
+
+/// Routes right-clicks on blocks to the tool bound to the held item, if any.
+pub struct ToolListener;
+
+#[async_trait]
+impl EventHandler<PlayerInteractEvent> for ToolListener {
+    async fn handle_blocking(&self, server: &Arc<Server>, event: &mut PlayerInteractEvent) {
+        let left = match event.action {
+            InteractAction::LeftClickBlock => true,
+            InteractAction::RightClickBlock => false,
+            _ => return,
+        };
+        let Some(pos) = event.clicked_pos else {
+            return;
+        };
+        let item = {
+            let stack = event.item.lock().await;
+            if stack.is_empty() {
+                return;
+            }
+            stack.item.registry_key.to_string()
+        };
+        let Some(tool) = tool_for(&event.player.gameprofile.id, &item) else {
+            return;
+        };
+        if left && !tool.uses_left_click() {
+            return;
+        }
+
+        // The click was meant for the tool, not the block
+        event.set_cancelled(true);
+        match tool {
+            Tool::Inspect => inspect(server, &event.player, &pos).await,
+            Tool::Wand => select(&event.player, pos, left).await,
+            Tool::Line => line(&event.player, pos, left).await,
+        }
+    }
+}
+
+/// Set position 1 (left-click) or position 2 (right-click) to the clicked block.
+async fn select(player: &Player, pos: BlockPos, left: bool) {
+    if !player.has_permission(COMMAND).await {
+        return;
+    }
+    let world = player.world();
+    let cleared = {
+        let mut state = PLAYER_DATA.lock();
+        let data = state.entry(player.gameprofile.id).or_default();
+        data.set_corner(left, pos, world.get_world_name())
+    };
+    let key = if left { "pos1_set" } else { "pos2_set" };
+    notify_player(player, Level::Success, msg!(key, pos.0.x, pos.0.y, pos.0.z)).await;
+    if let Some(other_world) = cleared {
+        let other = if left { 2 } else { 1 };
+        notify_player(player, Level::Warning, msg!("sel_corner_cleared", other, other_world)).await;
+    }
+}
+
+/// Move the line anchor to the clicked block. On right-click with an anchor set, first draw
+/// the line from the old anchor to the clicked block as its own undo entry.
+async fn line(player: &Player, pos: BlockPos, left: bool) {
+    if !player.has_permission(COMMAND).await {
+        return;
+    }
+    let settings = {
+        let mut state = PLAYER_DATA.lock();
+        let data = state.entry(player.gameprofile.id).or_default();
+        let settings = data.line_tool;
+        if let Some(line_tool) = data.line_tool.as_mut() {
+            line_tool.anchor = Some(pos);
+        }
+        settings
+    };
+    let Some(settings) = settings else {
+        notify_player(player, Level::Warning, msg!("tool_line_unset")).await;
+        return;
+    };
+    let (x, y, z) = (pos.0.x, pos.0.y, pos.0.z);
+    let start = match settings.anchor {
+        Some(start) if !left => start,
+        _ => {
+            notify_player(player, Level::Success, msg!("line_anchor_set", x, y, z)).await;
+            return;
+        }
+    };
+
+    match draw_line(player, start, pos, settings).await {
+        Ok((done, note)) => {
+            let message = msg!("line_done", format_count(done.blocks), done.time(), x, y, z, note);
+            notify_player(player, Level::Success, message).await;
+        }
+        Err(CommandError::CommandFailed(message)) => player.send_system_message(&message).await,
+        Err(_) => {}
+    }
+}
+
+/// Place the line from `start` to `end`, `settings.thickness` blocks thick. Blocks beyond
+/// the world's build height are left out.
+async fn draw_line(
+    player: &Player,
+    start: BlockPos,
+    end: BlockPos,
+    settings: LineTool,
+) -> Result<(EditSummary, String), CommandError> {
+    let radius = settings.thickness - 1;
+    let brush = Ellipsoid::new(Vector3::new(radius, radius, radius), Half::Whole, false).offsets();
+    let world = player.world().clone();
+    let (min_y, max_y) = world.height_range();
+
+    let mut seen = HashSet::new();
+    let mut positions = Vec::new();
+    for point in shapes::line(start.0, end.0) {
+        for offset in &brush {
+            let pos = Vector3::new(point.x + offset.x, point.y + offset.y, point.z + offset.z);
+            if (min_y..=max_y).contains(&pos.y) && seen.insert((pos.x, pos.y, pos.z)) {
+                positions.push(BlockPos(pos));
+            }
+        }
+    }
+    if positions.len() as i64 > MAX_BLOCKS {
+        return Err(CommandError::CommandFailed(msg!(
+            "selection_too_large",
+            format_count(positions.len()),
+            format_count(MAX_BLOCKS)
+        )));
+    }
+
+    let player_id = player.gameprofile.id;
+    let flags = placement_flags(&player_id, false);
+    let command = format!(
+        "line tool ({}, {}, {}) to ({}, {}, {})",
+        start.0.x, start.0.y, start.0.z, end.0.x, end.0.y, end.0.z
+    );
+    let mut session = EditSession::new(world, player_id, command, flags)?;
+    session.group_strokes("line tool");
+    let result = async {
+        for pos in positions {
+            session.set_block(pos, settings.state_id).await?;
+        }
+        Ok(())
+    }
+    .await;
+    Ok((session.finish(result)?, placement_note(flags)))
+}
+
+/// Print the tracked edits at `pos`, newest first.
+async fn inspect(server: &Server, player: &Player, pos: &BlockPos) {
+    if !player.has_permission(INSPECT).await {
+        player.send_system_message(&msg!("inspect_no_permission")).await;
+        return;
+    }
+
+    let world = player.world().get_world_name().to_string();
+    let edits = tracking::history_at(&world, pos);
+    let (x, y, z) = (pos.0.x, pos.0.y, pos.0.z);
+    if edits.is_empty() {
+        player.send_system_message(&msg!("inspect_nothing", x, y, z)).await;
+        return;
+    }
+
+    player.send_system_message(&msg!("inspect_header", x, y, z, edits.len())).await;
+    for edit in edits {
+        // Offline players are shown by UUID
+        let who = match server.get_player_by_uuid(edit.player_id).await {
+            Some(editor) => editor.gameprofile.name.clone(),
+            None => edit.player_id.to_string(),
+        };
+        player
+            .send_system_message(
+                &msg!("inspect_entry", format_ago(edit.age()), who, edit.command),
+            )
+            .await;
+    }
+}
@@ -0,0 +1,319 @@
+//! Per-block change index: which player last edited a position with WorldEdit, and how.
+//!
+//! Operations are indexed in memory (bounded per position) and appended to one log file per
+//! world in batches by a background task, so recording never touches the disk on the edit path.
+//! Log lines older than the retention period are dropped on load and by periodic compaction.
+
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use pumpkin_util::math::position::BlockPos;
+use uuid::Uuid;
+
+use crate::config;
+use crate::state::StateLock;
+
+/// How often buffered operations are appended to the log files.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(5);
+/// How often expired entries are pruned from memory and the log files compacted.
+const PRUNE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// One recorded operation, shared by every position it touched.
+pub struct TrackedEdit {
+    pub player_id: Uuid,
+    /// Command as typed, e.g. `//set stone`.
+    pub command: String,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+impl TrackedEdit {
+    /// Time since the edit was made.
+    pub fn age(&self) -> Duration {
+        Duration::from_secs(unix_now().saturating_sub(self.timestamp))
+    }
+}
+
+type BlockKey = (i32, i32, i32);
+
+#[derive(Default)]
+struct Tracker {
+    /// Log directory; `None` until `init` runs, in which case nothing is persisted.
+    dir: Option<PathBuf>,
+    /// Per world: edits per position, newest last.
+    worlds: HashMap<String, HashMap<BlockKey, VecDeque<Arc<TrackedEdit>>>>,
+    /// Log lines not yet written, per world.
+    pending: HashMap<String, Vec<String>>,
+}
+
+static TRACKER: LazyLock<StateLock<Tracker>> = LazyLock::new(|| StateLock::new(Tracker::default()));
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Oldest timestamp still within the retention period.
+fn retention_cutoff() -> u64 {
+    let days = u64::from(config::get().tracking_retention_days);
+    unix_now().saturating_sub(days * 86400)
+}
+
+fn log_path(dir: &Path, world: &str) -> PathBuf {
+    let file_name: String = world
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+        .collect();
+    dir.join(format!("{file_name}.log"))
+}
+
+// ============================================================================
+// Log Format
+// ============================================================================
+
+/// `<timestamp>\t<world>\t<uuid>\t<command>\t<x,y,z;x,y,z;...>`
+fn format_line(world: &str, edit: &TrackedEdit, positions: &[BlockPos]) -> String {
+    let positions = positions
+        .iter()
+        .map(|pos| format!("{},{},{}", pos.0.x, pos.0.y, pos.0.z))
+        .collect::<Vec<_>>()
+        .join(";");
+    let clean = |s: &str| s.replace(['\t', '\n', '\r'], " ");
+    format!(
+        "{}\t{}\t{}\t{}\t{positions}",
+        edit.timestamp,
+        clean(world),
+        edit.player_id,
+        clean(&edit.command)
+    )
+}
+
+fn parse_line(line: &str) -> Option<(String, TrackedEdit, Vec<BlockKey>)> {
+    let mut fields = line.splitn(5, '\t');
+    let timestamp = fields.next()?.parse().ok()?;
+    let world = fields.next()?.to_string();
+    let player_id = fields.next()?.parse().ok()?;
+    let command = fields.next()?.to_string();
+    let positions = fields
+        .next()?
+        .split(';')
+        .filter_map(|pos| {
+            let mut coords = pos.split(',').map(|c| c.parse::<i32>().ok());
+            Some((coords.next()??, coords.next()??, coords.next()??))
+        })
+        .collect();
+    let edit = TrackedEdit {
+        player_id,
+        command,
+        timestamp,
+    };
+    Some((world, edit, positions))
+}
+
+// ============================================================================
+// Index
+// ============================================================================
+
+impl Tracker {
+    fn index(&mut self, world: &str, edit: Arc<TrackedEdit>, positions: &[BlockKey]) {
+        let limit = config::get().tracking_entries_per_block.max(1);
+        let blocks = self.worlds.entry(world.to_string()).or_default();
+        for key in positions {
+            let entries = blocks.entry(*key).or_default();
+            entries.push_back(edit.clone());
+            while entries.len() > limit {
+                entries.pop_front();
+            }
+        }
+    }
+
+    fn prune(&mut self, cutoff: u64) {
+        for blocks in self.worlds.values_mut() {
+            blocks.retain(|_, entries| {
+                entries.retain(|edit| edit.timestamp >= cutoff);
+                !entries.is_empty()
+            });
+        }
+    }
+}
+
+/// Record that `player_id` changed `positions` in `world` with `command`.
+pub fn record(world: &str, player_id: Uuid, command: &str, positions: &[BlockPos]) {
+    if positions.is_empty() || !config::get().tracking_enabled {
+        return;
+    }
+    let edit = Arc::new(TrackedEdit {
+        player_id,
+        command: command.to_string(),
+        timestamp: unix_now(),
+    });
+    let line = format_line(world, &edit, positions);
+    let keys: Vec<BlockKey> = positions.iter().map(|p| (p.0.x, p.0.y, p.0.z)).collect();
+
+    let mut tracker = TRACKER.lock();
+    tracker.index(world, edit, &keys);
+    if tracker.dir.is_some() {
+        tracker.pending.entry(world.to_string()).or_default().push(line);
+    }
+}
+
+/// Edits recorded at `pos` in `world`, newest first.
+pub fn history_at(world: &str, pos: &BlockPos) -> Vec<Arc<TrackedEdit>> {
+    let tracker = TRACKER.lock();
+    tracker
+        .worlds
+        .get(world)
+        .and_then(|blocks| blocks.get(&(pos.0.x, pos.0.y, pos.0.z)))
+        .map(|entries| entries.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+// ============================================================================
+// Persistence
+// ============================================================================
+
+/// Load retained log entries from `dir` (compacting the files) and start the background
+/// flush task.
+pub fn init(dir: PathBuf) -> Result<(), String> {
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create tracking directory: {e}"))?;
+
+    let cutoff = retention_cutoff();
+    let mut loaded = 0usize;
+    {
+        let mut tracker = TRACKER.lock();
+        for path in log_files(&dir) {
+            let content = fs::read_to_string(&path)
+                .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+            let mut unreadable = 0usize;
+            let kept: Vec<&str> = content
+                .lines()
+                .filter(|line| {
+                    let Some((world, edit, positions)) = parse_line(line) else {
+                        unreadable += 1;
+                        return false;
+                    };
+                    if edit.timestamp < cutoff {
+                        return false;
+                    }
+                    tracker.index(&world, Arc::new(edit), &positions);
+                    true
+                })
+                .collect();
+            if unreadable > 0 {
+                log::warn!(
+                    "Discarding {unreadable} unreadable line(s) from {}",
+                    path.display()
+                );
+            }
+            loaded += kept.len();
+            write_lines(&path, &kept, false)?;
+        }
+        tracker.dir = Some(dir.clone());
+    }
+    log::info!("Change tracking: {loaded} operation(s) loaded from {}", dir.display());
+
+    tokio::spawn(async {
+        let mut flush = tokio::time::interval(FLUSH_INTERVAL);
+        let mut last_prune = SystemTime::now();
+        loop {
+            flush.tick().await;
+            let prune = last_prune.elapsed().unwrap_or_default() >= PRUNE_INTERVAL;
+            if prune {
+                last_prune = SystemTime::now();
+            }
+            let result = tokio::task::spawn_blocking(move || {
+                flush_pending()?;
+                if prune {
+                    compact()?;
+                }
+                Ok::<(), String>(())
+            })
+            .await;
+            match result {
+                Ok(Err(e)) => log::warn!("Change tracking: {e}"),
+                Err(e) => log::warn!("Change tracking task failed: {e}"),
+                Ok(Ok(())) => {}
+            }
+        }
+    });
+    Ok(())
+}
+
+fn log_files(dir: &Path) -> Vec<PathBuf> {
+    fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|ext| ext == "log"))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn write_lines(path: &Path, lines: &[impl AsRef<str>], append: bool) -> Result<(), String> {
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))?;
+    let mut buffer = String::new();
+    for line in lines {
+        buffer.push_str(line.as_ref());
+        buffer.push('\n');
+    }
+    file.write_all(buffer.as_bytes())
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Append buffered operations to their world's log file.
+fn flush_pending() -> Result<(), String> {
+    let (dir, pending) = {
+        let mut tracker = TRACKER.lock();
+        let Some(dir) = tracker.dir.clone() else {
+            return Ok(());
+        };
+        (dir, std::mem::take(&mut tracker.pending))
+    };
+    for (world, lines) in pending {
+        write_lines(&log_path(&dir, &world), &lines, true)?;
+    }
+    Ok(())
+}
+
+/// Drop expired entries from memory and rewrite the log files without them.
+fn compact() -> Result<(), String> {
+    let cutoff = retention_cutoff();
+    let dir = {
+        let mut tracker = TRACKER.lock();
+        tracker.prune(cutoff);
+        tracker.dir.clone()
+    };
+    let Some(dir) = dir else {
+        return Ok(());
+    };
+    for path in log_files(&dir) {
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read {}: {e}", path.display()))?;
+        let kept: Vec<&str> = content
+            .lines()
+            .filter(|line| {
+                line.split('\t')
+                    .next()
+                    .and_then(|t| t.parse::<u64>().ok())
+                    .is_some_and(|timestamp| timestamp >= cutoff)
+            })
+            .collect();
+        if kept.len() < content.lines().count() {
+            write_lines(&path, &kept, false)?;
+        }
+    }
+    Ok(())
+}
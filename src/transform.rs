@@ -0,0 +1,727 @@
+use std::collections::HashMap;
+
+use pumpkin_data::Block;
+use pumpkin_util::math::{position::BlockPos, vector3::Vector3};
+
+use crate::config;
+use crate::flags::Flags;
+use crate::messages::{format_count, msg_string};
+use crate::schematic::state_from_properties;
+use crate::state::ClipboardData;
+
+// ============================================================================
+// Transform Types
+// ============================================================================
+
+/// Clockwise rotation around the Y axis, seen from above.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    R90,
+    R180,
+    R270,
+    /// Any other angle (1..359 degrees), applied by resampling and therefore lossy.
+    Free(i32),
+}
+
+impl Rotation {
+    /// Parse an angle in degrees (negative angles rotate counter-clockwise).
+    pub fn parse(angle: &str) -> Result<Self, String> {
+        match angle.trim().parse::<i32>().map(|a| a.rem_euclid(360)) {
+            Ok(90) => Ok(Self::R90),
+            Ok(180) => Ok(Self::R180),
+            Ok(270) => Ok(Self::R270),
+            Ok(degrees) if degrees != 0 => Ok(Self::Free(degrees)),
+            _ => Err(msg_string!("invalid_rotation", angle)),
+        }
+    }
+
+    /// Nearest number of clockwise quarter turns.
+    fn quarter_turns(self) -> usize {
+        match self {
+            Self::R90 => 1,
+            Self::R180 => 2,
+            Self::R270 => 3,
+            Self::Free(degrees) => (f64::from(degrees) / 90.0).round() as usize % 4,
+        }
+    }
+
+    pub fn degrees(self) -> i32 {
+        match self {
+            Self::Free(degrees) => degrees,
+            _ => self.quarter_turns() as i32 * 90,
+        }
+    }
+
+    /// Whether the rotation can lose or duplicate blocks (any angle that isn't a right angle).
+    pub fn is_lossy(self) -> bool {
+        matches!(self, Self::Free(_))
+    }
+}
+
+/// Axis a clipboard is mirrored across.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum FlipAxis {
+    /// East ↔ west.
+    X,
+    /// Up ↔ down.
+    Y,
+    /// North ↔ south.
+    Z,
+}
+
+impl FlipAxis {
+    /// Parse a direction (`north`, `east`, `up`, ...) or its first letter into the axis it lies on.
+    pub fn parse(direction: &str) -> Result<Self, String> {
+        match direction.trim().to_lowercase().as_str() {
+            "east" | "west" | "e" | "w" | "x" => Ok(Self::X),
+            "up" | "down" | "u" | "d" | "y" => Ok(Self::Y),
+            "north" | "south" | "n" | "s" | "z" => Ok(Self::Z),
+            _ => Err(msg_string!("invalid_flip_direction", direction)),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::X => "east-west",
+            Self::Y => "up-down",
+            Self::Z => "north-south",
+        }
+    }
+}
+
+/// Largest component accepted in a `dx,dy,dz` direction vector.
+pub const MAX_VECTOR_COMPONENT: i32 = 1024;
+
+/// Step for a direction (`north`, `east`, `up`, ...) or its first letter, or an explicit
+/// `dx,dy,dz` vector such as `1,0,1` for diagonals. Vectors must not be zero and each
+/// component is limited to [`MAX_VECTOR_COMPONENT`].
+pub fn parse_direction(direction: &str) -> Result<Vector3<i32>, String> {
+    let direction = direction.trim();
+    if direction.contains(',') {
+        return parse_direction_vector(direction);
+    }
+    match direction.to_lowercase().as_str() {
+        "north" | "n" => Ok(Vector3::new(0, 0, -1)),
+        "south" | "s" => Ok(Vector3::new(0, 0, 1)),
+        "east" | "e" => Ok(Vector3::new(1, 0, 0)),
+        "west" | "w" => Ok(Vector3::new(-1, 0, 0)),
+        "up" | "u" => Ok(Vector3::new(0, 1, 0)),
+        "down" | "d" => Ok(Vector3::new(0, -1, 0)),
+        _ => Err(msg_string!("invalid_direction", direction)),
+    }
+}
+
+/// Parse the `dx,dy,dz` form of [`parse_direction`].
+fn parse_direction_vector(input: &str) -> Result<Vector3<i32>, String> {
+    let components = input
+        .split(',')
+        .map(|part| part.trim().parse::<i32>().ok())
+        .collect::<Option<Vec<_>>>()
+        .filter(|components| components.len() == 3)
+        .ok_or_else(|| msg_string!("invalid_direction_vector", input))?;
+    let vector = Vector3::new(components[0], components[1], components[2]);
+    if (vector.x, vector.y, vector.z) == (0, 0, 0) {
+        return Err(msg_string!("zero_direction_vector"));
+    }
+    if [vector.x, vector.y, vector.z].iter().any(|c| c.abs() > MAX_VECTOR_COMPONENT) {
+        return Err(msg_string!("direction_vector_too_long", input, MAX_VECTOR_COMPONENT));
+    }
+    Ok(vector)
+}
+
+/// Name of a step as accepted by [`parse_direction`]: the direction's name for a unit step
+/// along one axis, `dx,dy,dz` for anything else.
+pub fn direction_name(step: Vector3<i32>) -> String {
+    let name = match (step.x, step.y, step.z) {
+        (1, 0, 0) => "east",
+        (-1, 0, 0) => "west",
+        (0, 1, 0) => "up",
+        (0, -1, 0) => "down",
+        (0, 0, -1) => "north",
+        (0, 0, 1) => "south",
+        (x, y, z) => return format!("{x},{y},{z}"),
+    };
+    name.to_string()
+}
+
+/// One lossless step of a transform; every rotation and flip is built from these.
+#[derive(Clone, Copy)]
+enum Step {
+    Quarter,
+    Flip(FlipAxis),
+}
+
+/// Optional rotation and flip applied to a clipboard. The flip is applied first.
+#[derive(Clone, Copy, Default)]
+pub struct Transform {
+    pub rotation: Option<Rotation>,
+    pub flip: Option<FlipAxis>,
+}
+
+impl Transform {
+    /// Build the transform from the `-r <angle>` and `-f <direction>` flags.
+    pub fn from_flags(flags: &Flags) -> Result<Self, String> {
+        Ok(Self {
+            rotation: flags.value("-r").map(Rotation::parse).transpose()?,
+            flip: flags.value("-f").map(FlipAxis::parse).transpose()?,
+        })
+    }
+
+    pub fn is_identity(&self) -> bool {
+        self.rotation.is_none() && self.flip.is_none()
+    }
+
+    /// Transform offsets and remap block states (facing, axis, connections, ...) in place.
+    /// Fails if a non-right rotation would grow the clipboard past the block limit.
+    pub fn apply(&self, clipboard: &mut ClipboardData) -> Result<(), String> {
+        if let Some(axis) = self.flip {
+            apply_step(clipboard, Step::Flip(axis));
+        }
+        match self.rotation {
+            Some(Rotation::Free(degrees)) => rotate_free(clipboard, degrees)?,
+            Some(rotation) => {
+                for _ in 0..rotation.quarter_turns() {
+                    apply_step(clipboard, Step::Quarter);
+                }
+            }
+            None => {}
+        }
+        Ok(())
+    }
+
+    pub fn is_lossy(&self) -> bool {
+        self.rotation.is_some_and(Rotation::is_lossy)
+    }
+
+    /// Short description for completion messages, e.g. ` (rotated 90°, flipped north-south)`.
+    pub fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(rotation) = self.rotation {
+            parts.push(msg_string!("transform_rotated", rotation.degrees()));
+        }
+        if let Some(axis) = self.flip {
+            parts.push(msg_string!("transform_flipped", axis.name()));
+        }
+        if parts.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", parts.join(", "))
+        }
+    }
+}
+
+/// Split `-r <angle>` and `-f <direction>` out of the flag tokens, returning the transform and
+/// the remaining tokens for the caller to interpret.
+pub fn parse_transform_flags(tokens: Vec<&str>) -> Result<(Transform, Vec<&str>), String> {
+    let mut transform = Transform::default();
+    let mut rest = Vec::new();
+    let mut tokens = tokens.into_iter();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            "-r" => {
+                let value = tokens
+                    .next()
+                    .ok_or_else(|| msg_string!("missing_flag_value", "angle", "-r"))?;
+                transform.rotation = Some(Rotation::parse(value)?);
+            }
+            "-f" => {
+                let value = tokens
+                    .next()
+                    .ok_or_else(|| msg_string!("missing_flag_value", "direction", "-f"))?;
+                transform.flip = Some(FlipAxis::parse(value)?);
+            }
+            other => rest.push(other),
+        }
+    }
+    Ok((transform, rest))
+}
+
+// ============================================================================
+// Trim
+// ============================================================================
+
+/// Whether a state is any kind of air.
+pub fn is_air(state_id: u16) -> bool {
+    matches!(Block::from_state_id(state_id).name, "air" | "cave_air" | "void_air")
+}
+
+/// Bounding box (min, max) of the offsets accepted by `filter`.
+fn bounds(
+    clipboard: &ClipboardData,
+    filter: impl Fn(u16) -> bool,
+) -> Option<(Vector3<i32>, Vector3<i32>)> {
+    let mut iter = clipboard.blocks.iter().filter(|(_, state_id)| filter(*state_id));
+    let (first, _) = iter.next()?;
+    Some(iter.fold((*first, *first), |(min, max), (offset, _)| {
+        (
+            Vector3::new(min.x.min(offset.x), min.y.min(offset.y), min.z.min(offset.z)),
+            Vector3::new(max.x.max(offset.x), max.y.max(offset.y), max.z.max(offset.z)),
+        )
+    }))
+}
+
+/// Bounding box (min, max) of the whole clipboard, as offsets from the paste origin.
+pub fn clipboard_bounds(clipboard: &ClipboardData) -> Option<(Vector3<i32>, Vector3<i32>)> {
+    bounds(clipboard, |_| true)
+}
+
+/// World corners (min, max) the clipboard would cover if pasted at `origin`, or `None` if
+/// it holds no blocks.
+pub fn paste_bounds(clipboard: &ClipboardData, origin: &BlockPos) -> Option<(BlockPos, BlockPos)> {
+    let (min, max) = clipboard_bounds(clipboard)?;
+    let at = |offset: Vector3<i32>| {
+        BlockPos(Vector3::new(
+            origin.0.x + offset.x,
+            origin.0.y + offset.y,
+            origin.0.z + offset.z,
+        ))
+    };
+    Some((at(min), at(max)))
+}
+
+fn dimensions((min, max): (Vector3<i32>, Vector3<i32>)) -> Vector3<i32> {
+    Vector3::new(max.x - min.x + 1, max.y - min.y + 1, max.z - min.z + 1)
+}
+
+/// Crop the clipboard to the tight bounding box of its non-air blocks.
+///
+/// Offsets are rebased so the trimmed box starts where the original box started relative to
+/// the origin; a schematic anchored at its corner stays anchored at its corner.
+/// Returns the (old, new) dimensions, or `None` if the clipboard holds nothing but air.
+pub fn trim_air(clipboard: &mut ClipboardData) -> Option<(Vector3<i32>, Vector3<i32>)> {
+    let old = clipboard_bounds(clipboard)?;
+    let new = crop_air(clipboard)?;
+    let shift = Vector3::new(new.0.x - old.0.x, new.0.y - old.0.y, new.0.z - old.0.z);
+    for (offset, _) in &mut clipboard.blocks {
+        *offset = Vector3::new(offset.x - shift.x, offset.y - shift.y, offset.z - shift.z);
+    }
+    Some((dimensions(old), dimensions(new)))
+}
+
+/// Drop the clipboard entries outside the tight bounding box of its non-air blocks, keeping
+/// the offsets of the rest so a paste still puts them where they were copied from. Returns
+/// the new bounds, or `None` (leaving the clipboard as it is) if it holds nothing but air.
+pub fn crop_air(clipboard: &mut ClipboardData) -> Option<(Vector3<i32>, Vector3<i32>)> {
+    let new = bounds(clipboard, |state_id| !is_air(state_id))?;
+    let inside = |offset: &Vector3<i32>| {
+        (new.0.x..=new.1.x).contains(&offset.x)
+            && (new.0.y..=new.1.y).contains(&offset.y)
+            && (new.0.z..=new.1.z).contains(&offset.z)
+    };
+    // Biomes run parallel to the blocks, so drop the same entries from both
+    if let Some(biomes) = &mut clipboard.biomes {
+        let mut keep = clipboard.blocks.iter().map(|(offset, _)| inside(offset));
+        biomes.retain(|_| keep.next().unwrap_or(false));
+    }
+    clipboard.blocks.retain(|(offset, _)| inside(offset));
+    Some(new)
+}
+
+// ============================================================================
+// Offsets
+// ============================================================================
+
+fn transform_offset(offset: Vector3<i32>, step: Step) -> Vector3<i32> {
+    match step {
+        // North (-z) turns to east (+x)
+        Step::Quarter => Vector3::new(-offset.z, offset.y, offset.x),
+        Step::Flip(FlipAxis::X) => Vector3::new(-offset.x, offset.y, offset.z),
+        Step::Flip(FlipAxis::Y) => Vector3::new(offset.x, -offset.y, offset.z),
+        Step::Flip(FlipAxis::Z) => Vector3::new(offset.x, offset.y, -offset.z),
+    }
+}
+
+fn apply_step(clipboard: &mut ClipboardData, step: Step) {
+    for (offset, state_id) in &mut clipboard.blocks {
+        *offset = transform_offset(*offset, step);
+        *state_id = transform_state(*state_id, step);
+    }
+}
+
+// ============================================================================
+// Free Rotation
+// ============================================================================
+
+/// Rotate by a non-right angle about the clipboard's center. Every position of the rotated
+/// bounding box takes the nearest block of the source grid (nearest-neighbor resampling), so
+/// blocks can be dropped or doubled. Block states turn by the nearest quarter turn, and
+/// 16-step `rotation` properties (signs, banners, heads) by the nearest sixteenth.
+fn rotate_free(clipboard: &mut ClipboardData, degrees: i32) -> Result<(), String> {
+    let Some((min, max)) = clipboard_bounds(clipboard) else {
+        return Ok(());
+    };
+    let (center_x, center_z) = (f64::from(min.x + max.x) / 2.0, f64::from(min.z + max.z) / 2.0);
+    let (sin, cos) = f64::from(degrees).to_radians().sin_cos();
+
+    // Extent of the rotated box around the center
+    let half_width = f64::from(max.x - min.x) / 2.0;
+    let half_length = f64::from(max.z - min.z) / 2.0;
+    let new_half_width = cos.abs() * half_width + sin.abs() * half_length;
+    let new_half_length = sin.abs() * half_width + cos.abs() * half_length;
+    let (min_x, max_x) = (
+        (center_x - new_half_width).floor() as i32,
+        (center_x + new_half_width).ceil() as i32,
+    );
+    let (min_z, max_z) = (
+        (center_z - new_half_length).floor() as i32,
+        (center_z + new_half_length).ceil() as i32,
+    );
+    let volume = i64::from(max_x - min_x + 1)
+        * i64::from(max.y - min.y + 1)
+        * i64::from(max_z - min_z + 1);
+    let limit = config::get().max_clipboard_blocks;
+    if volume as u64 > limit {
+        return Err(msg_string!(
+            "rotated_clipboard_limit",
+            format_count(volume),
+            format_count(limit)
+        ));
+    }
+
+    let index: HashMap<(i32, i32, i32), usize> = clipboard
+        .blocks
+        .iter()
+        .enumerate()
+        .map(|(i, (offset, _))| ((offset.x, offset.y, offset.z), i))
+        .collect();
+    let quarter_turns = Rotation::Free(degrees).quarter_turns();
+    let extra_sixteenths = (f64::from(degrees) / 22.5).round() as i32 - quarter_turns as i32 * 4;
+    let mut states: HashMap<u16, u16> = HashMap::new();
+
+    let mut blocks = Vec::new();
+    let mut biomes = clipboard.biomes.as_ref().map(|_| Vec::new());
+    for x in min_x..=max_x {
+        for z in min_z..=max_z {
+            // Inverse rotation: where in the source this target position comes from.
+            // Clockwise seen from above, like quarter turns (north turns to east).
+            let (dx, dz) = (f64::from(x) - center_x, f64::from(z) - center_z);
+            let source_x = (center_x + dx * cos + dz * sin).round() as i32;
+            let source_z = (center_z - dx * sin + dz * cos).round() as i32;
+            for y in min.y..=max.y {
+                let Some(&i) = index.get(&(source_x, y, source_z)) else {
+                    continue;
+                };
+                let state_id = clipboard.blocks[i].1;
+                let state_id = *states.entry(state_id).or_insert_with(|| {
+                    let turned = (0..quarter_turns)
+                        .fold(state_id, |state_id, _| transform_state(state_id, Step::Quarter));
+                    turn_sixteenths(turned, extra_sixteenths)
+                });
+                blocks.push((Vector3::new(x, y, z), state_id));
+                if let (Some(biomes), Some(source)) = (&mut biomes, &clipboard.biomes) {
+                    biomes.push(source[i]);
+                }
+            }
+        }
+    }
+    clipboard.blocks = blocks;
+    clipboard.biomes = biomes;
+    Ok(())
+}
+
+/// Turn a 16-step `rotation` property by `steps` sixteenths of a full turn.
+fn turn_sixteenths(state_id: u16, steps: i32) -> u16 {
+    if steps == 0 {
+        return state_id;
+    }
+    let block = Block::from_state_id(state_id);
+    let Some(props) = block.properties(state_id) else {
+        return state_id;
+    };
+    let props: Vec<(&str, String)> = props
+        .to_props()
+        .into_iter()
+        .map(|(key, value)| match (key, value.parse::<i32>()) {
+            ("rotation", Ok(rotation)) => (key, (rotation + steps).rem_euclid(16).to_string()),
+            _ => (key, value.to_string()),
+        })
+        .collect();
+    let props: Vec<(&str, &str)> = props.iter().map(|(k, v)| (*k, v.as_str())).collect();
+    state_from_properties(block, &props).unwrap_or(state_id)
+}
+
+// ============================================================================
+// Block States
+// ============================================================================
+
+/// The state of `block` closest to `state_id`: properties both blocks have (facing, half,
+/// waterlogged, ...) keep their values, the rest take `block`'s defaults. Falls back to the
+/// default state if the registry rejects the combination.
+pub fn carry_properties(state_id: u16, block: &Block) -> u16 {
+    let default = block.default_state.id;
+    let (Some(old), Some(new)) = (
+        Block::from_state_id(state_id).properties(state_id),
+        block.properties(default),
+    ) else {
+        return default;
+    };
+    let old = old.to_props();
+    let props: Vec<(&str, &str)> = new
+        .to_props()
+        .into_iter()
+        .map(|(key, value)| {
+            let kept = old.iter().find(|(old_key, _)| *old_key == key);
+            (key, kept.map_or(value, |(_, old_value)| *old_value))
+        })
+        .collect();
+    state_from_properties(block, &props).unwrap_or(default)
+}
+
+/// Whether the block of `state_id` has the property `key`.
+pub fn has_property(state_id: u16, key: &str) -> bool {
+    let block = Block::from_state_id(state_id);
+    block
+        .properties(state_id)
+        .is_some_and(|props| props.to_props().iter().any(|(name, _)| *name == key))
+}
+
+/// Value of the property `key` of `state_id`, or `None` if its block has no such property.
+pub fn property(state_id: u16, key: &str) -> Option<&'static str> {
+    Block::from_state_id(state_id)
+        .properties(state_id)?
+        .to_props()
+        .into_iter()
+        .find_map(|(name, value)| (name == key).then_some(value))
+}
+
+/// `state_id` with the property `key` set to `value` and all other properties kept, or
+/// `None` if the block has no such property or doesn't accept the value.
+pub fn with_property(state_id: u16, key: &str, value: &str) -> Option<u16> {
+    let block = Block::from_state_id(state_id);
+    let props: Vec<(&str, &str)> = block
+        .properties(state_id)?
+        .to_props()
+        .into_iter()
+        .map(|(name, old)| if name == key { (name, value) } else { (name, old) })
+        .collect();
+    if !props.iter().any(|(name, _)| *name == key) {
+        return None;
+    }
+    state_from_properties(block, &props)
+}
+
+fn transform_direction(direction: &str, step: Step) -> Option<&'static str> {
+    let mapped = match (step, direction) {
+        (Step::Quarter, "north") => "east",
+        (Step::Quarter, "east") => "south",
+        (Step::Quarter, "south") => "west",
+        (Step::Quarter, "west") => "north",
+        (Step::Quarter, "up") => "up",
+        (Step::Quarter, "down") => "down",
+        (Step::Flip(FlipAxis::X), "east") => "west",
+        (Step::Flip(FlipAxis::X), "west") => "east",
+        (Step::Flip(FlipAxis::Y), "up") => "down",
+        (Step::Flip(FlipAxis::Y), "down") => "up",
+        (Step::Flip(FlipAxis::Z), "north") => "south",
+        (Step::Flip(FlipAxis::Z), "south") => "north",
+        (Step::Flip(_), "north") => "north",
+        (Step::Flip(_), "east") => "east",
+        (Step::Flip(_), "south") => "south",
+        (Step::Flip(_), "west") => "west",
+        (Step::Flip(_), "up") => "up",
+        (Step::Flip(_), "down") => "down",
+        _ => return None,
+    };
+    Some(mapped)
+}
+
+/// Rail shapes: `north_south`, `ascending_east`, `south_west`, ...
+/// Curves are written north/south first, straight pieces as `north_south` / `east_west`.
+fn transform_rail_shape(shape: &str, step: Step) -> Option<String> {
+    if let Some(direction) = shape.strip_prefix("ascending_") {
+        return Some(format!("ascending_{}", transform_direction(direction, step)?));
+    }
+    let (a, b) = shape.split_once('_')?;
+    let a = transform_direction(a, step)?;
+    let b = transform_direction(b, step)?;
+    let is_ns = |d: &str| d == "north" || d == "south";
+    Some(match (is_ns(a), is_ns(b)) {
+        (true, true) => "north_south".to_string(),
+        (false, false) => "east_west".to_string(),
+        (true, false) => format!("{a}_{b}"),
+        (false, true) => format!("{b}_{a}"),
+    })
+}
+
+fn swap_left_right(value: &str) -> Option<String> {
+    if value.contains("left") {
+        Some(value.replace("left", "right"))
+    } else if value.contains("right") {
+        Some(value.replace("right", "left"))
+    } else {
+        None
+    }
+}
+
+/// New value for one property, or `None` to keep it.
+fn transform_value(key: &str, value: &str, step: Step) -> Option<String> {
+    // Left/right handedness only changes when mirroring horizontally
+    let mirrored = matches!(step, Step::Flip(FlipAxis::X | FlipAxis::Z));
+    match key {
+        "facing" | "vertical_direction" => transform_direction(value, step).map(str::to_string),
+        "orientation" => value
+            .split('_')
+            .map(|d| transform_direction(d, step))
+            .collect::<Option<Vec<_>>>()
+            .map(|parts| parts.join("_")),
+        "axis" if matches!(step, Step::Quarter) => match value {
+            "x" => Some("z".to_string()),
+            "z" => Some("x".to_string()),
+            _ => None,
+        },
+        "rotation" => {
+            let rotation: i32 = value.parse().ok()?;
+            let turned = match step {
+                Step::Quarter => rotation + 4,
+                Step::Flip(FlipAxis::X) => 16 - rotation,
+                Step::Flip(FlipAxis::Z) => 24 - rotation,
+                Step::Flip(FlipAxis::Y) => rotation,
+            };
+            Some(turned.rem_euclid(16).to_string())
+        }
+        "shape" => transform_rail_shape(value, step).or_else(|| {
+            if mirrored {
+                swap_left_right(value)
+            } else {
+                None
+            }
+        }),
+        "hinge" | "type" if mirrored && (value == "left" || value == "right") => {
+            swap_left_right(value)
+        }
+        "half" | "type" if matches!(step, Step::Flip(FlipAxis::Y)) => match value {
+            "top" => Some("bottom".to_string()),
+            "bottom" => Some("top".to_string()),
+            "upper" => Some("lower".to_string()),
+            "lower" => Some("upper".to_string()),
+            _ => None,
+        },
+        "face" | "attachment" if matches!(step, Step::Flip(FlipAxis::Y)) => match value {
+            "floor" => Some("ceiling".to_string()),
+            "ceiling" => Some("floor".to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Remap a block state for one transform step. Direction-named properties (fence and wall
+/// connections, vines, ...) move to their new key; states the registry rejects stay unchanged.
+fn transform_state(state_id: u16, step: Step) -> u16 {
+    let block = Block::from_state_id(state_id);
+    let Some(props) = block.properties(state_id) else {
+        return state_id;
+    };
+    let old: Vec<(&str, &str)> = props.to_props();
+
+    let mut changed = false;
+    let new: Vec<(String, String)> = old
+        .iter()
+        .map(|&(key, value)| {
+            // Only rename a direction key when the block also has the target key
+            // (walls have `up` but no `down`)
+            let new_key = transform_direction(key, step)
+                .filter(|target| old.iter().any(|(k, _)| k == target))
+                .unwrap_or(key);
+            let new_value = transform_value(key, value, step);
+            changed |= new_key != key || new_value.is_some();
+            (
+                new_key.to_string(),
+                new_value.unwrap_or_else(|| value.to_string()),
+            )
+        })
+        .collect();
+    if !changed {
+        return state_id;
+    }
+
+    let new: Vec<(&str, &str)> = new.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    state_from_properties(block, &new).unwrap_or(state_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(block: &Block, properties: &[(&str, &str)]) -> u16 {
+        properties.iter().fold(block.default_state.id, |state_id, (key, value)| {
+            with_property(state_id, key, value).expect("the block has the property")
+        })
+    }
+
+    /// A clipboard of oriented blocks around the origin.
+    fn sample() -> ClipboardData {
+        let blocks = vec![
+            (Vector3::new(0, 0, -1), state(&Block::OAK_STAIRS, &[("facing", "north")])),
+            (
+                Vector3::new(1, 0, 0),
+                state(&Block::OAK_STAIRS, &[("facing", "west"), ("shape", "inner_left")]),
+            ),
+            (Vector3::new(2, 1, -3), state(&Block::OAK_LOG, &[("axis", "x")])),
+            (Vector3::new(-1, 0, 2), state(&Block::RAIL, &[("shape", "north_east")])),
+            (Vector3::new(0, 2, 0), state(&Block::OAK_FENCE, &[("east", "true")])),
+            (Vector3::new(3, 0, 1), state(&Block::WALL_TORCH, &[("facing", "south")])),
+        ];
+        ClipboardData { blocks, biomes: None, source: None }
+    }
+
+    fn contents(clipboard: &ClipboardData) -> Vec<((i32, i32, i32), u16)> {
+        clipboard.blocks.iter().map(|(o, state_id)| ((o.x, o.y, o.z), *state_id)).collect()
+    }
+
+    fn rotated(degrees: &str) -> Transform {
+        Transform { rotation: Some(Rotation::parse(degrees).unwrap()), flip: None }
+    }
+
+    #[test]
+    fn four_quarter_turns_are_the_identity() {
+        let mut clipboard = sample();
+        let original = contents(&clipboard);
+        for turn in 1..=4 {
+            rotated("90").apply(&mut clipboard).unwrap();
+            assert_eq!(contents(&clipboard) == original, turn == 4, "after {turn} turn(s)");
+        }
+    }
+
+    #[test]
+    fn a_quarter_turn_moves_north_to_east() {
+        let mut clipboard = sample();
+        rotated("90").apply(&mut clipboard).unwrap();
+        let (offset, stairs) = clipboard.blocks[0];
+        assert_eq!((offset.x, offset.y, offset.z), (1, 0, 0));
+        assert_eq!(property(stairs, "facing"), Some("east"));
+        assert_eq!(property(clipboard.blocks[2].1, "axis"), Some("z"));
+        assert_eq!(property(clipboard.blocks[4].1, "south"), Some("true"));
+    }
+
+    #[test]
+    fn half_turns_and_double_flips_compose() {
+        let mut twice = sample();
+        rotated("90").apply(&mut twice).unwrap();
+        rotated("-270").apply(&mut twice).unwrap();
+        let mut once = sample();
+        rotated("180").apply(&mut once).unwrap();
+        assert_eq!(contents(&twice), contents(&once));
+
+        for axis in [FlipAxis::X, FlipAxis::Y, FlipAxis::Z] {
+            let mut clipboard = sample();
+            let flip = Transform { rotation: None, flip: Some(axis) };
+            flip.apply(&mut clipboard).unwrap();
+            assert_ne!(contents(&clipboard), contents(&sample()));
+            flip.apply(&mut clipboard).unwrap();
+            assert_eq!(contents(&clipboard), contents(&sample()));
+        }
+    }
+
+    #[test]
+    fn transform_flags_are_split_from_the_rest() {
+        let (transform, rest) = parse_transform_flags(vec!["-a", "-r", "270", "-f", "n"]).unwrap();
+        assert!(transform.rotation == Some(Rotation::R270));
+        assert!(transform.flip == Some(FlipAxis::Z));
+        assert_eq!(rest, ["-a"]);
+        assert!(parse_transform_flags(vec!["-r"]).is_err());
+        assert!(parse_transform_flags(vec!["-r", "0"]).is_err());
+        assert!(parse_transform_flags(vec!["-f", "sideways"]).is_err());
+    }
+}